@@ -0,0 +1,108 @@
+#ifndef MCTS_H
+#define MCTS_H
+
+/* Generated with cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Opaque game handle.
+ */
+typedef struct MctsGame MctsGame;
+
+/**
+ * Opaque search handle.
+ */
+typedef struct MctsSearch MctsSearch;
+
+/**
+ * Create a new game: "2048", "tictactoe" or "hex" (11x11).
+ *
+ * Returns null for unknown games.
+ */
+MctsGame *mcts_game_new(const char *kind);
+
+/**
+ * Create a game from its JSON state, as used by the engine protocol.
+ *
+ * Returns null for unknown games and invalid states.
+ */
+MctsGame *mcts_game_from_json(const char *kind, const char *state);
+
+/**
+ * Release a game.
+ */
+void mcts_game_free(MctsGame *game);
+
+/**
+ * Write up to `capacity` allowed actions to `actions`.
+ *
+ * Returns the total number of allowed actions, which may exceed
+ * `capacity`; 0 means the game is over.
+ */
+size_t mcts_game_allowed_actions(const MctsGame *game, int *actions, size_t capacity);
+
+/**
+ * Make a move; returns 0 on success and -1 for illegal actions.
+ */
+int mcts_game_make_move(MctsGame *game, int action);
+
+/**
+ * Reward of the current state; for two-player games from player 0's view.
+ */
+float mcts_game_reward(const MctsGame *game);
+
+/**
+ * Index of the player to move.
+ */
+int mcts_game_current_player(const MctsGame *game);
+
+/**
+ * The state as a JSON string; release it with `mcts_string_free`.
+ */
+char *mcts_game_to_json(const MctsGame *game);
+
+/**
+ * Release a string returned by this library.
+ */
+void mcts_string_free(char *text);
+
+/**
+ * Start a search from the current state of `game`.
+ *
+ * The search works on a copy; later moves in `game` do not affect it.
+ */
+MctsSearch *mcts_search_new(const MctsGame *game, size_t ensemble_size);
+
+/**
+ * Release a search.
+ */
+void mcts_search_free(MctsSearch *search);
+
+/**
+ * Perform `n_samples` iterations per ensemble member.
+ */
+void mcts_search_iterations(MctsSearch *search, size_t n_samples, float c);
+
+/**
+ * Search for the given time budget (in s).
+ */
+void mcts_search_time(MctsSearch *search, float budget_seconds, float c);
+
+/**
+ * The best action found so far; -1 if there is none.
+ */
+int mcts_search_best_action(const MctsSearch *search);
+
+/**
+ * Mean reward of the best action; NaN if there is none.
+ */
+float mcts_search_value(const MctsSearch *search);
+
+/**
+ * Seed the random number generator of the calling thread.
+ */
+void mcts_seed(uint32_t seed);
+
+#endif /* MCTS_H */
@@ -0,0 +1,442 @@
+//!
+//! Adversarial 2048.
+//!
+//! The tile spawns of 2048 are made by a second player instead of a random
+//! source: after every move of the mover (player 0), the spawner (player 1)
+//! places a 2 or a 4 on an empty cell. The game is zero-sum; the mover
+//! collects the reward and the spawner tries to keep it as low as possible.
+//!
+//! Searching as the spawner answers where tiles should spawn to end a given
+//! mover's game fastest; searching as the mover gives a strategy that is
+//! robust against the worst possible spawns.
+//!
+//! With `random_spawns` the spawner is chance instead, as in 2048: it picks
+//! any empty cell with the same probability and places a 4 there with
+//! probability `FOUR_PROBABILITY`, a 2 otherwise. The search of the mover
+//! then plans for the average spawn rather than for the worst one.
+//!
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game, RolloutPolicy};
+#[cfg(feature = "serde")]
+use protocol::SerializableGame;
+#[cfg(feature = "serde")]
+use savegame::Saveable;
+use twofortyeight::{self, TwoFortyEight, HeuristicRollout, FOUR_PROBABILITY};
+
+/// Direction of a move of the mover.
+pub type Direction = twofortyeight::Action;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// A move of either player.
+pub enum Action {
+    Move(Direction),
+    Spawn { row: u8, col: u8, tile: u16 },
+}
+impl GameAction for Action {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// What the mover is rewarded for.
+pub enum Objective {
+    /// The 2048 score, i.e. the sum of all merged tiles.
+    Score,
+    /// The number of moves survived.
+    Moves,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Objective, String> {
+        match name {
+            "score" => Ok(Objective::Score),
+            "moves" => Ok(Objective::Moves),
+            _ => Err(format!("Unknown objective '{}', expected score or moves", name))
+        }
+    }
+}
+
+#[derive(Clone)]
+/// Implementation of the adversarial 2048 game mechanics.
+pub struct Adversarial2048 {
+    pub board: TwoFortyEight,
+    pub objective: Objective,
+    pub spawner_to_move: bool,
+    pub random_spawns: bool,
+}
+
+impl Adversarial2048 {
+    /// Create a new game with two random tiles in it; the mover moves first.
+    pub fn new() -> Adversarial2048 {
+        Adversarial2048::from_board(TwoFortyEight::new())
+    }
+
+    /// Create a new game whose first two tiles are drawn from the given seed.
+    pub fn with_seed(seed: u32) -> Adversarial2048 {
+        Adversarial2048::from_board(TwoFortyEight::with_seed(seed))
+    }
+
+    /// Create a new game on a board of the given size, see
+    /// `TwoFortyEight::with_size`.
+    pub fn with_size(width: usize, height: usize, seed: Option<u32>) -> Adversarial2048 {
+        Adversarial2048::from_board(TwoFortyEight::with_size(width, height, seed))
+    }
+
+    /// Continue from the given board with the mover to move.
+    pub fn from_board(board: TwoFortyEight) -> Adversarial2048 {
+        Adversarial2048 { board: board, objective: Objective::Score, spawner_to_move: false, random_spawns: false }
+    }
+
+    /// Check the rules of 2048 against the current position, see
+    /// `TwoFortyEight::check_invariants`; a tile is pending while the
+    /// spawner is to move.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.board.check_invariants_pending(if self.spawner_to_move { 1 } else { 0 })
+    }
+
+    /// Whether the mover has no moves left.
+    pub fn is_over(&self) -> bool {
+        !self.spawner_to_move && self.board.is_terminal()
+    }
+}
+
+impl Game<Action> for Adversarial2048 {
+
+    /// A 2 or a 4 on any empty cell for the spawner, slide directions for
+    /// the mover.
+    fn allowed_actions(&self) -> Vec<Action> {
+        if self.spawner_to_move {
+            let mut actions = Vec::new();
+            for row in 0..self.board.height() {
+                for col in 0..self.board.width() {
+                    if self.board.get_tile(row, col) == 0 {
+                        for &tile in &[2, 4] {
+                            actions.push(Action::Spawn { row: row as u8, col: col as u8, tile: tile });
+                        }
+                    }
+                }
+            }
+            actions
+        } else {
+            self.board.allowed_actions().into_iter().map(Action::Move).collect()
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        if self.spawner_to_move {
+            self.board.board_full()
+        } else {
+            self.board.is_terminal()
+        }
+    }
+
+    fn make_move(&mut self, action: &Action) {
+        match *action {
+            Action::Move(ref direction) => {
+                assert!(!self.spawner_to_move, "The spawner cannot slide tiles");
+                self.board.slide(direction);
+            },
+            Action::Spawn { row, col, tile } => {
+                assert!(self.spawner_to_move, "The mover cannot spawn tiles");
+                self.board.spawn(row as usize, col as usize, tile);
+            }
+        }
+        self.spawner_to_move = !self.spawner_to_move;
+        if cfg!(debug_assertions) {
+            // Whose turn it is may have been set up by hand
+            if let Err(msg) = self.board.check_tiles() {
+                panic!("Broken invariant after {:?}: {}\n{}", action, msg, self);
+            }
+        }
+    }
+
+    /// Reward for the mover; the spawner gets the negated reward.
+    ///
+    /// The score objective includes the bonus or penalty of the board for
+    /// reaching its target tile, see `TwoFortyEight::game_status`.
+    fn reward(&self) -> f32 {
+        match self.objective {
+            Objective::Score => self.board.reward(),
+            Objective::Moves => self.board.moves as f32,
+        }
+    }
+
+    fn running_reward(&self) -> bool {
+        true
+    }
+
+    /// With random spawns, every empty cell is equally likely and gets a
+    /// 4 with probability `FOUR_PROBABILITY`.
+    fn chance_outcomes(&self) -> Option<Vec<(Action, f32)>> {
+        if !(self.random_spawns && self.spawner_to_move) {
+            return None;
+        }
+        let actions = self.allowed_actions();
+        let cell = 2. / actions.len() as f32;
+        Some(actions.into_iter().map(|action| match action {
+            Action::Spawn { tile: 4, .. } => (action, cell * FOUR_PROBABILITY),
+            _ => (action, cell * (1. - FOUR_PROBABILITY))
+        }).collect())
+    }
+
+    /// Both sides are players, there is nothing random to seed.
+    fn set_rng_seed(&mut self, _seed: u32) {}
+
+    fn current_player(&self) -> usize {
+        if self.spawner_to_move { 1 } else { 0 }
+    }
+
+    fn hash_state(&self) -> Option<u64> {
+        self.board.hash_state().map(|hash| hash ^ self.spawner_to_move as u64)
+    }
+}
+
+/// The board as in 2048, whose turn it is and what the mover is rewarded
+/// for.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for Adversarial2048 {
+    fn to_json(&self) -> Value {
+        json!({
+            "board": self.board.to_json(),
+            "spawner_to_move": self.spawner_to_move,
+            "objective": match self.objective { Objective::Score => "score", Objective::Moves => "moves" },
+            "random_spawns": self.random_spawns,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Adversarial2048, String> {
+        let mut game = Adversarial2048::from_board(try!(TwoFortyEight::from_json(&value["board"])));
+        game.spawner_to_move = value["spawner_to_move"].as_bool().unwrap_or(false);
+        game.objective = try!(value["objective"].as_str().unwrap_or("score").parse());
+        game.random_spawns = value["random_spawns"].as_bool().unwrap_or(false);
+        Ok(game)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Saveable<Action> for Adversarial2048 {
+    fn board(&self) -> &TwoFortyEight {
+        &self.board
+    }
+
+    fn board_mut(&mut self) -> &mut TwoFortyEight {
+        &mut self.board
+    }
+
+    /// Any slide, or a spawn on any cell of the board.
+    fn parse_move(&self, text: &str) -> Result<Action, String> {
+        let mut actions = [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter()
+            .map(|&direction| Action::Move(direction))
+            .collect::<Vec<_>>();
+        for row in 0..self.board.height() {
+            for col in 0..self.board.width() {
+                for &tile in &[2, 4] {
+                    actions.push(Action::Spawn { row: row as u8, col: col as u8, tile: tile });
+                }
+            }
+        }
+        actions.into_iter()
+            .find(|action| format!("{:?}", action) == text)
+            .ok_or(format!("invalid move '{}'", text))
+    }
+}
+
+/// The mover slides as in 2048, the spawner spawns at random.
+impl RolloutPolicy<Adversarial2048, Action> for HeuristicRollout {
+    fn weights(&self, game: &Adversarial2048, actions: &[Action]) -> Vec<f32> {
+        if game.spawner_to_move {
+            return vec![1.; actions.len()];
+        }
+        let directions = actions.iter().map(|action| match *action {
+            Action::Move(direction) => direction,
+            Action::Spawn { .. } => panic!("The mover cannot spawn tiles"),
+        }).collect::<Vec<_>>();
+        self.slide_weights(&game.board, &directions)
+    }
+}
+
+impl fmt::Display for Adversarial2048 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}", self.board));
+        let to_move = if self.spawner_to_move { "spawner" } else { "mover" };
+        write!(f, "\n{} to move", to_move)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use utils::choose_random;
+    use adv2048::*;
+    use twofortyeight::{WIDTH, HEIGHT};
+
+    /// A board without merges and a single empty cell in the corner.
+    fn nearly_full() -> TwoFortyEight {
+        let mut board = TwoFortyEight::new_empty();
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                board.set_tile(row, col, if (row + col) % 2 == 0 { 2 } else { 4 });
+            }
+        }
+        board.set_tile(3, 3, 0);
+        board
+    }
+
+    #[test]
+    fn test_turns() {
+        let mut game = Adversarial2048::with_seed(1);
+        assert_eq!(game.current_player(), 0);
+        assert!(game.allowed_actions().iter().all(|a| match *a { Action::Move(_) => true, _ => false }));
+
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+        assert_eq!(game.current_player(), 1);
+        assert_eq!(game.allowed_actions().len(), 2 * (WIDTH * HEIGHT - 2));
+
+        let spawn = game.allowed_actions()[0];
+        game.make_move(&spawn);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.board.moves, 1);
+
+        let mut game = Adversarial2048::with_size(3, 2, Some(1));
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+        assert_eq!(game.allowed_actions().len(), 2 * (3 * 2 - 2));
+    }
+
+    #[test]
+    fn test_game_over() {
+        // (3, 2) holds a 4 and (2, 3) a 4, so a 2 in the corner merges with nothing
+        let mut game = Adversarial2048::from_board(nearly_full());
+        game.objective = Objective::Moves;
+        game.spawner_to_move = true;
+        assert_eq!(game.allowed_actions(), vec![Action::Spawn { row: 3, col: 3, tile: 2 },
+                                                Action::Spawn { row: 3, col: 3, tile: 4 }]);
+
+        let mut four = game.clone();
+        four.make_move(&Action::Spawn { row: 3, col: 3, tile: 4 });
+        assert!(!four.is_over());
+
+        game.make_move(&Action::Spawn { row: 3, col: 3, tile: 2 });
+        assert!(game.is_over());
+        assert!(game.allowed_actions().is_empty());
+        assert_eq!(game.reward(), 0.);
+        assert_eq!("moves".parse(), Ok(Objective::Moves));
+        assert!("tiles".parse::<Objective>().is_err());
+    }
+
+    #[test]
+    fn test_invariants() {
+        for seed in 0..20 {
+            let mut game = Adversarial2048::with_seed(seed);
+            while let Some(&action) = choose_random(&game.allowed_actions()) {
+                game.make_move(&action);
+                assert!(game.check_invariants().is_ok(), "seed {} after {:?}:\n{}", seed, action, game);
+            }
+            assert!(!game.spawner_to_move);
+        }
+
+        let mut game = Adversarial2048::with_seed(3);
+        game.spawner_to_move = true;
+        assert!(game.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_random_spawns() {
+        let mut game = Adversarial2048::with_seed(2);
+        assert_eq!(game.chance_outcomes(), None);
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+        assert_eq!(game.chance_outcomes(), None);
+
+        game.random_spawns = true;
+        let outcomes = game.chance_outcomes().unwrap();
+        assert_eq!(outcomes.len(), game.allowed_actions().len());
+        let cells = outcomes.len() as f32 / 2.;
+        for &(action, p) in &outcomes {
+            match action {
+                Action::Spawn { tile: 4, .. } => assert!((p * cells - 0.1).abs() < 1e-6),
+                _ => assert!((p * cells - 0.9).abs() < 1e-6)
+            }
+        }
+
+        game.make_move(&outcomes[0].0);
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(100, 1.);
+        let root = &mcts.roots()[0];
+        assert!(!root.is_chance());
+        // The mover's moves searched past the spawn lead to chance nodes
+        assert!(root.children().iter().filter(|child| child.visits() > 1).all(|child| child.is_chance()));
+    }
+
+    #[test]
+    fn test_heuristic_rollout() {
+        let mut game = Adversarial2048::with_seed(4);
+        let policy = HeuristicRollout::new();
+        let directions = game.board.allowed_actions();
+        let actions = game.allowed_actions();
+        assert_eq!(policy.weights(&game, &actions), policy.slide_weights(&game.board, &directions));
+
+        game.make_move(&actions[0]);
+        let spawns = game.allowed_actions();
+        assert_eq!(policy.weights(&game, &spawns), vec![1.; spawns.len()]);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.set_rollout_policy(policy);
+        mcts.search(50, 1.);
+        assert!(mcts.best_action().is_some());
+    }
+
+    #[test]
+    fn test_spawner_search() {
+        let mut game = Adversarial2048::with_seed(2);
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(100, 1.);
+        let spawn = mcts.best_action().unwrap();
+        assert!(game.allowed_actions().contains(&spawn));
+        match spawn {
+            Action::Spawn { .. } => {},
+            _ => panic!("Expected a spawn, got {:?}", spawn)
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_save_load() {
+        use std::env;
+        use std::fs;
+        use savegame::SavedGame;
+
+        let mut game = Adversarial2048::with_size(3, 4, Some(5));
+        game.objective = Objective::Moves;
+        let mut saved = SavedGame::new(game.clone(), Some(5), 2);
+        for _ in 0..5 {
+            let action = game.allowed_actions()[0];
+            game.make_move(&action);
+            saved.record(&game, action);
+        }
+        assert!(game.spawner_to_move);
+
+        let path = env::temp_dir().join(format!("mcts-adv2048-{}.json", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        saved.save(path).unwrap();
+        let loaded = SavedGame::<Adversarial2048, Action>::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.history, saved.history);
+        assert_eq!(loaded.index, 2);
+        assert_eq!(loaded.game.to_json(), game.to_json());
+        assert_eq!(loaded.game.allowed_actions(), game.allowed_actions());
+        assert!(game.parse_move("Spawn { row: 3, col: 2, tile: 8 }").is_err());
+    }
+}
@@ -0,0 +1,539 @@
+//!
+//! Agents that pick moves in a game.
+//!
+//! An `Agent` encapsulates a move-selection strategy so that different
+//! strategies and configurations can be pitted against each other, e.g.
+//! in tournaments. `AgentConfig` describes an agent in a compact textual
+//! form that is convenient on the command line.
+//!
+
+use std::fmt;
+
+use mcts::{GameAction, Game, MCTS, MctsConfig, TreePolicy, expected_reward, playout};
+use expectimax::best_value;
+#[cfg(feature = "serde")]
+use protocol::SerializableGame;
+#[cfg(feature = "serde")]
+use external::{ExternalAgent, EngineDialect};
+use timecontrol::{Clock, TimeControl, timed_search};
+use utils::{choose_random, seconds, RunningStats};
+
+/// Something that picks moves.
+pub trait Agent<G: Game<A>, A: GameAction> {
+
+    /// Choose an action for the given game state; None if the game is over.
+    fn choose_action(&mut self, game: &G) -> Option<A>;
+
+    /// Tell the agent its remaining time before it chooses an action in a
+    /// timed game; agents are free to ignore it.
+    fn set_clock(&mut self, _remaining: f32, _increment: f32) {}
+}
+
+/// Let the given agents play a game; agent i takes the turns of player i.
+///
+/// Returns the final game state.
+pub fn play_game<G: Game<A>, A: GameAction>(initial: &G, agents: &mut [&mut dyn Agent<G, A>]) -> G {
+    let mut game = initial.clone();
+
+    loop {
+        let player = game.current_player();
+        match agents[player].choose_action(&game) {
+            Some(action) => game.make_move(&action),
+            None => break
+        }
+    }
+    game
+}
+
+/// Outcome of a game played with clocks.
+pub struct TimedResult<G> {
+    pub game: G,
+    pub clock: Clock,
+    /// The player who ran out of time, if any.
+    pub flagged: Option<usize>,
+    /// Reward for player 0; running out of time loses the game.
+    pub reward: f32,
+}
+
+/// Let two agents play a game with the given time control.
+///
+/// Like `play_game`, but every move is charged to the mover's clock and a
+/// player whose time runs out loses.
+pub fn play_timed_game<G: Game<A>, A: GameAction>(initial: &G, agents: &mut [&mut dyn Agent<G, A>],
+                                                  control: TimeControl) -> TimedResult<G> {
+    let mut game = initial.clone();
+    let mut clock = Clock::new(control, agents.len());
+
+    loop {
+        let player = game.current_player();
+        agents[player].set_clock(clock.remaining(player), control.increment);
+
+        let t0 = seconds();
+        let action = agents[player].choose_action(&game);
+        if !clock.charge(player, (seconds() - t0) as f32) {
+            let reward = if player == 0 { -1. } else { 1. };
+            return TimedResult { game: game, clock: clock, flagged: Some(player), reward: reward };
+        }
+        match action {
+            Some(action) => game.make_move(&action),
+            None => break
+        }
+    }
+    let reward = game.reward();
+    TimedResult { game: game, clock: clock, flagged: None, reward: reward }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Agent choosing uniformly among the allowed actions.
+pub struct RandomAgent;
+
+impl<G: Game<A>, A: GameAction> Agent<G, A> for RandomAgent {
+    fn choose_action(&mut self, game: &G) -> Option<A> {
+        choose_random(&game.allowed_actions()).cloned()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How much search an `MctsAgent` or `FlatMonteCarloAgent` performs for
+/// every move.
+pub enum Budget {
+    Iterations(usize),                  // iterations per ensemble member
+    Time(f32),                          // seconds per move
+}
+
+/// Agent running a fresh MCTS search for every move.
+///
+/// In timed games the budget is ignored and the thinking time taken from
+/// the clock instead.
+pub struct MctsAgent {
+    pub ensemble_size: usize,
+    pub c: f32,
+    pub budget: Budget,
+    /// Further search parameters; `c` takes the place of its exploration.
+    pub config: MctsConfig,
+    clock: Option<(f32, f32)>,
+}
+
+impl MctsAgent {
+    /// Create a new agent with the given ensemble size and exploration constant.
+    pub fn new(ensemble_size: usize, c: f32, budget: Budget) -> MctsAgent {
+        MctsAgent {
+            ensemble_size: ensemble_size,
+            c: c,
+            budget: budget,
+            config: MctsConfig::default(),
+            clock: None,
+        }
+    }
+}
+
+impl<G: Game<A>, A: GameAction> Agent<G, A> for MctsAgent {
+    fn choose_action(&mut self, game: &G) -> Option<A> {
+        if let Some((remaining, increment)) = self.clock {
+            return timed_search(game, remaining, increment, self.ensemble_size, self.c).best_action();
+        }
+
+        let mut mcts = MCTS::with_config(game, self.ensemble_size, self.config);
+        match self.budget {
+            Budget::Iterations(n) => mcts.search(n, self.c),
+            Budget::Time(t) => mcts.search_time(t, self.c),
+        };
+        mcts.best_action()
+    }
+
+    fn set_clock(&mut self, remaining: f32, increment: f32) {
+        self.clock = Some((remaining, increment));
+    }
+}
+
+/// Agent trying every allowed action and choosing the one with the best
+/// expected reward over random playouts, without a tree.
+///
+/// With an iteration budget, every action gets that many playouts; with a
+/// time budget, the actions take turns until the time is up.
+pub struct FlatMonteCarloAgent {
+    pub budget: Budget,
+}
+
+impl FlatMonteCarloAgent {
+    /// Create a new agent with the given budget.
+    pub fn new(budget: Budget) -> FlatMonteCarloAgent {
+        FlatMonteCarloAgent { budget: budget }
+    }
+}
+
+impl<G: Game<A>, A: GameAction> Agent<G, A> for FlatMonteCarloAgent {
+    fn choose_action(&mut self, game: &G) -> Option<A> {
+        let successors = game.allowed_actions().into_iter()
+            .map(|action| {
+                let mut next = game.clone();
+                next.make_move(&action);
+                (action, next)
+            })
+            .collect::<Vec<_>>();
+
+        let values = match self.budget {
+            Budget::Iterations(n) => successors.iter()
+                .map(|&(action, ref next)| (action, expected_reward(next, n)))
+                .collect::<Vec<_>>(),
+            Budget::Time(t) => {
+                let t0 = seconds();
+                let mut stats = vec![RunningStats::new(); successors.len()];
+                while seconds() - t0 < t as f64 || stats.iter().any(|s| s.count() == 0) {
+                    for (&(_, ref next), stats) in successors.iter().zip(stats.iter_mut()) {
+                        stats.push(playout(next).reward());
+                    }
+                }
+                successors.iter().zip(&stats)
+                    .map(|(&(action, _), stats)| (action, stats.mean()))
+                    .collect()
+            }
+        };
+        best_value(&values, game.current_player()).map(|(action, _)| action)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Move-selection strategies an `AgentConfig` can describe.
+pub enum Policy {
+    Mcts, Random, FlatMonteCarlo, External
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A named agent configuration.
+///
+/// The textual form is `NAME[:KEY=VALUE,...]` with the keys
+///
+///   * `policy` -- `mcts` (default), `random`, `flat` (flat Monte Carlo) or
+///     `external`
+///   * `c` -- exploration constant (default 1.0)
+///   * `e` -- ensemble size (default 1)
+///   * `n` -- iterations per move (default 1000); playouts per action for
+///     flat Monte Carlo
+///   * `t` -- seconds per move
+///   * `cmd` -- command line of an external engine speaking the protocol
+///     of the `engine` binary; `n` and `t` are passed on to its `go`
+///   * `tree` -- tree policy: `ucb1` (default), `ucb1-tuned`, `greedy`,
+///     `puct` or `thompson`
+///   * `d` -- maximal number of moves per playout (default unlimited)
+///   * `p` -- playouts per new leaf (default 1)
+///   * `rave` -- RAVE parameter (default none)
+///   * `fpu` -- first play urgency (default none)
+///   * `contempt` -- contempt for draws, positive to avoid them (default 0)
+///
+/// `n` and `t` both set the budget; whichever comes last wins.
+///
+/// e.g. `explorer:c=2,n=500`, `short:d=10,p=4`, `baseline:policy=random`,
+/// `flat:policy=flat,n=100` or `other:policy=external,cmd=./engine -g tictactoe,t=0.5`.
+pub struct AgentConfig {
+    pub name: String,
+    pub policy: Policy,
+    pub c: f32,
+    pub ensemble_size: usize,
+    pub budget: Budget,
+    pub command: String,
+    /// Search parameters of MCTS agents besides `c`.
+    pub search: MctsConfig,
+}
+
+impl AgentConfig {
+    /// Default MCTS configuration with the given name.
+    pub fn new(name: &str) -> AgentConfig {
+        AgentConfig {
+            name: name.to_string(),
+            policy: Policy::Mcts,
+            c: 1.,
+            ensemble_size: 1,
+            budget: Budget::Iterations(1000),
+            command: String::new(),
+            search: MctsConfig::default(),
+        }
+    }
+
+    /// Parse a configuration from its textual form.
+    pub fn parse(spec: &str) -> Result<AgentConfig, String> {
+        let mut parts = spec.splitn(2, ':');
+        let name = parts.next().unwrap().trim();
+        if name.len() == 0 {
+            return Err(format!("Agent '{}' has no name", spec));
+        }
+
+        let mut config = AgentConfig::new(name);
+        try!(config.apply(parts.next().unwrap_or("")));
+        Ok(config)
+    }
+
+    /// Apply comma separated `KEY=VALUE` options to this configuration.
+    pub fn apply(&mut self, options: &str) -> Result<(), String> {
+        for option in options.split(',').filter(|o| o.trim().len() > 0) {
+            let mut kv = option.splitn(2, '=');
+            let key = kv.next().unwrap().trim();
+            match kv.next() {
+                Some(value) => try!(self.set(key, value.trim())),
+                None => return Err(format!("Expected KEY=VALUE, got '{}'", option))
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a single option; see above for the valid keys.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let bad_value = || format!("Invalid value '{}' for '{}' in agent '{}'", value, key, self.name);
+
+        match key {
+            "policy" => self.policy = match value {
+                "mcts" => Policy::Mcts,
+                "random" => Policy::Random,
+                "flat" => Policy::FlatMonteCarlo,
+                "external" => Policy::External,
+                _ => return Err(bad_value())
+            },
+            "c" => self.c = try!(value.parse().map_err(|_| bad_value())),
+            "e" => {
+                self.ensemble_size = try!(value.parse().map_err(|_| bad_value()));
+                if self.ensemble_size == 0 {
+                    return Err(format!("Agent '{}' needs an ensemble size of at least 1", self.name));
+                }
+            },
+            "n" => self.budget = Budget::Iterations(try!(value.parse().map_err(|_| bad_value()))),
+            "t" => self.budget = Budget::Time(try!(value.parse().map_err(|_| bad_value()))),
+            "cmd" => self.command = value.to_string(),
+            "tree" => self.search.tree_policy = match value {
+                "ucb1" => TreePolicy::Ucb1,
+                "ucb1-tuned" => TreePolicy::Ucb1Tuned,
+                "greedy" => TreePolicy::Greedy,
+                "puct" => TreePolicy::Puct,
+                "thompson" => TreePolicy::Thompson,
+                _ => return Err(bad_value())
+            },
+            "d" => self.search.max_playout_depth = Some(try!(value.parse().map_err(|_| bad_value()))),
+            "p" => self.search.playouts = try!(value.parse().map_err(|_| bad_value())),
+            "rave" => self.search.rave = Some(try!(value.parse().map_err(|_| bad_value()))),
+            "fpu" => self.search.fpu = Some(try!(value.parse().map_err(|_| bad_value()))),
+            "contempt" => self.search.contempt = try!(value.parse().map_err(|_| bad_value())),
+            _ => return Err(format!("Unknown option '{}' in agent '{}'", key, self.name))
+        }
+        Ok(())
+    }
+
+    /// Create an agent for this configuration.
+    ///
+    /// Fails if an external engine cannot be started.
+    #[cfg(feature = "serde")]
+    pub fn build<G: SerializableGame<A>, A: GameAction>(&self) -> Result<Box<dyn Agent<G, A>>, String> {
+        match self.policy {
+            Policy::External => {
+                if self.command.trim().is_empty() {
+                    return Err(format!("Agent '{}' needs a cmd to run", self.name));
+                }
+                let dialect = EngineDialect::new(Some(self.budget));
+                ExternalAgent::spawn::<G, A>(&self.command, dialect)
+                    .map(|agent| Box::new(agent) as Box<dyn Agent<G, A>>)
+                    .map_err(|e| format!("Failed to start '{}' for agent '{}': {}", self.command, self.name, e))
+            },
+            _ => Ok(self.build_local())
+        }
+    }
+
+    /// Create an agent for this configuration.
+    ///
+    /// External engines exchange their states as JSON and need the
+    /// `serde` feature.
+    #[cfg(not(feature = "serde"))]
+    pub fn build<G: Game<A>, A: GameAction>(&self) -> Result<Box<dyn Agent<G, A>>, String> {
+        match self.policy {
+            Policy::External => Err(format!("Agent '{}' needs the serde feature to run an engine", self.name)),
+            _ => Ok(self.build_local())
+        }
+    }
+
+    /// Create an agent searching in this process.
+    fn build_local<G: Game<A>, A: GameAction>(&self) -> Box<dyn Agent<G, A>> {
+        match self.policy {
+            Policy::Mcts => {
+                let mut agent = MctsAgent::new(self.ensemble_size, self.c, self.budget);
+                agent.config = self.search;
+                Box::new(agent)
+            },
+            Policy::Random => Box::new(RandomAgent),
+            Policy::FlatMonteCarlo => Box::new(FlatMonteCarloAgent::new(self.budget)),
+            Policy::External => unreachable!("External engines run in another process")
+        }
+    }
+}
+
+impl fmt::Display for AgentConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.policy {
+            Policy::Random => write!(f, "{}:policy=random", self.name),
+            Policy::FlatMonteCarlo => match self.budget {
+                Budget::Iterations(n) => write!(f, "{}:policy=flat,n={}", self.name, n),
+                Budget::Time(t) => write!(f, "{}:policy=flat,t={}", self.name, t),
+            },
+            Policy::External => {
+                try!(write!(f, "{}:policy=external,cmd={}", self.name, self.command));
+                match self.budget {
+                    Budget::Iterations(n) => write!(f, ",n={}", n),
+                    Budget::Time(t) => write!(f, ",t={}", t),
+                }
+            },
+            Policy::Mcts => {
+                try!(write!(f, "{}:c={},e={}", self.name, self.c, self.ensemble_size));
+                try!(match self.budget {
+                    Budget::Iterations(n) => write!(f, ",n={}", n),
+                    Budget::Time(t) => write!(f, ",t={}", t),
+                });
+                let search = &self.search;
+                try!(match search.tree_policy {
+                    TreePolicy::Ucb1Tuned => write!(f, ",tree=ucb1-tuned"),
+                    TreePolicy::Greedy => write!(f, ",tree=greedy"),
+                    TreePolicy::Puct => write!(f, ",tree=puct"),
+                    TreePolicy::Thompson => write!(f, ",tree=thompson"),
+                    _ => Ok(())
+                });
+                if let Some(depth) = search.max_playout_depth {
+                    try!(write!(f, ",d={}", depth));
+                }
+                if search.playouts != 1 {
+                    try!(write!(f, ",p={}", search.playouts));
+                }
+                if let Some(rave) = search.rave {
+                    try!(write!(f, ",rave={}", rave));
+                }
+                if let Some(fpu) = search.fpu {
+                    try!(write!(f, ",fpu={}", fpu));
+                }
+                if search.contempt != 0. {
+                    try!(write!(f, ",contempt={}", search.contempt));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use agents::*;
+    use timecontrol::TimeControl;
+    use tictactoe::*;
+
+    #[test]
+    fn test_parse() {
+        let config = AgentConfig::parse("explorer:c=2.5, e=4,n=500").unwrap();
+        assert_eq!(config.name, "explorer");
+        assert_eq!(config.policy, Policy::Mcts);
+        assert_eq!(config.c, 2.5);
+        assert_eq!(config.ensemble_size, 4);
+        assert_eq!(config.budget, Budget::Iterations(500));
+        assert_eq!(format!("{}", config), "explorer:c=2.5,e=4,n=500");
+
+        let mut config = AgentConfig::parse("timed:n=10,t=0.5").unwrap();
+        assert_eq!(config.budget, Budget::Time(0.5));
+        config.apply("n=20, c=3").unwrap();
+        assert_eq!(config.budget, Budget::Iterations(20));
+        assert_eq!(config.c, 3.);
+
+        assert_eq!(AgentConfig::parse("plain").unwrap(), AgentConfig::new("plain"));
+        assert_eq!(AgentConfig::parse("r:policy=random").unwrap().policy, Policy::Random);
+        let config = AgentConfig::parse("f:policy=flat,n=50").unwrap();
+        assert_eq!(config.policy, Policy::FlatMonteCarlo);
+        assert_eq!(AgentConfig::parse(&format!("{}", config)).unwrap(), config);
+
+        let config = AgentConfig::parse("x:policy=external,cmd=./engine -g tictactoe,t=0.5").unwrap();
+        assert_eq!(config.policy, Policy::External);
+        assert_eq!(config.command, "./engine -g tictactoe");
+        assert_eq!(format!("{}", config), "x:policy=external,cmd=./engine -g tictactoe,t=0.5");
+        assert!(AgentConfig::parse("x:policy=external").unwrap().build::<TicTacToe, _>().is_err());
+
+        let config = AgentConfig::parse("short:tree=ucb1-tuned,d=10,p=4,rave=300,fpu=0.5,contempt=0.1").unwrap();
+        assert_eq!(config.search, MctsConfig { tree_policy: TreePolicy::Ucb1Tuned, max_playout_depth: Some(10),
+                                               playouts: 4, rave: Some(300.), fpu: Some(0.5), contempt: 0.1,
+                                               ..MctsConfig::default() });
+        assert_eq!(format!("{}", config),
+                   "short:c=1,e=1,n=1000,tree=ucb1-tuned,d=10,p=4,rave=300,fpu=0.5,contempt=0.1");
+        assert_eq!(AgentConfig::parse(&format!("{}", config)).unwrap(), config);
+        assert!(AgentConfig::parse("x:tree=foo").is_err());
+
+        assert!(AgentConfig::parse(":c=1").is_err());
+        assert!(AgentConfig::parse("x:c").is_err());
+        assert!(AgentConfig::parse("x:c=abc").is_err());
+        assert!(AgentConfig::parse("x:e=0").is_err());
+        assert!(AgentConfig::parse("x:foo=1").is_err());
+    }
+
+    #[test]
+    fn test_play_game() {
+        let mut random1 = RandomAgent;
+        let mut random2 = RandomAgent;
+
+        let game = play_game(&TicTacToe::new(), &mut [&mut random1, &mut random2]);
+        assert!(game.is_finished());
+    }
+
+    #[test]
+    fn test_mcts_beats_random() {
+        let mut mcts = MctsAgent::new(1, 1., Budget::Iterations(1000));
+        let mut random = RandomAgent;
+
+        for _ in 0..5 {
+            let game = play_game(&TicTacToe::new(), &mut [&mut mcts, &mut random]);
+            assert!(game.reward() >= 0.);
+            let game = play_game(&TicTacToe::new(), &mut [&mut random, &mut mcts]);
+            assert!(game.reward() <= 0.);
+        }
+    }
+
+    #[test]
+    fn test_flat_monte_carlo() {
+        // X wins in the top row
+        let mut game = TicTacToe::new();
+        for &(row, col) in &[(0, 0), (1, 0), (0, 1), (2, 2)] {
+            game.make_move(&Action { row: row, col: col });
+        }
+        let mut flat = FlatMonteCarloAgent::new(Budget::Iterations(100));
+        assert_eq!(flat.choose_action(&game), Some(Action { row: 0, col: 2 }));
+
+        // ... and O has to block it
+        let mut game = TicTacToe::new();
+        for &(row, col) in &[(0, 0), (1, 1), (0, 1)] {
+            game.make_move(&Action { row: row, col: col });
+        }
+        assert_eq!(flat.choose_action(&game), Some(Action { row: 0, col: 2 }));
+
+        let mut flat = FlatMonteCarloAgent::new(Budget::Time(0.01));
+        let mut random = RandomAgent;
+        let game = play_game(&TicTacToe::new(), &mut [&mut flat, &mut random]);
+        assert!(game.is_finished());
+        assert_eq!(flat.choose_action(&game), None);
+    }
+
+    #[test]
+    fn test_timed_game() {
+        let control = TimeControl { base: 0.5, increment: 0.05 };
+        let mut mcts = MctsAgent::new(1, 1., Budget::Iterations(1));
+        let mut random = RandomAgent;
+
+        let result = play_timed_game(&TicTacToe::new(), &mut [&mut mcts, &mut random], control);
+        assert_eq!(result.flagged, None);
+        assert!(result.game.is_finished());
+        assert!(result.reward >= 0.);
+        assert!(result.clock.remaining(0) < 0.5 + 5. * 0.05);
+    }
+
+    #[bench]
+    fn bench_play_random(b: &mut Bencher) {
+        let mut random1 = RandomAgent;
+        let mut random2 = RandomAgent;
+
+        b.iter(|| play_game(&TicTacToe::new(), &mut [&mut random1, &mut random2]));
+    }
+}
@@ -0,0 +1,221 @@
+//!
+//! Tools to compare two agents.
+//!
+//! `MatchStats` collects the results of a match and estimates the
+//! difference in playing strength on the Elo scale, using the usual
+//! logistic model: an agent that is D Elo points stronger is expected to
+//! score 1 / (1 + 10^(-D/400)) points per game.
+//!
+
+use std::fmt;
+use std::f32;
+
+use rand::Rng;
+
+use mcts::{GameAction, Game};
+use utils::{choose_random_with, with_thread_rng};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// Wins, draws and losses from the perspective of one agent.
+pub struct MatchStats {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchStats {
+    /// Create empty statistics.
+    pub fn new() -> MatchStats {
+        MatchStats::default()
+    }
+
+    /// Record the outcome of a game given the reward for our agent.
+    pub fn add_result(&mut self, reward: f32) {
+        if reward > 0. {
+            self.wins += 1;
+        } else if reward < 0. {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    /// Add the results of another match.
+    pub fn merge(&mut self, other: &MatchStats) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    /// The same results from the perspective of the opponent.
+    pub fn reversed(&self) -> MatchStats {
+        MatchStats { wins: self.losses, draws: self.draws, losses: self.wins }
+    }
+
+    /// Number of games played.
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points scored; a win counts 1 and a draw 1/2.
+    pub fn points(&self) -> f32 {
+        self.wins as f32 + 0.5 * self.draws as f32
+    }
+
+    /// Average points per game.
+    pub fn score(&self) -> f32 {
+        self.points() / self.games() as f32
+    }
+
+    /// Standard error of the average score.
+    pub fn score_stderr(&self) -> f32 {
+        let n = self.games() as f32;
+        let s = self.score();
+        let var = (self.wins as f32 * (1. - s).powi(2) +
+                   self.draws as f32 * (0.5 - s).powi(2) +
+                   self.losses as f32 * s.powi(2)) / n;
+        (var / n).sqrt()
+    }
+
+    /// Confidence interval for the average score, clipped to [0, 1].
+    ///
+    /// `z` is the number of standard errors, e.g. 1.96 for 95%.
+    pub fn score_interval(&self, z: f32) -> (f32, f32) {
+        let s = self.score();
+        let delta = z * self.score_stderr();
+        ((s - delta).max(0.), (s + delta).min(1.))
+    }
+
+    /// Estimated Elo difference to the opponent.
+    pub fn elo(&self) -> f32 {
+        elo_from_score(self.score())
+    }
+
+    /// Confidence interval for the Elo difference.
+    ///
+    /// `z` is the number of standard errors, e.g. 1.96 for 95%.
+    pub fn elo_interval(&self, z: f32) -> (f32, f32) {
+        let s = self.score();
+        let delta = z * self.score_stderr();
+        (elo_from_score(s - delta), elo_from_score(s + delta))
+    }
+}
+
+impl fmt::Display for MatchStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (lower, upper) = self.elo_interval(1.96);
+        write!(f, "+{} ={} -{}  score {:.3}  Elo {:+.0} [{:+.0}, {:+.0}]",
+               self.wins, self.draws, self.losses, self.score(), self.elo(), lower, upper)
+    }
+}
+
+/// Convert an average score into an Elo difference.
+///
+/// Scores of 0 and 1 map to infinite differences.
+pub fn elo_from_score(score: f32) -> f32 {
+    if score <= 0. {
+        f32::NEG_INFINITY
+    } else if score >= 1. {
+        f32::INFINITY
+    } else {
+        -400. * (1. / score - 1.).log10()
+    }
+}
+
+/// Play up to `n_moves` random moves to create a varied starting position.
+pub fn random_opening<G: Game<A>, A: GameAction>(initial: &G, n_moves: usize) -> G {
+    with_thread_rng(|rng| random_opening_with(initial, n_moves, rng))
+}
+
+/// Play up to `n_moves` random moves drawn from `rng`.
+pub fn random_opening_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, n_moves: usize, rng: &mut R) -> G {
+    let mut game = initial.clone();
+
+    for _ in 0..n_moves {
+        match choose_random_with(rng, &game.allowed_actions()) {
+            Some(action) => game.make_move(action),
+            None => break,
+        }
+    }
+    game
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+    use test::Bencher;
+
+    use mcts::*;
+    use arena::*;
+    use tictactoe::*;
+
+    fn allmost_equal(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_add_result() {
+        let mut stats = MatchStats::new();
+        stats.add_result(1.);
+        stats.add_result(0.);
+        stats.add_result(-1.);
+        stats.add_result(0.5);
+
+        assert_eq!(stats, MatchStats { wins: 2, draws: 1, losses: 1 });
+        assert_eq!(stats.games(), 4);
+        assert_eq!(stats.points(), 2.5);
+        assert_eq!(stats.reversed(), MatchStats { wins: 1, draws: 1, losses: 2 });
+        println!("{}", stats);
+    }
+
+    #[test]
+    fn test_elo() {
+        assert_eq!(elo_from_score(0.5), 0.);
+        assert!(allmost_equal(elo_from_score(1. / 1.1), 400.));
+        assert!(allmost_equal(elo_from_score(0.1 / 1.1), -400.));
+        assert_eq!(elo_from_score(1.), f32::INFINITY);
+        assert_eq!(elo_from_score(0.), f32::NEG_INFINITY);
+
+        let stats = MatchStats { wins: 55, draws: 0, losses: 45 };
+        assert!(allmost_equal(stats.score(), 0.55));
+        assert!(allmost_equal(stats.score_stderr(), (0.2475f32 / 100.).sqrt()));
+
+        // 100 games are not enough to tell whether 55% is significant
+        let (lower, upper) = stats.elo_interval(1.96);
+        assert!(lower < stats.elo() && stats.elo() < upper);
+        assert!(lower < 0. && upper > 0.);
+        let (lower, upper) = stats.score_interval(1.96);
+        assert!(allmost_equal(upper - 0.55, 0.55 - lower));
+        assert!(lower < 0.5 && upper > 0.5);
+        assert_eq!(MatchStats { wins: 3, draws: 0, losses: 0 }.score_interval(1.96), (1., 1.));
+    }
+
+    #[test]
+    fn test_elo_interval_shrinks() {
+        let few = MatchStats { wins: 6, draws: 2, losses: 2 };
+        let many = MatchStats { wins: 600, draws: 200, losses: 200 };
+        let (few_lo, few_hi) = few.elo_interval(1.96);
+        let (many_lo, many_hi) = many.elo_interval(1.96);
+
+        assert!(allmost_equal(few.elo(), many.elo()));
+        assert!(many_hi - many_lo < few_hi - few_lo);
+    }
+
+    #[test]
+    fn test_random_opening() {
+        let game = random_opening(&TicTacToe::new(), 3);
+        assert_eq!(game.moves, 3);
+
+        let game = random_opening(&TicTacToe::new(), 20);
+        assert!(game.is_finished());
+        assert_eq!(game.allowed_actions().len(), 0);
+    }
+
+    #[bench]
+    fn bench_random_opening(b: &mut Bencher) {
+        let game = TicTacToe::new();
+        b.iter(|| random_opening(&game, 4));
+    }
+}
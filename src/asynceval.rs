@@ -0,0 +1,279 @@
+//!
+//! Asynchronous leaf evaluation (with the `async` feature).
+//!
+//! Evaluators that wait for I/O, like an inference server behind HTTP or
+//! gRPC, implement `AsyncEvaluator`. Its `evaluate` returns a future, so in
+//! crates of the 2018 edition and later it is simply an `async fn`:
+//!
+//! ```ignore
+//! impl AsyncEvaluator<TwoFortyEight> for Server {
+//!     async fn evaluate(&self, game: TwoFortyEight) -> f32 {
+//!         self.client.value(game.to_json()).await.unwrap_or(0.)
+//!     }
+//! }
+//!
+//! let dispatch = Dispatch::new(Server::connect(url))?;
+//! mcts.run_pipelined(1000, 16, &mut dispatch.pipeline());
+//! ```
+//!
+//! `Dispatch` drives the futures on a tokio runtime. With its `pipeline`,
+//! the search does not stop for the replies: it goes on selecting leaves,
+//! with virtual losses on their paths, while up to 16 evaluations are out,
+//! and folds in every reply as soon as it arrives; see
+//! `MCTS::run_pipelined`. Meanwhile the I/O runs on the worker threads of
+//! the runtime.
+//!
+//! `Dispatch` is also a plain `Evaluator`, e.g. for `MCTS::run_batched`.
+//! That is a blocking adapter: all leaves of a batch are evaluated
+//! concurrently, but the search thread waits for the slowest reply of the
+//! batch before it goes on.
+//!
+
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::future::{self, Future};
+use std::task::{Context, Poll, Waker};
+
+use tokio::runtime::{Builder, Runtime};
+
+use mcts::{Evaluator, PipelinedEvaluator};
+
+/// Values states that are not over yet for player 0, asynchronously.
+pub trait AsyncEvaluator<G> {
+    /// The value of `game` for player 0, once it is known.
+    fn evaluate(&self, game: G) -> impl Future<Output = f32>;
+}
+
+/// An `Evaluator` waiting for an `AsyncEvaluator` on a tokio runtime.
+///
+/// It must not be used from within the runtime itself, like any blocking
+/// call.
+pub struct Dispatch<E> {
+    evaluator: E,
+    runtime: Runtime,
+}
+
+impl<E> Dispatch<E> {
+    /// Dispatch to `evaluator` on a new multi-threaded runtime.
+    pub fn new(evaluator: E) -> io::Result<Dispatch<E>> {
+        let runtime = try!(Builder::new_multi_thread().enable_all().build());
+        Ok(Dispatch::with_runtime(evaluator, runtime))
+    }
+
+    /// Dispatch to `evaluator` on the given runtime.
+    pub fn with_runtime(evaluator: E, runtime: Runtime) -> Dispatch<E> {
+        Dispatch { evaluator: evaluator, runtime: runtime }
+    }
+
+    /// The asynchronous evaluator.
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+
+    /// The runtime the evaluations run on.
+    pub fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+
+    /// A `PipelinedEvaluator` for `MCTS::run_pipelined`, which only blocks
+    /// when the search waits for a reply.
+    pub fn pipeline<'a>(&'a self) -> Pipeline<'a, E> {
+        Pipeline { dispatch: self, pending: Vec::new() }
+    }
+}
+
+impl<G: Clone, E: AsyncEvaluator<G>> Evaluator<G> for Dispatch<E> {
+    fn evaluate(&self, game: &G) -> f32 {
+        self.runtime.block_on(self.evaluator.evaluate(game.clone()))
+    }
+
+    fn evaluate_batch(&self, games: &[G]) -> Vec<f32> {
+        let pending = games.iter().map(|game| self.evaluator.evaluate(game.clone())).collect();
+        self.runtime.block_on(JoinAll::new(pending))
+    }
+}
+
+/// An evaluation that is out.
+type Pending<'a> = Pin<Box<dyn Future<Output = f32> + 'a>>;
+
+/// The evaluations of a `Dispatch` that are out, see `Dispatch::pipeline`.
+///
+/// The futures are polled on the search thread, within the context of the
+/// runtime, whenever the search looks for replies.
+pub struct Pipeline<'a, E: 'a> {
+    dispatch: &'a Dispatch<E>,
+    pending: Vec<(usize, Pending<'a>)>,
+}
+
+impl<'a, E> Pipeline<'a, E> {
+    /// Poll every pending evaluation once; returns the finished ones.
+    fn poll_pending(&mut self, cx: &mut Context) -> Vec<(usize, f32)> {
+        let mut values = Vec::new();
+        self.pending.retain_mut(|&mut (ticket, ref mut future)| match future.as_mut().poll(cx) {
+            Poll::Ready(value) => {
+                values.push((ticket, value));
+                false
+            },
+            Poll::Pending => true
+        });
+        values
+    }
+}
+
+impl<'a, G: 'a, E: AsyncEvaluator<G>> PipelinedEvaluator<G> for Pipeline<'a, E> {
+    fn submit(&mut self, ticket: usize, game: G) {
+        self.pending.push((ticket, Box::pin(self.dispatch.evaluator.evaluate(game))));
+    }
+
+    fn ready(&mut self) -> Vec<(usize, f32)> {
+        let _context = self.dispatch.runtime.enter();
+        self.poll_pending(&mut Context::from_waker(Waker::noop()))
+    }
+
+    fn wait(&mut self) -> Vec<(usize, f32)> {
+        let dispatch = self.dispatch;
+        dispatch.runtime.block_on(future::poll_fn(|cx| {
+            let values = self.poll_pending(cx);
+            if values.is_empty() { Poll::Pending } else { Poll::Ready(values) }
+        }))
+    }
+}
+
+/// Waits for all of a number of futures, polling them concurrently.
+struct JoinAll<F: Future> {
+    pending: Vec<Option<Pin<Box<F>>>>,
+    values: Vec<Option<F::Output>>,
+}
+
+impl<F: Future> JoinAll<F> {
+    fn new(futures: Vec<F>) -> JoinAll<F> {
+        let values = futures.iter().map(|_| None).collect();
+        JoinAll { pending: futures.into_iter().map(|future| Some(Box::pin(future))).collect(), values: values }
+    }
+}
+
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Vec<F::Output>> {
+        let this = &mut *self;
+        for (slot, value) in this.pending.iter_mut().zip(this.values.iter_mut()) {
+            let ready = match *slot {
+                Some(ref mut future) => future.as_mut().poll(cx),
+                None => continue
+            };
+            if let Poll::Ready(output) = ready {
+                *value = Some(output);
+                *slot = None;
+            }
+        }
+        if this.pending.iter().any(Option::is_some) {
+            return Poll::Pending;
+        }
+        let values = mem::take(&mut this.values);
+        Poll::Ready(values.into_iter().map(Option::unwrap).collect())
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::future::{self, Future};
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+
+    use mcts::*;
+    use asynceval::*;
+    use minigame::MiniGame;
+    use utils::seeded_rng;
+
+    struct Constant(f32);
+
+    impl<G> AsyncEvaluator<G> for Constant {
+        fn evaluate(&self, _: G) -> impl Future<Output = f32> {
+            future::ready(self.0)
+        }
+    }
+
+    /// Every evaluation waits until `size` of them have started.
+    struct Rendezvous {
+        size: usize,
+        arrived: Arc<Mutex<(usize, Vec<Waker>)>>,
+    }
+
+    struct Arrival {
+        size: usize,
+        arrived: Arc<Mutex<(usize, Vec<Waker>)>>,
+        counted: bool,
+    }
+
+    impl Future for Arrival {
+        type Output = f32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<f32> {
+            let shared = self.arrived.clone();
+            let mut arrived = shared.lock().unwrap();
+            if !self.counted {
+                arrived.0 += 1;
+            }
+            self.counted = true;
+            if arrived.0 < self.size {
+                arrived.1.push(cx.waker().clone());
+                return Poll::Pending;
+            }
+            for waker in arrived.1.drain(..) {
+                waker.wake();
+            }
+            Poll::Ready(0.5)
+        }
+    }
+
+    impl<G> AsyncEvaluator<G> for Rendezvous {
+        fn evaluate(&self, _: G) -> impl Future<Output = f32> {
+            Arrival { size: self.size, arrived: self.arrived.clone(), counted: false }
+        }
+    }
+
+    #[test]
+    fn test_dispatch() {
+        // Dispatched evaluations are the plain ones
+        let game = MiniGame::new();
+        let dispatch = Dispatch::new(Constant(0.25)).unwrap();
+        assert_eq!(Evaluator::<MiniGame>::evaluate_batch(&dispatch, &[game.clone(), game.clone()]), vec![0.25, 0.25]);
+
+        let mut plain = MCTS::new(&game, 1);
+        plain.run_evaluated_with(50, &|_: &MiniGame| 0.25, &mut seeded_rng(2));
+        let mut dispatched = MCTS::new(&game, 1);
+        dispatched.run_batched_with(50, 1, &dispatch, &mut seeded_rng(2));
+        assert_eq!(plain.action_values(), dispatched.action_values());
+        let mut pipelined = MCTS::new(&game, 1);
+        pipelined.run_pipelined_with(50, 1, &mut dispatch.pipeline(), &mut seeded_rng(2));
+        assert_eq!(plain.action_values(), pipelined.action_values());
+    }
+
+    #[test]
+    fn test_concurrent_batch() {
+        // The evaluations of a batch only finish once all of them started
+        let evaluator = Rendezvous { size: 4, arrived: Arc::new(Mutex::new((0, Vec::new()))) };
+        let dispatch = Dispatch::new(evaluator).unwrap();
+        let games = vec![MiniGame::new(); 4];
+        assert_eq!(dispatch.evaluate_batch(&games), vec![0.5; 4]);
+        assert_eq!(dispatch.evaluator().arrived.lock().unwrap().0, 4);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        // No reply comes before four leaves are out, so a search that
+        // waited for every reply before selecting the next leaf would hang
+        let evaluator = Rendezvous { size: 4, arrived: Arc::new(Mutex::new((0, Vec::new()))) };
+        let dispatch = Dispatch::new(evaluator).unwrap();
+        let mut mcts = MCTS::new(&MiniGame::new(), 2);
+        mcts.run_pipelined_with(30, 4, &mut dispatch.pipeline(), &mut seeded_rng(3));
+        assert!(mcts.roots().iter().all(|root| root.visits() == 30));
+    }
+}
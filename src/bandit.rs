@@ -0,0 +1,260 @@
+//!
+//! Implementation of a k-armed bandit.
+//!
+//! The agent makes a single decision: which arm to pull. Each arm pays out a
+//! reward of 1 with a fixed, arm specific probability and 0 otherwise.
+//!
+//! Because there is only a single move, the children of an MCTS root node
+//! are exactly the arms of the bandit. This makes the game useful to test
+//! the tree policy in isolation: the number of times suboptimal arms get
+//! selected directly corresponds to the regret of the policy.
+//!
+
+use std::fmt;
+use rand::{Rng, XorShiftRng};
+
+use mcts::{GameAction, Game};
+use utils::seeded_rng;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Pull the arm with the given index.
+pub struct Arm(pub usize);
+impl GameAction for Arm {}
+
+#[derive(Clone)]
+/// A k-armed bandit with Bernoulli distributed rewards.
+pub struct Bandit {
+    rng: XorShiftRng,
+    probs: Vec<f32>,                    // success probability for each arm
+    pulled: Option<usize>,              // the arm we pulled, if any
+    score: f32,
+}
+
+impl Bandit {
+    /// Create a new bandit with the given success probabilities.
+    pub fn new(probs: Vec<f32>) -> Bandit {
+        assert!(probs.len() > 0);
+        assert!(probs.iter().all(|&p| p >= 0. && p <= 1.));
+
+        Bandit {
+            rng: seeded_rng(1),
+            probs: probs,
+            pulled: None,
+            score: 0.,
+        }
+    }
+
+    /// Number of arms.
+    pub fn arms(&self) -> usize {
+        self.probs.len()
+    }
+
+    /// The arm that was pulled, if any.
+    pub fn pulled(&self) -> Option<Arm> {
+        self.pulled.map(|idx| Arm(idx))
+    }
+
+    /// The arm with the highest success probability.
+    pub fn best_arm(&self) -> Arm {
+        let mut best = 0;
+        for (idx, &p) in self.probs.iter().enumerate() {
+            if p > self.probs[best] {
+                best = idx;
+            }
+        }
+        Arm(best)
+    }
+
+    /// Expected reward when pulling the given arm.
+    pub fn mean(&self, arm: &Arm) -> f32 {
+        self.probs[arm.0]
+    }
+
+    /// Expected loss when pulling the given arm instead of the best one.
+    pub fn regret(&self, arm: &Arm) -> f32 {
+        self.mean(&self.best_arm()) - self.mean(arm)
+    }
+}
+
+impl Game<Arm> for Bandit {
+
+    /// Return a list with all allowed actions given the current game state.
+    fn allowed_actions(&self) -> Vec<Arm> {
+        match self.pulled {
+            Some(_) => Vec::new(),
+            None    => (0..self.arms()).map(|idx| Arm(idx)).collect()
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.pulled.is_some()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, arm: &Arm) {
+        assert!(self.pulled.is_none(), "Illegal move");
+
+        self.pulled = Some(arm.0);
+        if self.rng.next_f32() < self.probs[arm.0] {
+            self.score = 1.;
+        }
+    }
+
+    /// Reward for the player when reaching the current game state.
+    fn reward(&self) -> f32 {
+        self.score
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, seed: u32) {
+        self.rng = seeded_rng(seed);
+    }
+}
+
+impl fmt::Display for Bandit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "Bandit with probabilities {:?}", self.probs));
+        match self.pulled {
+            Some(idx) => write!(f, ": pulled arm {} with reward {}", idx, self.score),
+            None      => Ok(())
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+    use test::Bencher;
+
+    use mcts::*;
+    use bandit::*;
+    use utils::seeded_rng;
+
+    #[test]
+    fn test_new() {
+        let bandit = Bandit::new(vec![0.1, 0.9, 0.5]);
+
+        assert_eq!(bandit.arms(), 3);
+        assert_eq!(bandit.allowed_actions(), vec![Arm(0), Arm(1), Arm(2)]);
+        assert_eq!(bandit.best_arm(), Arm(1));
+        assert!((bandit.regret(&Arm(0)) - 0.8).abs() < 1e-6);
+        assert_eq!(bandit.regret(&Arm(1)), 0.);
+        println!("{}", bandit);
+    }
+
+    #[test]
+    fn test_pull() {
+        let mut bandit = Bandit::new(vec![0., 1.]);
+
+        bandit.make_move(&Arm(1));
+        assert_eq!(bandit.pulled(), Some(Arm(1)));
+        assert_eq!(bandit.reward(), 1.);
+        assert_eq!(bandit.allowed_actions().len(), 0);
+        println!("{}", bandit);
+    }
+
+    #[test]
+    fn test_reward_probability() {
+        let bandit = Bandit::new(vec![0.3]);
+
+        let mut sum = 0.;
+        for seed in 0..1000 {
+            let mut b = bandit.clone();
+            b.set_rng_seed(seed);
+            b.make_move(&Arm(0));
+            sum += b.reward();
+        }
+        assert!((sum / 1000. - 0.3).abs() < 0.05);
+    }
+
+    /// Pull arms according to the tree policy and return the total regret
+    /// after every pull.
+    ///
+    /// Every iteration uses a differently seeded bandit, so that the
+    /// rewards are independent draws.
+    fn tree_policy_regret(bandit: &Bandit, n_pulls: usize, policy: TreePolicy) -> Vec<f32> {
+        let config = MctsConfig { tree_policy: policy, ..MctsConfig::default() };
+        let mut rng = seeded_rng(5);
+        let mut tree = Tree::new();
+
+        let mut regret = 0.;
+        let mut regrets = Vec::new();
+        for t in 0..n_pulls {
+            let mut b = bandit.clone();
+            b.set_rng_seed(t as u32);
+            tree.iteration_config(&mut b, &config, &mut rng, None);
+
+            regret += bandit.regret(&b.pulled().unwrap());
+            regrets.push(regret);
+        }
+        regrets
+    }
+
+    /// Upper bound on the expected regret of UCB1 after `n_pulls` pulls
+    /// (Auer et al., 2002).
+    fn ucb1_bound(bandit: &Bandit, n_pulls: usize) -> f32 {
+        let gaps = (0..bandit.arms())
+                .map(|idx| bandit.regret(&Arm(idx)))
+                .filter(|&gap| gap > 0.)
+                .collect::<Vec<_>>();
+        gaps.iter().fold(0., |sum, gap| {
+            sum + 8. * (n_pulls as f32).ln() / gap + (1. + f32::consts::PI.powi(2) / 3.) * gap
+        })
+    }
+
+    /// Regret grows logarithmically: the second half of the pulls adds
+    /// less regret than the first half.
+    fn logarithmic(regrets: &[f32]) -> bool {
+        let (half, total) = (regrets[regrets.len() / 2 - 1], regrets[regrets.len() - 1]);
+        total - half < half
+    }
+
+    #[test]
+    fn test_ucb1_regret() {
+        let bandit = Bandit::new(vec![0.2, 0.4, 0.6, 0.8]);
+        let n_pulls = 4000;
+
+        let regrets = tree_policy_regret(&bandit, n_pulls, TreePolicy::Ucb1);
+        let regret = regrets[n_pulls-1];
+        let bound = ucb1_bound(&bandit, n_pulls);
+        println!("UCB1 regret after {} pulls: {} (bound {})", n_pulls, regret, bound);
+        assert!(regret < bound);
+        assert!(logarithmic(&regrets));
+    }
+
+    #[test]
+    fn test_ucb1_tuned_regret() {
+        // The rewards of the arms vary little, which UCB1-Tuned exploits
+        let bandit = Bandit::new(vec![0.05, 0.1, 0.15, 0.3]);
+        let n_pulls = 4000;
+
+        let tuned = tree_policy_regret(&bandit, n_pulls, TreePolicy::Ucb1Tuned);
+        let ucb1 = tree_policy_regret(&bandit, n_pulls, TreePolicy::Ucb1);
+        println!("UCB1-Tuned regret after {} pulls: {} (UCB1 {})", n_pulls, tuned[n_pulls-1], ucb1[n_pulls-1]);
+        assert!(tuned[n_pulls-1] < ucb1[n_pulls-1]);
+        assert!(tuned[n_pulls-1] < ucb1_bound(&bandit, n_pulls));
+        assert!(logarithmic(&tuned));
+    }
+
+    #[test]
+    fn test_thompson_regret() {
+        let bandit = Bandit::new(vec![0.2, 0.4, 0.6, 0.8]);
+        let n_pulls = 4000;
+
+        let thompson = tree_policy_regret(&bandit, n_pulls, TreePolicy::Thompson);
+        let ucb1 = tree_policy_regret(&bandit, n_pulls, TreePolicy::Ucb1);
+        println!("Thompson regret after {} pulls: {} (UCB1 {})", n_pulls, thompson[n_pulls-1], ucb1[n_pulls-1]);
+        assert!(thompson[n_pulls-1] < ucb1[n_pulls-1]);
+        assert!(logarithmic(&thompson));
+    }
+
+    #[bench]
+    fn bench_iteration(b: &mut Bencher) {
+        let bandit = Bandit::new(vec![0.2, 0.4, 0.6, 0.8]);
+        let mut tree = Tree::new();
+
+        b.iter(|| tree.iteration(&mut bandit.clone(), 1.))
+    }
+}
@@ -1,90 +1,609 @@
 
 extern crate argparse;
+extern crate ctrlc;
 extern crate time;
+#[macro_use]
+extern crate serde_json;
+extern crate env_logger;
 extern crate mcts;
 
-use argparse::{ArgumentParser, StoreTrue, Store};
+use std::io;
+use std::process;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
-use mcts::mcts::{Game, MCTS};
-use mcts::twofortyeight::TwoFortyEight;
+use serde_json::Value;
+
+use argparse::{ArgumentParser, StoreTrue, Store, StoreOption};
+
+use mcts::mcts::{Game, MCTS, MctsConfig, Afterstates, SearchReport};
+use mcts::twofortyeight::{TwoFortyEight, Action, GameStatus, HeuristicRollout, WIDTH, HEIGHT, DEFAULT_TARGET};
+use mcts::expectimax::{Expectimax, best_value};
+use mcts::agents::Budget;
+use mcts::cli::{Engine, Session, play};
+use mcts::config::Config;
+use mcts::records::{RecordWriter, Analysis, action_values_json, expectimax_values_json, board_json};
+use mcts::savegame::SavedGame;
+use mcts::treedump::TreeDump;
+use mcts::utils::{derive_seed, seed_thread_rng, seconds, RunningStats};
+
+/// The size of the board, when a game counts as won, and what the search
+/// gets for winning or losing on top of the score.
+#[derive(Debug, Clone, Copy)]
+struct Rules {
+    width: usize,
+    height: usize,
+    target: u16,
+    win_bonus: f32,
+    loss_penalty: f32,
+}
+
+impl Rules {
+    /// Set the target of a game; its board keeps its size.
+    fn apply(&self, game: &mut TwoFortyEight) {
+        game.target = self.target;
+        game.win_bonus = self.win_bonus;
+        game.loss_penalty = self.loss_penalty;
+    }
+}
+
+/// Which search picks the moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Mcts,
+    /// Depth-limited expectimax, as a baseline.
+    Expectimax,
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Method, String> {
+        match name {
+            "mcts" => Ok(Method::Mcts),
+            "expectimax" => Ok(Method::Expectimax),
+            _ => Err(format!("Unknown engine '{}', expected mcts or expectimax", name))
+        }
+    }
+}
+
+/// Which search picks the moves; how much the MCTS searches every move,
+/// how it plays out positions and whether its tree stands for afterstates,
+/// or how deep expectimax looks ahead.
+#[derive(Debug, Clone, Copy)]
+struct Search {
+    method: Method,
+    depth: usize,
+    budget: Budget,
+    ensemble_size: usize,
+    rollout: Option<HeuristicRollout>,
+    afterstates: Option<Afterstates>,
+}
+
+impl Search {
+    /// The parameters of the tree search.
+    fn config(&self) -> MctsConfig {
+        MctsConfig { afterstates: self.afterstates, ..MctsConfig::default() }
+    }
+
+    /// A new search of the given position.
+    fn start(&self, game: &TwoFortyEight) -> MCTS<TwoFortyEight, Action> {
+        let mut mcts = MCTS::with_config(game, self.ensemble_size, self.config());
+        if let Some(rollout) = self.rollout {
+            mcts.set_rollout_policy(rollout);
+        }
+        mcts
+    }
+}
+
+/// Create the game with the given index.
+///
+/// With a master seed, both the tile spawns and the search are seeded
+/// from it, so that the game can be replayed exactly.
+fn new_game(seed: Option<u32>, index: usize, rules: Rules) -> TwoFortyEight {
+    let seed = seed.map(|seed| {
+        seed_thread_rng(derive_seed(seed, 2*index as u32 + 1));
+        derive_seed(seed, 2*index as u32)
+    });
+    let mut game = TwoFortyEight::with_size(rules.width, rules.height, seed);
+    rules.apply(&mut game);
+    game
+}
+
+/// The game in progress, written to the save file on interrupt.
+type Progress = Arc<Mutex<Option<SavedGame>>>;
+
+/// Save the game in progress and exit when the user presses Ctrl-C.
+fn save_on_interrupt(progress: Progress, path: String) {
+    ctrlc::set_handler(move || {
+        if let Some(ref saved) = *progress.lock().unwrap() {
+            match saved.save(&path) {
+                Ok(()) => eprintln!("\nSaved the game after {} moves to {}; continue with --resume.",
+                                    saved.history.len(), path),
+                Err(msg) => eprintln!("\n{}", msg),
+            }
+        }
+        process::exit(130);
+    }).expect("Failed to install the interrupt handler");
+}
+
+/// Start the game with the given index, or continue the resumed one.
+fn start_game(seed: Option<u32>, index: usize, rules: Rules, resume: &mut Option<SavedGame>,
+              progress: &Progress) -> SavedGame {
+    let saved = match resume.take() {
+        Some(mut saved) => {
+            rules.apply(&mut saved.game);
+            saved
+        },
+        None => SavedGame::new(new_game(seed, index, rules), seed, index)
+    };
+    *progress.lock().unwrap() = Some(saved.clone());
+    saved
+}
+
+/// Search the current position within the given budget; returns the
+/// analysis of the result and the report of the search.
+fn think(mcts: &mut MCTS<TwoFortyEight, Action>, budget: Budget) -> (Analysis, SearchReport<Action>) {
+    let report = match budget {
+        Budget::Iterations(n) => mcts.search(n, 1.0),
+        Budget::Time(t) => mcts.search_time(t, 1.0),
+    };
+    (Analysis::from_report(mcts, &report), report)
+}
+
+/// Pick a move with expectimax; returns it with the analysis of the search
+/// and the values of all actions.
+fn expectimax_move(game: &TwoFortyEight, depth: usize) -> (Option<Action>, Analysis, Value) {
+    let t0 = seconds();
+    let mut expectimax = Expectimax::new(depth);
+    let values = expectimax.action_values(game);
+    let best = best_value(&values, 0);
+    let analysis = Analysis::expectimax(&expectimax, best, (seconds() - t0) as f32);
+    (best.map(|(action, _)| action), analysis, expectimax_values_json(&values))
+}
+
+/// Dump the trees of the last search if requested; exits on failure.
+fn dump_tree(dump: Option<&TreeDump>, mcts: &MCTS<TwoFortyEight, Action>, game: usize, move_number: usize) {
+    if let Some(dump) = dump {
+        if let Err(msg) = dump.write(mcts, game, move_number) {
+            println!("{}", msg);
+            process::exit(1);
+        }
+    }
+}
+
+/// Let the engine play repeat games on its own and print summary statistics.
+///
+/// With `records` given, JSON records for every move and game are
+/// written there instead of printing the boards. A resumed game is
+/// finished first, followed by the remaining games of its run.
+fn play_auto(repeats: usize, verbose: bool, search: Search, seed: Option<u32>, rules: Rules,
+             mut records: Option<RecordWriter>, mut resume: Option<SavedGame>, progress: &Progress,
+             dump: Option<&TreeDump>) {
+    // Summary statistics
+    let mut moves = RunningStats::new();
+    let mut scores = RunningStats::new();
+    let mut won = 0;
+
+    // Play repeat games in total...
+    let first = resume.as_ref().map_or(0, |saved| saved.index);
+    let seed = resume.as_ref().map_or(seed, |saved| saved.seed);
+    let repeats = repeats.max(first + 1);
+    for g in first..repeats {
+        // Create a game and a MCTS solver
+        let mut saved = start_game(seed, g, rules, &mut resume, progress);
+        let mut game = saved.game.clone();
+        let mut mcts = search.start(&game);
+
+        if records.is_none() {
+            println!("{}", game);
+        }
+        loop {
+            let (action, analysis, actions, report) = match search.method {
+                Method::Mcts => {
+                    let (analysis, report) = think(&mut mcts, search.budget);
+                    dump_tree(dump, &mcts, g, game.moves);
+                    let agreement = if verbose && mcts.ensemble_size() > 1 { Some(mcts.agreement()) } else { None };
+                    (mcts.best_action(), analysis, action_values_json(&mcts), Some((report, agreement)))
+                },
+                Method::Expectimax => {
+                    let (action, analysis, actions) = expectimax_move(&game, search.depth);
+                    (action, analysis, actions, None)
+                },
+            };
+
+            if verbose && records.is_none() {
+                println!("{}", analysis);
+                if let Some((report, agreement)) = report {
+                    println!("{}", report);
+                    if let Some(agreement) = agreement {
+                        println!("Ensemble {}", agreement);
+                    }
+                }
+            }
+
+            match action {
+                Some(action) => {
+                    if let Some(ref mut records) = records {
+                        records.write(&json!({
+                            "type": "move",
+                            "game": g,
+                            "move": game.moves,
+                            "action": format!("{:?}", action),
+                            "value": analysis.value,
+                            "actions": actions,
+                            "analysis": analysis.to_json(),
+                            "score": game.score,
+                            "board": board_json(&game),
+                        })).expect("Failed to write record");
+                    }
+                    game.make_move(&action);
+                    if search.method == Method::Mcts {
+                        mcts.advance_game(&game);
+                    }
+                    saved.record(&game, action);
+                    *progress.lock().unwrap() = Some(saved.clone());
+                    if records.is_none() {
+                        println!("\n... moving {:?}: {}", action, game);
+                    }
+                },
+                None => break
+            }
+        }
+
+        *progress.lock().unwrap() = None;
+        if let Some(ref mut records) = records {
+            records.write(&json!({
+                "type": "game",
+                "game": g,
+                "seed": seed,
+                "moves": game.moves,
+                "score": game.score,
+                "max_tile": game.max_tile(),
+                "won": game.game_status() == GameStatus::Won,
+                "board": board_json(&game),
+            })).expect("Failed to write record");
+        }
+
+        // Update summary statistics
+        moves.push(game.moves as f32);
+        scores.push(game.score as f32);
+        if game.game_status() == GameStatus::Won {
+            won += 1;
+        }
+    }
+
+    if repeats - first > 1 {
+        let games = repeats - first;
+        let (avg_moves, avg_moves_err) = (moves.mean(), moves.stderr());
+        let (avg_score, avg_score_err) = (scores.mean(), scores.stderr());
+        let win_rate = won as f32 / games as f32;
+
+        match records {
+            Some(ref mut records) => records.write(&json!({
+                "type": "summary",
+                "games": games,
+                "avg_moves": avg_moves,
+                "avg_moves_err": avg_moves_err,
+                "avg_score": avg_score,
+                "avg_score_err": avg_score_err,
+                "target": rules.target,
+                "won": won,
+                "win_rate": win_rate,
+            })).expect("Failed to write record"),
+            None => {
+                println!("Played {} games.", games);
+                println!("  Average # moves: {} (+/- {})", avg_moves, avg_moves_err);
+                println!("  Average Score:   {} (+/- {})", avg_score, avg_score_err);
+                println!("  {:<17}{} of {} games ({:.1}%)", format!("Reached {}:", rules.target),
+                         won, games, 100. * win_rate);
+            }
+        }
+    }
+}
+
+/// The MCTS assisting a human player, keeping the game in progress up to
+/// date for the save file.
+struct Assistant<'a> {
+    search: Search,
+    dump: Option<&'a TreeDump>,
+    saved: SavedGame,
+    resumed_moves: usize,
+    progress: &'a Progress,
+}
+
+impl<'a> Engine<TwoFortyEight, Action> for Assistant<'a> {
+    /// Evaluate the current position and print the analysis line.
+    fn search(&mut self, game: &TwoFortyEight) -> MCTS<TwoFortyEight, Action> {
+        let mut mcts = self.search.start(game);
+        let (analysis, _) = think(&mut mcts, self.search.budget);
+        dump_tree(self.dump, &mcts, self.saved.index, game.moves);
+        println!("{}", analysis);
+        mcts
+    }
+
+    fn changed(&mut self, session: &Session<TwoFortyEight, Action>) {
+        self.saved.game = session.game().clone();
+        self.saved.history.truncate(self.resumed_moves);
+        self.saved.history.extend(session.moves());
+        *self.progress.lock().unwrap() = Some(self.saved.clone());
+    }
+}
+
+/// Let a human play, optionally assisted by the MCTS.
+fn play_interactive(search: Search, seed: Option<u32>, rules: Rules,
+                    mut resume: Option<SavedGame>, progress: &Progress, dump: Option<&TreeDump>) {
+    let saved = start_game(seed, 0, rules, &mut resume, progress);
+    let mut session = Session::new(&saved.game);
+    let mut assistant = Assistant {
+        search: search,
+        dump: dump,
+        resumed_moves: saved.history.len(),
+        saved: saved,
+        progress: progress,
+    };
+
+    let stdin = io::stdin();
+    play(&mut session, 0, &mut assistant, stdin.lock(), io::stdout()).expect("Failed to talk to the terminal");
+    let game = session.game();
+    println!("Game over after {} moves with a score of {}.", game.moves, game.score);
+    if game.game_status() == GameStatus::Won {
+        println!("You reached {}!", game.target);
+    }
+}
+
+#[cfg(feature = "tui")]
+/// Let the MCTS play while showing a live view of the search.
+fn play_tui(game: TwoFortyEight, time_per_move: f32, search: Search) {
+    match mcts::tui::play_2048(game, time_per_move, search.ensemble_size, search.config(), search.rollout, 1.0) {
+        Ok(game) => println!("Game over after {} moves with a score of {}.", game.moves, game.score),
+        Err(e) => println!("Terminal error: {}", e)
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn play_tui(_: TwoFortyEight, _: f32, _: Search) {
+    println!("The live viewer requires building with '--features tui'.");
+}
 
 #[cfg_attr(test, allow(dead_code))]
 fn main() {
+    env_logger::init();
+
+    let mut auto = false;
+    let mut tui = false;
+    let mut json = false;
+    let mut output = "-".to_string();
     let mut repeats = 1;
     let mut verbose = false;
     let mut time_per_move = 1.0;
     let mut ensemble_size = 10;
+    let mut iterations = 0;
+    let mut method = Method::Mcts;
+    let mut depth = 2;
+    let mut heuristic_rollout = false;
+    let mut afterstates: Option<Afterstates> = None;
+    let mut seed: Option<u32> = None;
+    let mut save_path = "2048.save".to_string();
+    let mut resume = false;
+    let mut dump_path = String::new();
+    let mut dump_depth: Option<usize> = None;
+    let mut dump_min_visits = 0;
+    let mut config_path = String::new();
+    let mut rules = Rules { width: WIDTH, height: HEIGHT, target: DEFAULT_TARGET, win_bonus: 0., loss_penalty: 0. };
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("2048", &["auto", "json", "output", "verbose", "time_per_move",
+                                             "iterations", "seed", "ensemble_size", "engine", "depth", "heuristic_rollout",
+                                             "afterstates", "repeat", "save", "dump_tree", "dump_depth", "dump_min_visits",
+                                             "width", "height", "target", "win_bonus", "loss_penalty"])
+        .and_then(|_| config.get("2048", "auto", &mut auto))
+        .and_then(|_| config.get("2048", "json", &mut json))
+        .and_then(|_| config.get("2048", "output", &mut output))
+        .and_then(|_| config.get("2048", "verbose", &mut verbose))
+        .and_then(|_| config.get("2048", "time_per_move", &mut time_per_move))
+        .and_then(|_| config.get("2048", "iterations", &mut iterations))
+        .and_then(|_| config.get_option("2048", "seed", &mut seed))
+        .and_then(|_| config.get("2048", "ensemble_size", &mut ensemble_size))
+        .and_then(|_| config.get("2048", "engine", &mut method))
+        .and_then(|_| config.get("2048", "depth", &mut depth))
+        .and_then(|_| config.get("2048", "heuristic_rollout", &mut heuristic_rollout))
+        .and_then(|_| config.get_option("2048", "afterstates", &mut afterstates))
+        .and_then(|_| config.get("2048", "repeat", &mut repeats))
+        .and_then(|_| config.get("2048", "save", &mut save_path))
+        .and_then(|_| config.get("2048", "dump_tree", &mut dump_path))
+        .and_then(|_| config.get_option("2048", "dump_depth", &mut dump_depth))
+        .and_then(|_| config.get("2048", "dump_min_visits", &mut dump_min_visits))
+        .and_then(|_| config.get("2048", "width", &mut rules.width))
+        .and_then(|_| config.get("2048", "height", &mut rules.height))
+        .and_then(|_| config.get("2048", "target", &mut rules.target))
+        .and_then(|_| config.get("2048", "win_bonus", &mut rules.win_bonus))
+        .and_then(|_| config.get("2048", "loss_penalty", &mut rules.loss_penalty));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
 
     {
         let mut ap = ArgumentParser::new();
         ap.set_description("2048 playing.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in a [2048] section");
+        ap.refer(&mut auto)
+            .add_option(&["-a", "--auto"], StoreTrue,
+            "Let the MCTS play on its own instead of assisting a human player");
+        ap.refer(&mut tui)
+            .add_option(&["--tui"], StoreTrue,
+            "Let the MCTS play on its own with a live view of the search");
+        ap.refer(&mut json)
+            .add_option(&["--json"], StoreTrue,
+            "Let the MCTS play on its own and emit JSON records instead of boards");
+        ap.refer(&mut output)
+            .add_option(&["--output", "-o"], Store,
+            "File for the JSON records (default: stdout)");
         ap.refer(&mut verbose)
             .add_option(&["-v", "--verbose"], StoreTrue,
-            "Be verbose");
+            "Print an engine-style analysis line for every move, and how much the ensemble agrees");
         ap.refer(&mut time_per_move)
-            .add_option(&["--time-per-second", "-t"], Store,
+            .add_option(&["--time-per-move", "--time-per-second", "-t"], Store,
             "Time budget per move (in seconds)");
+        ap.refer(&mut iterations)
+            .add_option(&["--iterations", "-n"], Store,
+            "Iterations per ensemble member and move; overrides the time budget");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed for tile spawns and search; runs with an iteration \
+             budget are fully reproducible");
         ap.refer(&mut ensemble_size)
             .add_option(&["--ensemble_size", "-e"], Store,
             "Ensemble size.");
+        ap.refer(&mut method)
+            .add_option(&["--engine"], Store,
+            "Search that picks the moves: mcts or expectimax (only with --auto or --json)");
+        ap.refer(&mut depth)
+            .add_option(&["--depth"], Store,
+            "Moves expectimax looks ahead (default: 2)");
+        ap.refer(&mut heuristic_rollout)
+            .add_option(&["--heuristic-rollout"], StoreTrue,
+            "Play out positions preferring moves that keep the largest tile in a \
+             corner and the rows monotone, instead of random moves");
+        ap.refer(&mut afterstates)
+            .add_option(&["--afterstates"], StoreOption,
+            "Search over the boards before the spawns and value new ones by a sampled spawn \
+             or by all of them: sampled or integrated");
         ap.refer(&mut repeats)
             .add_option(&["--repeat", "-r"], Store,
-            "Numer of games to play.");
+            "Numer of games to play (with --auto).");
+        ap.refer(&mut rules.width)
+            .add_option(&["--width"], Store,
+            "Number of columns of the board (default: 4)");
+        ap.refer(&mut rules.height)
+            .add_option(&["--height"], Store,
+            "Number of rows of the board (default: 4)");
+        ap.refer(&mut rules.target)
+            .add_option(&["--target"], Store,
+            "Tile that wins the game (default: 2048)");
+        ap.refer(&mut rules.win_bonus)
+            .add_option(&["--win-bonus"], Store,
+            "Reward on top of the score for reaching the target tile");
+        ap.refer(&mut rules.loss_penalty)
+            .add_option(&["--loss-penalty"], Store,
+            "Reward taken off the score when the game ends without the target tile");
+        ap.refer(&mut save_path)
+            .add_option(&["--save"], Store,
+            "File the game in progress is saved to when interrupted with Ctrl-C");
+        ap.refer(&mut resume)
+            .add_option(&["--resume"], StoreTrue,
+            "Continue the game from the save file; tile spawns continue exactly");
+        ap.refer(&mut dump_path)
+            .add_option(&["--dump-tree"], Store,
+            "Dump the search trees to this file (DOT if it ends in .dot, else JSON); \
+             {game} and {move} in the name are replaced by the game and move number");
+        ap.refer(&mut dump_depth)
+            .add_option(&["--dump-depth"], StoreOption,
+            "Only dump nodes up to this depth");
+        ap.refer(&mut dump_min_visits)
+            .add_option(&["--dump-min-visits"], Store,
+            "Only dump nodes with at least this many visits");
         ap.parse_args_or_exit();
     }
 
-    println!("Playing 2048\n");
-    println!("Time per move: {} s", time_per_move);
-    println!("Ensemble size: {}", ensemble_size);
-    println!("");
+    let dump = if dump_path.is_empty() {
+        None
+    } else {
+        Some(TreeDump { path: dump_path, max_depth: dump_depth, min_visits: dump_min_visits })
+    };
 
-    // Summary statistics
-    let mut sum_moves = 0.;
-    let mut sum_score = 0.;
-    let mut sum_moves_sq = 0.;
-    let mut sum_score_sq = 0.;
+    let budget = if iterations > 0 {
+        Budget::Iterations(iterations)
+    } else {
+        Budget::Time(time_per_move)
+    };
+    let search = Search {
+        method: method,
+        depth: depth,
+        budget: budget,
+        ensemble_size: ensemble_size,
+        rollout: if heuristic_rollout { Some(HeuristicRollout::new()) } else { None },
+        afterstates: afterstates,
+    };
 
-    // Play repeat games in total...
-    for _ in 0..repeats {
-        // Create a game and a MCTS solver
-        let mut game = TwoFortyEight::new();
-        let mut mcts = MCTS::new(&game, ensemble_size);
+    if rules.width < 2 || rules.height < 2 {
+        println!("The board needs at least 2x2 cells.");
+        process::exit(1);
+    }
 
-        println!("{}", game);
-        loop {
-            mcts.search_time(time_per_move, 1.0);
+    if method == Method::Expectimax && (tui || !(auto || json)) {
+        println!("Expectimax only plays on its own, with --auto or --json.");
+        process::exit(1);
+    }
+
+    if tui {
+        play_tui(new_game(seed, 0, rules), time_per_move, search);
+        return;
+    }
 
-            if verbose {
-                println!("{:?}", mcts.tree_statistics());
+    let resume = if resume {
+        match SavedGame::load(&save_path) {
+            Ok(saved) => Some(saved),
+            Err(msg) => {
+                println!("{}", msg);
+                process::exit(1);
             }
+        }
+    } else {
+        None
+    };
+    let progress: Progress = Arc::new(Mutex::new(None));
+    save_on_interrupt(progress.clone(), save_path);
 
-            let action = mcts.best_action();
-            match action {
-                Some(action) => {
-                    game.make_move(&action);
-                    mcts.advance_game(&game);
-                    println!("\n... moving {:?}: {}", action, game);
-                },
-                None => break
+    if json {
+        match RecordWriter::open(&output) {
+            Ok(records) => play_auto(repeats, verbose, search, seed, rules, Some(records), resume, &progress,
+                                     dump.as_ref()),
+            Err(e) => {
+                println!("Failed to open {}: {}", output, e);
+                process::exit(1);
             }
         }
+        return;
+    }
 
-        // Update summary statistics
-        sum_moves += game.moves as f32;
-        sum_score += game.score as f32;
-        sum_moves_sq += (game.moves * game.moves) as f32;
-        sum_score_sq += (game.score * game.score) as f32;
-    }
-
-    if repeats > 1 {
-        let frepeats = repeats as f32;
-        let avg_moves = sum_moves / frepeats;
-        let avg_score = sum_score / frepeats;
-        let avg_moves_err = ((sum_moves_sq - sum_moves.powi(2)) / ((frepeats-1.) * frepeats)).sqrt();
-        let avg_score_err = ((sum_score_sq - sum_score.powi(2)) / ((frepeats-1.) * frepeats)).sqrt();
-
-        println!("Played {} games.", repeats);
-        println!("  Average # moves: {} (+/- {})", avg_moves, avg_moves_err);
-        println!("  Average Score:   {} (+/- {})", avg_score, avg_score_err);
+    println!("Playing 2048\n");
+    match method {
+        Method::Mcts => {
+            match budget {
+                Budget::Iterations(n) => println!("Iterations per move: {}", n),
+                Budget::Time(t) => println!("Time per move: {} s", t),
+            }
+            println!("Ensemble size: {}", ensemble_size);
+            if heuristic_rollout {
+                println!("Rollout:       heuristic");
+            }
+            if let Some(afterstates) = afterstates {
+                println!("Afterstates:   {:?}", afterstates);
+            }
+        },
+        Method::Expectimax => println!("Expectimax:    depth {}", depth),
+    }
+    println!("Board:         {}x{}", rules.width, rules.height);
+    println!("Target:        {}", rules.target);
+    if let Some(seed) = seed {
+        println!("Seed:          {}", seed);
+    }
+    if let Some(ref saved) = resume {
+        println!("Resuming:      game {} after {} moves", saved.index, saved.history.len());
+    }
+    println!("");
+
+    if auto {
+        play_auto(repeats, verbose, search, seed, rules, None, resume, &progress, dump.as_ref());
+    } else {
+        play_interactive(search, seed, rules, resume, &progress, dump.as_ref());
     }
 }
@@ -0,0 +1,535 @@
+extern crate argparse;
+extern crate rand;
+extern crate env_logger;
+extern crate ctrlc;
+extern crate mcts;
+
+use std::io;
+use std::io::Write;
+use std::process;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use argparse::{ArgumentParser, StoreTrue, Store, StoreOption};
+
+use mcts::mcts::{Game, MCTS, SearchReport, random_action};
+use mcts::adv2048::{Adversarial2048, Action, Direction, Objective};
+use mcts::twofortyeight::{GameStatus, HeuristicRollout, WIDTH, HEIGHT, DEFAULT_TARGET};
+use mcts::expectimax::{Expectimax, best_value};
+use mcts::agents::Budget;
+use mcts::config::Config;
+use mcts::protocol::SearchSettings;
+use mcts::records::Analysis;
+use mcts::savegame::SavedGame;
+use mcts::treedump::TreeDump;
+use mcts::utils::{derive_seed, seed_thread_rng, seconds, RunningStats};
+
+/// Who controls one side of the game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Controller {
+    /// The MCTS.
+    Engine,
+    /// Uniformly random moves, or spawns as in 2048.
+    Random,
+    /// Prefer Down, then Left, Right and Up (mover only).
+    Corner,
+    /// Moves entered on stdin (mover only).
+    Human,
+}
+
+impl FromStr for Controller {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Controller, String> {
+        match name {
+            "engine" => Ok(Controller::Engine),
+            "random" => Ok(Controller::Random),
+            "corner" => Ok(Controller::Corner),
+            "human" => Ok(Controller::Human),
+            _ => Err(format!("Unknown controller '{}', expected engine, random, corner or human", name))
+        }
+    }
+}
+
+/// Which search the engine uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Mcts,
+    /// Depth-limited expectimax, as a baseline.
+    Expectimax,
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Method, String> {
+        match name {
+            "mcts" => Ok(Method::Mcts),
+            "expectimax" => Ok(Method::Expectimax),
+            _ => Err(format!("Unknown engine '{}', expected mcts or expectimax", name))
+        }
+    }
+}
+
+/// How the engine searches, and how it plays out positions.
+#[derive(Debug, Clone, Copy)]
+struct EngineSettings {
+    method: Method,
+    depth: usize,
+    settings: SearchSettings,
+    rollout: Option<HeuristicRollout>,
+}
+
+impl EngineSettings {
+    /// Search the given position within the budget; returns the tree and
+    /// the report of the search.
+    fn search(&self, game: &Adversarial2048) -> (MCTS<Adversarial2048, Action>, SearchReport<Action>) {
+        let mut mcts = MCTS::new(game, self.settings.ensemble_size);
+        if let Some(rollout) = self.rollout {
+            mcts.set_rollout_policy(rollout);
+        }
+        let report = self.settings.run(&mut mcts);
+        (mcts, report)
+    }
+}
+
+/// Read a move from stdin; returns None on EOF or when the user quits.
+fn read_move(game: &Adversarial2048) -> Option<Action> {
+    loop {
+        print!("\nYour move: ");
+        io::stdout().flush().expect("Failed to flush stdout");
+
+        let mut line = String::new();
+        let n_read = io::stdin().read_line(&mut line).expect("Failed to read from stdin");
+        if n_read == 0 {
+            return None;  // EOF
+        }
+
+        let direction = match line.trim().to_lowercase().as_ref() {
+            "q" | "quit" => return None,
+            "w" | "up"    => Direction::Up,
+            "s" | "down"  => Direction::Down,
+            "a" | "left"  => Direction::Left,
+            "d" | "right" => Direction::Right,
+            input => {
+                println!("Unknown command '{}'.", input);
+                continue;
+            }
+        };
+        if game.allowed_actions().contains(&Action::Move(direction)) {
+            return Some(Action::Move(direction));
+        }
+        println!("Cannot move {:?}.", direction);
+    }
+}
+
+/// Choose the next action for the side to move; None if the user quits.
+///
+/// With `dump` given, the trees of engine searches are dumped as move
+/// `ply` of game `index`.
+fn choose(game: &Adversarial2048, controller: Controller, engine: &EngineSettings,
+          verbose: bool, dump: Option<&TreeDump>, index: usize, ply: usize) -> Option<Action> {
+    let actions = game.allowed_actions();
+    match controller {
+        Controller::Engine if engine.method == Method::Expectimax => {
+            let t0 = seconds();
+            let mut expectimax = Expectimax::new(engine.depth);
+            let values = expectimax.action_values(game);
+            let best = best_value(&values, game.current_player());
+            if verbose {
+                println!("{}", Analysis::expectimax(&expectimax, best, (seconds() - t0) as f32));
+            }
+            best.map(|(action, _)| action)
+        },
+        Controller::Engine => {
+            let (mcts, report) = engine.search(game);
+            if verbose {
+                println!("{}", Analysis::from_report(&mcts, &report));
+                println!("{}", report);
+                if mcts.ensemble_size() > 1 {
+                    println!("Ensemble {}", mcts.agreement());
+                }
+            }
+            if let Some(dump) = dump {
+                if let Err(msg) = dump.write(&mcts, index, ply) {
+                    println!("{}", msg);
+                    process::exit(1);
+                }
+            }
+            mcts.best_action()
+        },
+        Controller::Random => random_action(game),
+        Controller::Corner => [Direction::Down, Direction::Left, Direction::Right, Direction::Up].iter()
+            .map(|&direction| Action::Move(direction))
+            .find(|action| actions.contains(action)),
+        Controller::Human => read_move(game),
+    }
+}
+
+/// The game in progress, written to the save file on interrupt.
+type Progress = Arc<Mutex<Option<SavedGame<Adversarial2048, Action>>>>;
+
+/// Save the game in progress and exit when the user presses Ctrl-C.
+fn save_on_interrupt(progress: Progress, path: String) {
+    ctrlc::set_handler(move || {
+        if let Some(ref saved) = *progress.lock().unwrap() {
+            match saved.save(&path) {
+                Ok(()) => eprintln!("\nSaved the game after {} moves to {}; continue with --resume.",
+                                    saved.history.len(), path),
+                Err(msg) => eprintln!("\n{}", msg),
+            }
+        }
+        process::exit(130);
+    }).expect("Failed to install the interrupt handler");
+}
+
+/// Play a game to the end; returns None if the user quits.
+///
+/// With `progress` given, it is kept up to date with the game after every
+/// move, for the save file.
+fn play(mut saved: SavedGame<Adversarial2048, Action>, mover: Controller, spawner: Controller,
+        engine: &EngineSettings, verbose: bool, dump: Option<&TreeDump>,
+        progress: Option<&Progress>) -> Option<Adversarial2048> {
+    let mut game = saved.game.clone();
+    let show = verbose || mover == Controller::Human;
+    if show {
+        println!("{}", game);
+    }
+    while !game.is_over() {
+        let controller = if game.spawner_to_move { spawner } else { mover };
+        let action = match choose(&game, controller, engine, verbose, dump, saved.index, saved.history.len()) {
+            Some(action) => action,
+            None => return None
+        };
+        game.make_move(&action);
+        if let Some(progress) = progress {
+            saved.record(&game, action);
+            *progress.lock().unwrap() = Some(saved.clone());
+        }
+        if show {
+            println!("\n... {:?}: {}", action, game);
+        }
+    }
+    if let Some(progress) = progress {
+        *progress.lock().unwrap() = None;
+    }
+    Some(game)
+}
+
+/// Create the game with the given index.
+///
+/// With a master seed, both the initial tiles and the search are seeded
+/// from it, so that the game can be replayed exactly. With a random
+/// spawner the engine treats the spawns as chance moves.
+fn new_game(seed: Option<u32>, index: usize, size: (usize, usize), objective: Objective,
+            spawner: Controller) -> Adversarial2048 {
+    let seed = seed.map(|seed| {
+        seed_thread_rng(derive_seed(seed, 2*index as u32 + 1));
+        derive_seed(seed, 2*index as u32)
+    });
+    let mut game = Adversarial2048::with_size(size.0, size.1, seed);
+    game.objective = objective;
+    game.random_spawns = spawner == Controller::Random;
+    game
+}
+
+/// Play games on a pool of worker threads.
+///
+/// Every game is seeded from the master seed and its index, so the results
+/// do not depend on the number of threads. Finished games are sent back
+/// with their index as soon as they are over.
+fn play_batch(repeats: usize, threads: usize, seed: u32, size: (usize, usize), objective: Objective,
+              mover: Controller, spawner: Controller, engine: EngineSettings,
+              dump: Option<TreeDump>) -> mpsc::Receiver<(usize, Adversarial2048)> {
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..threads {
+        let next = next.clone();
+        let sender = sender.clone();
+        let dump = dump.clone();
+        thread::spawn(move || {
+            loop {
+                let g = next.fetch_add(1, Ordering::SeqCst);
+                if g >= repeats {
+                    break;
+                }
+                let game = SavedGame::new(new_game(Some(seed), g, size, objective, spawner), Some(seed), g);
+                let game = play(game, mover, spawner, &engine, false, dump.as_ref(), None).expect("Scripted games cannot be aborted");
+                if sender.send((g, game)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    receiver
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut mover = Controller::Engine;
+    let mut spawner = Controller::Engine;
+    let mut objective = Objective::Score;
+    let mut width = WIDTH;
+    let mut height = HEIGHT;
+    let mut repeats = 1;
+    let mut threads = 1;
+    let mut verbose = false;
+    let mut time_per_move = 1.0;
+    let mut iterations = 0;
+    let mut ensemble_size = 1;
+    let mut method = Method::Mcts;
+    let mut depth = 2;
+    let mut heuristic_rollout = false;
+    let mut c = 1.0;
+    let mut seed: Option<u32> = None;
+    let mut save_path = "adv2048.save".to_string();
+    let mut resume = false;
+    let mut dump_path = String::new();
+    let mut dump_depth: Option<usize> = None;
+    let mut dump_min_visits = 0;
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("adv2048", &["mover", "spawner", "objective", "width", "height", "repeat",
+                                                "threads", "verbose", "time_per_move", "iterations",
+                                                "ensemble_size", "engine", "depth", "heuristic_rollout", "c", "seed", "save",
+                                                "dump_tree", "dump_depth", "dump_min_visits"])
+        .and_then(|_| config.get("adv2048", "mover", &mut mover))
+        .and_then(|_| config.get("adv2048", "spawner", &mut spawner))
+        .and_then(|_| config.get("adv2048", "objective", &mut objective))
+        .and_then(|_| config.get("adv2048", "width", &mut width))
+        .and_then(|_| config.get("adv2048", "height", &mut height))
+        .and_then(|_| config.get("adv2048", "repeat", &mut repeats))
+        .and_then(|_| config.get("adv2048", "threads", &mut threads))
+        .and_then(|_| config.get("adv2048", "verbose", &mut verbose))
+        .and_then(|_| config.get("adv2048", "time_per_move", &mut time_per_move))
+        .and_then(|_| config.get("adv2048", "iterations", &mut iterations))
+        .and_then(|_| config.get("adv2048", "ensemble_size", &mut ensemble_size))
+        .and_then(|_| config.get("adv2048", "engine", &mut method))
+        .and_then(|_| config.get("adv2048", "depth", &mut depth))
+        .and_then(|_| config.get("adv2048", "heuristic_rollout", &mut heuristic_rollout))
+        .and_then(|_| config.get("adv2048", "c", &mut c))
+        .and_then(|_| config.get_option("adv2048", "seed", &mut seed))
+        .and_then(|_| config.get("adv2048", "save", &mut save_path))
+        .and_then(|_| config.get("adv2048", "dump_tree", &mut dump_path))
+        .and_then(|_| config.get_option("adv2048", "dump_depth", &mut dump_depth))
+        .and_then(|_| config.get("adv2048", "dump_min_visits", &mut dump_min_visits));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Adversarial 2048: a mover slides tiles, a spawner places new 2's and 4's.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in an [adv2048] section");
+        ap.refer(&mut mover)
+            .add_option(&["--mover", "-m"], Store,
+            "Who slides the tiles: engine, random, corner or human");
+        ap.refer(&mut spawner)
+            .add_option(&["--spawner", "-p"], Store,
+            "Who places the tiles: engine or random; the engine mover plans for random spawns as chance moves");
+        ap.refer(&mut objective)
+            .add_option(&["--objective"], Store,
+            "What the mover is rewarded for: score or moves");
+        ap.refer(&mut width)
+            .add_option(&["--width"], Store,
+            "Number of columns of the board (default: 4)");
+        ap.refer(&mut height)
+            .add_option(&["--height"], Store,
+            "Number of rows of the board (default: 4)");
+        ap.refer(&mut repeats)
+            .add_option(&["--repeat", "-r"], Store,
+            "Number of games to play");
+        ap.refer(&mut threads)
+            .add_option(&["--threads", "-j"], Store,
+            "Play the games in a batch on this many threads; every game gets its own seed");
+        ap.refer(&mut verbose)
+            .add_option(&["-v", "--verbose"], StoreTrue,
+            "Print the boards, an engine-style analysis line for every search and how much the ensemble agrees");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Time budget per move (in seconds)");
+        ap.refer(&mut iterations)
+            .add_option(&["--iterations", "-n"], Store,
+            "Iterations per ensemble member and move; overrides the time budget");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut method)
+            .add_option(&["--engine"], Store,
+            "Search of the engine controller: mcts or expectimax");
+        ap.refer(&mut depth)
+            .add_option(&["--depth"], Store,
+            "Moves of the mover and an engine spawner expectimax looks ahead (default: 2)");
+        ap.refer(&mut heuristic_rollout)
+            .add_option(&["--heuristic-rollout"], StoreTrue,
+            "Play out positions with mover moves that keep the largest tile in a corner \
+             and the rows monotone, instead of random moves");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed for the initial tiles and the search");
+        ap.refer(&mut save_path)
+            .add_option(&["--save"], Store,
+            "Save the game in progress to this file on Ctrl-C (default: adv2048.save)");
+        ap.refer(&mut resume)
+            .add_option(&["--resume"], StoreTrue,
+            "Continue the game from the save file, then play the remaining games of its run");
+        ap.refer(&mut dump_path)
+            .add_option(&["--dump-tree"], Store,
+            "Dump the search trees to this file (DOT if it ends in .dot, else JSON); \
+             {game} and {move} in the name are replaced by the game and ply number");
+        ap.refer(&mut dump_depth)
+            .add_option(&["--dump-depth"], StoreOption,
+            "Only dump nodes up to this depth");
+        ap.refer(&mut dump_min_visits)
+            .add_option(&["--dump-min-visits"], Store,
+            "Only dump nodes with at least this many visits");
+        ap.parse_args_or_exit();
+    }
+
+    if spawner == Controller::Corner || spawner == Controller::Human {
+        println!("The spawner can only be controlled by the engine or at random.");
+        process::exit(1);
+    }
+
+    if width < 2 || height < 2 {
+        println!("The board needs at least 2x2 cells.");
+        process::exit(1);
+    }
+
+    if threads > 1 && mover == Controller::Human {
+        println!("A human mover cannot play a batch of games.");
+        process::exit(1);
+    }
+    if threads > 1 && resume {
+        println!("A batch of games cannot be resumed.");
+        process::exit(1);
+    }
+    // Games of a batch need independent seeds
+    let seed = if threads > 1 { Some(seed.unwrap_or_else(rand::random)) } else { seed };
+
+    let dump = if dump_path.is_empty() {
+        None
+    } else {
+        Some(TreeDump { path: dump_path, max_depth: dump_depth, min_visits: dump_min_visits })
+    };
+
+    let budget = if iterations > 0 {
+        Budget::Iterations(iterations)
+    } else {
+        Budget::Time(time_per_move)
+    };
+    let engine = EngineSettings {
+        method: method,
+        depth: depth,
+        settings: SearchSettings { budget: budget, ensemble_size: ensemble_size, c: c },
+        rollout: if heuristic_rollout { Some(HeuristicRollout::new()) } else { None },
+    };
+
+    let mut resume = if resume {
+        match SavedGame::load(&save_path) {
+            Ok(saved) => Some(saved),
+            Err(msg) => {
+                println!("{}", msg);
+                process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    println!("Playing adversarial 2048\n");
+    println!("Mover:         {:?}", mover);
+    println!("Spawner:       {:?}", spawner);
+    println!("Objective:     {:?}", objective);
+    println!("Board:         {}x{}", width, height);
+    match method {
+        Method::Mcts => {
+            match budget {
+                Budget::Iterations(n) => println!("Iterations per move: {}", n),
+                Budget::Time(t) => println!("Time per move: {} s", t),
+            }
+            if heuristic_rollout {
+                println!("Rollout:       heuristic");
+            }
+        },
+        Method::Expectimax => println!("Expectimax:    depth {}", depth),
+    }
+    if let Some(seed) = seed {
+        println!("Seed:          {}", seed);
+    }
+    if threads > 1 {
+        println!("Threads:       {}", threads);
+    }
+    if let Some(ref saved) = resume {
+        println!("Resuming:      game {} after {} moves", saved.index, saved.history.len());
+    }
+    println!("");
+
+    // Summary statistics
+    let mut moves = RunningStats::new();
+    let mut scores = RunningStats::new();
+    let mut won = 0;
+
+    let mut report = |g: usize, game: &Adversarial2048| {
+        println!("Game {}: {} moves, score {}, max tile {}",
+                 g, game.board.moves, game.board.score, game.board.max_tile());
+
+        moves.push(game.board.moves as f32);
+        scores.push(game.board.score);
+        if game.board.game_status() == GameStatus::Won {
+            won += 1;
+        }
+    };
+
+    if threads > 1 {
+        let seed = seed.unwrap();
+        for (g, game) in play_batch(repeats, threads, seed, (width, height), objective, mover, spawner, engine, dump) {
+            report(g, &game);
+        }
+    } else {
+        let progress: Progress = Arc::new(Mutex::new(None));
+        save_on_interrupt(progress.clone(), save_path);
+
+        // A resumed game is finished first, followed by the remaining games of its run
+        let first = resume.as_ref().map_or(0, |saved| saved.index);
+        let seed = resume.as_ref().map_or(seed, |saved| saved.seed);
+        for g in first..repeats.max(first + 1) {
+            let game = match resume.take() {
+                Some(saved) => saved,
+                None => SavedGame::new(new_game(seed, g, (width, height), objective, spawner), seed, g)
+            };
+            match play(game, mover, spawner, &engine, verbose, dump.as_ref(), Some(&progress)) {
+                Some(game) => report(g, &game),
+                None => return
+            }
+        }
+    }
+
+    let games = moves.count() as usize;
+    if games > 1 {
+        let (avg_moves, avg_moves_err) = (moves.mean(), moves.stderr());
+        let (avg_score, avg_score_err) = (scores.mean(), scores.stderr());
+
+        println!("\nPlayed {} games.", games);
+        println!("  Average # moves: {} (+/- {})", avg_moves, avg_moves_err);
+        println!("  Average Score:   {} (+/- {})", avg_score, avg_score_err);
+        println!("  {:<17}{} of {} games ({:.1}%)", format!("Reached {}:", DEFAULT_TARGET),
+                 won, games, 100. * won as f32 / games as f32);
+    }
+}
@@ -0,0 +1,290 @@
+
+extern crate argparse;
+#[macro_use]
+extern crate serde_json;
+extern crate env_logger;
+extern crate mcts;
+
+use std::process;
+
+use argparse::{ArgumentParser, Store, StoreTrue, StoreOption};
+use serde_json::Value;
+
+use mcts::mcts::{GameAction, RewardEstimate};
+use mcts::protocol::SerializableGame;
+use mcts::agents::{Agent, AgentConfig, play_game, play_timed_game};
+use mcts::config::Config;
+use mcts::arena::{MatchStats, random_opening};
+use mcts::records::RecordWriter;
+use mcts::timecontrol::TimeControl;
+use mcts::utils::{derive_seed, seed_thread_rng, RunningStats};
+use mcts::tictactoe::TicTacToe;
+use mcts::pentago::Pentago;
+
+/// Create the agent for a configuration or exit.
+fn build_agent<G: SerializableGame<A>, A: GameAction>(config: &AgentConfig) -> Box<dyn Agent<G, A>> {
+    config.build::<G, A>().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    })
+}
+
+/// Play a game, with clocks if a time control is given.
+///
+/// Returns the reward for player 0 and the player who lost on time, if any.
+fn play<G: SerializableGame<A>, A: GameAction>(initial: &G, agents: &mut [&mut dyn Agent<G, A>],
+                                               control: Option<TimeControl>) -> (f32, Option<usize>) {
+    match control {
+        Some(control) => {
+            let result = play_timed_game(initial, agents, control);
+            (result.reward, result.flagged)
+        },
+        None => (play_game(initial, agents).reward(), None)
+    }
+}
+
+/// Play a match between two agents and print Elo estimates.
+///
+/// Games are played in pairs starting from the same random opening, with
+/// each agent moving first once. With `records` given, JSON records for
+/// every game and the final result are written there instead. With a
+/// time `control`, the agents play with clocks instead of their budgets.
+fn arena<G: SerializableGame<A>, A: GameAction>(initial: &G, config_a: &AgentConfig, config_b: &AgentConfig,
+                                    n_games: usize, opening_moves: usize, report_every: usize,
+                                    seed: Option<u32>, control: Option<TimeControl>,
+                                    mut records: Option<RecordWriter>) {
+    let mut agent_a = build_agent::<G, A>(config_a);
+    let mut agent_b = build_agent::<G, A>(config_b);
+
+    // Results of A, split by whether A moved first or second
+    let mut first = MatchStats::new();
+    let mut second = MatchStats::new();
+    // Games lost on time by A and B
+    let mut time_losses = (0, 0);
+    // Rewards of A, for games whose rewards are more than win or loss
+    let mut rewards = RunningStats::new();
+
+    let mut opening = random_opening(initial, opening_moves);
+    for g in 0..n_games {
+        if let Some(seed) = seed {
+            seed_thread_rng(derive_seed(seed, g as u32));
+        }
+        let (reward_a, flagged_a) = if g % 2 == 0 {
+            opening = random_opening(initial, opening_moves);
+            let (reward, flagged) = play(&opening, &mut [&mut *agent_a, &mut *agent_b], control);
+            first.add_result(reward);
+            (reward, flagged.map(|player| player == 0))
+        } else {
+            let (reward, flagged) = play(&opening, &mut [&mut *agent_b, &mut *agent_a], control);
+            second.add_result(-reward);
+            (-reward, flagged.map(|player| player == 1))
+        };
+        rewards.push(reward_a);
+        match flagged_a {
+            Some(true) => time_losses.0 += 1,
+            Some(false) => time_losses.1 += 1,
+            None => {}
+        }
+
+        if let Some(ref mut records) = records {
+            records.write(&json!({
+                "type": "game",
+                "game": g,
+                "a_first": g % 2 == 0,
+                "reward_a": reward_a,
+                "a_lost_on_time": flagged_a,
+            })).expect("Failed to write record");
+            continue;
+        }
+
+        if report_every > 0 && (g+1) % report_every == 0 && g+1 < n_games {
+            let mut total = first;
+            total.merge(&second);
+            println!("After {:>5} games: {}", g+1, total);
+        }
+    }
+
+    let mut total = first;
+    total.merge(&second);
+    let reward = RewardEstimate::from_stats(&rewards);
+
+    if let Some(ref mut records) = records {
+        let (reward_lower, reward_upper) = reward.interval(1.96);
+        records.write(&json!({
+            "type": "result",
+            "agent_a": format!("{}", config_a),
+            "agent_b": format!("{}", config_b),
+            "first": stats_json(&first),
+            "second": stats_json(&second),
+            "total": stats_json(&total),
+            "reward_a": {
+                "mean": reward.mean,
+                "stderr": reward.stderr,
+                "lower": reward_lower,
+                "upper": reward_upper,
+                "min": reward.min,
+                "max": reward.max,
+            },
+            "time_losses_a": time_losses.0,
+            "time_losses_b": time_losses.1,
+        })).expect("Failed to write record");
+        return;
+    }
+
+    println!("\n{} vs {} after {} games:\n", config_a.name, config_b.name, total.games());
+    println!("  moving first:  {}", first);
+    println!("  moving second: {}", second);
+    println!("  total:         {}", total);
+    if control.is_some() {
+        println!("  lost on time:  A {}, B {}", time_losses.0, time_losses.1);
+    }
+
+    let (lower, upper) = total.score_interval(1.96);
+    println!("\nScore of A:     {:.3} (95% confidence interval [{:.3}, {:.3}])",
+             total.score(), lower, upper);
+    println!("Reward of A:    {}", reward);
+    let (lower, upper) = total.elo_interval(1.96);
+    println!("Elo difference: {:+.1} (95% confidence interval [{:+.1}, {:+.1}])",
+             total.elo(), lower, upper);
+}
+
+/// Results, score and Elo estimate with their 95% confidence intervals.
+fn stats_json(stats: &MatchStats) -> Value {
+    let (lower, upper) = stats.elo_interval(1.96);
+    let (score_lower, score_upper) = stats.score_interval(1.96);
+    json!({
+        "wins": stats.wins,
+        "draws": stats.draws,
+        "losses": stats.losses,
+        "score": stats.score(),
+        "score_lower": score_lower,
+        "score_upper": score_upper,
+        "elo": stats.elo(),
+        "elo_lower": lower,
+        "elo_upper": upper,
+    })
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "tictactoe".to_string();
+    let mut n_games = 100;
+    let mut opening_moves = 2;
+    let mut report_every = 10;
+    let mut spec_a = String::new();
+    let mut spec_b = String::new();
+    let mut seed: Option<u32> = None;
+    let mut json = false;
+    let mut output = "-".to_string();
+    let mut time_control = String::new();
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("arena", &["game", "games", "opening_moves", "report_every", "seed", "time_control", "json", "output", "agent_a", "agent_b"])
+        .and_then(|_| config.get("arena", "game", &mut game))
+        .and_then(|_| config.get("arena", "games", &mut n_games))
+        .and_then(|_| config.get("arena", "opening_moves", &mut opening_moves))
+        .and_then(|_| config.get("arena", "report_every", &mut report_every))
+        .and_then(|_| config.get_option("arena", "seed", &mut seed))
+        .and_then(|_| config.get("arena", "time_control", &mut time_control))
+        .and_then(|_| config.get("arena", "json", &mut json))
+        .and_then(|_| config.get("arena", "output", &mut output))
+        .and_then(|_| config.get("arena", "agent_a", &mut spec_a))
+        .and_then(|_| config.get("arena", "agent_b", &mut spec_b));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Play a long match between two agents and estimate their Elo difference.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with an [arena] section and [agents.NAME] tables");
+        ap.refer(&mut game)
+            .add_option(&["--game", "-g"], Store,
+            "Game to play: tictactoe or pentago");
+        ap.refer(&mut n_games)
+            .add_option(&["--games", "-n"], Store,
+            "Number of games to play");
+        ap.refer(&mut opening_moves)
+            .add_option(&["--opening-moves", "-o"], Store,
+            "Number of random moves played before the agents take over");
+        ap.refer(&mut report_every)
+            .add_option(&["--report-every", "-r"], Store,
+            "Print intermediate results every so many games (0 to disable)");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed for openings and search; runs with iteration budgets are reproducible");
+        ap.refer(&mut time_control)
+            .add_option(&["--time-control", "-T"], Store,
+            "Play with clocks: BASE[+INCREMENT] seconds per player, e.g. 60+0.5");
+        ap.refer(&mut json)
+            .add_option(&["--json"], StoreTrue,
+            "Emit JSON records instead of human readable results");
+        ap.refer(&mut output)
+            .add_option(&["--output"], Store,
+            "File for the JSON records (default: stdout)");
+        ap.refer(&mut spec_a)
+            .add_argument("agent_a", Store,
+            "First agent as NAME[:KEY=VALUE,...]; see the tournament binary");
+        ap.refer(&mut spec_b)
+            .add_argument("agent_b", Store,
+            "Second agent as NAME[:KEY=VALUE,...]");
+        ap.parse_args_or_exit();
+    }
+
+    if spec_a.len() == 0 || spec_b.len() == 0 {
+        println!("Two agents are needed, on the command line or as agent_a/agent_b in the configuration.");
+        process::exit(1);
+    }
+    let parse = |spec: &str| match config.agent(spec) {
+        Ok(config) => config,
+        Err(msg) => {
+            println!("{}", msg);
+            process::exit(1);
+        }
+    };
+    let config_a = parse(&spec_a);
+    let config_b = parse(&spec_b);
+    let control = if time_control.is_empty() {
+        None
+    } else {
+        Some(time_control.parse::<TimeControl>().unwrap_or_else(|msg| {
+            println!("{}", msg);
+            process::exit(1);
+        }))
+    };
+
+    let records = if json {
+        match RecordWriter::open(&output) {
+            Ok(records) => Some(records),
+            Err(e) => {
+                println!("Failed to open {}: {}", output, e);
+                process::exit(1);
+            }
+        }
+    } else {
+        println!("Playing {} games of {} with {} opening moves\n", n_games, game, opening_moves);
+        println!("  A: {}", config_a);
+        println!("  B: {}", config_b);
+        println!("");
+        None
+    };
+
+    match game.as_ref() {
+        "tictactoe" => arena(&TicTacToe::new(), &config_a, &config_b, n_games, opening_moves, report_every, seed, control, records),
+        "pentago" => arena(&Pentago::new(), &config_a, &config_b, n_games, opening_moves, report_every, seed, control, records),
+        _ => {
+            println!("Unknown game '{}'.", game);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io;
+use std::process;
+
+use argparse::{ArgumentParser, Store};
+
+use mcts::agents::Budget;
+use mcts::config::Config;
+use mcts::protocol::{SearchSettings, run};
+use mcts::twofortyeight::TwoFortyEight;
+use mcts::tictactoe::TicTacToe;
+use mcts::hex::Hex;
+use mcts::pentago::Pentago;
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "2048".to_string();
+    let mut time_per_move = 1.0;
+    let mut iterations = 0;
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("engine", &["game", "time_per_move", "iterations", "ensemble_size", "c"])
+        .and_then(|_| config.get("engine", "game", &mut game))
+        .and_then(|_| config.get("engine", "time_per_move", &mut time_per_move))
+        .and_then(|_| config.get("engine", "iterations", &mut iterations))
+        .and_then(|_| config.get("engine", "ensemble_size", &mut ensemble_size))
+        .and_then(|_| config.get("engine", "c", &mut c));
+    if let Err(msg) = loaded {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Drive the MCTS through a line-based protocol on stdin/stdout \
+                            (newgame, state, actions, move, go, bestmove, stats, quit).");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in an [engine] section");
+        ap.refer(&mut game)
+            .add_option(&["-g", "--game"], Store,
+            "Game to play: 2048, tictactoe, hex or pentago");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Default thinking time per move (in seconds)");
+        ap.refer(&mut iterations)
+            .add_option(&["--iterations", "-n"], Store,
+            "Default iterations per ensemble member; overrides the time budget");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.parse_args_or_exit();
+    }
+
+    let settings = SearchSettings {
+        budget: if iterations > 0 { Budget::Iterations(iterations) } else { Budget::Time(time_per_move) },
+        ensemble_size: ensemble_size,
+        c: c,
+    };
+
+    let stdin = io::stdin();
+    let result = match game.as_ref() {
+        "2048" => run(&TwoFortyEight::new(), &settings, stdin.lock(), io::stdout()),
+        "tictactoe" => run(&TicTacToe::new(), &settings, stdin.lock(), io::stdout()),
+        "hex" => run(&Hex::new(), &settings, stdin.lock(), io::stdout()),
+        "pentago" => run(&Pentago::new(), &settings, stdin.lock(), io::stdout()),
+        _ => {
+            eprintln!("Unknown game '{}'", game);
+            process::exit(1);
+        }
+    };
+    result.expect("Failed to communicate over stdin/stdout");
+}
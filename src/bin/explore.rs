@@ -0,0 +1,76 @@
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io;
+use std::process;
+
+use argparse::{ArgumentParser, Store};
+
+use mcts::agents::Budget;
+use mcts::config::Config;
+use mcts::explore::run;
+use mcts::protocol::SearchSettings;
+use mcts::twofortyeight::TwoFortyEight;
+use mcts::tictactoe::TicTacToe;
+use mcts::hex::Hex;
+use mcts::pentago::Pentago;
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "tictactoe".to_string();
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("explore", &["game", "ensemble_size", "c"])
+        .and_then(|_| config.get("explore", "game", &mut game))
+        .and_then(|_| config.get("explore", "ensemble_size", &mut ensemble_size))
+        .and_then(|_| config.get("explore", "c", &mut c));
+    if let Err(msg) = loaded {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Step through the search interactively: grow the tree a few \
+                            iterations at a time, walk around in it and inspect UCT terms.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in an [explore] section");
+        ap.refer(&mut game)
+            .add_option(&["-g", "--game"], Store,
+            "Game to explore: 2048, tictactoe, hex or pentago");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.parse_args_or_exit();
+    }
+
+    // The explorer searches a given number of iterations at a time
+    let settings = SearchSettings { budget: Budget::Iterations(1), ensemble_size: ensemble_size, c: c };
+
+    let stdin = io::stdin();
+    let result = match game.as_ref() {
+        "2048" => run(&TwoFortyEight::new(), &settings, stdin.lock(), io::stdout()),
+        "tictactoe" => run(&TicTacToe::new(), &settings, stdin.lock(), io::stdout()),
+        "hex" => run(&Hex::new(), &settings, stdin.lock(), io::stdout()),
+        "pentago" => run(&Pentago::new(), &settings, stdin.lock(), io::stdout()),
+        _ => {
+            eprintln!("Unknown game '{}'", game);
+            process::exit(1);
+        }
+    };
+    result.expect("Failed to communicate over stdin/stdout");
+}
@@ -0,0 +1,309 @@
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io;
+use std::io::{BufRead, Write};
+use std::process;
+
+use argparse::{ArgumentParser, Store, StoreTrue};
+
+use mcts::mcts::MCTS;
+use mcts::ponder::Ponder;
+use mcts::hex::{Hex, Action, Player, MAX_SIZE, parse_vertex, format_vertex};
+use mcts::agents::Budget;
+use mcts::config::Config;
+
+/// Commands understood by the engine, as reported by `list_commands`.
+const COMMANDS: [&'static str; 16] = [
+    "protocol_version", "name", "version", "known_command", "list_commands", "quit",
+    "boardsize", "clear_board", "komi", "play", "genmove", "undo", "showboard",
+    "final_score", "mcts-analyze", "gogui-analyze_commands",
+];
+
+/// Parse a color argument ("b", "black", "w" or "white").
+fn parse_color(arg: &str) -> Result<Player, String> {
+    match arg.to_lowercase().as_ref() {
+        "b" | "black" => Ok(Player::Black),
+        "w" | "white" => Ok(Player::White),
+        _ => Err(format!("invalid color '{}'", arg))
+    }
+}
+
+/// Game state and search settings of the engine.
+///
+/// With `ponder` set the engine keeps searching the position after the
+/// predicted reply while the opponent thinks; if the opponent plays that
+/// move, the next `genmove` continues from the tree.
+struct Engine {
+    game: Hex,
+    history: Vec<(Player, Action)>,
+    budget: Budget,
+    ensemble_size: usize,
+    c: f32,
+    ponder: bool,
+    pondering: Option<Ponder<Hex, Action>>,
+    tree: Option<(Player, MCTS<Hex, Action>)>,  // kept after a ponder hit
+}
+
+impl Engine {
+    fn new(size: usize, budget: Budget, ensemble_size: usize, c: f32, ponder: bool) -> Engine {
+        Engine {
+            game: Hex::with_size(size),
+            history: Vec::new(),
+            budget: budget,
+            ensemble_size: ensemble_size,
+            c: c,
+            ponder: ponder,
+            pondering: None,
+            tree: None,
+        }
+    }
+
+    /// Search the current position with `player` to move, continuing
+    /// from the tree kept after a ponder hit if there is one.
+    fn search(&mut self, player: Player) -> MCTS<Hex, Action> {
+        let mut mcts = match self.tree.take() {
+            Some((to_move, tree)) if to_move == player => tree,
+            _ => {
+                let mut game = self.game.clone();
+                game.set_to_move(player);
+                MCTS::new(&game, self.ensemble_size)
+            }
+        };
+        match self.budget {
+            Budget::Iterations(n) => mcts.search(n, self.c),
+            Budget::Time(t) => mcts.search_time(t, self.c),
+        };
+        mcts
+    }
+
+    /// Stop pondering and forget any kept tree.
+    fn stop_pondering(&mut self) {
+        if let Some(pondering) = self.pondering.take() {
+            pondering.cancel();
+        }
+        self.tree = None;
+    }
+
+    fn play(&mut self, player: Player, action: Action) -> Result<(), String> {
+        if self.game.is_finished() {
+            return Err("game is already over".to_string());
+        }
+        if self.game.get_cell(action.row as usize, action.col as usize).is_some() {
+            return Err("illegal move".to_string());
+        }
+        self.game.place(player, action.row as usize, action.col as usize);
+        self.history.push((player, action));
+        Ok(())
+    }
+
+    /// Execute a single command; returns the response text.
+    fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        let arg = |i: usize| args.get(i).map(|a| *a).ok_or("missing argument".to_string());
+
+        match command {
+            "boardsize" | "clear_board" | "undo" | "quit" => self.stop_pondering(),
+            _ => {}
+        }
+
+        match command {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("rust-mcts".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(COMMANDS.contains(&try!(arg(0))).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => Ok(String::new()),
+            "boardsize" => {
+                let size: usize = try!(try!(arg(0)).parse().map_err(|_| "boardsize not an integer".to_string()));
+                if size < 1 || size > MAX_SIZE {
+                    return Err("unacceptable size".to_string());
+                }
+                self.game = Hex::with_size(size);
+                self.history.clear();
+                Ok(String::new())
+            },
+            "clear_board" => {
+                self.game = Hex::with_size(self.game.size());
+                self.history.clear();
+                Ok(String::new())
+            },
+            "komi" => Ok(String::new()),   // meaningless in hex
+            "play" => {
+                let player = try!(parse_color(try!(arg(0))));
+                let action = try!(parse_vertex(try!(arg(1)), self.game.size()));
+                try!(self.play(player, action));
+                if let Some(pondering) = self.pondering.take() {
+                    self.tree = pondering.resolve(&action).map(|tree| (player.opponent(), tree));
+                }
+                Ok(String::new())
+            },
+            "genmove" => {
+                let player = try!(parse_color(try!(arg(0))));
+                if self.game.is_finished() {
+                    return Ok("resign".to_string());
+                }
+                if let Some(pondering) = self.pondering.take() {
+                    pondering.cancel();
+                }
+                let mut mcts = self.search(player);
+                let action = mcts.best_action().expect("No move available");
+                try!(self.play(player, action));
+                if self.ponder && !self.game.is_finished() {
+                    mcts.advance(&action);
+                    self.pondering = Ponder::start(mcts, self.c);
+                }
+                Ok(format_vertex(&action))
+            },
+            "undo" => {
+                if self.history.pop().is_none() {
+                    return Err("cannot undo".to_string());
+                }
+                // Replay the remaining moves on an empty board
+                let mut game = Hex::with_size(self.game.size());
+                for &(player, action) in &self.history {
+                    game.place(player, action.row as usize, action.col as usize);
+                }
+                self.game = game;
+                Ok(String::new())
+            },
+            "showboard" => Ok(format!("\n{}", self.game.to_string().trim_end())),
+            "final_score" => match self.game.winner() {
+                Some(Player::Black) => Ok("B+".to_string()),
+                Some(Player::White) => Ok("W+".to_string()),
+                None => Err("game is not over".to_string())
+            },
+            "mcts-analyze" => {
+                let player = match args.get(0) {
+                    Some(arg) => try!(parse_color(arg)),
+                    None => self.game.to_move(),
+                };
+                let mcts = self.search(player);
+                let lines = mcts.action_values().iter()
+                    .map(|&(action, visits, value)| {
+                        format!("{} visits={} value={:.3}", format_vertex(&action), visits, value)
+                    })
+                    .collect::<Vec<_>>();
+                if self.ponder {
+                    self.tree = Some((player, mcts));
+                }
+                Ok(lines.join("\n"))
+            },
+            "gogui-analyze_commands" => Ok("string/MCTS Analyze/mcts-analyze %c".to_string()),
+            _ => Err("unknown command".to_string())
+        }
+    }
+}
+
+/// Strip comments and control characters from a command line.
+fn preprocess(line: &str) -> String {
+    let line = line.split('#').next().unwrap();
+    line.chars()
+        .filter(|&c| c == '\t' || !c.is_control())
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut size = 11;
+    let mut time_per_move = 1.0;
+    let mut iterations = 0;
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+    let mut ponder = false;
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("gtp", &["size", "time_per_move", "iterations", "ensemble_size", "c", "ponder"])
+        .and_then(|_| config.get("gtp", "size", &mut size))
+        .and_then(|_| config.get("gtp", "time_per_move", &mut time_per_move))
+        .and_then(|_| config.get("gtp", "iterations", &mut iterations))
+        .and_then(|_| config.get("gtp", "ensemble_size", &mut ensemble_size))
+        .and_then(|_| config.get("gtp", "c", &mut c))
+        .and_then(|_| config.get("gtp", "ponder", &mut ponder));
+    if let Err(msg) = loaded {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Play Hex over the Go Text Protocol (GTP) on stdin/stdout.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in a [gtp] section");
+        ap.refer(&mut size)
+            .add_option(&["--size", "-s"], Store,
+            "Initial board size");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Thinking time per move (in seconds)");
+        ap.refer(&mut iterations)
+            .add_option(&["--iterations", "-n"], Store,
+            "Iterations per ensemble member and move; overrides the time budget");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.refer(&mut ponder)
+            .add_option(&["--ponder"], StoreTrue,
+            "Keep searching on the predicted reply while the opponent thinks");
+        ap.parse_args_or_exit();
+    }
+
+    if size < 1 || size > MAX_SIZE {
+        eprintln!("Board size must be between 1 and {}", MAX_SIZE);
+        process::exit(1);
+    }
+
+    let budget = if iterations > 0 {
+        Budget::Iterations(iterations)
+    } else {
+        Budget::Time(time_per_move)
+    };
+    let mut engine = Engine::new(size, budget, ensemble_size, c, ponder);
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in stdin.lock().lines() {
+        let line = preprocess(&line.expect("Failed to read from stdin"));
+        if line.is_empty() {
+            continue;
+        }
+
+        // Commands may be preceded by a numeric id which is echoed back
+        let mut words = line.split_whitespace().collect::<Vec<_>>();
+        let id = if words[0].chars().all(|c| c.is_digit(10)) {
+            words.remove(0)
+        } else {
+            ""
+        };
+        if words.is_empty() {
+            continue;
+        }
+
+        let (status, text) = match engine.execute(words[0], &words[1..]) {
+            Ok(text) => ("=", text),
+            Err(text) => ("?", text),
+        };
+        let separator = if text.is_empty() { "" } else { " " };
+        write!(out, "{}{}{}{}\n\n", status, id, separator, text).expect("Failed to write to stdout");
+        out.flush().expect("Failed to flush stdout");
+
+        if words[0] == "quit" {
+            break;
+        }
+    }
+}
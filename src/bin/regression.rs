@@ -0,0 +1,75 @@
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::process;
+
+use argparse::{ArgumentParser, Store, StoreTrue};
+
+use mcts::regression::{load_suite, save_suite};
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut suite = "regression/suite.jsonl".to_string();
+    let mut record = false;
+    let mut tolerance = 1e-4;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Replay a suite of positions with fixed seeds and budgets and \
+                            compare the chosen moves, root values, root action statistics \
+                            and tree shapes against the recorded results.");
+        ap.refer(&mut suite)
+            .add_option(&["-s", "--suite"], Store,
+            "JSON lines file with the regression cases");
+        ap.refer(&mut record)
+            .add_option(&["--record"], StoreTrue,
+            "Record the current results as the new baseline");
+        ap.refer(&mut tolerance)
+            .add_option(&["-t", "--tolerance"], Store,
+            "Relative tolerance for values, visits and tree sizes");
+        ap.parse_args_or_exit();
+    }
+
+    let mut cases = load_suite(&suite).unwrap_or_else(|msg| {
+        eprintln!("{}", msg);
+        process::exit(1);
+    });
+
+    let mut changed = 0;
+    for case in cases.iter_mut() {
+        let outcome = case.run().unwrap_or_else(|msg| {
+            eprintln!("{}", msg);
+            process::exit(1);
+        });
+        if record {
+            case.expected = Some(outcome);
+            continue;
+        }
+        let diff = case.diff(&outcome, tolerance);
+        if diff.is_empty() {
+            println!("ok      {}", case.name);
+        } else {
+            println!("CHANGED {}", case.name);
+            for line in diff {
+                println!("    {}", line);
+            }
+            changed += 1;
+        }
+    }
+
+    if record {
+        if let Err(msg) = save_suite(&suite, &cases) {
+            eprintln!("{}", msg);
+            process::exit(1);
+        }
+        println!("Recorded {} cases in {}", cases.len(), suite);
+    } else if changed > 0 {
+        println!("{} of {} cases changed", changed, cases.len());
+        process::exit(1);
+    } else {
+        println!("All {} cases unchanged", cases.len());
+    }
+}
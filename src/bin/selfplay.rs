@@ -0,0 +1,311 @@
+
+extern crate argparse;
+extern crate rand;
+extern crate env_logger;
+extern crate mcts;
+
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use argparse::{ArgumentParser, Store, StoreOption};
+use rand::Rng;
+
+use mcts::mcts::{Game, GameAction, MCTS};
+use mcts::tictactoe::TicTacToe;
+use mcts::pentago::Pentago;
+use mcts::config::Config;
+use mcts::utils::{seeded_rng, derive_seed, seed_thread_rng, softmax, sample_symmetric_dirichlet};
+
+/// Self-play settings shared by all workers.
+#[derive(Debug, Clone)]
+struct Settings {
+    n_iterations: usize,                // MCTS iterations per move
+    c: f32,                             // exploration constant
+    temperature: f32,                   // for sampling the played move
+    temperature_moves: usize,           // moves after which we play greedily
+    dirichlet_alpha: f32,
+    noise_fraction: f32,                // weight of the noise when sampling moves
+}
+
+/// One training sample: a position, the search policy and the final outcome.
+struct Sample {
+    player: usize,
+    features: Vec<f32>,
+    policy: Vec<(String, f32)>,
+    value: f32,                         // outcome from the perspective of `player`
+}
+
+/// Games that can be encoded as training samples.
+///
+/// Features are given from the perspective of the player to move:
+/// 1 for own stones, -1 for the opponent's and 0 for empty cells.
+trait Encode {
+    fn features(&self) -> Vec<f32>;
+}
+
+impl Encode for TicTacToe {
+    fn features(&self) -> Vec<f32> {
+        let me = self.to_move();
+        let mut features = Vec::new();
+        for row in 0..mcts::tictactoe::SIZE {
+            for col in 0..mcts::tictactoe::SIZE {
+                features.push(match self.get_cell(row, col) {
+                    None => 0.,
+                    Some(p) if p == me => 1.,
+                    Some(_) => -1.,
+                });
+            }
+        }
+        features
+    }
+}
+
+impl Encode for Pentago {
+    fn features(&self) -> Vec<f32> {
+        let me = self.to_move();
+        let mut features = Vec::new();
+        for row in 0..mcts::pentago::SIZE {
+            for col in 0..mcts::pentago::SIZE {
+                features.push(match self.get_cell(row, col) {
+                    None => 0.,
+                    Some(p) if p == me => 1.,
+                    Some(_) => -1.,
+                });
+            }
+        }
+        features
+    }
+}
+
+/// Sample an index according to the (unnormalized) weights.
+fn sample_index<R: Rng>(rng: &mut R, weights: &[f32]) -> usize {
+    let total = weights.iter().fold(0., |sum, w| sum + w);
+    let mut u = rng.next_f32() * total;
+    for (idx, &w) in weights.iter().enumerate() {
+        if u < w {
+            return idx;
+        }
+        u -= w;
+    }
+    weights.len() - 1
+}
+
+/// Play a single self-play game and return its training samples.
+fn self_play<G, A, R>(initial: &G, settings: &Settings, rng: &mut R) -> Vec<Sample>
+        where G: Game<A> + Encode, A: GameAction, R: Rng {
+    let mut game = initial.clone();
+    let mut samples = Vec::new();
+
+    let mut moves = 0;
+    loop {
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(settings.n_iterations, settings.c);
+
+        let values = mcts.action_values();
+        if values.len() == 0 {
+            break;
+        }
+
+        // The search policy is the normalized visit distribution
+        let total_visits = values.iter().map(|v| v.1).sum::<u64>() as f32;
+        samples.push(Sample {
+            player: game.current_player(),
+            features: game.features(),
+            policy: values.iter().map(|v| (format!("{:?}", v.0), v.1 as f32 / total_visits)).collect(),
+            value: 0.,
+        });
+
+        // Choose the move to play
+        let idx = if moves < settings.temperature_moves && settings.temperature > 0. {
+            // Visit shares raised to 1/T, i.e. a softmax over their logarithms
+            let log_shares = values.iter().map(|v| (v.1 as f32 / total_visits).ln()).collect::<Vec<_>>();
+            let mut weights = softmax(&log_shares, settings.temperature);
+            let noise = sample_symmetric_dirichlet(rng, settings.dirichlet_alpha, weights.len());
+            for (w, n) in weights.iter_mut().zip(noise) {
+                *w = (1. - settings.noise_fraction) * *w + settings.noise_fraction * n;
+            }
+            sample_index(rng, &weights)
+        } else {
+            // Most visited action
+            let mut best = 0;
+            for (idx, v) in values.iter().enumerate() {
+                if v.1 > values[best].1 {
+                    best = idx;
+                }
+            }
+            best
+        };
+        game.make_move(&values[idx].0);
+        moves += 1;
+    }
+
+    // Fill in the final outcome
+    let reward = game.reward();
+    for sample in &mut samples {
+        sample.value = if sample.player == 0 { reward } else { -reward };
+    }
+    samples
+}
+
+/// Write samples as JSON lines.
+fn write_samples<W: Write>(out: &mut W, game_id: usize, samples: &[Sample]) -> std::io::Result<()> {
+    for (ply, sample) in samples.iter().enumerate() {
+        let features = sample.features.iter().map(|f| format!("{}", f)).collect::<Vec<_>>();
+        let policy = sample.policy.iter()
+                .map(|&(ref a, p)| format!("[\"{}\",{}]", a, p))
+                .collect::<Vec<_>>();
+        try!(writeln!(out, "{{\"game\":{},\"ply\":{},\"player\":{},\"features\":[{}],\"policy\":[{}],\"value\":{}}}",
+                      game_id, ply, sample.player, features.join(","), policy.join(","), sample.value));
+    }
+    Ok(())
+}
+
+/// Play `n_games` on `n_workers` threads; every worker writes its own file.
+///
+/// With a master seed, every game is seeded from its index, so the
+/// samples do not depend on which worker played the game.
+fn run<G, A>(initial: G, settings: Settings, n_games: usize, n_workers: usize, output_dir: &str,
+             seed: Option<u32>)
+        where G: Game<A> + Encode + Send + Sync + 'static, A: GameAction + 'static {
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let initial = Arc::new(initial);
+
+    let mut workers = Vec::new();
+    for w in 0..n_workers {
+        let next_game = next_game.clone();
+        let initial = initial.clone();
+        let settings = settings.clone();
+        let filename = Path::new(output_dir).join(format!("selfplay-{}.jsonl", w));
+
+        workers.push(thread::spawn(move || {
+            let file = File::create(&filename).expect("Failed to create output file");
+            let mut out = BufWriter::new(file);
+            let mut rng = seeded_rng(w as u32);
+            let mut n_samples = 0;
+
+            loop {
+                let game_id = next_game.fetch_add(1, Ordering::SeqCst);
+                if game_id >= n_games {
+                    break;
+                }
+                if let Some(seed) = seed {
+                    rng = seeded_rng(derive_seed(seed, 2*game_id as u32));
+                    seed_thread_rng(derive_seed(seed, 2*game_id as u32 + 1));
+                }
+                let samples = self_play(&*initial, &settings, &mut rng);
+                write_samples(&mut out, game_id, &samples).expect("Failed to write samples");
+                n_samples += samples.len();
+                println!("Worker {}: finished game {} ({} samples)", w, game_id, samples.len());
+            }
+            n_samples
+        }));
+    }
+
+    let n_samples = workers.into_iter().fold(0, |sum, w| sum + w.join().unwrap());
+    println!("\nWrote {} samples from {} games to {}", n_samples, n_games, output_dir);
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "tictactoe".to_string();
+    let mut n_games = 100;
+    let mut n_workers = 1;
+    let mut output_dir = "selfplay".to_string();
+    let mut seed: Option<u32> = None;
+    let mut settings = Settings {
+        n_iterations: 1000,
+        c: 1.,
+        temperature: 1.,
+        temperature_moves: 4,
+        dirichlet_alpha: 0.3,
+        noise_fraction: 0.25,
+    };
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("selfplay", &["game", "games", "iterations", "c", "temperature", "temperature_moves", "dirichlet_alpha", "noise_fraction", "workers", "output_dir", "seed"])
+        .and_then(|_| config.get("selfplay", "game", &mut game))
+        .and_then(|_| config.get("selfplay", "games", &mut n_games))
+        .and_then(|_| config.get("selfplay", "iterations", &mut settings.n_iterations))
+        .and_then(|_| config.get("selfplay", "c", &mut settings.c))
+        .and_then(|_| config.get("selfplay", "temperature", &mut settings.temperature))
+        .and_then(|_| config.get("selfplay", "temperature_moves", &mut settings.temperature_moves))
+        .and_then(|_| config.get("selfplay", "dirichlet_alpha", &mut settings.dirichlet_alpha))
+        .and_then(|_| config.get("selfplay", "noise_fraction", &mut settings.noise_fraction))
+        .and_then(|_| config.get("selfplay", "workers", &mut n_workers))
+        .and_then(|_| config.get("selfplay", "output_dir", &mut output_dir))
+        .and_then(|_| config.get_option("selfplay", "seed", &mut seed));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Generate training data from self-play games.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in a [selfplay] section");
+        ap.refer(&mut game)
+            .add_option(&["--game", "-g"], Store,
+            "Game to play: tictactoe or pentago");
+        ap.refer(&mut n_games)
+            .add_option(&["--games", "-n"], Store,
+            "Number of games to play");
+        ap.refer(&mut settings.n_iterations)
+            .add_option(&["--iterations", "-i"], Store,
+            "MCTS iterations per move");
+        ap.refer(&mut settings.c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.refer(&mut settings.temperature)
+            .add_option(&["--temperature", "-T"], Store,
+            "Temperature for sampling moves from the visit counts");
+        ap.refer(&mut settings.temperature_moves)
+            .add_option(&["--temperature-moves"], Store,
+            "Number of moves sampled with temperature; later moves are greedy");
+        ap.refer(&mut settings.dirichlet_alpha)
+            .add_option(&["--dirichlet-alpha"], Store,
+            "Concentration of the Dirichlet noise");
+        ap.refer(&mut settings.noise_fraction)
+            .add_option(&["--noise-fraction"], Store,
+            "Weight of the Dirichlet noise when sampling moves (0 to disable)");
+        ap.refer(&mut n_workers)
+            .add_option(&["--workers", "-w"], Store,
+            "Number of games to play in parallel");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed; makes the generated games reproducible");
+        ap.refer(&mut output_dir)
+            .add_option(&["--output-dir", "-o"], Store,
+            "Directory for the selfplay-<worker>.jsonl files");
+        ap.parse_args_or_exit();
+    }
+
+    if let Err(e) = fs::create_dir_all(&output_dir) {
+        println!("Failed to create {}: {}", output_dir, e);
+        process::exit(1);
+    }
+
+    println!("Self-play: {} games of {} with {:?}\n", n_games, game, settings);
+    match game.as_ref() {
+        "tictactoe" => run(TicTacToe::new(), settings, n_games, n_workers.max(1), &output_dir, seed),
+        "pentago" => run(Pentago::new(), settings, n_games, n_workers.max(1), &output_dir, seed),
+        _ => {
+            println!("Unknown game '{}'.", game);
+            process::exit(1);
+        }
+    }
+}
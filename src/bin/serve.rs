@@ -0,0 +1,156 @@
+extern crate argparse;
+#[macro_use]
+extern crate serde_json;
+extern crate tiny_http;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io::Read;
+use std::process;
+
+use argparse::{ArgumentParser, Store};
+use serde_json::Value;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use mcts::agents::Budget;
+use mcts::protocol::{SearchSettings, analyze};
+use mcts::twofortyeight::TwoFortyEight;
+use mcts::tictactoe::TicTacToe;
+use mcts::hex::Hex;
+
+const USAGE: &'static str = "POST /analyze/{2048,tictactoe,hex} or /bestmove/{2048,tictactoe,hex} with a JSON \
+    body {\"state\": STATE, \"time\": SECONDS, \"iterations\": N, \"ensemble_size\": E, \"c\": C}; \
+    all fields but the state are optional and a body without \"state\" is taken as the state. The state is \
+    given as JSON or as a string in the text notation of the game, like \"XX./OO./... X\" for tictactoe or \
+    \"2,2,4,8/0,0,0,0/0,0,0,0/0,0,0,2\" for 2048. The answer holds the best action, the statistics of \
+    every action and the principal variation.";
+
+/// Limits protecting the server from overly expensive requests.
+struct Limits {
+    max_time: f32,
+    max_iterations: usize,
+}
+
+/// Search settings requested in the body, falling back to the defaults.
+fn request_settings(body: &Value, defaults: &SearchSettings, limits: &Limits) -> Result<SearchSettings, String> {
+    let budget = if let Some(n) = body["iterations"].as_u64() {
+        Budget::Iterations((n as usize).min(limits.max_iterations))
+    } else if let Some(t) = body["time"].as_f64() {
+        Budget::Time((t as f32).min(limits.max_time))
+    } else {
+        defaults.budget
+    };
+    let ensemble_size = body["ensemble_size"].as_u64().map(|e| e as usize).unwrap_or(defaults.ensemble_size);
+    if ensemble_size < 1 || ensemble_size > 100 {
+        return Err("ensemble_size must be between 1 and 100".to_string());
+    }
+
+    Ok(SearchSettings {
+        budget: budget,
+        ensemble_size: ensemble_size,
+        c: body["c"].as_f64().map(|c| c as f32).unwrap_or(defaults.c),
+    })
+}
+
+/// Handle a single request; returns the status code and JSON answer.
+fn handle(request: &mut Request, defaults: &SearchSettings, limits: &Limits) -> (u16, Value) {
+    let game = match (request.method(), request.url()) {
+        (&Method::Get, "/") => return (200, json!({ "usage": USAGE })),
+        (&Method::Post, url) if url.starts_with("/analyze/") => url["/analyze/".len()..].to_string(),
+        (&Method::Post, url) if url.starts_with("/bestmove/") => url["/bestmove/".len()..].to_string(),
+        _ => return (404, json!({ "error": "not found", "usage": USAGE })),
+    };
+
+    let mut text = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut text) {
+        return (400, json!({ "error": format!("failed to read body: {}", e) }));
+    }
+    let body = match text.parse::<Value>() {
+        Ok(body) => body,
+        Err(e) => return (400, json!({ "error": format!("invalid JSON: {}", e) })),
+    };
+    let settings = match request_settings(&body, defaults, limits) {
+        Ok(settings) => settings,
+        Err(msg) => return (400, json!({ "error": msg })),
+    };
+    let state = if body.get("state").is_some() { &body["state"] } else { &body };
+
+    let result = match game.as_ref() {
+        "2048" => analyze::<TwoFortyEight, _>(state, &settings),
+        "tictactoe" => analyze::<TicTacToe, _>(state, &settings),
+        "hex" => analyze::<Hex, _>(state, &settings),
+        _ => return (404, json!({ "error": format!("unknown game '{}'", game) })),
+    };
+    match result {
+        Ok(result) => (200, result),
+        Err(msg) => (400, json!({ "error": msg })),
+    }
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut address = "127.0.0.1:8048".to_string();
+    let mut time_per_move = 0.5;
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+    let mut limits = Limits { max_time: 10., max_iterations: 1000000 };
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("HTTP server analyzing game states with the MCTS.");
+        ap.refer(&mut address)
+            .add_option(&["-a", "--address"], Store,
+            "Address to listen on");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Default thinking time per request (in seconds)");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Default ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Default exploration constant");
+        ap.refer(&mut limits.max_time)
+            .add_option(&["--max-time"], Store,
+            "Largest thinking time a request may ask for (in seconds)");
+        ap.refer(&mut limits.max_iterations)
+            .add_option(&["--max-iterations"], Store,
+            "Largest number of iterations a request may ask for");
+        ap.parse_args_or_exit();
+    }
+
+    let defaults = SearchSettings {
+        budget: Budget::Time(time_per_move),
+        ensemble_size: ensemble_size,
+        c: c,
+    };
+    let server = Server::http(&address[..]).unwrap_or_else(|e| {
+        println!("Failed to listen on {}: {}", address, e);
+        process::exit(1);
+    });
+    println!("Listening on http://{}/", address);
+
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    // Allow requests from browser extensions and other origins
+    let allow_origin = Header::from_bytes(&b"Access-Control-Allow-Origin"[..], &b"*"[..]).unwrap();
+    let allow_headers = Header::from_bytes(&b"Access-Control-Allow-Headers"[..], &b"Content-Type"[..]).unwrap();
+
+    for mut request in server.incoming_requests() {
+        let response = if *request.method() == Method::Options {
+            Response::from_string("").with_status_code(204)
+        } else {
+            let (status, answer) = handle(&mut request, &defaults, &limits);
+            Response::from_string(answer.to_string())
+                .with_status_code(status)
+                .with_header(content_type.clone())
+        };
+        let response = response
+            .with_header(allow_origin.clone())
+            .with_header(allow_headers.clone());
+        if let Err(e) = request.respond(response) {
+            println!("Failed to send response: {}", e);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+
+use argparse::{ArgumentParser, Store, StoreOption};
+
+use mcts::mcts::{Game, GameAction, MCTS, random_action_with};
+use mcts::twofortyeight::TwoFortyEight;
+use mcts::gridworld::GridWorld;
+use mcts::config::Config;
+use mcts::utils::{derive_seed, seed_thread_rng, seeded_rng, RunningStats};
+
+/// Offset for the seeds of the played games so that they never coincide
+/// with the seeds the MCTS uses for its ensemble.
+const SEED_OFFSET: u32 = 1000000;
+
+#[derive(Debug, Clone, Copy)]
+/// One point of the parameter grid.
+struct Cell {
+    c: f32,
+    ensemble_size: usize,
+    time_per_move: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Outcome of a single game.
+struct Outcome {
+    score: f32,
+    moves: usize,
+    max_tile: Option<u16>,              // only for 2048
+}
+
+/// Games the sweep knows how to set up and summarize.
+trait SweepGame<A: GameAction>: Game<A> {
+    fn create(seed: u32) -> Self;
+    fn outcome(&self) -> Outcome;
+}
+
+impl SweepGame<mcts::twofortyeight::Action> for TwoFortyEight {
+    fn create(seed: u32) -> TwoFortyEight {
+        TwoFortyEight::with_seed(seed)
+    }
+
+    fn outcome(&self) -> Outcome {
+        Outcome { score: self.score, moves: self.moves, max_tile: Some(self.max_tile()) }
+    }
+}
+
+impl SweepGame<mcts::gridworld::Action> for GridWorld {
+    /// The slips are chance moves, drawn by `play` from the seed.
+    fn create(_seed: u32) -> GridWorld {
+        GridWorld::frozen_lake()
+    }
+
+    fn outcome(&self) -> Outcome {
+        Outcome { score: self.reward(), moves: self.steps, max_tile: None }
+    }
+}
+
+/// Let the MCTS play a single game with the given parameters.
+fn play<G: SweepGame<A>, A: GameAction>(cell: &Cell, seed: u32) -> Outcome {
+    let mut game = G::create(seed);
+    let mut mcts = MCTS::new(&game, cell.ensemble_size);
+    let mut rng = seeded_rng(seed);
+
+    loop {
+        let action = if game.chance_outcomes().is_some() {
+            random_action_with(&game, &mut rng)
+        } else {
+            mcts.search_time(cell.time_per_move, cell.c);
+            mcts.best_action()
+        };
+        match action {
+            Some(action) => {
+                game.make_move(&action);
+                mcts.advance_game(&game);
+            },
+            None => break
+        }
+    }
+    game.outcome()
+}
+
+/// Statistics of the values.
+fn statistics<I: Iterator<Item=f32>>(values: I) -> RunningStats {
+    let mut stats = RunningStats::new();
+    for value in values {
+        stats.push(value);
+    }
+    stats
+}
+
+/// Play all games of the sweep on `n_workers` threads.
+///
+/// Returns the outcomes for every cell of the grid. With a master seed,
+/// game spawns and search randomness are derived from it.
+fn sweep<G, A>(cells: &[Cell], n_games: usize, n_workers: usize, seed: Option<u32>) -> Vec<Vec<Outcome>>
+        where G: SweepGame<A> + 'static, A: GameAction + 'static {
+    // Every (cell, game) pair is a job; all cells play the same seeds
+    let mut jobs = Vec::new();
+    for (idx, cell) in cells.iter().enumerate() {
+        for g in 0..n_games {
+            let game_seed = match seed {
+                Some(seed) => derive_seed(seed, 2*g as u32),
+                None => SEED_OFFSET + g as u32,
+            };
+            let search_seed = seed.map(|seed| derive_seed(seed, 2*g as u32 + 1));
+            jobs.push((idx, *cell, game_seed, search_seed));
+        }
+    }
+    jobs.reverse();
+    let n_jobs = jobs.len();
+    let jobs = Arc::new(Mutex::new(jobs));
+
+    let (tx, rx) = channel();
+    let mut workers = Vec::new();
+    for _ in 0..n_workers {
+        let jobs = jobs.clone();
+        let tx = tx.clone();
+        workers.push(thread::spawn(move || {
+            loop {
+                let job = jobs.lock().unwrap().pop();
+                match job {
+                    Some((idx, cell, seed, search_seed)) => {
+                        if let Some(search_seed) = search_seed {
+                            seed_thread_rng(search_seed);
+                        }
+                        let outcome = play::<G, A>(&cell, seed);
+                        tx.send((idx, outcome)).unwrap();
+                    },
+                    None => break
+                }
+            }
+        }));
+    }
+
+    let mut results = vec![Vec::new(); cells.len()];
+    for done in 0..n_jobs {
+        let (idx, outcome) = rx.recv().unwrap();
+        results[idx].push(outcome);
+        if (done+1) % 10 == 0 || done+1 == n_jobs {
+            print!("\rPlayed {} of {} games", done+1, n_jobs);
+            std::io::stdout().flush().expect("Failed to flush stdout");
+        }
+    }
+    println!("");
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    results
+}
+
+/// Write one CSV row with aggregate statistics per cell.
+fn write_csv(filename: &str, cells: &[Cell], results: &[Vec<Outcome>]) -> std::io::Result<()> {
+    let mut file = try!(File::create(filename));
+    try!(writeln!(file, "c,ensemble_size,time_per_move,games,score_mean,score_std,\
+                        moves_mean,moves_std,max_tile_mean,max_tile_max"));
+
+    for (cell, outcomes) in cells.iter().zip(results) {
+        let scores = statistics(outcomes.iter().map(|o| o.score));
+        let moves = statistics(outcomes.iter().map(|o| o.moves as f32));
+        let tiles = statistics(outcomes.iter().filter_map(|o| o.max_tile).map(|t| t as f32));
+        let (score_mean, score_std) = (scores.mean(), scores.std_dev());
+        let (moves_mean, moves_std) = (moves.mean(), moves.std_dev());
+
+        let (tile_mean, tile_max) = match tiles.max() {
+            Some(max) => (format!("{}", tiles.mean()), format!("{}", max)),
+            None => (String::new(), String::new())
+        };
+
+        try!(writeln!(file, "{},{},{},{},{},{},{},{},{},{}",
+                      cell.c, cell.ensemble_size, cell.time_per_move, outcomes.len(),
+                      score_mean, score_std, moves_mean, moves_std, tile_mean, tile_max));
+    }
+    Ok(())
+}
+
+/// Parse a comma separated list of values.
+fn parse_list<T: FromStr>(name: &str, list: &str) -> Vec<T> {
+    let values = list.split(',').map(|v| v.trim().parse::<T>().ok()).collect::<Option<Vec<_>>>();
+    match values {
+        Some(values) => values,
+        None => {
+            println!("Invalid list of values for {}: '{}'", name, list);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "2048".to_string();
+    let mut c_list = "1.0".to_string();
+    let mut ensemble_list = "10".to_string();
+    let mut time_list = "0.1".to_string();
+    let mut n_games = 10;
+    let mut n_workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut output = "sweep.csv".to_string();
+    let mut seed: Option<u32> = None;
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("sweep", &["game", "c", "ensemble_size", "time_per_move", "games", "workers", "seed", "output"])
+        .and_then(|_| config.get("sweep", "game", &mut game))
+        .and_then(|_| config.get("sweep", "c", &mut c_list))
+        .and_then(|_| config.get("sweep", "ensemble_size", &mut ensemble_list))
+        .and_then(|_| config.get("sweep", "time_per_move", &mut time_list))
+        .and_then(|_| config.get("sweep", "games", &mut n_games))
+        .and_then(|_| config.get("sweep", "workers", &mut n_workers))
+        .and_then(|_| config.get_option("sweep", "seed", &mut seed))
+        .and_then(|_| config.get("sweep", "output", &mut output));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Grid search over MCTS parameters.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with default settings in a [sweep] section");
+        ap.refer(&mut game)
+            .add_option(&["--game", "-g"], Store,
+            "Game to play: 2048 or frozenlake");
+        ap.refer(&mut c_list)
+            .add_option(&["--c", "-c"], Store,
+            "Comma separated exploration constants");
+        ap.refer(&mut ensemble_list)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Comma separated ensemble sizes");
+        ap.refer(&mut time_list)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Comma separated time budgets per move (in seconds)");
+        ap.refer(&mut n_games)
+            .add_option(&["--games", "-n"], Store,
+            "Number of games per grid cell");
+        ap.refer(&mut n_workers)
+            .add_option(&["--workers", "-w"], Store,
+            "Number of games to play in parallel");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed for tile spawns and search");
+        ap.refer(&mut output)
+            .add_option(&["--output", "-o"], Store,
+            "CSV file to write the results to");
+        ap.parse_args_or_exit();
+    }
+
+    let mut cells = Vec::new();
+    for &c in &parse_list::<f32>("--c", &c_list) {
+        for &ensemble_size in &parse_list::<usize>("--ensemble_size", &ensemble_list) {
+            for &time_per_move in &parse_list::<f32>("--time-per-move", &time_list) {
+                cells.push(Cell { c: c, ensemble_size: ensemble_size, time_per_move: time_per_move });
+            }
+        }
+    }
+
+    println!("Sweeping {} parameter combinations with {} games each on {} workers\n",
+             cells.len(), n_games, n_workers);
+
+    let results = match game.as_ref() {
+        "2048" => sweep::<TwoFortyEight, _>(&cells, n_games, n_workers.max(1), seed),
+        "frozenlake" => sweep::<GridWorld, _>(&cells, n_games, n_workers.max(1), seed),
+        _ => {
+            println!("Unknown game '{}'.", game);
+            process::exit(1);
+        }
+    };
+
+    match write_csv(&output, &cells, &results) {
+        Ok(()) => println!("Results written to {}", output),
+        Err(e) => {
+            println!("Failed to write {}: {}", output, e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io;
+use std::process;
+
+use argparse::{ArgumentParser, StoreTrue, Store, StoreOption};
+
+use mcts::mcts::MCTS;
+use mcts::cli::{Session, play};
+use mcts::tictactoe::{TicTacToe, Player};
+use mcts::records::Analysis;
+use mcts::treedump::TreeDump;
+use mcts::utils::seconds;
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut human_second = false;
+    let mut time_per_move = 1.0;
+    let mut ensemble_size = 1;
+    let mut verbose = false;
+    let mut dump_path = String::new();
+    let mut dump_depth: Option<usize> = None;
+    let mut dump_min_visits = 0;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Play TicTacToe against the MCTS.");
+        ap.refer(&mut human_second)
+            .add_option(&["-s", "--second"], StoreTrue,
+            "Let the MCTS make the first move");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Thinking time per move (in seconds)");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut verbose)
+            .add_option(&["-v", "--verbose"], StoreTrue,
+            "Print an engine-style analysis line for every move of the MCTS");
+        ap.refer(&mut dump_path)
+            .add_option(&["--dump-tree"], Store,
+            "Dump the search trees to this file (DOT if it ends in .dot, else JSON); \
+             {move} in the name is replaced by the move number");
+        ap.refer(&mut dump_depth)
+            .add_option(&["--dump-depth"], StoreOption,
+            "Only dump nodes up to this depth");
+        ap.refer(&mut dump_min_visits)
+            .add_option(&["--dump-min-visits"], Store,
+            "Only dump nodes with at least this many visits");
+        ap.parse_args_or_exit();
+    }
+
+    let dump = if dump_path.is_empty() {
+        None
+    } else {
+        Some(TreeDump { path: dump_path, max_depth: dump_depth, min_visits: dump_min_visits })
+    };
+    let human = if human_second { Player::O } else { Player::X };
+
+    println!("Playing TicTacToe\n");
+    println!("Time per move: {} s", time_per_move);
+    println!("You are playing {:?}.", human);
+    println!("");
+
+    let mut engine = |game: &TicTacToe| {
+        let t0 = seconds();
+        let mut mcts = MCTS::new(game, ensemble_size);
+        mcts.search_time(time_per_move, 1.0);
+        if verbose {
+            // Show the PV in the same "row col" notation as the input
+            let mut analysis = Analysis::new(&mcts, (seconds() - t0) as f32);
+            analysis.pv = mcts.principal_variation(usize::MAX).iter()
+                .map(|action| format!("{},{}", action.row+1, action.col+1))
+                .collect();
+            println!("{}", analysis);
+        }
+        if let Some(ref dump) = dump {
+            if let Err(msg) = dump.write(&mcts, 0, game.moves) {
+                println!("{}", msg);
+                process::exit(1);
+            }
+        }
+        mcts
+    };
+    let mut session = Session::new(&TicTacToe::new());
+    let stdin = io::stdin();
+    let player = if human_second { 1 } else { 0 };
+    play(&mut session, player, &mut engine, stdin.lock(), io::stdout()).expect("Failed to talk to the terminal");
+
+    let game = session.game();
+    if !game.is_finished() {
+        return;
+    }
+    match game.winner() {
+        Some(player) if player == human => println!("You win!"),
+        Some(_) => println!("The MCTS wins."),
+        None    => println!("Draw.")
+    }
+}
@@ -0,0 +1,284 @@
+
+extern crate argparse;
+#[macro_use]
+extern crate serde_json;
+extern crate env_logger;
+extern crate mcts;
+
+use std::process;
+
+use argparse::{ArgumentParser, Store, StoreTrue, StoreOption, List};
+
+use mcts::mcts::GameAction;
+use mcts::protocol::SerializableGame;
+use mcts::agents::{Agent, AgentConfig, play_game, play_timed_game};
+use mcts::config::Config;
+use mcts::arena::MatchStats;
+use mcts::records::RecordWriter;
+use mcts::timecontrol::TimeControl;
+use mcts::utils::{derive_seed, seed_thread_rng};
+use mcts::tictactoe::TicTacToe;
+use mcts::pentago::Pentago;
+
+/// Create the agent for a configuration or exit.
+fn build_agent<G: SerializableGame<A>, A: GameAction>(config: &AgentConfig) -> Box<dyn Agent<G, A>> {
+    config.build::<G, A>().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    })
+}
+
+/// Play a game, with clocks if a time control is given.
+///
+/// Returns the reward for player 0 and the player who lost on time, if any.
+fn play<G: SerializableGame<A>, A: GameAction>(initial: &G, agents: &mut [&mut dyn Agent<G, A>],
+                                               control: Option<TimeControl>) -> (f32, Option<usize>) {
+    match control {
+        Some(control) => {
+            let result = play_timed_game(initial, agents, control);
+            (result.reward, result.flagged)
+        },
+        None => (play_game(initial, agents).reward(), None)
+    }
+}
+
+/// Play a round-robin tournament and print the results.
+///
+/// Every pairing plays `n_games` games, alternating who moves first.
+/// With `records` given, JSON records for every game, pairing and rank
+/// are written there instead of printing tables. With a time `control`,
+/// the agents play with clocks instead of their budgets.
+fn tournament<G: SerializableGame<A>, A: GameAction>(initial: &G, configs: &[AgentConfig], n_games: usize,
+                                         seed: Option<u32>, control: Option<TimeControl>,
+                                         mut records: Option<RecordWriter>) {
+    let n_agents = configs.len();
+    let mut agents = configs.iter().map(|c| build_agent::<G, A>(c)).collect::<Vec<_>>();
+
+    // results[i][j]: results of agent i against agent j
+    let mut results = vec![vec![MatchStats::new(); n_agents]; n_agents];
+    let mut game_index = 0;
+
+    for i in 0..n_agents {
+        for j in (i+1)..n_agents {
+            for g in 0..n_games {
+                // Agent i moves first in even games
+                let first = if g % 2 == 0 { i } else { j };
+                if let Some(seed) = seed {
+                    seed_thread_rng(derive_seed(seed, game_index));
+                }
+                game_index += 1;
+                let (reward, flagged) = {
+                    let (left, right) = agents.split_at_mut(j);
+                    let (a_i, a_j) = (&mut *left[i], &mut *right[0]);
+                    if first == i {
+                        play(initial, &mut [a_i, a_j], control)
+                    } else {
+                        play(initial, &mut [a_j, a_i], control)
+                    }
+                };
+
+                // reward is for the player that moved first
+                let reward_i = if first == i { reward } else { -reward };
+                results[i][j].add_result(reward_i);
+                results[j][i] = results[i][j].reversed();
+
+                if let Some(ref mut records) = records {
+                    let second = if first == i { j } else { i };
+                    records.write(&json!({
+                        "type": "game",
+                        "first": configs[first].name,
+                        "second": configs[second].name,
+                        "reward": reward,
+                        "lost_on_time": flagged.map(|player| &configs[if player == 0 { first } else { second }].name),
+                    })).expect("Failed to write record");
+                }
+            }
+            let r = results[i][j];
+            match records {
+                Some(ref mut records) => records.write(&json!({
+                    "type": "pairing",
+                    "agent": configs[i].name,
+                    "opponent": configs[j].name,
+                    "wins": r.wins,
+                    "draws": r.draws,
+                    "losses": r.losses,
+                })).expect("Failed to write record"),
+                None => println!("{} vs {}: {} wins, {} draws, {} losses",
+                                 configs[i].name, configs[j].name, r.wins, r.draws, r.losses)
+            }
+        }
+    }
+
+    // Final ranking by points; a win counts 1 and a draw 1/2
+    let mut totals = (0..n_agents).map(|i| {
+        let mut total = MatchStats::new();
+        for r in &results[i] {
+            total.merge(r);
+        }
+        (i, total)
+    }).collect::<Vec<_>>();
+    totals.sort_by(|a, b| b.1.points().partial_cmp(&a.1.points()).unwrap());
+
+    if let Some(ref mut records) = records {
+        for (rank, &(i, total)) in totals.iter().enumerate() {
+            records.write(&json!({
+                "type": "ranking",
+                "rank": rank+1,
+                "agent": configs[i].name,
+                "config": format!("{}", configs[i]),
+                "points": total.points(),
+                "games": total.games(),
+                "wins": total.wins,
+                "draws": total.draws,
+                "losses": total.losses,
+            })).expect("Failed to write record");
+        }
+        return;
+    }
+
+    // Pairing table
+    let width = configs.iter().map(|c| c.name.len()).max().unwrap_or(0).max(8);
+    println!("\nResults (W/D/L of row agent against column agent):\n");
+    print!("{:width$}", "", width=width);
+    for config in configs {
+        print!("  {:>width$}", config.name, width=width);
+    }
+    println!("");
+    for i in 0..n_agents {
+        print!("{:width$}", configs[i].name, width=width);
+        for j in 0..n_agents {
+            let cell = if i == j {
+                "-".to_string()
+            } else {
+                let r = results[i][j];
+                format!("{}/{}/{}", r.wins, r.draws, r.losses)
+            };
+            print!("  {:>width$}", cell, width=width);
+        }
+        println!("");
+    }
+
+    println!("\nRanking:\n");
+    for (rank, &(i, total)) in totals.iter().enumerate() {
+        println!("{:>3}. {:width$}  {:>6.1} / {:<4}  ({}/{}/{})  {}",
+                 rank+1, configs[i].name, total.points(), total.games(),
+                 total.wins, total.draws, total.losses, configs[i], width=width);
+    }
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut game = "tictactoe".to_string();
+    let mut n_games = 10;
+    let mut specs: Vec<String> = Vec::new();
+    let mut seed: Option<u32> = None;
+    let mut json = false;
+    let mut output = "-".to_string();
+    let mut time_control = String::new();
+    let mut config_path = String::new();
+
+    // Settings from the configuration file serve as defaults
+    let config = Config::from_args().unwrap_or_else(|msg| {
+        println!("{}", msg);
+        process::exit(1);
+    });
+    let loaded = config.check_keys("tournament", &["game", "games", "seed", "time_control", "json", "output"])
+        .and_then(|_| config.get("tournament", "game", &mut game))
+        .and_then(|_| config.get("tournament", "games", &mut n_games))
+        .and_then(|_| config.get_option("tournament", "seed", &mut seed))
+        .and_then(|_| config.get("tournament", "time_control", &mut time_control))
+        .and_then(|_| config.get("tournament", "json", &mut json))
+        .and_then(|_| config.get("tournament", "output", &mut output));
+    if let Err(msg) = loaded {
+        println!("{}", msg);
+        process::exit(1);
+    }
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Round-robin tournament between agent configurations.");
+        ap.refer(&mut config_path)
+            .add_option(&["--config"], Store,
+            "TOML file with a [tournament] section and [agents.NAME] tables");
+        ap.refer(&mut game)
+            .add_option(&["--game", "-g"], Store,
+            "Game to play: tictactoe or pentago");
+        ap.refer(&mut n_games)
+            .add_option(&["--games", "-n"], Store,
+            "Number of games per pairing");
+        ap.refer(&mut seed)
+            .add_option(&["--seed", "-s"], StoreOption,
+            "Master seed for the search; runs with iteration budgets are reproducible");
+        ap.refer(&mut time_control)
+            .add_option(&["--time-control", "-T"], Store,
+            "Play with clocks: BASE[+INCREMENT] seconds per player, e.g. 60+0.5");
+        ap.refer(&mut json)
+            .add_option(&["--json"], StoreTrue,
+            "Emit JSON records instead of tables");
+        ap.refer(&mut output)
+            .add_option(&["--output", "-o"], Store,
+            "File for the JSON records (default: stdout)");
+        ap.refer(&mut specs)
+            .add_argument("agents", List,
+            "Agents as NAME[:KEY=VALUE,...] with keys policy (mcts, random, flat or external), \
+             c (exploration), e (ensemble size), n (iterations per move), \
+             t (seconds per move), cmd (external engine), tree (ucb1, ucb1-tuned, greedy, puct or thompson), \
+             d (moves per playout), p (playouts per leaf), rave, fpu and contempt (for draws); NAME may \
+             refer to an agent from the configuration file. Defaults to all agents from the file.");
+        ap.parse_args_or_exit();
+    }
+
+    if specs.len() == 0 {
+        specs = config.agent_names();
+    }
+    let mut configs: Vec<AgentConfig> = Vec::new();
+    for spec in &specs {
+        match config.agent(spec) {
+            Ok(config) => configs.push(config),
+            Err(msg) => {
+                println!("{}", msg);
+                process::exit(1);
+            }
+        }
+    }
+    if configs.len() < 2 {
+        println!("A tournament needs at least two agents.");
+        process::exit(1);
+    }
+    let control = if time_control.is_empty() {
+        None
+    } else {
+        Some(time_control.parse::<TimeControl>().unwrap_or_else(|msg| {
+            println!("{}", msg);
+            process::exit(1);
+        }))
+    };
+
+    let records = if json {
+        match RecordWriter::open(&output) {
+            Ok(records) => Some(records),
+            Err(e) => {
+                println!("Failed to open {}: {}", output, e);
+                process::exit(1);
+            }
+        }
+    } else {
+        println!("Playing {} with {} games per pairing\n", game, n_games);
+        for config in &configs {
+            println!("  {}", config);
+        }
+        println!("");
+        None
+    };
+
+    match game.as_ref() {
+        "tictactoe" => tournament(&TicTacToe::new(), &configs, n_games, seed, control, records),
+        "pentago" => tournament(&Pentago::new(), &configs, n_games, seed, control, records),
+        _ => {
+            println!("Unknown game '{}'.", game);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+extern crate argparse;
+extern crate env_logger;
+extern crate mcts;
+
+use std::io;
+use std::io::{BufRead, Write};
+
+use argparse::{ArgumentParser, Store};
+
+use mcts::mcts::{Game, MCTS};
+use mcts::chess::Chess;
+use mcts::agents::Budget;
+
+/// Fraction of the remaining clock time spent on a move.
+const CLOCK_FRACTION: f32 = 1. / 30.;
+
+/// Search settings of the engine.
+struct Settings {
+    budget: Budget,
+    ensemble_size: usize,
+    c: f32,
+}
+
+/// Set up the position from the arguments of the `position` command.
+fn parse_position(args: &[&str]) -> Result<Chess, String> {
+    let moves_at = args.iter().position(|&a| a == "moves").unwrap_or(args.len());
+    let mut game = match args.first() {
+        Some(&"startpos") => Chess::new(),
+        Some(&"fen") => try!(Chess::from_fen(&args[1..moves_at].join(" "))),
+        _ => return Err("expected 'startpos' or 'fen'".to_string())
+    };
+
+    for text in args.iter().skip(moves_at + 1) {
+        let action = try!(game.parse_uci(text));
+        game.make_move(&action);
+    }
+    Ok(game)
+}
+
+/// Determine the search budget from the arguments of the `go` command.
+///
+/// Supports `movetime`, `nodes` and clock based time controls; anything
+/// else (including `infinite`) falls back to the default budget.
+fn parse_budget(args: &[&str], game: &Chess, settings: &Settings) -> Budget {
+    let value = |name: &str| -> Option<f32> {
+        args.iter().position(|&a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .and_then(|v| v.parse().ok())
+    };
+    let (time, inc) = if game.current_player() == 0 { ("wtime", "winc") } else { ("btime", "binc") };
+
+    if let Some(ms) = value("movetime") {
+        Budget::Time(ms / 1000.)
+    } else if let Some(nodes) = value("nodes") {
+        Budget::Iterations((nodes as usize / settings.ensemble_size).max(1))
+    } else if let Some(ms) = value(time) {
+        let moves_to_go = value("movestogo").map(|n| 1. / n.max(1.)).unwrap_or(CLOCK_FRACTION);
+        let increment = value(inc).unwrap_or(0.);
+        Budget::Time((ms * moves_to_go + 0.8 * increment) / 1000.)
+    } else {
+        settings.budget
+    }
+}
+
+/// Convert a mean reward in [-1, 1] into a centipawn score.
+fn centipawns(value: f32) -> i32 {
+    let p = ((value + 1.) / 2.).max(0.001).min(0.999);
+    (400. * (p / (1. - p)).log10()).round() as i32
+}
+
+/// Search the position and report the result; returns the best move.
+fn go(game: &Chess, budget: Budget, settings: &Settings, out: &mut dyn Write) -> io::Result<Option<String>> {
+    let mut mcts = MCTS::new(game, settings.ensemble_size);
+    let report = match budget {
+        Budget::Iterations(n) => mcts.search(n, settings.c),
+        Budget::Time(t) => mcts.search_time(t, settings.c),
+    };
+
+    let elapsed = report.elapsed;
+    let pv = mcts.principal_variation(usize::MAX).iter().map(Chess::uci).collect::<Vec<_>>();
+    if let Some(value) = mcts.root_value() {
+        try!(writeln!(out, "info depth {} seldepth {} nodes {} nps {} time {} score cp {} pv {}",
+                      pv.len(), report.max_depth, report.nodes, (report.nodes as f32 / elapsed.max(0.001)) as u64,
+                      (elapsed * 1000.) as u64, centipawns(value), pv.join(" ")));
+    }
+    for (action, visits, value) in mcts.action_values() {
+        try!(writeln!(out, "info string move {} visits {} value {:.3}", Chess::uci(&action), visits, value));
+    }
+    Ok(pv.first().cloned())
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut time_per_move = 1.0;
+    let mut iterations = 0;
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Chess engine speaking the UCI protocol on stdin/stdout.");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Thinking time per move when the GUI sets no limit (in seconds)");
+        ap.refer(&mut iterations)
+            .add_option(&["--iterations", "-n"], Store,
+            "Iterations per ensemble member when the GUI sets no limit; overrides the time");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.parse_args_or_exit();
+    }
+
+    let settings = Settings {
+        budget: if iterations > 0 { Budget::Iterations(iterations) } else { Budget::Time(time_per_move) },
+        ensemble_size: ensemble_size,
+        c: c,
+    };
+    let mut game = Chess::new();
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in stdin.lock().lines() {
+        let line = line.expect("Failed to read from stdin");
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+
+        let result = match words[0] {
+            "uci" => writeln!(out, "id name rust-mcts {}\nid author {}\nuciok",
+                              env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_AUTHORS")),
+            "isready" => writeln!(out, "readyok"),
+            "ucinewgame" => {
+                game = Chess::new();
+                Ok(())
+            },
+            "position" => match parse_position(&words[1..]) {
+                Ok(position) => {
+                    game = position;
+                    Ok(())
+                },
+                Err(msg) => writeln!(out, "info string {}", msg)
+            },
+            "go" => {
+                let budget = parse_budget(&words[1..], &game, &settings);
+                match go(&game, budget, &settings, &mut out) {
+                    Ok(Some(action)) => writeln!(out, "bestmove {}", action),
+                    Ok(None) => writeln!(out, "bestmove 0000"),
+                    Err(e) => Err(e),
+                }
+            },
+            "d" => write!(out, "{}", game),
+            "quit" => break,
+            _ => Ok(())      // includes "stop": searches are not interruptible
+        };
+        result.and_then(|_| out.flush()).expect("Failed to write to stdout");
+    }
+}
@@ -0,0 +1,235 @@
+extern crate argparse;
+#[macro_use]
+extern crate serde_json;
+extern crate tungstenite;
+extern crate env_logger;
+extern crate mcts;
+
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::thread;
+use std::time::Instant;
+
+use argparse::{ArgumentParser, Store};
+use serde_json::Value;
+use tungstenite::{Message, WebSocket};
+
+use mcts::mcts::{Game, GameAction, MCTS};
+use mcts::agents::Budget;
+use mcts::protocol::{SerializableGame, SearchSettings, parse_action};
+use mcts::records::search_json;
+use mcts::twofortyeight::TwoFortyEight;
+use mcts::tictactoe::TicTacToe;
+use mcts::hex::Hex;
+
+/// How often thinking updates are sent while searching (in s).
+const REFRESH: f32 = 0.1;
+
+/// Number of thinking updates for searches with an iteration budget.
+const UPDATES: usize = 10;
+
+type Socket = WebSocket<TcpStream>;
+
+fn send(socket: &mut Socket, message: Value) -> tungstenite::Result<()> {
+    socket.send(Message::Text(message.to_string()))
+}
+
+fn send_error(socket: &mut Socket, message: &str) -> tungstenite::Result<()> {
+    send(socket, json!({ "type": "error", "message": message }))
+}
+
+/// Wait for the next JSON message; None when the client went away.
+fn receive(socket: &mut Socket) -> tungstenite::Result<Option<Value>> {
+    loop {
+        match try!(socket.read()) {
+            Message::Text(text) => match text.parse::<Value>() {
+                Ok(message) => return Ok(Some(message)),
+                Err(e) => try!(send_error(socket, &format!("invalid JSON: {}", e))),
+            },
+            Message::Close(_) => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+fn send_state<G: SerializableGame<A>, A: GameAction>(socket: &mut Socket, game: &G) -> tungstenite::Result<()> {
+    let actions = game.allowed_actions().iter()
+        .map(|action| json!(format!("{:?}", action)))
+        .collect::<Vec<_>>();
+    send(socket, json!({
+        "type": "state",
+        "state": game.to_json(),
+        "actions": actions,
+        "to_move": game.current_player(),
+        "finished": actions.is_empty(),
+        "reward": game.reward(),
+    }))
+}
+
+/// Search the game, sending a thinking update after every slice of the budget.
+fn think<G: Game<A>, A: GameAction>(socket: &mut Socket, game: &G, settings: &SearchSettings)
+        -> tungstenite::Result<MCTS<G, A>> {
+    let t0 = Instant::now();
+    let mut mcts = MCTS::new(game, settings.ensemble_size);
+    let mut iterations = 0;
+
+    loop {
+        let done = match settings.budget {
+            Budget::Iterations(n) => {
+                let slice = (n / UPDATES).max(1).min(n - iterations);
+                mcts.search(slice, settings.c);
+                iterations += slice;
+                iterations >= n
+            },
+            Budget::Time(t) => {
+                mcts.search_time(REFRESH.min(t), settings.c);
+                t0.elapsed().as_secs_f32() >= t
+            },
+        };
+
+        let mut update = search_json(&mcts);
+        update["type"] = json!("thinking");
+        update["elapsed"] = json!(t0.elapsed().as_secs_f32());
+        try!(send(socket, update));
+        if done {
+            return Ok(mcts);
+        }
+    }
+}
+
+/// Let the engine make a move and report it.
+fn engine_move<G: SerializableGame<A>, A: GameAction>(socket: &mut Socket, game: &mut G, settings: &SearchSettings)
+        -> tungstenite::Result<()> {
+    let mcts = try!(think(socket, game, settings));
+    if let Some(action) = mcts.best_action() {
+        game.make_move(&action);
+        try!(send(socket, json!({ "type": "engine_move", "action": format!("{:?}", action) })));
+    }
+    send_state(socket, game)
+}
+
+/// Play a game until the client closes the connection or starts a new game.
+///
+/// In two-player games the engine replies to every move of the client;
+/// `engine_player` is the player it takes the turns of.
+fn play<G: SerializableGame<A>, A: GameAction>(socket: &mut Socket, mut game: G, engine_player: Option<usize>,
+                                               settings: &SearchSettings) -> tungstenite::Result<Option<Value>> {
+    let engine_to_move = |game: &G| {
+        engine_player == Some(game.current_player()) && !game.is_terminal()
+    };
+
+    try!(send_state(socket, &game));
+    if engine_to_move(&game) {
+        try!(engine_move(socket, &mut game, settings));
+    }
+
+    while let Some(message) = try!(receive(socket)) {
+        match message["type"].as_str().unwrap_or("") {
+            "new" => return Ok(Some(message)),
+            "state" => try!(send_state(socket, &game)),
+            "move" => match parse_action(&game, message["action"].as_str().unwrap_or("")) {
+                Ok(action) => {
+                    game.make_move(&action);
+                    try!(send_state(socket, &game));
+                    if engine_to_move(&game) {
+                        try!(engine_move(socket, &mut game, settings));
+                    }
+                },
+                Err(msg) => try!(send_error(socket, &msg)),
+            },
+            "go" => try!(engine_move(socket, &mut game, settings)),
+            "hint" => {
+                let mcts = try!(think(socket, &game, settings));
+                let mut hint = search_json(&mcts);
+                hint["type"] = json!("hint");
+                hint["action"] = json!(mcts.best_action().map(|action| format!("{:?}", action)));
+                try!(send(socket, hint));
+            },
+            other => try!(send_error(socket, &format!("unknown message type '{}'", other))),
+        }
+    }
+    Ok(None)
+}
+
+/// Serve a single client.
+///
+/// Clients send JSON messages with a `type`: `new` (with `game` and, for
+/// two-player games, `engine_first`), `move` (with an `action` from the
+/// last state), `go` to let the engine move, `hint` and `state`. The server
+/// answers with `state`, `thinking`, `engine_move`, `hint` and `error`
+/// messages.
+fn handle(mut socket: Socket, settings: SearchSettings) -> tungstenite::Result<()> {
+    let mut next = try!(receive(&mut socket));
+    while let Some(message) = next {
+        if message["type"] != "new" {
+            try!(send_error(&mut socket, "start a game with {\"type\": \"new\", \"game\": ...}"));
+            next = try!(receive(&mut socket));
+            continue;
+        }
+
+        let engine_player = if message["engine_first"].as_bool().unwrap_or(false) { 0 } else { 1 };
+        next = match message["game"].as_str().unwrap_or("") {
+            "2048" => try!(play(&mut socket, TwoFortyEight::new(), None, &settings)),
+            "tictactoe" => try!(play(&mut socket, TicTacToe::new(), Some(engine_player), &settings)),
+            "hex" => try!(play(&mut socket, Hex::new(), Some(engine_player), &settings)),
+            other => {
+                try!(send_error(&mut socket, &format!("unknown game '{}'", other)));
+                try!(receive(&mut socket))
+            }
+        };
+    }
+    Ok(())
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+    env_logger::init();
+
+    let mut address = "127.0.0.1:8049".to_string();
+    let mut time_per_move = 1.0;
+    let mut ensemble_size = 1;
+    let mut c = 1.0;
+
+    {
+        let mut ap = ArgumentParser::new();
+        ap.set_description("WebSocket server for playing 2048, TicTacToe and Hex with the MCTS from a browser.");
+        ap.refer(&mut address)
+            .add_option(&["-a", "--address"], Store,
+            "Address to listen on");
+        ap.refer(&mut time_per_move)
+            .add_option(&["--time-per-move", "-t"], Store,
+            "Thinking time per move (in seconds)");
+        ap.refer(&mut ensemble_size)
+            .add_option(&["--ensemble_size", "-e"], Store,
+            "Ensemble size.");
+        ap.refer(&mut c)
+            .add_option(&["-c"], Store,
+            "Exploration constant");
+        ap.parse_args_or_exit();
+    }
+
+    let settings = SearchSettings {
+        budget: Budget::Time(time_per_move),
+        ensemble_size: ensemble_size,
+        c: c,
+    };
+    let listener = TcpListener::bind(&address[..]).unwrap_or_else(|e| {
+        println!("Failed to listen on {}: {}", address, e);
+        process::exit(1);
+    });
+    println!("Listening on ws://{}/", address);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || match tungstenite::accept(stream) {
+                    Ok(socket) => if let Err(e) = handle(socket, settings) {
+                        println!("Connection closed: {}", e);
+                    },
+                    Err(e) => println!("Handshake failed: {}", e),
+                });
+            },
+            Err(e) => println!("Failed to accept connection: {}", e),
+        }
+    }
+}
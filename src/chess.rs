@@ -0,0 +1,229 @@
+//!
+//! Chess, based on the rules implementation of the shakmaty crate.
+//!
+//! The game ends by checkmate, stalemate, insufficient material or the
+//! fifty-move rule. Threefold repetition is not detected; instead, games
+//! are adjudicated as a draw after `max_plies` half-moves so that random
+//! playouts always terminate.
+//!
+//! White is player 0; rewards are given from white's perspective.
+//!
+//! Only available with the `chess` feature.
+//!
+
+use std::fmt;
+
+use shakmaty::{CastlingMode, Color, EnPassantMode, File, KnownOutcome, Move, Outcome, Position, Rank, Square};
+use shakmaty::Chess as ChessPosition;
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+
+use mcts::{GameAction, Game};
+
+/// Default number of half-moves after which a game is drawn.
+pub const MAX_PLIES: usize = 300;
+
+impl GameAction for Move {}
+
+#[derive(Debug, Clone)]
+/// A chess position together with the number of half-moves played.
+pub struct Chess {
+    position: ChessPosition,
+    pub max_plies: usize,
+    pub moves: usize,
+}
+
+impl Chess {
+    /// Create a new game in the standard starting position.
+    pub fn new() -> Chess {
+        Chess {
+            position: ChessPosition::default(),
+            max_plies: MAX_PLIES,
+            moves: 0,
+        }
+    }
+
+    /// Create a new game from a position in Forsyth-Edwards notation.
+    pub fn from_fen(fen: &str) -> Result<Chess, String> {
+        let fen: Fen = try!(fen.parse().map_err(|e| format!("Invalid FEN '{}': {}", fen, e)));
+        let position = try!(fen.into_position(CastlingMode::Standard)
+            .map_err(|e| format!("Illegal position: {}", e)));
+        Ok(Chess {
+            position: position,
+            max_plies: MAX_PLIES,
+            moves: 0,
+        })
+    }
+
+    /// The position in Forsyth-Edwards notation.
+    pub fn fen(&self) -> String {
+        Fen::from_position(&self.position, EnPassantMode::Legal).to_string()
+    }
+
+    /// The underlying shakmaty position.
+    pub fn position(&self) -> &ChessPosition {
+        &self.position
+    }
+
+    /// The color to move.
+    pub fn turn(&self) -> Color {
+        self.position.turn()
+    }
+
+    /// Parse a move in UCI notation (e.g. "e2e4" or "e7e8q").
+    pub fn parse_uci(&self, text: &str) -> Result<Move, String> {
+        let uci: UciMove = try!(text.parse().map_err(|_| format!("Invalid move '{}'", text)));
+        uci.to_move(&self.position).map_err(|_| format!("Illegal move '{}'", text))
+    }
+
+    /// Format a move in UCI notation.
+    pub fn uci(action: &Move) -> String {
+        action.to_uci(CastlingMode::Standard).to_string()
+    }
+
+    /// The winner of the game, if any.
+    pub fn winner(&self) -> Option<Color> {
+        match self.position.outcome() {
+            Outcome::Known(KnownOutcome::Decisive { winner }) => Some(winner),
+            _ => None
+        }
+    }
+
+    /// Is the game over, either by the rules or by adjudication?
+    pub fn is_finished(&self) -> bool {
+        self.moves >= self.max_plies ||
+            self.position.halfmoves() >= 100 ||
+            self.position.is_game_over()
+    }
+}
+
+impl Game<Move> for Chess {
+
+    /// Return a list with all allowed actions given the current game state.
+    fn allowed_actions(&self) -> Vec<Move> {
+        if self.moves >= self.max_plies || self.position.halfmoves() >= 100 ||
+           self.position.is_insufficient_material() {
+            return Vec::new();
+        }
+        self.position.legal_moves().into_iter().collect()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Move) {
+        assert!(self.position.is_legal(*action), "Illegal move");
+        self.position.play_unchecked(*action);
+        self.moves += 1;
+    }
+
+    /// Reward from the perspective of white.
+    ///
+    /// 1 for a win, -1 for a loss and 0 for draws and unfinished games.
+    fn reward(&self) -> f32 {
+        match self.winner() {
+            Some(Color::White) =>  1.,
+            Some(Color::Black) => -1.,
+            None => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    /// White is player 0, black is player 1.
+    fn current_player(&self) -> usize {
+        match self.position.turn() {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+}
+
+impl fmt::Display for Chess {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let board = self.position.board();
+        for rank in Rank::ALL.iter().rev() {
+            try!(write!(f, "{} ", rank.char()));
+            for file in &File::ALL {
+                let piece = board.piece_at(Square::from_coords(*file, *rank));
+                try!(write!(f, " {}", piece.map_or('.', |p| p.char())));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("   a b c d e f g h\n")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use chess::*;
+
+    #[test]
+    fn test_new() {
+        let game = Chess::new();
+
+        assert_eq!(game.allowed_actions().len(), 20);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.reward(), 0.);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_uci_moves() {
+        let mut game = Chess::new();
+        for text in &["f2f3", "e7e5", "g2g4", "d8h4"] {
+            let action = game.parse_uci(text).unwrap();
+            assert_eq!(Chess::uci(&action), *text);
+            game.make_move(&action);
+        }
+
+        // Fool's mate
+        assert!(game.is_finished());
+        assert_eq!(game.winner(), Some(Color::Black));
+        assert_eq!(game.reward(), -1.);
+        assert_eq!(game.allowed_actions().len(), 0);
+
+        assert!(Chess::new().parse_uci("e2e5").is_err());
+        assert!(Chess::new().parse_uci("xyz").is_err());
+    }
+
+    #[test]
+    fn test_fen() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+        let game = Chess::from_fen(fen).unwrap();
+        assert_eq!(game.fen(), fen);
+
+        // Insufficient material
+        assert!(game.is_finished());
+        assert_eq!(game.allowed_actions().len(), 0);
+
+        assert!(Chess::from_fen("not a fen").is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        let final_game = playout(&Chess::new());
+        assert!(final_game.is_finished());
+        assert!(final_game.moves <= MAX_PLIES);
+    }
+
+    #[test]
+    fn test_mcts_mates() {
+        // White mates with Ra8
+        let game = Chess::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(2000, 1.);
+        assert_eq!(mcts.best_action().map(|a| Chess::uci(&a)), Some("a1a8".to_string()));
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = Chess::new();
+        b.iter(|| playout(&game));
+    }
+}
@@ -0,0 +1,228 @@
+//!
+//! Interactive play between a human and the MCTS in the terminal.
+//!
+//! `play` runs the game loop for any game whose moves a human can type,
+//! see `DisplayAction`. The human takes the turns of one player and the
+//! engine those of everybody else; on their turn the human enters a move
+//! or one of the commands
+//!
+//!   * `h`, `hint` -- let the engine search the position and suggest a move
+//!   * `u`, `undo` -- take back the last move of the human, and the replies
+//!     of the engine to it
+//!   * `?`, `help` -- explain the commands
+//!   * `q`, `quit` -- stop playing
+//!
+
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+
+use mcts::{GameAction, Game, MCTS};
+
+/// Games whose moves a human can type.
+pub trait DisplayAction<A: GameAction>: Game<A> + fmt::Display {
+
+    /// Parse a move the way a human enters it, in lower case; None if the
+    /// text is no move at all, whether or not it is allowed.
+    fn parse_action(&self, text: &str) -> Option<A>;
+
+    /// Show a move in the notation `parse_action` reads.
+    fn show_action(&self, action: &A) -> String;
+
+    /// How to enter moves, e.g. "row and column, like '2 3'".
+    fn input_help(&self) -> String;
+}
+
+/// The engine side of an interactive game.
+///
+/// Closures that search a state are engines.
+pub trait Engine<G: Game<A>, A: GameAction> {
+
+    /// Search `game`, for the reply of the engine or for a hint.
+    fn search(&mut self, game: &G) -> MCTS<G, A>;
+
+    /// Called after every move and undo; e.g. to keep a save file.
+    fn changed(&mut self, _session: &Session<G, A>) {}
+}
+
+impl<G: Game<A>, A: GameAction, F: FnMut(&G) -> MCTS<G, A>> Engine<G, A> for F {
+    fn search(&mut self, game: &G) -> MCTS<G, A> {
+        self(game)
+    }
+}
+
+/// A game in progress with the states before every move, for undo.
+#[derive(Debug, Clone)]
+pub struct Session<G: Game<A>, A: GameAction> {
+    game: G,
+    history: Vec<(G, A)>,
+}
+
+impl<G: Game<A>, A: GameAction> Session<G, A> {
+    /// Start playing from `game`.
+    pub fn new(game: &G) -> Session<G, A> {
+        Session { game: game.clone(), history: Vec::new() }
+    }
+
+    /// The current state.
+    pub fn game(&self) -> &G {
+        &self.game
+    }
+
+    /// The moves made so far.
+    pub fn moves(&self) -> Vec<A> {
+        self.history.iter().map(|&(_, action)| action).collect()
+    }
+
+    /// Make a move.
+    pub fn make_move(&mut self, action: &A) {
+        self.history.push((self.game.clone(), *action));
+        self.game.make_move(action);
+    }
+
+    /// Take back the last move of `player` and every move after it.
+    ///
+    /// Returns whether `player` made a move to take back.
+    pub fn undo(&mut self, player: usize) -> bool {
+        match self.history.iter().rposition(|(game, _)| game.current_player() == player) {
+            Some(idx) => {
+                self.game = self.history[idx].0.clone();
+                self.history.truncate(idx);
+                true
+            },
+            None => false
+        }
+    }
+}
+
+/// Print the engine's assessment of the current state and its suggestion.
+fn print_hint<G, A, O>(game: &G, mcts: &MCTS<G, A>, output: &mut O) -> io::Result<()>
+        where G: DisplayAction<A>, A: GameAction, O: Write {
+    for (action, visits, value) in mcts.action_values() {
+        try!(writeln!(output, "  {:<8} value={:<10.3} visits={}", game.show_action(&action), value, visits));
+    }
+    match mcts.best_action() {
+        Some(action) => writeln!(output, "Suggested move: {}", game.show_action(&action)),
+        None => writeln!(output, "No move to suggest.")
+    }
+}
+
+/// Let a human play `player` against `engine` until the game is over or
+/// the human quits, reading commands from `input`.
+///
+/// Every state is shown on `output` as it is printed with `{}`.
+pub fn play<G, A, E, I, O>(session: &mut Session<G, A>, player: usize, engine: &mut E,
+                           input: I, mut output: O) -> io::Result<()>
+        where G: DisplayAction<A>, A: GameAction, E: Engine<G, A>, I: BufRead, O: Write {
+    let help = format!("Enter a move ({}), h for a hint, u to undo, q to quit.", session.game().input_help());
+    try!(writeln!(output, "{}\n{}", help, session.game()));
+
+    let mut lines = input.lines();
+    while !session.game().is_terminal() {
+        if session.game().current_player() != player {
+            let action = match engine.search(session.game()).best_action() {
+                Some(action) => action,
+                None => break
+            };
+            let shown = session.game().show_action(&action);
+            session.make_move(&action);
+            engine.changed(session);
+            try!(writeln!(output, "\n... the MCTS plays {}:\n{}", shown, session.game()));
+            continue;
+        }
+
+        try!(write!(output, "\nYour move: "));
+        try!(output.flush());
+        let line = match lines.next() {
+            Some(line) => try!(line),
+            None => break       // EOF
+        };
+
+        let input = line.trim().to_lowercase();
+        match input.as_ref() {
+            "q" | "quit" => break,
+            "?" | "help" => try!(writeln!(output, "{}", help)),
+            "h" | "hint" => {
+                let mcts = engine.search(session.game());
+                try!(print_hint(session.game(), &mcts, &mut output));
+            },
+            "u" | "undo" => {
+                if session.undo(player) {
+                    engine.changed(session);
+                    try!(writeln!(output, "\n... taken back:\n{}", session.game()));
+                } else {
+                    try!(writeln!(output, "Nothing to undo."));
+                }
+            },
+            _ => match session.game().parse_action(&input) {
+                Some(action) if session.game().allowed_actions().contains(&action) => {
+                    let shown = session.game().show_action(&action);
+                    session.make_move(&action);
+                    engine.changed(session);
+                    try!(writeln!(output, "\n... you play {}:\n{}", shown, session.game()));
+                },
+                Some(action) => try!(writeln!(output, "{} is not allowed here.", session.game().show_action(&action))),
+                None => try!(writeln!(output, "Unknown command '{}'; enter ? for help.", input))
+            }
+        }
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use cli::*;
+    use tictactoe::{TicTacToe, Action};
+    use utils::seeded_rng;
+
+    /// Play against a fixed number of iterations, seeded for every search.
+    fn transcript(input: &str, player: usize) -> (Session<TicTacToe, Action>, String) {
+        let mut session = Session::new(&TicTacToe::new());
+        let mut seed = 0;
+        let mut engine = |game: &TicTacToe| {
+            seed += 1;
+            let mut mcts = MCTS::new(game, 1);
+            mcts.search_with(200, 1., &mut seeded_rng(seed));
+            mcts
+        };
+        let mut output = Vec::new();
+        play(&mut session, player, &mut engine, input.as_bytes(), &mut output).unwrap();
+        (session, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_session_undo() {
+        let mut session = Session::new(&TicTacToe::new());
+        assert!(!session.undo(0));
+        for &(row, col) in &[(0, 0), (1, 1), (0, 1)] {
+            session.make_move(&Action { row: row, col: col });
+        }
+        assert!(session.undo(1));
+        assert_eq!(session.moves(), vec![Action { row: 0, col: 0 }]);
+        assert_eq!(session.game().moves, 1);
+        assert!(session.undo(0));
+        assert!(session.moves().is_empty());
+    }
+
+    #[test]
+    fn test_play() {
+        // The engine replies to every move; undo takes back both
+        let (session, output) = transcript("2 2\nu\nx\n4 4\n2 2\n2 2\nh\nq\n", 0);
+        assert_eq!(session.moves().len(), 2);
+        assert_eq!(session.moves()[0], Action { row: 1, col: 1 });
+        assert_eq!(output.matches("the MCTS plays").count(), 2);
+        assert!(output.contains("taken back"));
+        assert!(output.contains("Unknown command 'x'"));
+        assert!(output.contains("Unknown command '4 4'"));
+        assert!(output.contains("2 2 is not allowed here."));
+        assert!(output.contains("Suggested move: "));
+
+        // Playing second, the engine opens; EOF ends the game
+        let (session, output) = transcript("", 1);
+        assert_eq!(session.moves().len(), 1);
+        assert!(output.ends_with("Your move: "));
+    }
+}
@@ -0,0 +1,257 @@
+//!
+//! TOML configuration files for the binaries.
+//!
+//! Every binary reads the section named after it; its keys are the long
+//! command line options with dashes replaced by underscores. Agents are
+//! described in `[agents.NAME]` tables with the keys known to
+//! `AgentConfig`:
+//!
+//! ```toml
+//! [2048]
+//! time_per_move = 0.5
+//! ensemble_size = 10
+//!
+//! [agents.strong]
+//! c = 1.4
+//! n = 2000
+//! ```
+//!
+//! Values given in the file serve as defaults; command line options
+//! override them.
+//!
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+use toml::Value;
+use toml::value::Table;
+
+use agents::AgentConfig;
+
+/// A parsed configuration file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    table: Table,
+}
+
+impl Config {
+    /// An empty configuration.
+    pub fn empty() -> Config {
+        Config { table: Table::new() }
+    }
+
+    /// Parse a configuration from a string.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        match text.parse::<Value>() {
+            Ok(Value::Table(table)) => Ok(Config { table: table }),
+            Ok(_) => Err("Configuration is not a table".to_string()),
+            Err(e) => Err(format!("Invalid configuration: {}", e)),
+        }
+    }
+
+    /// Read and parse a configuration file.
+    pub fn load(path: &str) -> Result<Config, String> {
+        let mut text = String::new();
+        try!(File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e)));
+        Config::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Load the file given by `--config PATH` on the command line, if any.
+    ///
+    /// Binaries call this before parsing the remaining options so that
+    /// those can override the values from the file.
+    pub fn from_args() -> Result<Config, String> {
+        let args = env::args().collect::<Vec<_>>();
+        for (i, arg) in args.iter().enumerate() {
+            if arg == "--config" {
+                return match args.get(i+1) {
+                    Some(path) => Config::load(path),
+                    None => Err("--config needs a file name".to_string()),
+                };
+            }
+            if arg.starts_with("--config=") {
+                return Config::load(&arg["--config=".len()..]);
+            }
+        }
+        Ok(Config::empty())
+    }
+
+    /// Parse the value of `key` in `section`, if present.
+    fn value<T: FromStr>(&self, section: &str, key: &str) -> Result<Option<T>, String> {
+        let value = match self.table.get(section).and_then(|s| s.get(key)) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let text = try!(scalar_to_string(value)
+            .ok_or(format!("[{}] {}: expected a scalar value", section, key)));
+        text.parse()
+            .map(|v| Some(v))
+            .map_err(|_| format!("[{}] {}: invalid value '{}'", section, key, text))
+    }
+
+    /// Overwrite `target` with the value of `key` in `section`, if present.
+    pub fn get<T: FromStr>(&self, section: &str, key: &str, target: &mut T) -> Result<(), String> {
+        if let Some(value) = try!(self.value(section, key)) {
+            *target = value;
+        }
+        Ok(())
+    }
+
+    /// Like `get`, but for optional values.
+    pub fn get_option<T: FromStr>(&self, section: &str, key: &str, target: &mut Option<T>) -> Result<(), String> {
+        if let Some(value) = try!(self.value(section, key)) {
+            *target = Some(value);
+        }
+        Ok(())
+    }
+
+    /// Make sure `section` only contains the given keys.
+    pub fn check_keys(&self, section: &str, keys: &[&str]) -> Result<(), String> {
+        if let Some(table) = self.table.get(section).and_then(|s| s.as_table()) {
+            for key in table.keys() {
+                if !keys.contains(&key.as_ref()) {
+                    return Err(format!("[{}]: unknown key '{}'", section, key));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of all agents defined in `[agents.NAME]` tables.
+    pub fn agent_names(&self) -> Vec<String> {
+        match self.table.get("agents").and_then(|a| a.as_table()) {
+            Some(agents) => agents.keys().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Create an agent configuration from `NAME[:KEY=VALUE,...]`.
+    ///
+    /// If the file defines `[agents.NAME]`, its values are applied first;
+    /// options given in `spec` override them.
+    pub fn agent(&self, spec: &str) -> Result<AgentConfig, String> {
+        let mut parts = spec.splitn(2, ':');
+        let name = parts.next().unwrap().trim();
+        let mut config = try!(AgentConfig::parse(name));
+
+        let table = self.table.get("agents")
+                .and_then(|a| a.get(name))
+                .and_then(|a| a.as_table());
+        if let Some(table) = table {
+            for (key, value) in table {
+                let text = try!(scalar_to_string(value)
+                    .ok_or(format!("[agents.{}] {}: expected a scalar value", name, key)));
+                try!(config.set(key, &text));
+            }
+        }
+        try!(config.apply(parts.next().unwrap_or("")));
+        Ok(config)
+    }
+}
+
+/// Textual form of numbers, strings and booleans.
+///
+/// Arrays of those are joined with commas, the format the binaries
+/// use for lists on the command line.
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match *value {
+        Value::String(ref s) => Some(s.clone()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::Boolean(b) => Some(b.to_string()),
+        Value::Array(ref values) => {
+            let items = values.iter()
+                .map(|v| match *v {
+                    Value::Array(_) => None,
+                    _ => scalar_to_string(v),
+                })
+                .collect::<Option<Vec<_>>>();
+            items.map(|items| items.join(","))
+        },
+        _ => None,
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use agents::*;
+    use config::*;
+
+    const EXAMPLE: &'static str = r#"
+        [2048]
+        time_per_move = 0.5
+        ensemble_size = 4
+        seed = 7
+        c = [0.5, 1, 2]
+
+        [agents.strong]
+        c = 1.5
+        n = 2000
+
+        [agents.baseline]
+        policy = "random"
+    "#;
+
+    #[test]
+    fn test_get() {
+        let config = Config::parse(EXAMPLE).unwrap();
+
+        let mut time_per_move = 1.0f32;
+        let mut ensemble_size = 10usize;
+        let mut repeats = 1usize;
+        let mut seed: Option<u32> = None;
+        config.get("2048", "time_per_move", &mut time_per_move).unwrap();
+        config.get("2048", "ensemble_size", &mut ensemble_size).unwrap();
+        config.get("2048", "repeat", &mut repeats).unwrap();
+        config.get_option("2048", "seed", &mut seed).unwrap();
+
+        assert_eq!(time_per_move, 0.5);
+        assert_eq!(ensemble_size, 4);
+        assert_eq!(repeats, 1);
+        assert_eq!(seed, Some(7));
+
+        let mut c_list = String::new();
+        config.get("2048", "c", &mut c_list).unwrap();
+        assert_eq!(c_list, "0.5,1,2");
+
+        let mut c = 0u32;
+        assert!(config.get("2048", "time_per_move", &mut c).is_err());
+
+        assert!(config.check_keys("2048", &["time_per_move", "ensemble_size", "seed", "c"]).is_ok());
+        assert!(config.check_keys("2048", &["time_per_move"]).is_err());
+        assert!(config.check_keys("missing", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_agents() {
+        let config = Config::parse(EXAMPLE).unwrap();
+        assert_eq!(config.agent_names(), vec!["baseline", "strong"]);
+
+        let strong = config.agent("strong").unwrap();
+        assert_eq!(strong.c, 1.5);
+        assert_eq!(strong.budget, Budget::Iterations(2000));
+
+        // Command line options override the file
+        let strong = config.agent("strong:n=10").unwrap();
+        assert_eq!(strong.c, 1.5);
+        assert_eq!(strong.budget, Budget::Iterations(10));
+
+        assert_eq!(config.agent("baseline").unwrap().policy, Policy::Random);
+        assert_eq!(config.agent("other:c=2").unwrap().c, 2.);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Config::parse("[2048\n").is_err());
+        assert!(Config::parse("[agents.x]\nfoo = 1").unwrap().agent("x").is_err());
+        assert!(Config::parse("[agents.x]\nc = [1, 2]").unwrap().agent("x").is_err());
+        assert!(Config::parse("[agents.x]\nc = { a = 1 }").unwrap().agent("x").is_err());
+        assert!(Config::load("/nonexistent/engine.toml").is_err());
+    }
+}
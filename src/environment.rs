@@ -0,0 +1,220 @@
+//!
+//! Gym-style environments and adapters to and from `Game`.
+//!
+//! An `Environment` is reset to an initial observation and then stepped
+//! with actions, returning the next observation, the reward for the step
+//! and whether the episode is over.
+//!
+//!   * `GameEnv` turns any `Game` into an environment whose observations
+//!     are the game states and whose step rewards are the increments of
+//!     `Game::reward`.
+//!   * `EnvGame` turns a cloneable environment into a `Game` so it can be
+//!     planned over with the MCTS; `plan` does so for the current state,
+//!     which lets the search act as the policy inside an RL loop.
+//!
+
+use mcts::{Game, GameAction};
+use protocol::SearchSettings;
+
+/// A reinforcement learning environment.
+pub trait Environment {
+    type Action: GameAction;
+    type Observation;
+
+    /// Start a new episode and return its first observation.
+    fn reset(&mut self) -> Self::Observation;
+
+    /// Perform an action; returns the observation, the reward for this step
+    /// and whether the episode is over.
+    fn step(&mut self, action: &Self::Action) -> (Self::Observation, f32, bool);
+
+    /// Actions that may be passed to the next `step`.
+    fn legal_actions(&self) -> Vec<Self::Action>;
+
+    /// Make the environment's random choices reproducible.
+    fn seed(&mut self, _seed: u32) {}
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Games as environments
+
+/// A `Game` stepped like an environment.
+///
+/// Observations are copies of the game state. For two-player games the
+/// environment plays both sides and rewards are those of player 0.
+pub struct GameEnv<G: Game<A>, A: GameAction> {
+    initial: G,
+    pub game: G,
+    phantom: ::std::marker::PhantomData<A>,
+}
+
+impl<G: Game<A>, A: GameAction> GameEnv<G, A> {
+    /// Create an environment whose episodes start from `initial`.
+    pub fn new(initial: G) -> GameEnv<G, A> {
+        GameEnv { game: initial.clone(), initial: initial, phantom: ::std::marker::PhantomData }
+    }
+}
+
+impl<G: Game<A>, A: GameAction> Environment for GameEnv<G, A> {
+    type Action = A;
+    type Observation = G;
+
+    fn reset(&mut self) -> G {
+        self.game = self.initial.clone();
+        self.game.clone()
+    }
+
+    fn step(&mut self, action: &A) -> (G, f32, bool) {
+        let before = self.game.reward();
+        self.game.make_move(action);
+        let done = self.game.is_terminal();
+        (self.game.clone(), self.game.reward() - before, done)
+    }
+
+    fn legal_actions(&self) -> Vec<A> {
+        self.game.allowed_actions()
+    }
+
+    fn seed(&mut self, seed: u32) {
+        self.initial.set_rng_seed(seed);
+        self.game.set_rng_seed(seed);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Environments as games
+
+/// A cloneable `Environment` searchable with the MCTS.
+///
+/// The reward is the sum of the step rewards since the game was created.
+#[derive(Clone)]
+pub struct EnvGame<E: Environment + Clone> {
+    pub env: E,
+    pub total_reward: f32,
+    pub done: bool,
+}
+
+impl<E: Environment + Clone> EnvGame<E> {
+    /// Plan from the current state of `env`.
+    pub fn new(env: E) -> EnvGame<E> {
+        EnvGame { env: env, total_reward: 0., done: false }
+    }
+}
+
+impl<E: Environment + Clone> Game<E::Action> for EnvGame<E> {
+    fn allowed_actions(&self) -> Vec<E::Action> {
+        if self.done {
+            return Vec::new();
+        }
+        self.env.legal_actions()
+    }
+
+    fn make_move(&mut self, action: &E::Action) {
+        let (_, reward, done) = self.env.step(action);
+        self.total_reward += reward;
+        self.done = done;
+    }
+
+    fn reward(&self) -> f32 {
+        self.total_reward
+    }
+
+    fn running_reward(&self) -> bool {
+        true
+    }
+
+    fn set_rng_seed(&mut self, seed: u32) {
+        self.env.seed(seed);
+    }
+}
+
+/// Choose an action for the current state of `env` by searching over
+/// copies of it; None if there is no legal action.
+pub fn plan<E: Environment + Clone>(env: &E, settings: &SearchSettings) -> Option<E::Action> {
+    settings.search(&EnvGame::new(env.clone())).best_action()
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use agents::Budget;
+    use protocol::SearchSettings;
+    use environment::*;
+    use twofortyeight::{self, TwoFortyEight};
+    use utils::seed_thread_rng;
+
+    /// Walk along a corridor of length 5; reaching the right end pays 1,
+    /// every step costs 0.1.
+    #[derive(Clone)]
+    struct Corridor {
+        position: i32,
+        steps: usize,
+    }
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+    enum Step { Left, Right }
+    impl GameAction for Step {}
+
+    impl Environment for Corridor {
+        type Action = Step;
+        type Observation = i32;
+
+        fn reset(&mut self) -> i32 {
+            self.position = 0;
+            self.steps = 0;
+            self.position
+        }
+
+        fn step(&mut self, action: &Step) -> (i32, f32, bool) {
+            self.position += if *action == Step::Right { 1 } else { -1 };
+            self.position = self.position.max(0);
+            self.steps += 1;
+            let goal = self.position == 4;
+            (self.position, if goal { 0.9 } else { -0.1 }, goal || self.steps == 10)
+        }
+
+        fn legal_actions(&self) -> Vec<Step> {
+            vec![Step::Left, Step::Right]
+        }
+    }
+
+    #[test]
+    fn test_game_env() {
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 0, 2);
+        game.set_tile(0, 1, 2);
+        let mut env = GameEnv::new(game);
+
+        let (obs, reward, done) = env.step(&twofortyeight::Action::Left);
+        assert_eq!(obs.get_tile(0, 0), 4);
+        assert_eq!(reward, 4.);
+        assert!(!done);
+
+        let obs = env.reset();
+        assert_eq!(obs.get_tile(0, 0), 2);
+        // Up leaves the top row as it is
+        assert_eq!(env.legal_actions().len(), 3);
+    }
+
+    #[test]
+    fn test_plan() {
+        seed_thread_rng(1);
+        let settings = SearchSettings { budget: Budget::Iterations(200), ensemble_size: 1, c: 1. };
+        let mut env = Corridor { position: 0, steps: 0 };
+        env.reset();
+
+        let mut total = 0.;
+        loop {
+            let action = plan(&env, &settings).unwrap();
+            let (_, reward, done) = env.step(&action);
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        assert_eq!(env.position, 4);
+        assert!((total - 0.6).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,236 @@
+//!
+//! Depth-limited expectimax, the classical approach to 2048, as a baseline
+//! to compare the MCTS with.
+//!
+//! The search plays through every move up to a fixed depth on the same
+//! `Game` hooks the MCTS uses:
+//!
+//!   * Player 0 picks the move with the highest value, every other player
+//!     the one with the lowest, as in a zero-sum game.
+//!   * Chance moves, see `Game::chance_outcomes`, and the dice rolled after
+//!     the move of a player, see `Game::afterstate_outcomes`, count with
+//!     the expectation over their outcomes. Games that cannot list the
+//!     outcomes of their dice roll them once with `Game::resolve_chance`.
+//!   * States at the depth limit are valued by an `Evaluator`, by default
+//!     by `Game::evaluate`; states where the game is over by their reward.
+//!
+//! The depth counts the moves of the players; chance moves come for free.
+//! All values are for player 0, like `Game::reward`.
+//!
+
+use std::f32;
+
+use mcts::{Game, GameAction, Evaluator};
+
+/// Depth-limited expectimax search.
+#[derive(Debug, Clone)]
+pub struct Expectimax {
+    /// Moves of the players to look ahead.
+    pub depth: usize,
+    nodes: u64,
+}
+
+impl Expectimax {
+    /// Search `depth` moves ahead.
+    pub fn new(depth: usize) -> Expectimax {
+        Expectimax { depth: depth, nodes: 0 }
+    }
+
+    /// The number of states the last search visited.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// The value of `game` for player 0, valuing the states at the depth
+    /// limit with `Game::evaluate`.
+    pub fn value<G: Game<A>, A: GameAction>(&mut self, game: &G) -> f32 {
+        self.value_with(game, &|game: &G| game.evaluate())
+    }
+
+    /// The value of `game` for player 0, valuing the states at the depth
+    /// limit with `evaluator`.
+    pub fn value_with<G: Game<A>, A: GameAction, E: Evaluator<G>>(&mut self, game: &G, evaluator: &E) -> f32 {
+        self.nodes = 0;
+        let depth = self.depth;
+        self.state_value(game, depth, evaluator)
+    }
+
+    /// The values of the allowed actions of `game` for player 0, valuing the
+    /// states at the depth limit with `Game::evaluate`.
+    pub fn action_values<G: Game<A>, A: GameAction>(&mut self, game: &G) -> Vec<(A, f32)> {
+        self.action_values_with(game, &|game: &G| game.evaluate())
+    }
+
+    /// The values of the allowed actions of `game` for player 0, valuing the
+    /// states at the depth limit with `evaluator`.
+    pub fn action_values_with<G, A, E>(&mut self, game: &G, evaluator: &E) -> Vec<(A, f32)>
+            where G: Game<A>, A: GameAction, E: Evaluator<G> {
+        self.nodes = 1;
+        let depth = self.depth.max(1);
+        game.allowed_actions().into_iter()
+            .map(|action| (action, self.move_value(game, &action, depth, evaluator)))
+            .collect()
+    }
+
+    /// The best action for the player to move, or None if the game is over.
+    pub fn best_action<G: Game<A>, A: GameAction>(&mut self, game: &G) -> Option<A> {
+        self.best_action_with(game, &|game: &G| game.evaluate())
+    }
+
+    /// The best action for the player to move, valuing the states at the
+    /// depth limit with `evaluator`.
+    pub fn best_action_with<G, A, E>(&mut self, game: &G, evaluator: &E) -> Option<A>
+            where G: Game<A>, A: GameAction, E: Evaluator<G> {
+        let values = self.action_values_with(game, evaluator);
+        best_value(&values, game.current_player()).map(|(action, _)| action)
+    }
+
+    /// The value of a state with `depth` moves of the players to go.
+    fn state_value<G: Game<A>, A: GameAction, E: Evaluator<G>>(&mut self, game: &G, depth: usize,
+                                                                 evaluator: &E) -> f32 {
+        self.nodes += 1;
+        if game.is_terminal() {
+            return game.reward();
+        }
+        if let Some(outcomes) = game.chance_outcomes() {
+            return outcomes.iter().map(|&(action, probability)| {
+                let mut outcome = game.clone();
+                outcome.make_move(&action);
+                probability * self.state_value(&outcome, depth, evaluator)
+            }).sum();
+        }
+        if depth == 0 {
+            return evaluator.evaluate(game);
+        }
+
+        let values = game.allowed_actions().iter()
+            .map(|action| self.move_value(game, action, depth, evaluator))
+            .collect::<Vec<_>>();
+        if game.current_player() == 0 {
+            values.into_iter().fold(f32::NEG_INFINITY, f32::max)
+        } else {
+            values.into_iter().fold(f32::INFINITY, f32::min)
+        }
+    }
+
+    /// The value of making `action` in `game`, with the expectation over
+    /// the dice rolled after it.
+    fn move_value<G: Game<A>, A: GameAction, E: Evaluator<G>>(&mut self, game: &G, action: &A, depth: usize,
+                                                                evaluator: &E) -> f32 {
+        if game.chance_outcomes().is_some() {
+            let mut outcome = game.clone();
+            outcome.make_move(action);
+            return self.state_value(&outcome, depth, evaluator);
+        }
+        let mut after = game.clone();
+        after.apply_player_move(action);
+        match after.afterstate_outcomes() {
+            Some(outcomes) => outcomes.iter()
+                .map(|&(ref outcome, probability)| probability * self.state_value(outcome, depth - 1, evaluator))
+                .sum(),
+            None => {
+                after.resolve_chance();
+                self.state_value(&after, depth - 1, evaluator)
+            }
+        }
+    }
+}
+
+/// The best of the action `values` for `player`: the highest for player 0,
+/// the lowest for everybody else. The first one wins ties.
+pub fn best_value<A: GameAction>(values: &[(A, f32)], player: usize) -> Option<(A, f32)> {
+    let sign = if player == 0 { 1. } else { -1. };
+    values.iter().fold(None, |best: Option<(A, f32)>, &(action, value)| match best {
+        Some((_, best_value)) if sign * best_value >= sign * value => best,
+        _ => Some((action, value))
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+    use test::Bencher;
+
+    use mcts::*;
+    use expectimax::*;
+    use adv2048::Adversarial2048;
+    use tictactoe::{TicTacToe, Action};
+    use twofortyeight::{TwoFortyEight, Action as Direction};
+
+    #[test]
+    fn test_twofortyeight() {
+        let mut game = TwoFortyEight::new_empty();
+        for (col, &tile) in [2, 2, 4, 8].iter().enumerate() {
+            game.set_tile(0, col, tile);
+        }
+
+        // One move ahead, the spawn does not change the score; every empty
+        // cell gets a 2 or a 4
+        let mut expectimax = Expectimax::new(1);
+        let values = expectimax.action_values(&game);
+        let mut spawns = 0;
+        for &(action, value) in &values {
+            let mut slid = game.clone();
+            slid.slide(&action);
+            assert!((value - slid.score).abs() < 1e-3, "{:?}: {}", action, value);
+            spawns += 2 * (0..16).filter(|&i| slid.get_tile(i / 4, i % 4) == 0).count() as u64;
+        }
+        assert_eq!(values.len(), 3);
+        assert_eq!(expectimax.nodes(), 1 + spawns);
+
+        // Two moves ahead, both merges of the first row pay off; left and
+        // right are as good
+        let mut expectimax = Expectimax::new(2);
+        let values = expectimax.action_values(&game);
+        let value = values.iter().map(|&(_, value)| value).fold(f32::NEG_INFINITY, f32::max);
+        let best = expectimax.best_action(&game).unwrap();
+        assert!(values.contains(&(best, value)));
+        assert!(best == Direction::Left || best == Direction::Right);
+        assert_eq!(expectimax.value(&game), value);
+        assert!(value >= 4. + 8.);
+    }
+
+    #[test]
+    fn test_min_player() {
+        // X to move wins in the top row; if X plays elsewhere, O wins in the
+        // middle one
+        let mut game = TicTacToe::new();
+        for &(row, col) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.make_move(&Action { row: row, col: col });
+        }
+        let mut expectimax = Expectimax::new(1);
+        assert_eq!(expectimax.best_action(&game), Some(Action { row: 0, col: 2 }));
+        assert_eq!(expectimax.value(&game), 1.);
+
+        game.make_move(&Action { row: 2, col: 2 });
+        assert_eq!(expectimax.best_action(&game), Some(Action { row: 1, col: 2 }));
+        assert_eq!(expectimax.value(&game), -1.);
+        let values = [(Direction::Up, 0.5), (Direction::Down, -1.), (Direction::Left, -1.)];
+        assert_eq!(best_value(&values, 1), Some((Direction::Down, -1.)));
+        assert_eq!(best_value(&values, 0), Some((Direction::Up, 0.5)));
+    }
+
+    #[test]
+    fn test_chance_moves() {
+        // With random spawns, the spawn is the expectation over them
+        let mut game = Adversarial2048::with_seed(2);
+        game.random_spawns = true;
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+
+        let mut expectimax = Expectimax::new(1);
+        let spawns = game.allowed_actions().len();
+        let value = expectimax.value(&game);
+        assert!(value >= game.reward());
+        assert!(expectimax.nodes() as usize > spawns);
+        assert!(expectimax.best_action(&Adversarial2048::with_seed(2)).is_some());
+    }
+
+    #[bench]
+    fn bench_depth_2(b: &mut Bencher) {
+        let game = TwoFortyEight::with_seed(1);
+        b.iter(|| Expectimax::new(2).best_action(&game))
+    }
+}
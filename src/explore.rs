@@ -0,0 +1,261 @@
+//!
+//! An interactive debugger for the search itself.
+//!
+//! The explorer keeps a search tree for a position and lets the user grow
+//! it a few iterations at a time while walking around in it:
+//!
+//!   * `newgame`, `state <json>` or `load <file>` -- set the position and
+//!     start a fresh tree; `state` alone prints the position
+//!   * `search <n>` -- run n more iterations in every ensemble member
+//!   * `c <value>` -- set the exploration constant used from now on
+//!   * `tree <i>` -- browse the tree of ensemble member i
+//!   * `show` -- the current node and its children with their UCT terms
+//!   * `down <action>`, `up`, `top` -- move around in the tree
+//!   * `expand [action]` -- force the expansion of the current node,
+//!     evaluating the new child with a single playout
+//!   * `pv` -- the principal variation of the whole ensemble
+//!   * `quit`
+//!
+//! Actions are written the way they are printed with `{:?}`.
+//!
+
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Read, Write};
+
+use serde_json::Value;
+
+use mcts::{GameAction, Game, MCTS, TreeNode};
+use protocol::{SerializableGame, SearchSettings, parse_action};
+
+const HELP: &'static str = "\
+commands: newgame, state [json], load <file>, search <n>, c [value], tree [i], \
+show, down <action>, up, top, expand [action], pv, help, quit";
+
+/// Explorer state between commands.
+struct Explorer<G: Game<A>, A: GameAction> {
+    mcts: MCTS<G, A>,
+    ensemble_size: usize,
+    c: f32,
+    member: usize,
+    path: Vec<A>,
+}
+
+impl<G: SerializableGame<A>, A: GameAction> Explorer<G, A> {
+    fn new(game: &G, ensemble_size: usize, c: f32) -> Explorer<G, A> {
+        Explorer { mcts: MCTS::new(game, ensemble_size), ensemble_size: ensemble_size, c: c,
+                   member: 0, path: Vec::new() }
+    }
+
+    /// Start a fresh tree for `game`.
+    fn reset(&mut self, game: &G) {
+        *self = Explorer::new(game, self.ensemble_size, self.c);
+    }
+
+    /// The node at the current path.
+    fn node(&self) -> TreeNode<'_, A> {
+        self.mcts.roots()[self.member].get(&self.path).expect("Path leads out of the tree")
+    }
+
+    /// The game state at the current node, as seen by the current member.
+    fn game(&self) -> G {
+        let mut game = self.mcts.games()[self.member].clone();
+        for action in &self.path {
+            game.make_move(action);
+        }
+        game
+    }
+
+    /// Describe the current node and its children.
+    fn show(&self) -> String {
+        let node = self.node();
+        let mut path = vec!["root".to_string()];
+        path.extend(self.path.iter().map(|action| format!("{:?}", action)));
+
+        let mut lines = vec![
+            format!("tree {} of {}: {}", self.member, self.ensemble_size, path.join(" > ")),
+            format!("visits {}, mean {}, {}", node.visits(), format_mean(&node),
+                    if node.is_expandable() { "expandable" } else { "fully expanded" }),
+            format!("state {}", self.game().to_json()),
+        ];
+
+        let mut children = node.children();
+        children.sort_by(|a, b| b.visits().partial_cmp(&a.visits()).unwrap());
+        if !children.is_empty() {
+            lines.push(format!("  {:<28} {:>8} {:>12} {:>10} {:>12}", "action", "visits", "mean", "explore", "uct"));
+        }
+        for child in children {
+            let (exploitation, exploration) = child.uct_terms(node.visits(), self.c);
+            lines.push(format!("  {:<28} {:>8} {:>12} {:>10.3} {:>12.3}",
+                               format!("{:?}", child.action().unwrap()), child.visits(),
+                               format_mean(&child), exploration, exploitation + exploration));
+        }
+        lines.join("\n")
+    }
+
+    /// Execute a single command; returns the answer.
+    fn execute(&mut self, initial: &G, command: &str, arg: &str) -> Result<String, String> {
+        match command {
+            "help" => Ok(HELP.to_string()),
+            "newgame" => {
+                self.reset(initial);
+                Ok("ok".to_string())
+            },
+            "state" if arg.is_empty() => Ok(self.game().to_json().to_string()),
+            "state" => {
+                let value = try!(arg.parse::<Value>().map_err(|e| format!("invalid JSON: {}", e)));
+                let game = try!(G::from_json(&value));
+                self.reset(&game);
+                Ok("ok".to_string())
+            },
+            "load" => {
+                let mut text = String::new();
+                try!(File::open(arg)
+                    .and_then(|mut file| file.read_to_string(&mut text))
+                    .map_err(|e| format!("failed to read {}: {}", arg, e)));
+                let value = try!(text.parse::<Value>().map_err(|e| format!("invalid JSON: {}", e)));
+                let game = try!(G::from_json(&value));
+                self.reset(&game);
+                Ok("ok".to_string())
+            },
+            "search" => {
+                let n: usize = try!(arg.parse().map_err(|_| format!("invalid number of iterations '{}'", arg)));
+                self.mcts.search(n, self.c);
+                Ok(self.show())
+            },
+            "c" if arg.is_empty() => Ok(format!("c {}", self.c)),
+            "c" => {
+                self.c = try!(arg.parse().map_err(|_| format!("invalid exploration constant '{}'", arg)));
+                Ok(format!("c {}", self.c))
+            },
+            "tree" if arg.is_empty() => Ok(format!("tree {} of {}", self.member, self.ensemble_size)),
+            "tree" => {
+                let member: usize = try!(arg.parse().map_err(|_| format!("invalid tree '{}'", arg)));
+                if member >= self.ensemble_size {
+                    return Err(format!("there are only {} trees", self.ensemble_size));
+                }
+                self.member = member;
+                self.path.clear();
+                Ok(self.show())
+            },
+            "show" => Ok(self.show()),
+            "down" => {
+                let action = try!(self.node().children().iter()
+                    .filter_map(|child| child.action())
+                    .find(|action| format!("{:?}", action) == arg)
+                    .ok_or(format!("no child '{}'", arg)));
+                self.path.push(action);
+                Ok(self.show())
+            },
+            "up" => {
+                if self.path.pop().is_none() {
+                    return Err("already at the root".to_string());
+                }
+                Ok(self.show())
+            },
+            "top" => {
+                self.path.clear();
+                Ok(self.show())
+            },
+            "expand" => {
+                let action = if arg.is_empty() { None } else { Some(try!(parse_action(&self.game(), arg))) };
+                match self.mcts.expand_at(self.member, &self.path, action) {
+                    Some(_) => Ok(self.show()),
+                    None => Err("nothing to expand".to_string())
+                }
+            },
+            "pv" => Ok(self.mcts.principal_variation(usize::MAX).iter()
+                .map(|action| format!("{:?}", action))
+                .collect::<Vec<_>>()
+                .join(" ")),
+            _ => Err(format!("unknown command '{}'; try help", command))
+        }
+    }
+}
+
+/// The mean reward of a node, or "-" if it was never visited.
+fn format_mean<A: GameAction>(node: &TreeNode<A>) -> String {
+    if node.visits() > 0 { format!("{:.3}", node.mean()) } else { "-".to_string() }
+}
+
+/// Run the explorer on the commands read from `input` until `quit` or EOF.
+///
+/// `initial` is the position at startup and for `newgame`; the ensemble
+/// size and exploration constant are taken from `settings`.
+pub fn run<G, A, R, W>(initial: &G, settings: &SearchSettings, input: R, mut output: W) -> io::Result<()>
+        where G: SerializableGame<A>, A: GameAction, R: BufRead, W: Write {
+    let mut explorer = Explorer::new(initial, settings.ensemble_size, settings.c);
+
+    try!(writeln!(output, "{}", HELP));
+    try!(write!(output, "> "));
+    try!(output.flush());
+    for line in input.lines() {
+        let line = try!(line);
+        let line = line.trim();
+
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let command = parts.next().unwrap();
+        let arg = parts.next().unwrap_or("").trim();
+        if command == "quit" {
+            break;
+        }
+
+        if !command.is_empty() {
+            match explorer.execute(initial, command, arg) {
+                Ok(answer) => try!(writeln!(output, "{}", answer)),
+                Err(msg) => try!(writeln!(output, "error: {}", msg)),
+            }
+        }
+        try!(write!(output, "> "));
+        try!(output.flush());
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use agents::Budget;
+    use protocol::SearchSettings;
+    use explore::*;
+    use tictactoe::*;
+
+    fn explore(commands: &str) -> String {
+        let settings = SearchSettings { budget: Budget::Iterations(1), ensemble_size: 2, c: 1. };
+        let mut output = Vec::new();
+        run(&TicTacToe::new(), &settings, Cursor::new(commands), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_search_and_walk() {
+        let output = explore("search 50\ndown Action { row: 1, col: 1 }\nup\nup\ntree 1\ntree 2\n");
+        assert!(output.contains("tree 0 of 2: root\nvisits 50, mean"));
+        assert!(output.contains("tree 0 of 2: root > Action { row: 1, col: 1 }"));
+        assert!(output.contains("error: already at the root"));
+        assert!(output.contains("tree 1 of 2: root"));
+        assert!(output.contains("error: there are only 2 trees"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let output = explore("expand Action { row: 0, col: 2 }\nexpand Action { row: 0, col: 2 }\n\
+                              down Action { row: 0, col: 2 }\nexpand\nshow\n");
+        assert!(output.contains("visits 1, mean"));
+        assert!(output.contains("error: nothing to expand"));
+        assert!(output.contains("tree 0 of 2: root > Action { row: 0, col: 2 }\nvisits 2, mean"));
+    }
+
+    #[test]
+    fn test_state_and_errors() {
+        let output = explore("state {\"board\": [\"XX.\", \"OO.\", \"...\"], \"to_move\": \"X\"}\n\
+                              search 200\npv\nc 0.5\nfly\nsearch many\n");
+        assert!(output.contains("> Action { row: 0, col: 2 }"));
+        assert!(output.contains("c 0.5"));
+        assert!(output.contains("error: unknown command 'fly'"));
+        assert!(output.contains("error: invalid number of iterations 'many'"));
+    }
+}
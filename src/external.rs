@@ -0,0 +1,358 @@
+//!
+//! Agents backed by external engine processes.
+//!
+//! An `ExternalAgent` starts a program and asks it for a move whenever it
+//! is its turn. The whole position is sent for every move, so the engine
+//! needs no knowledge of the game history. A `Dialect` translates between
+//! game states and the protocol the engine speaks:
+//!
+//!   * `EngineDialect` -- the line-based protocol of the `engine` binary
+//!     (see `protocol`), for every `SerializableGame` (with the `serde`
+//!     feature)
+//!   * `GtpDialect` -- the Go Text Protocol, for Hex
+//!   * `UciDialect` -- the Universal Chess Interface (with the `chess` feature)
+//!
+//! This allows e.g. the arena and tournament binaries to pit this crate's
+//! engine directly against third-party programs.
+//!
+
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use mcts::{Game, GameAction};
+use agents::Agent;
+#[cfg(feature = "serde")]
+use agents::Budget;
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, parse_action};
+#[cfg(any(feature = "serde", feature = "chess"))]
+use timecontrol::allocate;
+use hex::{self, Hex, Player, parse_vertex, format_vertex};
+#[cfg(feature = "chess")]
+use chess::Chess;
+#[cfg(feature = "chess")]
+use shakmaty::Move;
+
+/// An error reported by or about the external engine.
+fn protocol_error(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A running engine process, talked to through its stdin and stdout.
+pub struct EngineProcess {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+}
+
+impl EngineProcess {
+    /// Start the given command line; arguments are separated by whitespace.
+    pub fn spawn(command: &str) -> io::Result<EngineProcess> {
+        let mut words = command.split_whitespace();
+        let program = try!(words.next().ok_or(protocol_error("empty engine command".to_string())));
+
+        let mut child = try!(Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn());
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Ok(EngineProcess { child: child, input: input, output: output })
+    }
+
+    /// Send a single line.
+    pub fn send(&mut self, line: &str) -> io::Result<()> {
+        try!(writeln!(self.input, "{}", line));
+        self.input.flush()
+    }
+
+    /// Read a single line without its line break.
+    pub fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if try!(self.output.read_line(&mut line)) == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine closed its output"));
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+impl Drop for EngineProcess {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A protocol for asking external engines for moves.
+pub trait Dialect<G: Game<A>, A: GameAction> {
+    /// Prepare a freshly started engine.
+    fn start(&mut self, _engine: &mut EngineProcess) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Ask the engine for a move in the given position; None if it has
+    /// none or gives up.
+    fn choose(&mut self, engine: &mut EngineProcess, game: &G) -> io::Result<Option<A>>;
+
+    /// Remaining time before the next `choose` in a timed game.
+    fn set_clock(&mut self, _remaining: f32, _increment: f32) {}
+}
+
+/// Agent asking an external engine for its moves.
+///
+/// Panics if the engine breaks the protocol or goes away.
+pub struct ExternalAgent<D> {
+    engine: EngineProcess,
+    dialect: D,
+}
+
+impl<D> ExternalAgent<D> {
+    /// Start the engine and prepare it for the given dialect.
+    pub fn spawn<G: Game<A>, A: GameAction>(command: &str, mut dialect: D) -> io::Result<ExternalAgent<D>>
+            where D: Dialect<G, A> {
+        let mut engine = try!(EngineProcess::spawn(command));
+        try!(dialect.start(&mut engine));
+        Ok(ExternalAgent { engine: engine, dialect: dialect })
+    }
+}
+
+impl<G: Game<A>, A: GameAction, D: Dialect<G, A>> Agent<G, A> for ExternalAgent<D> {
+    fn choose_action(&mut self, game: &G) -> Option<A> {
+        if game.is_terminal() {
+            return None;
+        }
+        self.dialect.choose(&mut self.engine, game)
+            .unwrap_or_else(|e| panic!("External engine failed: {}", e))
+    }
+
+    fn set_clock(&mut self, remaining: f32, increment: f32) {
+        self.dialect.set_clock(remaining, increment);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The line-based protocol of the `engine` binary.
+#[cfg(feature = "serde")]
+pub struct EngineDialect {
+    budget: Option<Budget>,
+}
+
+#[cfg(feature = "serde")]
+impl EngineDialect {
+    /// Use the given budget for every move, or the engine's default.
+    pub fn new(budget: Option<Budget>) -> EngineDialect {
+        EngineDialect { budget: budget }
+    }
+
+    /// Send a command and return the answer; `error` answers become errors.
+    fn command(engine: &mut EngineProcess, command: &str) -> io::Result<String> {
+        try!(engine.send(command));
+        let answer = try!(engine.read_line());
+        if answer.starts_with("error") {
+            return Err(protocol_error(format!("'{}' failed: {}", command, answer)));
+        }
+        Ok(answer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<G: SerializableGame<A>, A: GameAction> Dialect<G, A> for EngineDialect {
+    fn choose(&mut self, engine: &mut EngineProcess, game: &G) -> io::Result<Option<A>> {
+        try!(EngineDialect::command(engine, &format!("state {}", game.to_json())));
+
+        let go = match self.budget {
+            Some(Budget::Iterations(n)) => format!("go iterations {}", n),
+            Some(Budget::Time(t)) => format!("go time {}", t),
+            None => "go".to_string(),
+        };
+        let answer = try!(EngineDialect::command(engine, &go));
+        match answer.splitn(2, ' ').collect::<Vec<_>>().as_slice() {
+            ["bestmove", "none"] => Ok(None),
+            ["bestmove", action] => parse_action(game, action).map(Some).map_err(protocol_error),
+            _ => Err(protocol_error(format!("unexpected answer '{}'", answer)))
+        }
+    }
+
+    fn set_clock(&mut self, remaining: f32, increment: f32) {
+        self.budget = Some(Budget::Time(allocate(remaining, increment)));
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The Go Text Protocol, for Hex.
+///
+/// The board is set up with `play` commands for every stone before the
+/// engine is asked to `genmove`; a resigning engine has no move.
+pub struct GtpDialect;
+
+impl GtpDialect {
+    /// Send a command and return the answer after the `= `.
+    fn command(engine: &mut EngineProcess, command: &str) -> io::Result<String> {
+        try!(engine.send(command));
+
+        let mut answer = try!(engine.read_line());
+        while answer.is_empty() {
+            answer = try!(engine.read_line());
+        }
+        // Answers end with an empty line
+        loop {
+            let line = try!(engine.read_line());
+            if line.is_empty() {
+                break;
+            }
+            answer.push('\n');
+            answer.push_str(&line);
+        }
+
+        if answer.starts_with('=') {
+            Ok(answer[1..].trim().to_string())
+        } else {
+            Err(protocol_error(format!("'{}' failed: {}", command, answer.trim_start_matches('?').trim())))
+        }
+    }
+}
+
+impl Dialect<Hex, hex::Action> for GtpDialect {
+    fn choose(&mut self, engine: &mut EngineProcess, game: &Hex) -> io::Result<Option<hex::Action>> {
+        let size = game.size();
+        try!(GtpDialect::command(engine, &format!("boardsize {}", size)));
+        try!(GtpDialect::command(engine, "clear_board"));
+        for row in 0..size {
+            for col in 0..size {
+                if let Some(player) = game.get_cell(row, col) {
+                    let vertex = format_vertex(&hex::Action { row: row as u8, col: col as u8 });
+                    try!(GtpDialect::command(engine, &format!("play {:?} {}", player, vertex).to_lowercase()));
+                }
+            }
+        }
+
+        let color = if game.to_move() == Player::Black { "black" } else { "white" };
+        let answer = try!(GtpDialect::command(engine, &format!("genmove {}", color)));
+        if answer.to_lowercase() == "resign" {
+            return Ok(None);
+        }
+        let action = try!(parse_vertex(&answer, size).map_err(protocol_error));
+        if !game.allowed_actions().contains(&action) {
+            return Err(protocol_error(format!("illegal move '{}'", answer)));
+        }
+        Ok(Some(action))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// The Universal Chess Interface.
+///
+/// `go` holds the arguments of the `go` command, e.g. `movetime 1000`.
+#[cfg(feature = "chess")]
+pub struct UciDialect {
+    pub go: String,
+}
+
+#[cfg(feature = "chess")]
+impl UciDialect {
+    /// Read lines until one starts with `prefix`.
+    fn wait_for(engine: &mut EngineProcess, prefix: &str) -> io::Result<String> {
+        loop {
+            let line = try!(engine.read_line());
+            if line.starts_with(prefix) {
+                return Ok(line);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chess")]
+impl Dialect<Chess, Move> for UciDialect {
+    fn start(&mut self, engine: &mut EngineProcess) -> io::Result<()> {
+        try!(engine.send("uci"));
+        try!(UciDialect::wait_for(engine, "uciok"));
+        try!(engine.send("isready"));
+        UciDialect::wait_for(engine, "readyok").map(|_| ())
+    }
+
+    fn choose(&mut self, engine: &mut EngineProcess, game: &Chess) -> io::Result<Option<Move>> {
+        try!(engine.send(&format!("position fen {}", game.fen())));
+        try!(engine.send(&format!("go {}", self.go)));
+        let line = try!(UciDialect::wait_for(engine, "bestmove"));
+        match line.split_whitespace().nth(1) {
+            None | Some("(none)") | Some("0000") => Ok(None),
+            Some(text) => game.parse_uci(text).map(Some).map_err(protocol_error),
+        }
+    }
+
+    fn set_clock(&mut self, remaining: f32, increment: f32) {
+        self.go = format!("movetime {}", (allocate(remaining, increment) * 1000.) as u64);
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use mcts::*;
+    use agents::*;
+    use external::*;
+    use hex::{Hex, Action};
+    #[cfg(feature = "serde")]
+    use tictactoe::{self, TicTacToe};
+
+    /// Write a shell script answering commands by their first word.
+    fn script_engine(name: &str, cases: &str) -> String {
+        let path = env::temp_dir().join(format!("mcts-external-{}-{}.sh", name, ::std::process::id()));
+        let script = format!("#!/bin/sh\nwhile read cmd rest; do\n  case $cmd in\n{}\n  esac\ndone\n", cases);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_engine_dialect() {
+        let command = script_engine("engine", "\
+            state) echo ok ;;\n\
+            go) echo \"bestmove Action { row: 2, col: 0 }\" ;;");
+        let mut agent = ExternalAgent::spawn::<TicTacToe, _>(&command, EngineDialect::new(None)).unwrap();
+
+        let action = agent.choose_action(&TicTacToe::new());
+        assert_eq!(action, Some(tictactoe::Action { row: 2, col: 0 }));
+    }
+
+    #[test]
+    fn test_gtp_dialect() {
+        let command = script_engine("gtp", "\
+            genmove) printf '= C2\\n\\n' ;;\n\
+            *) printf '=\\n\\n' ;;");
+        let mut agent = ExternalAgent::spawn::<Hex, _>(&command, GtpDialect).unwrap();
+
+        let mut game = Hex::with_size(3);
+        game.make_move(&Action { row: 0, col: 0 });
+        assert_eq!(agent.choose_action(&game), Some(Action { row: 1, col: 2 }));
+    }
+
+    #[test]
+    fn test_missing_engine() {
+        assert!(EngineProcess::spawn("").is_err());
+        assert!(EngineProcess::spawn("/nonexistent/engine").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_game_over() {
+        let mut game = TicTacToe::new();
+        while let Some(&action) = game.allowed_actions().first() {
+            game.make_move(&action);
+        }
+        let mut agent = ExternalAgent::spawn::<TicTacToe, _>("cat", EngineDialect::new(None)).unwrap();
+        assert_eq!(agent.choose_action(&game), None);
+    }
+}
@@ -0,0 +1,354 @@
+//!
+//! C API for embedding the engine.
+//!
+//! Games and searches are handed out as opaque pointers which have to be
+//! released with `mcts_game_free` and `mcts_search_free`. Actions are
+//! integers:
+//!
+//!   * 2048: 0 = up, 1 = down, 2 = left, 3 = right
+//!   * TicTacToe and Hex: `row * size + col`
+//!
+//! The matching header is `include/mcts.h`; regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/mcts.h`.
+//!
+//! Only available with the `ffi` feature.
+//!
+
+use std::ffi::{CStr, CString};
+use std::f32;
+use std::os::raw::{c_char, c_float, c_int};
+use std::ptr;
+
+use serde_json::Value;
+
+use mcts::{GameAction, MCTS};
+use protocol::SerializableGame;
+use openspiel::ActionCodec;
+use twofortyeight::TwoFortyEight;
+use tictactoe::TicTacToe;
+use hex::Hex;
+use utils::seed_thread_rng;
+
+/// Game interface with the action type erased.
+trait AnyGame {
+    fn allowed_actions(&self) -> Vec<c_int>;
+    fn make_move(&mut self, action: c_int) -> bool;
+    fn reward(&self) -> f32;
+    fn current_player(&self) -> usize;
+    fn to_json(&self) -> Value;
+    fn new_search(&self, ensemble_size: usize) -> Box<dyn AnySearch>;
+}
+
+/// Search interface with the action type erased.
+trait AnySearch {
+    fn search(&mut self, n_samples: usize, c: f32);
+    fn search_time(&mut self, budget_seconds: f32, c: f32);
+    fn best_action(&self) -> Option<c_int>;
+    fn root_value(&self) -> Option<f32>;
+}
+
+impl<G: ActionCodec<A> + SerializableGame<A> + 'static, A: GameAction + 'static> AnyGame for (G, ::std::marker::PhantomData<A>) {
+    fn allowed_actions(&self) -> Vec<c_int> {
+        self.0.allowed_actions().iter().map(|action| self.0.encode(action) as c_int).collect()
+    }
+
+    fn make_move(&mut self, action: c_int) -> bool {
+        match self.0.decode(action as i64) {
+            Some(action) if self.0.allowed_actions().contains(&action) => {
+                self.0.make_move(&action);
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn reward(&self) -> f32 {
+        self.0.reward()
+    }
+
+    fn current_player(&self) -> usize {
+        self.0.current_player()
+    }
+
+    fn to_json(&self) -> Value {
+        self.0.to_json()
+    }
+
+    fn new_search(&self, ensemble_size: usize) -> Box<dyn AnySearch> {
+        Box::new((MCTS::new(&self.0, ensemble_size), self.0.clone()))
+    }
+}
+
+impl<G: ActionCodec<A> + SerializableGame<A>, A: GameAction> AnySearch for (MCTS<G, A>, G) {
+    fn search(&mut self, n_samples: usize, c: f32) {
+        self.0.search(n_samples, c);
+    }
+
+    fn search_time(&mut self, budget_seconds: f32, c: f32) {
+        self.0.search_time(budget_seconds, c);
+    }
+
+    fn best_action(&self) -> Option<c_int> {
+        self.0.best_action().map(|action| self.1.encode(&action) as c_int)
+    }
+
+    fn root_value(&self) -> Option<f32> {
+        self.0.root_value()
+    }
+}
+
+/// Opaque game handle.
+pub struct MctsGame {
+    inner: Box<dyn AnyGame>,
+}
+
+/// Opaque search handle.
+pub struct MctsSearch {
+    inner: Box<dyn AnySearch>,
+}
+
+fn wrap<G: ActionCodec<A> + SerializableGame<A> + 'static, A: GameAction + 'static>(game: G) -> *mut MctsGame {
+    Box::into_raw(Box::new(MctsGame { inner: Box::new((game, ::std::marker::PhantomData)) }))
+}
+
+/// Borrow a C string; None for null pointers and invalid UTF-8.
+unsafe fn borrow_str<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+    CStr::from_ptr(text).to_str().ok()
+}
+
+/// Create a new game: "2048", "tictactoe" or "hex" (11x11).
+///
+/// Returns null for unknown games.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_new(kind: *const c_char) -> *mut MctsGame {
+    match borrow_str(kind) {
+        Some("2048") => wrap(TwoFortyEight::new()),
+        Some("tictactoe") => wrap(TicTacToe::new()),
+        Some("hex") => wrap(Hex::new()),
+        _ => ptr::null_mut()
+    }
+}
+
+/// Create a game from its JSON state, as used by the engine protocol.
+///
+/// Returns null for unknown games and invalid states.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_from_json(kind: *const c_char, state: *const c_char) -> *mut MctsGame {
+    let state = match borrow_str(state).and_then(|state| state.parse::<Value>().ok()) {
+        Some(state) => state,
+        None => return ptr::null_mut()
+    };
+    let game = match borrow_str(kind) {
+        Some("2048") => TwoFortyEight::from_json(&state).map(wrap),
+        Some("tictactoe") => TicTacToe::from_json(&state).map(wrap),
+        Some("hex") => Hex::from_json(&state).map(wrap),
+        _ => return ptr::null_mut()
+    };
+    game.unwrap_or(ptr::null_mut())
+}
+
+/// Release a game.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_free(game: *mut MctsGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// Write up to `capacity` allowed actions to `actions`.
+///
+/// Returns the total number of allowed actions, which may exceed
+/// `capacity`; 0 means the game is over.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_allowed_actions(game: *const MctsGame, actions: *mut c_int,
+                                                   capacity: usize) -> usize {
+    if game.is_null() {
+        return 0;
+    }
+    let allowed = (*game).inner.allowed_actions();
+    if !actions.is_null() {
+        for (i, &action) in allowed.iter().take(capacity).enumerate() {
+            *actions.offset(i as isize) = action;
+        }
+    }
+    allowed.len()
+}
+
+/// Make a move; returns 0 on success and -1 for illegal actions.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_make_move(game: *mut MctsGame, action: c_int) -> c_int {
+    if game.is_null() || !(*game).inner.make_move(action) {
+        return -1;
+    }
+    0
+}
+
+/// Reward of the current state; for two-player games from player 0's view.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_reward(game: *const MctsGame) -> c_float {
+    if game.is_null() {
+        return f32::NAN;
+    }
+    (*game).inner.reward()
+}
+
+/// Index of the player to move.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_current_player(game: *const MctsGame) -> c_int {
+    if game.is_null() {
+        return -1;
+    }
+    (*game).inner.current_player() as c_int
+}
+
+/// The state as a JSON string; release it with `mcts_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_game_to_json(game: *const MctsGame) -> *mut c_char {
+    if game.is_null() {
+        return ptr::null_mut();
+    }
+    match CString::new((*game).inner.to_json().to_string()) {
+        Ok(text) => text.into_raw(),
+        Err(_) => ptr::null_mut()
+    }
+}
+
+/// Release a string returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_string_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+/// Start a search from the current state of `game`.
+///
+/// The search works on a copy; later moves in `game` do not affect it.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_new(game: *const MctsGame, ensemble_size: usize) -> *mut MctsSearch {
+    if game.is_null() || ensemble_size == 0 {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(MctsSearch { inner: (*game).inner.new_search(ensemble_size) }))
+}
+
+/// Release a search.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_free(search: *mut MctsSearch) {
+    if !search.is_null() {
+        drop(Box::from_raw(search));
+    }
+}
+
+/// Perform `n_samples` iterations per ensemble member.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_iterations(search: *mut MctsSearch, n_samples: usize, c: c_float) {
+    if !search.is_null() {
+        (*search).inner.search(n_samples, c);
+    }
+}
+
+/// Search for the given time budget (in s).
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_time(search: *mut MctsSearch, budget_seconds: c_float, c: c_float) {
+    if !search.is_null() {
+        (*search).inner.search_time(budget_seconds, c);
+    }
+}
+
+/// The best action found so far; -1 if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_best_action(search: *const MctsSearch) -> c_int {
+    if search.is_null() {
+        return -1;
+    }
+    (*search).inner.best_action().unwrap_or(-1)
+}
+
+/// Mean reward of the best action; NaN if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn mcts_search_value(search: *const MctsSearch) -> c_float {
+    if search.is_null() {
+        return f32::NAN;
+    }
+    (*search).inner.root_value().unwrap_or(f32::NAN)
+}
+
+/// Seed the random number generator of the calling thread.
+#[no_mangle]
+pub extern "C" fn mcts_seed(seed: u32) {
+    seed_thread_rng(seed);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    use ffi::*;
+
+    #[test]
+    fn test_tictactoe() {
+        unsafe {
+            let kind = CString::new("tictactoe").unwrap();
+            let game = mcts_game_new(kind.as_ptr());
+            assert!(!game.is_null());
+
+            // X: 0, 1; O: 4, 3
+            for &action in &[0, 4, 1, 3] {
+                assert_eq!(mcts_game_make_move(game, action), 0);
+            }
+            assert_eq!(mcts_game_make_move(game, 4), -1);
+            assert_eq!(mcts_game_make_move(game, 9), -1);
+            assert_eq!(mcts_game_current_player(game), 0);
+
+            let mut actions = [0; 9];
+            assert_eq!(mcts_game_allowed_actions(game, actions.as_mut_ptr(), 9), 5);
+            assert_eq!(&actions[..5], &[2, 5, 6, 7, 8]);
+
+            let search = mcts_search_new(game, 1);
+            mcts_search_iterations(search, 500, 1.);
+            assert_eq!(mcts_search_best_action(search), 2);
+            assert_eq!(mcts_search_value(search), 1.);
+            mcts_search_free(search);
+
+            assert_eq!(mcts_game_make_move(game, 2), 0);
+            assert_eq!(mcts_game_reward(game), 1.);
+            assert_eq!(mcts_game_allowed_actions(game, ptr::null_mut(), 0), 0);
+
+            let json = mcts_game_to_json(game);
+            assert!(CStr::from_ptr(json).to_str().unwrap().contains("XXX"));
+            mcts_string_free(json);
+            mcts_game_free(game);
+        }
+    }
+
+    #[test]
+    fn test_from_json() {
+        unsafe {
+            let kind = CString::new("2048").unwrap();
+            let state = CString::new(r#"{"board": [[2, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]]}"#).unwrap();
+            let game = mcts_game_from_json(kind.as_ptr(), state.as_ptr());
+            assert!(!game.is_null());
+
+            // The tile sits in the top left corner, only Down and Right move it
+            let mut actions = [-1; 4];
+            assert_eq!(mcts_game_allowed_actions(game, actions.as_mut_ptr(), 1), 2);
+            assert_eq!(actions, [1, -1, -1, -1]);
+            assert_eq!(mcts_game_allowed_actions(game, actions.as_mut_ptr(), 4), 2);
+            assert_eq!(actions, [1, 3, -1, -1]);
+            mcts_game_free(game);
+
+            let invalid = CString::new("{").unwrap();
+            assert!(mcts_game_from_json(kind.as_ptr(), invalid.as_ptr()).is_null());
+            let unknown = CString::new("chess").unwrap();
+            assert!(mcts_game_new(unknown.as_ptr()).is_null());
+            assert!(mcts_game_new(ptr::null()).is_null());
+        }
+    }
+}
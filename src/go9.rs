@@ -0,0 +1,652 @@
+//!
+//! Implementation of Go on small boards, 9x9 by default.
+//!
+//! Black and white take turns placing stones or passing. Stones without
+//! liberties are captured; moves that would leave the own group without
+//! liberties (suicide) are not allowed, neither is retaking a ko at once
+//! (simple ko). The game ends after two passes in a row, or after
+//! `max_moves` moves so that random playouts always end.
+//!
+//! The score follows the Tromp-Taylor rules: every player counts their
+//! stones and the empty points that only reach their stones; white gets
+//! the komi on top. Black moves first and is player 0; rewards are given
+//! from black's perspective.
+//!
+//! The allowed actions leave out filling one's own eyes, i.e. empty points
+//! whose neighbours are all own stones with further liberties. Such a move
+//! never helps and lets random playouts kill every group; as usual for MCTS
+//! in Go, the player passes instead.
+//!
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game};
+use utils::hash_value;
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, parse_board_rows};
+use cli::DisplayAction;
+
+/// Largest supported board size.
+pub const MAX_SIZE: usize = 19;
+
+/// Board size used by `Go::new`.
+pub const DEFAULT_SIZE: usize = 9;
+
+/// Komi used by `Go::new`.
+pub const DEFAULT_KOMI: f32 = 7.;
+
+/// Column labels; `i` is left out as usual in Go.
+const COLUMNS: &[u8] = b"abcdefghjklmnopqrst";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Player {
+    Black, White
+}
+
+impl Player {
+    /// The other player.
+    pub fn opponent(&self) -> Player {
+        match *self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Place a stone on (row, col), or pass.
+pub enum Action {
+    Play { row: u8, col: u8 },
+    Pass,
+}
+impl GameAction for Action {}
+
+/// Parse a GTP vertex like "c4" or "pass": column letter and 1-based row.
+pub fn parse_vertex(arg: &str, size: usize) -> Result<Action, String> {
+    let arg = arg.to_lowercase();
+    if arg == "pass" {
+        return Ok(Action::Pass);
+    }
+
+    let invalid = || format!("invalid vertex '{}'", arg);
+    let mut chars = arg.chars();
+    let col = match chars.next().and_then(|c| COLUMNS.iter().position(|&label| label as char == c)) {
+        Some(col) => col,
+        None => return Err(invalid())
+    };
+    let row: usize = try!(chars.as_str().parse().map_err(|_| invalid()));
+    if col >= size || row < 1 || row > size {
+        return Err(format!("vertex '{}' is off the board", arg));
+    }
+    Ok(Action::Play { row: (row - 1) as u8, col: col as u8 })
+}
+
+/// Format an action as a GTP vertex.
+pub fn format_vertex(action: &Action) -> String {
+    match *action {
+        Action::Play { row, col } => format!("{}{}", COLUMNS[col as usize] as char, row + 1),
+        Action::Pass => "pass".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, Hash)]
+/// Implementation of the Go game mechanics.
+pub struct Go {
+    size: usize,
+    board: Vec<Option<Player>>,
+    to_move: Player,
+    ko: Option<usize>,                  // point that must not be played right now
+    passes: usize,                      // passes in a row
+    komi_halves: i32,                   // komi in half points, to stay hashable
+    max_moves: usize,
+    pub captures: [usize; 2],           // stones captured by black and white
+    pub moves: usize,
+}
+
+impl Go {
+    /// Create a new 9x9 game with the default komi; black moves first.
+    pub fn new() -> Go {
+        Go::with_size(DEFAULT_SIZE, DEFAULT_KOMI)
+    }
+
+    /// Create a new game on a `size` x `size` board with the given komi.
+    ///
+    /// The game is cut off after three times as many moves as there are
+    /// points on the board.
+    pub fn with_size(size: usize, komi: f32) -> Go {
+        assert!((2..=MAX_SIZE).contains(&size), "Unsupported board size");
+        Go {
+            size: size,
+            board: vec![None; size*size],
+            to_move: Player::Black,
+            ko: None,
+            passes: 0,
+            komi_halves: (komi * 2.).round() as i32,
+            max_moves: 3 * size * size,
+            captures: [0, 0],
+            moves: 0,
+        }
+    }
+
+    /// Number of rows and columns.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The points white gets on top of its area.
+    pub fn komi(&self) -> f32 {
+        self.komi_halves as f32 / 2.
+    }
+
+    /// The stone on the given point, if any.
+    pub fn get_cell(&self, row: usize, col: usize) -> Option<Player> {
+        self.board[row * self.size + col]
+    }
+
+    /// The player who makes the next move.
+    pub fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    /// The point retaking a ko, which the player to move must not play.
+    pub fn ko(&self) -> Option<(usize, usize)> {
+        self.ko.map(|idx| (idx / self.size, idx % self.size))
+    }
+
+    /// Is the game over?
+    pub fn is_finished(&self) -> bool {
+        self.passes >= 2 || self.moves >= self.max_moves
+    }
+
+    /// Tromp-Taylor score: the area of black minus the area of white and
+    /// the komi.
+    pub fn score(&self) -> f32 {
+        let mut area = 0i32;
+        let mut visited = vec![false; self.board.len()];
+        for idx in 0..self.board.len() {
+            match self.board[idx] {
+                Some(Player::Black) => area += 1,
+                Some(Player::White) => area -= 1,
+                None if !visited[idx] => {
+                    // Flood the empty region and see which colours it reaches
+                    let (mut points, mut black, mut white) = (0, false, false);
+                    let mut stack = vec![idx];
+                    visited[idx] = true;
+                    while let Some(point) = stack.pop() {
+                        points += 1;
+                        for next in self.neighbours(point) {
+                            match self.board[next] {
+                                Some(Player::Black) => black = true,
+                                Some(Player::White) => white = true,
+                                None if !visited[next] => {
+                                    visited[next] = true;
+                                    stack.push(next);
+                                },
+                                None => {}
+                            }
+                        }
+                    }
+                    if black && !white {
+                        area += points;
+                    } else if white && !black {
+                        area -= points;
+                    }
+                },
+                None => {}
+            }
+        }
+        area as f32 - self.komi()
+    }
+
+    /// The winner of a finished game; None for a draw or a game in progress.
+    pub fn winner(&self) -> Option<Player> {
+        if !self.is_finished() {
+            return None;
+        }
+        let score = self.score();
+        if score > 0. {
+            Some(Player::Black)
+        } else if score < 0. {
+            Some(Player::White)
+        } else {
+            None
+        }
+    }
+
+    /// The points next to `idx`.
+    fn neighbours(&self, idx: usize) -> Vec<usize> {
+        let (row, col, size) = (idx / self.size, idx % self.size, self.size);
+        let mut points = Vec::with_capacity(4);
+        if row > 0 { points.push(idx - size) }
+        if row + 1 < size { points.push(idx + size) }
+        if col > 0 { points.push(idx - 1) }
+        if col + 1 < size { points.push(idx + 1) }
+        points
+    }
+
+    /// The stones of the group at `idx` and its number of liberties.
+    fn group(&self, idx: usize) -> (Vec<usize>, usize) {
+        let player = self.board[idx];
+        let mut stones = vec![idx];
+        let mut liberties = Vec::new();
+        let mut i = 0;
+        while i < stones.len() {
+            for next in self.neighbours(stones[i]) {
+                if self.board[next].is_none() {
+                    if !liberties.contains(&next) {
+                        liberties.push(next);
+                    }
+                } else if self.board[next] == player && !stones.contains(&next) {
+                    stones.push(next);
+                }
+            }
+            i += 1;
+        }
+        (stones, liberties.len())
+    }
+
+    /// For every point, the number of liberties of the group on it; 0 for
+    /// empty points.
+    fn liberty_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.board.len()];
+        for idx in 0..self.board.len() {
+            if self.board[idx].is_some() && counts[idx] == 0 {
+                let (stones, liberties) = self.group(idx);
+                for stone in stones {
+                    counts[stone] = liberties;
+                }
+            }
+        }
+        counts
+    }
+
+    /// May the player to move place a stone on `idx`?
+    ///
+    /// The point has to be empty and not retake a ko, and the stone needs a
+    /// liberty: an empty neighbour, a neighbouring own group with another
+    /// liberty, or a neighbouring enemy group it captures. `liberties`
+    /// counts the liberties of the group on a point.
+    fn is_legal<F: Fn(usize) -> usize>(&self, idx: usize, liberties: F) -> bool {
+        if self.board[idx].is_some() || self.ko == Some(idx) {
+            return false;
+        }
+        let player = self.to_move;
+        self.neighbours(idx).into_iter().any(|next| match self.board[next] {
+            None => true,
+            Some(stone) if stone == player => liberties(next) > 1,
+            Some(_) => liberties(next) == 1
+        })
+    }
+
+    /// Is `idx` an eye of the player to move: all neighbours are own stones
+    /// that are not in atari?
+    fn is_own_eye(&self, idx: usize, liberties: &[usize]) -> bool {
+        let player = Some(self.to_move);
+        self.neighbours(idx).into_iter()
+            .all(|next| self.board[next] == player && liberties[next] > 1)
+    }
+
+    /// Place a stone for the player to move, capture and hand the move to
+    /// the opponent.
+    pub fn place(&mut self, row: usize, col: usize) {
+        assert!(!self.is_finished(), "Game is already over");
+        assert!(row < self.size && col < self.size, "Point is off the board");
+        let idx = row * self.size + col;
+        assert!(self.is_legal(idx, |next| self.group(next).1), "Illegal move");
+
+        let player = self.to_move;
+        self.board[idx] = Some(player);
+
+        let mut captured = Vec::new();
+        for next in self.neighbours(idx) {
+            if self.board[next] == Some(player.opponent()) {
+                let (stones, liberties) = self.group(next);
+                if liberties == 0 {
+                    for &stone in &stones {
+                        self.board[stone] = None;
+                    }
+                    captured.extend(stones);
+                }
+            }
+        }
+        captured.sort();
+        captured.dedup();
+        self.captures[player as usize] += captured.len();
+
+        // A single stone capturing a single stone may not be retaken at once
+        let (stones, liberties) = self.group(idx);
+        self.ko = if captured.len() == 1 && stones.len() == 1 && liberties == 1 {
+            Some(captured[0])
+        } else {
+            None
+        };
+
+        self.passes = 0;
+        self.moves += 1;
+        self.to_move = player.opponent();
+    }
+
+    /// Pass the move to the opponent.
+    pub fn pass(&mut self) {
+        assert!(!self.is_finished(), "Game is already over");
+        self.ko = None;
+        self.passes += 1;
+        self.moves += 1;
+        self.to_move = self.to_move.opponent();
+    }
+}
+
+impl Game<Action> for Go {
+
+    /// The legal moves except filling own eyes, and passing.
+    fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.is_finished() {
+            return actions;
+        }
+
+        let liberties = self.liberty_counts();
+        for idx in 0..self.board.len() {
+            if self.is_legal(idx, |next| liberties[next]) && !self.is_own_eye(idx, &liberties) {
+                actions.push(Action::Play { row: (idx / self.size) as u8, col: (idx % self.size) as u8 });
+            }
+        }
+        actions.push(Action::Pass);
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.is_finished()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Action) {
+        match *action {
+            Action::Play { row, col } => self.place(row as usize, col as usize),
+            Action::Pass => self.pass(),
+        }
+    }
+
+    /// Reward from the perspective of black.
+    ///
+    /// 1 for a win, -1 for a loss and 0 for draws and unfinished games.
+    fn reward(&self) -> f32 {
+        match self.winner() {
+            Some(Player::Black) =>  1.,
+            Some(Player::White) => -1.,
+            None => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    /// Black is player 0, white is player 1.
+    fn current_player(&self) -> usize {
+        match self.to_move {
+            Player::Black => 0,
+            Player::White => 1,
+        }
+    }
+
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(self))
+    }
+}
+
+/// The board as rows like `"X.O"`, the player to move, the komi, the ko
+/// point and the passes in a row.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for Go {
+    fn to_json(&self) -> Value {
+        let rows = (0..self.size).map(|row| {
+            (0..self.size).map(|col| match self.get_cell(row, col) {
+                None => '.',
+                Some(Player::Black) => 'X',
+                Some(Player::White) => 'O',
+            }).collect::<String>()
+        }).collect::<Vec<_>>();
+        json!({
+            "board": rows,
+            "to_move": format!("{:?}", self.to_move),
+            "komi": self.komi(),
+            "ko": self.ko().map(|(row, col)| [row, col]),
+            "passes": self.passes,
+            "moves": self.moves,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Go, String> {
+        let size = try!(value["board"].as_array().map(|rows| rows.len()).ok_or("missing board".to_string()));
+        if !(2..=MAX_SIZE).contains(&size) {
+            return Err("unsupported board size".to_string());
+        }
+
+        let komi = value["komi"].as_f64().unwrap_or(DEFAULT_KOMI as f64) as f32;
+        let mut game = Go::with_size(size, komi);
+        for (row, cells) in try!(parse_board_rows(value, size)).iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                game.board[row * size + col] = match *cell {
+                    '.' => None,
+                    'X' => Some(Player::Black),
+                    'O' => Some(Player::White),
+                    _ => return Err(format!("invalid cell '{}'", cell))
+                };
+            }
+        }
+        game.to_move = match value["to_move"].as_str() {
+            Some("Black") => Player::Black,
+            Some("White") => Player::White,
+            _ => return Err("to_move must be \"Black\" or \"White\"".to_string())
+        };
+        game.ko = match (value["ko"][0].as_u64(), value["ko"][1].as_u64()) {
+            (Some(row), Some(col)) if (row as usize) < size && (col as usize) < size =>
+                Some(row as usize * size + col as usize),
+            _ if value["ko"].is_null() => None,
+            _ => return Err("ko must be null or [row, col] on the board".to_string())
+        };
+        game.passes = value["passes"].as_u64().unwrap_or(0) as usize;
+        game.moves = value["moves"].as_u64()
+            .map(|moves| moves as usize)
+            .unwrap_or_else(|| game.board.iter().filter(|cell| cell.is_some()).count());
+        Ok(game)
+    }
+}
+
+/// Moves are entered as GTP vertices, e.g. "c4" or "pass".
+impl DisplayAction<Action> for Go {
+    fn parse_action(&self, text: &str) -> Option<Action> {
+        parse_vertex(text, self.size).ok()
+    }
+
+    fn show_action(&self, action: &Action) -> String {
+        format_vertex(action)
+    }
+
+    fn input_help(&self) -> String {
+        "column letter and row, e.g. 'c4', or 'pass'".to_string()
+    }
+}
+
+impl fmt::Display for Go {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str("   "));
+        for &label in &COLUMNS[..self.size] {
+            try!(write!(f, " {}", label as char));
+        }
+        try!(f.write_str("\n"));
+
+        for row in (0..self.size).rev() {
+            try!(write!(f, "{:>3}", row+1));
+            for col in 0..self.size {
+                let symbol = match self.get_cell(row, col) {
+                    None                => " .",
+                    Some(Player::Black) => " X",
+                    Some(Player::White) => " O",
+                };
+                try!(f.write_str(symbol));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use go9::*;
+    #[cfg(feature = "serde")]
+    use protocol::SerializableGame;
+
+    fn play(game: &mut Go, moves: &[&str]) {
+        for vertex in moves {
+            let action = parse_vertex(vertex, game.size()).unwrap();
+            game.make_move(&action);
+        }
+    }
+
+    fn point(vertex: &str) -> Action {
+        parse_vertex(vertex, MAX_SIZE).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        let game = Go::new();
+
+        assert_eq!(game.allowed_actions().len(), 82);
+        assert_eq!(game.to_move(), Player::Black);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.reward(), 0.);
+        assert_eq!(game.score(), -7.);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_capture() {
+        // White's stone on b2 loses its last liberty and may not come back
+        let mut game = Go::with_size(5, 0.5);
+        play(&mut game, &["b1", "b2", "a2", "e5", "c2", "e4", "b3"]);
+
+        assert_eq!(game.get_cell(1, 1), None);
+        assert_eq!(game.captures, [1, 0]);
+        assert!(!game.allowed_actions().contains(&point("b2")));
+    }
+
+    #[test]
+    fn test_suicide() {
+        // White may not play into black's corner, black does not fill its eye
+        let mut game = Go::with_size(5, 0.5);
+        play(&mut game, &["a2", "e5", "b1"]);
+        assert!(!game.allowed_actions().contains(&point("a1")));
+        play(&mut game, &["e4"]);
+        assert!(!game.allowed_actions().contains(&point("a1")));
+
+        // Once both black stones are in atari, a1 is suicide for black but
+        // captures for white
+        play(&mut game, &["e1", "b2", "e2", "a3", "e3", "c1"]);
+        assert!(!game.allowed_actions().contains(&point("a1")));
+        play(&mut game, &["d5"]);
+        assert!(game.allowed_actions().contains(&point("a1")));
+        play(&mut game, &["a1"]);
+        assert_eq!(game.captures, [0, 2]);
+    }
+
+    #[test]
+    fn test_ko() {
+        // Black takes the ko on c2; white may not retake on b2 at once
+        let mut game = Go::with_size(5, 0.5);
+        play(&mut game, &["b1", "c1", "a2", "d2", "b3", "c3", "pass", "b2", "c2"]);
+        assert_eq!(game.get_cell(1, 1), None);
+        assert_eq!(game.ko(), Some((1, 1)));
+        assert!(!game.allowed_actions().contains(&point("b2")));
+
+        // After a move elsewhere the ko can be retaken
+        play(&mut game, &["e5", "a5"]);
+        assert_eq!(game.ko(), None);
+        play(&mut game, &["b2"]);
+        assert_eq!(game.get_cell(1, 2), None);
+        assert_eq!(game.ko(), Some((1, 2)));
+    }
+
+    #[test]
+    fn test_tromp_taylor() {
+        // Black walls off the left two columns, white the right two
+        let mut game = Go::with_size(5, 0.5);
+        play(&mut game, &["b1", "d1", "b2", "d2", "b3", "d3", "b4", "d4", "b5", "d5"]);
+        assert_eq!(game.score(), -0.5);
+        assert!(!game.is_finished());
+        assert_eq!(game.reward(), 0.);
+
+        // The middle column is neutral, two passes end the game
+        play(&mut game, &["pass", "pass"]);
+        assert!(game.is_finished());
+        assert_eq!(game.winner(), Some(Player::White));
+        assert_eq!(game.reward(), -1.);
+        assert!(game.allowed_actions().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = Go::with_size(5, 0.5);
+        play(&mut game, &["b1", "c1", "a2", "d2", "b3", "c3", "pass", "b2", "c2"]);
+
+        let value = game.to_json();
+        assert_eq!(value["board"], json!([".XO..", "X.XO.", ".XO..", ".....", "....."]));
+        assert_eq!(value["to_move"], "White");
+        assert_eq!(value["ko"], json!([1, 1]));
+        let restored = Go::from_json(&value).unwrap();
+        assert_eq!(restored.to_json(), value);
+        assert_eq!(restored.allowed_actions(), game.allowed_actions());
+        assert!(Go::from_json(&json!({"board": ["X"], "to_move": "Black"})).is_err());
+    }
+
+    #[test]
+    fn test_vertex() {
+        assert_eq!(parse_vertex("J9", 9).unwrap(), Action::Play { row: 8, col: 8 });
+        assert_eq!(format_vertex(&Action::Play { row: 8, col: 8 }), "j9");
+        assert_eq!(parse_vertex("pass", 9).unwrap(), Action::Pass);
+        assert!(parse_vertex("i5", 9).is_err());
+        assert!(parse_vertex("k1", 9).is_err());
+        assert!(parse_vertex("a10", 9).is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        // Playouts end, almost always with a winner
+        for _ in 0..10 {
+            let final_game = playout(&Go::new());
+            assert!(final_game.is_finished());
+            assert!(final_game.moves < 3 * 81);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_mcts_wins() {
+        // Black to move captures the white stone on b3 and wins by half a point
+        let game = Go::from_json(&json!({
+            "board": [".X.O.", ".X.O.", "XO.O.", ".X.O.", ".X.O."],
+            "to_move": "Black",
+            "komi": 0.5
+        })).unwrap();
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(2000, 1.);
+        assert_eq!(mcts.best_action(), Some(point("c3")));
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = Go::new();
+        b.iter(|| playout(&game));
+    }
+}
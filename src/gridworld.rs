@@ -0,0 +1,569 @@
+//!
+//! Implementation of a simple GridWorld MDP.
+//!
+//! The agent walks on a rectangular grid that may contain walls, goal cells
+//! and penalty cells. Goal and penalty cells are absorbing: entering one ends
+//! the episode and pays out the cell reward. Every other step costs a small,
+//! configurable amount.
+//!
+//! Transitions are stochastic: with probability `slip_prob` the agent slips
+//! and moves into one of the two directions perpendicular to the intended one.
+//! Slips are chance moves: once the agent chose a direction, the direction it
+//! actually moves into follows with the probabilities of `chance_outcomes`,
+//! so the search averages over the slips instead of knowing them in advance.
+//! Without slipping, every move takes effect right away.
+//!
+//! `GridWorld::frozen_lake()` sets up the well known FrozenLake environment:
+//! holes are penalty cells with zero reward and the ice is so slippery that
+//! the agent only moves into the intended direction a third of the time.
+//! The full transition model is available through `transitions()`, so
+//! optimal values can be computed exactly, e.g. by value iteration.
+//!
+
+use std::fmt;
+
+use mcts::{GameAction, Game};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Content of a single cell of the grid.
+pub enum Cell {
+    Empty,
+    Wall,
+    Goal(f32),
+    Penalty(f32),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Possible moves in the GridWorld.
+pub enum Action {
+    Up, Down, Left, Right
+}
+impl GameAction for Action {}
+
+impl Action {
+    /// The two directions perpendicular to this one.
+    fn perpendicular(&self) -> [Action; 2] {
+        match *self {
+            Action::Up | Action::Down    => [Action::Left, Action::Right],
+            Action::Left | Action::Right => [Action::Up, Action::Down],
+        }
+    }
+}
+
+#[derive(Clone)]
+/// A GridWorld with walls, absorbing goal/penalty cells and slippery moves.
+pub struct GridWorld {
+    width: usize,
+    height: usize,
+    cells: Vec<Cell>,
+    row: usize, col: usize,            // current position of the agent
+    slip_prob: f32,                    // probability to move perpendicular
+    intended: Option<Action>,          // chosen direction, waiting for the slip
+    step_reward: f32,                  // reward for each non-terminal step
+    max_steps: usize,                  // episode length limit
+    done: bool,                        // entered a goal or penalty cell
+    pub score: f32,
+    pub steps: usize,
+}
+
+impl GridWorld {
+    /// Create a new empty grid with the agent in the top left corner.
+    pub fn new(width: usize, height: usize, slip_prob: f32) -> GridWorld {
+        assert!(width > 0 && height > 0);
+        assert!(slip_prob >= 0. && slip_prob <= 1.);
+
+        GridWorld {
+            width: width,
+            height: height,
+            cells: vec![Cell::Empty; width*height],
+            row: 0, col: 0,
+            slip_prob: slip_prob,
+            intended: None,
+            step_reward: 0.,
+            max_steps: 100,
+            done: false,
+            score: 0.,
+            steps: 0,
+        }
+    }
+
+    /// Create a new world from a textual map.
+    ///
+    /// Each string is one row of the grid: `.` is an empty cell, `#` a wall,
+    /// `S` the starting position, `G` a goal with reward 1 and `H` a hole
+    /// that ends the episode without any reward.
+    pub fn from_map(map: &[&str], slip_prob: f32) -> GridWorld {
+        let height = map.len();
+        let width = map[0].len();
+        let mut world = GridWorld::new(width, height, slip_prob);
+
+        for (row, line) in map.iter().enumerate() {
+            assert!(line.len() == width, "All rows must have the same length");
+            for (col, symbol) in line.chars().enumerate() {
+                let cell = match symbol {
+                    '.' | 'F' => Cell::Empty,
+                    'S' => { world.row = row; world.col = col; Cell::Empty },
+                    '#' => Cell::Wall,
+                    'G' => Cell::Goal(1.),
+                    'H' => Cell::Penalty(0.),
+                    _   => panic!("Unknown map symbol '{}'", symbol)
+                };
+                world.set_cell(row, col, cell);
+            }
+        }
+        world
+    }
+
+    /// Create the 4x4 FrozenLake.
+    pub fn frozen_lake() -> GridWorld {
+        GridWorld::from_map(&["S...",
+                              ".H.H",
+                              "...H",
+                              "H..G"], 2./3.)
+    }
+
+    /// Create the classic 4x3 world from Russell & Norvig.
+    ///
+    /// The agent starts in the bottom left corner; there is a single wall,
+    /// a +1 goal in the top right corner and a -1 penalty cell below it.
+    pub fn classic() -> GridWorld {
+        let mut world = GridWorld::new(4, 3, 0.2);
+        world.set_cell(1, 1, Cell::Wall);
+        world.set_cell(0, 3, Cell::Goal(1.));
+        world.set_cell(1, 3, Cell::Penalty(-1.));
+        world.set_position(2, 0);
+        world.set_step_reward(-0.04);
+        world
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Content of the cell at the given position.
+    pub fn get_cell(&self, row: usize, col: usize) -> Cell {
+        self.cells[row * self.width + col]
+    }
+
+    /// Change the content of the cell at the given position.
+    pub fn set_cell(&mut self, row: usize, col: usize, cell: Cell) {
+        let idx = row * self.width + col;
+        self.cells[idx] = cell;
+    }
+
+    /// Current (row, col) position of the agent.
+    pub fn position(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    /// Place the agent on the given cell.
+    pub fn set_position(&mut self, row: usize, col: usize) {
+        assert!(self.get_cell(row, col) != Cell::Wall, "Cannot place agent into a wall");
+        self.row = row;
+        self.col = col;
+    }
+
+    /// Set the reward the agent receives for each non-terminal step.
+    pub fn set_step_reward(&mut self, step_reward: f32) {
+        self.step_reward = step_reward;
+    }
+
+    /// Limit the number of steps per episode.
+    pub fn set_max_steps(&mut self, max_steps: usize) {
+        self.max_steps = max_steps;
+    }
+
+    /// Steps left before the episode ends.
+    pub fn steps_left(&self) -> usize {
+        self.max_steps.saturating_sub(self.steps)
+    }
+
+    /// Did the agent reach a goal or penalty cell or run out of steps?
+    pub fn is_finished(&self) -> bool {
+        self.done || self.steps >= self.max_steps
+    }
+
+    /// Position after moving into the given direction.
+    ///
+    /// Moving into a wall or off the grid leaves the agent where it is.
+    fn target(&self, action: &Action) -> (usize, usize) {
+        let (row, col) = (self.row, self.col);
+        let (row, col) = match *action {
+            Action::Up    if row > 0             => (row-1, col),
+            Action::Down  if row+1 < self.height => (row+1, col),
+            Action::Left  if col > 0             => (row, col-1),
+            Action::Right if col+1 < self.width  => (row, col+1),
+            _ => (row, col)
+        };
+        if self.get_cell(row, col) == Cell::Wall {
+            (self.row, self.col)
+        } else {
+            (row, col)
+        }
+    }
+
+    /// Possible outcomes of the given action as (probability, (row, col)) pairs.
+    ///
+    /// The agent moves into the intended direction with probability
+    /// `1 - slip_prob` and into each perpendicular direction with
+    /// probability `slip_prob / 2`.
+    pub fn transitions(&self, action: &Action) -> Vec<(f32, (usize, usize))> {
+        let perpendicular = action.perpendicular();
+        vec![
+            (1. - self.slip_prob, self.target(action)),
+            (self.slip_prob / 2., self.target(&perpendicular[0])),
+            (self.slip_prob / 2., self.target(&perpendicular[1])),
+        ]
+    }
+}
+
+impl Game<Action> for GridWorld {
+
+    /// Return a list with all allowed actions given the current game state.
+    ///
+    /// After the agent chose a direction, these are the directions it may
+    /// actually move into.
+    fn allowed_actions(&self) -> Vec<Action> {
+        if self.is_finished() {
+            Vec::new()
+        } else if let Some(outcomes) = self.chance_outcomes() {
+            outcomes.into_iter().map(|(action, _)| action).collect()
+        } else {
+            vec![Action::Up, Action::Down, Action::Left, Action::Right]
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.is_finished()
+    }
+
+    /// Change the current game state according to the given action.
+    ///
+    /// On slippery ground the agent first chooses a direction and the
+    /// following chance move is the direction it actually moves into.
+    fn make_move(&mut self, action: &Action) {
+        assert!(!self.is_finished(), "Illegal move");
+
+        if self.intended.is_none() && self.slip_prob > 0. {
+            self.intended = Some(*action);
+            return;
+        }
+        self.intended = None;
+
+        let (row, col) = self.target(action);
+        self.row = row;
+        self.col = col;
+        self.steps += 1;
+
+        match self.get_cell(row, col) {
+            Cell::Goal(r) | Cell::Penalty(r) => {
+                self.score += r;
+                self.done = true;
+            },
+            _ => self.score += self.step_reward
+        }
+    }
+
+    /// Reward for the player when reaching the current game state.
+    fn reward(&self) -> f32 {
+        self.score
+    }
+
+    /// Every step adds its reward to the score.
+    fn running_reward(&self) -> bool {
+        true
+    }
+
+    /// The direction the agent moves into after choosing one: the intended
+    /// direction with probability `1 - slip_prob` and each perpendicular
+    /// one with probability `slip_prob / 2`.
+    fn chance_outcomes(&self) -> Option<Vec<(Action, f32)>> {
+        self.intended.map(|action| {
+            let perpendicular = action.perpendicular();
+            let outcomes = vec![
+                (action, 1. - self.slip_prob),
+                (perpendicular[0], self.slip_prob / 2.),
+                (perpendicular[1], self.slip_prob / 2.),
+            ];
+            outcomes.into_iter().filter(|&(_, prob)| prob > 0.).collect()
+        })
+    }
+
+    /// Slips are chance moves, there is nothing random to seed.
+    fn set_rng_seed(&mut self, _seed: u32) {}
+}
+
+impl fmt::Display for GridWorld {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Steps={} Score={}:", self.steps, self.score));
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let symbol = if (row, col) == (self.row, self.col) {
+                    "A"
+                } else {
+                    match self.get_cell(row, col) {
+                        Cell::Empty      => ".",
+                        Cell::Wall       => "#",
+                        Cell::Goal(_)    => "+",
+                        Cell::Penalty(_) => "-",
+                    }
+                };
+                try!(f.write_str(symbol));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+    use test::Bencher;
+
+    use mcts::*;
+    use gridworld::*;
+
+    #[test]
+    fn test_classic() {
+        let world = GridWorld::classic();
+
+        assert_eq!(world.position(), (2, 0));
+        assert_eq!(world.reward(), 0.);
+        assert_eq!(world.allowed_actions().len(), 4);
+        println!("{}", world);
+    }
+
+    #[test]
+    fn test_walls_and_borders() {
+        let mut world = GridWorld::new(3, 3, 0.);
+        world.set_cell(1, 0, Cell::Wall);
+
+        world.make_move(&Action::Up);
+        assert_eq!(world.position(), (0, 0));
+        world.make_move(&Action::Left);
+        assert_eq!(world.position(), (0, 0));
+        world.make_move(&Action::Down);
+        assert_eq!(world.position(), (0, 0));
+        world.make_move(&Action::Right);
+        assert_eq!(world.position(), (0, 1));
+        assert_eq!(world.steps, 4);
+    }
+
+    #[test]
+    fn test_absorbing_cells() {
+        let mut world = GridWorld::new(3, 1, 0.);
+        world.set_cell(0, 2, Cell::Goal(1.));
+        world.set_step_reward(-0.1);
+
+        world.make_move(&Action::Right);
+        assert!(!world.is_finished());
+        world.make_move(&Action::Right);
+        assert!(world.is_finished());
+        assert_eq!(world.allowed_actions().len(), 0);
+        assert!((world.reward() - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut world = GridWorld::new(2, 2, 0.);
+        world.set_max_steps(3);
+
+        let final_world = playout(&world);
+        assert_eq!(final_world.steps, 3);
+        assert!(final_world.is_finished());
+
+        world.set_max_steps(0);
+        assert_eq!(world.allowed_actions().len(), 0);
+    }
+
+    #[test]
+    fn test_slip_outcomes() {
+        let mut world = GridWorld::new(5, 5, 0.5);
+        world.set_position(2, 2);
+        assert_eq!(world.chance_outcomes(), None);
+
+        // Choosing a direction waits for the slip
+        world.make_move(&Action::Up);
+        assert_eq!((world.position(), world.steps), ((2, 2), 0));
+        assert_eq!(world.chance_outcomes(),
+                   Some(vec![(Action::Up, 0.5), (Action::Left, 0.25), (Action::Right, 0.25)]));
+        assert_eq!(world.allowed_actions(), vec![Action::Up, Action::Left, Action::Right]);
+
+        world.make_move(&Action::Left);
+        assert_eq!((world.position(), world.steps), ((2, 1), 1));
+        assert_eq!(world.chance_outcomes(), None);
+    }
+
+    #[test]
+    fn test_slip_perpendicular() {
+        let mut world = GridWorld::new(3, 3, 1.);
+        world.set_position(1, 1);
+        world.make_move(&Action::Up);
+        assert_eq!(world.allowed_actions(), vec![Action::Left, Action::Right]);
+
+        for action in world.allowed_actions() {
+            let mut w = world.clone();
+            w.make_move(&action);
+            let (row, _) = w.position();
+            assert_eq!(row, 1);
+        }
+    }
+
+    #[test]
+    fn test_mcts() {
+        // A slippery corridor with the goal to the right and a penalty to the left
+        let mut world = GridWorld::new(7, 1, 0.2);
+        world.set_cell(0, 0, Cell::Penalty(-1.));
+        world.set_cell(0, 6, Cell::Goal(1.));
+        world.set_position(0, 4);
+        world.set_step_reward(-0.01);
+
+        let mut mcts = MCTS::new(&world, 20);
+        mcts.search(200, 1.);
+        let action = mcts.best_action();
+        assert_eq!(action, Some(Action::Right));
+    }
+
+    #[test]
+    fn test_from_map() {
+        let world = GridWorld::from_map(&["#S.",
+                                          "H.G"], 0.);
+
+        assert_eq!((world.width(), world.height()), (3, 2));
+        assert_eq!(world.position(), (0, 1));
+        assert_eq!(world.get_cell(0, 0), Cell::Wall);
+        assert_eq!(world.get_cell(1, 0), Cell::Penalty(0.));
+        assert_eq!(world.get_cell(1, 2), Cell::Goal(1.));
+    }
+
+    #[test]
+    fn test_transitions() {
+        let world = GridWorld::frozen_lake();
+
+        for action in world.allowed_actions() {
+            let transitions = world.transitions(&action);
+            let total = transitions.iter().fold(0., |sum, &(p, _)| sum + p);
+            assert!((total - 1.).abs() < 1e-6);
+        }
+
+        // Moving up from the top left corner stays, left or right
+        let targets = world.transitions(&Action::Up).iter()
+                .map(|&(_, target)| target)
+                .collect::<Vec<_>>();
+        assert_eq!(targets, vec![(0, 0), (0, 0), (0, 1)]);
+    }
+
+    /// Compute optimal action values for the current position by value iteration.
+    ///
+    /// The state is (position, steps left); entering a goal or penalty
+    /// cell pays out its reward and ends the episode.
+    fn optimal_action_values(world: &GridWorld) -> Vec<(Action, f32)> {
+        let (width, height) = (world.width(), world.height());
+        let actions = [Action::Up, Action::Down, Action::Left, Action::Right];
+
+        let q_value = |values: &Vec<f32>, row: usize, col: usize, action: &Action| {
+            let mut w = world.clone();
+            w.set_position(row, col);
+            w.transitions(action).iter().fold(0., |sum, &(p, (r, c))| {
+                sum + p * match w.get_cell(r, c) {
+                    Cell::Goal(reward) | Cell::Penalty(reward) => reward,
+                    Cell::Empty => world.step_reward + values[r*width + c],
+                    Cell::Wall  => panic!("Moved into a wall")
+                }
+            })
+        };
+
+        // Values with no steps left are zero
+        let mut values = vec![0.; width*height];
+        for _ in 1..world.steps_left() {
+            let mut new_values = vec![0.; width*height];
+            for row in 0..height {
+                for col in 0..width {
+                    if world.get_cell(row, col) == Cell::Empty {
+                        new_values[row*width + col] = actions.iter()
+                            .map(|a| q_value(&values, row, col, a))
+                            .fold(f32::NEG_INFINITY, f32::max);
+                    }
+                }
+            }
+            values = new_values;
+        }
+
+        let (row, col) = world.position();
+        actions.iter().map(|a| (*a, q_value(&values, row, col, a))).collect()
+    }
+
+    fn optimal_value(world: &GridWorld) -> f32 {
+        optimal_action_values(world).iter()
+            .map(|&(_, q)| q)
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    #[test]
+    fn test_value_iteration() {
+        // Deterministic: 6 steps to the goal, the last one pays out
+        let mut lake = GridWorld::frozen_lake();
+        lake.slip_prob = 0.;
+        lake.set_step_reward(-0.05);
+        assert!((optimal_value(&lake) - 0.75).abs() < 1e-5);
+
+        // Not enough steps to reach the goal: stop paying by jumping into a hole
+        lake.set_max_steps(5);
+        assert!((optimal_value(&lake) - -0.05).abs() < 1e-5);
+
+        // Slippery: the goal is reached most of the time but not always
+        let lake = GridWorld::frozen_lake();
+        let value = optimal_value(&lake);
+        assert!(value > 0.5 && value < 1.);
+    }
+
+    #[test]
+    fn test_mcts_converges_deterministic() {
+        let mut lake = GridWorld::frozen_lake();
+        lake.slip_prob = 0.;
+        lake.set_step_reward(-0.01);
+        lake.set_max_steps(10);
+
+        let mut mcts = MCTS::new(&lake, 1);
+        mcts.search(30000, 0.3);
+
+        // Mean rewards include exploration and approach the optimum from below
+        let value = mcts.root_value().unwrap();
+        let optimal = optimal_value(&lake);
+        assert!(value <= optimal + 1e-5);
+        assert!(value > optimal - 0.1);
+    }
+
+    #[test]
+    fn test_mcts_converges_slippery() {
+        // Two cells away from the goal, close enough for the tree to cover
+        // the slips of every step
+        let mut lake = GridWorld::frozen_lake();
+        lake.set_position(2, 2);
+        lake.set_max_steps(6);
+
+        let mut mcts = MCTS::new(&lake, 1);
+        mcts.search(30000, 0.3);
+
+        // The search averages over the slips instead of knowing them, so
+        // it approaches the optimum from below like the deterministic lake;
+        // the sampled slips add some noise.
+        let value = mcts.root_value().unwrap();
+        let optimal = optimal_value(&lake);
+        assert!(value < optimal + 0.02);
+        assert!(value > optimal - 0.08);
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let world = GridWorld::classic();
+        b.iter(|| playout(&world));
+    }
+}
@@ -0,0 +1,453 @@
+//!
+//! Implementation of Hex.
+//!
+//! Hex is played on a rhombus of hexagonal cells. Black tries to connect
+//! the top and the bottom edge with a chain of stones, white the left and
+//! the right edge. The board can never fill up without one of the players
+//! having won, so there are no draws.
+//!
+//! Black moves first and is player 0; rewards are given from black's
+//! perspective. The swap rule is not implemented.
+//!
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game};
+use utils::hash_value;
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, parse_board_rows, split_notation};
+
+/// Largest supported board size; columns are labelled a to s.
+pub const MAX_SIZE: usize = 19;
+
+/// Board size used by `Hex::new`.
+pub const DEFAULT_SIZE: usize = 11;
+
+/// Offsets of the six neighbours of a cell.
+const NEIGHBOURS: [(isize, isize); 6] = [
+    (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Player {
+    Black, White
+}
+
+impl Player {
+    /// The other player.
+    pub fn opponent(&self) -> Player {
+        match *self {
+            Player::Black => Player::White,
+            Player::White => Player::Black,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Place a stone on (row, col).
+pub struct Action {
+    pub row: u8,
+    pub col: u8,
+}
+impl GameAction for Action {}
+
+/// Parse a GTP vertex like "c4": column letter and 1-based row.
+pub fn parse_vertex(arg: &str, size: usize) -> Result<Action, String> {
+    let arg = arg.to_lowercase();
+    if arg == "pass" {
+        return Err("passing is not allowed in hex".to_string());
+    }
+
+    let invalid = || format!("invalid vertex '{}'", arg);
+    let mut chars = arg.chars();
+    let col = match chars.next() {
+        Some(c) if c >= 'a' && c <= 'z' => c as usize - 'a' as usize,
+        _ => return Err(invalid())
+    };
+    let row: usize = try!(chars.as_str().parse().map_err(|_| invalid()));
+    if col >= size || row < 1 || row > size {
+        return Err(format!("vertex '{}' is off the board", arg));
+    }
+    Ok(Action { row: (row - 1) as u8, col: col as u8 })
+}
+
+/// Format an action as a GTP vertex.
+pub fn format_vertex(action: &Action) -> String {
+    format!("{}{}", (b'a' + action.col) as char, action.row + 1)
+}
+
+#[derive(Debug, Clone, Hash)]
+/// Implementation of the Hex game mechanics.
+pub struct Hex {
+    size: usize,
+    board: Vec<Option<Player>>,
+    to_move: Player,
+    winner: Option<Player>,
+    pub moves: usize,
+}
+
+impl Hex {
+    /// Create a new game on a board of the default size; black moves first.
+    pub fn new() -> Hex {
+        Hex::with_size(DEFAULT_SIZE)
+    }
+
+    /// Create a new game on a `size` x `size` board.
+    pub fn with_size(size: usize) -> Hex {
+        assert!(size >= 1 && size <= MAX_SIZE, "Unsupported board size");
+        Hex {
+            size: size,
+            board: vec![None; size*size],
+            to_move: Player::Black,
+            winner: None,
+            moves: 0,
+        }
+    }
+
+    /// Number of rows and columns.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The stone on the given cell, if any.
+    pub fn get_cell(&self, row: usize, col: usize) -> Option<Player> {
+        self.board[row * self.size + col]
+    }
+
+    /// The player who makes the next move.
+    pub fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    /// Let `player` make the next move, e.g. to set up a position.
+    pub fn set_to_move(&mut self, player: Player) {
+        self.to_move = player;
+    }
+
+    /// The winner of the game, if any.
+    pub fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    /// Is the game over?
+    pub fn is_finished(&self) -> bool {
+        self.winner.is_some()
+    }
+
+    /// Place a stone for `player` and hand the move to the opponent.
+    pub fn place(&mut self, player: Player, row: usize, col: usize) {
+        assert!(!self.is_finished(), "Game is already over");
+        assert!(row < self.size && col < self.size, "Cell is off the board");
+        assert!(self.get_cell(row, col).is_none(), "Illegal move");
+
+        self.board[row * self.size + col] = Some(player);
+        self.moves += 1;
+        self.to_move = player.opponent();
+
+        if self.connects_edges(player, row, col) {
+            self.winner = Some(player);
+        }
+    }
+
+    /// Does the chain through (row, col) connect the edges of `player`?
+    fn connects_edges(&self, player: Player, row: usize, col: usize) -> bool {
+        let size = self.size;
+        let mut visited = vec![false; size*size];
+        let mut stack = vec![(row, col)];
+        let (mut first_edge, mut second_edge) = (false, false);
+
+        visited[row * size + col] = true;
+        while let Some((r, c)) = stack.pop() {
+            let along = if player == Player::Black { r } else { c };
+            first_edge |= along == 0;
+            second_edge |= along == size-1;
+
+            for &(dr, dc) in &NEIGHBOURS {
+                let (nr, nc) = (r as isize + dr, c as isize + dc);
+                if nr < 0 || nc < 0 || nr >= size as isize || nc >= size as isize {
+                    continue;
+                }
+                let idx = nr as usize * size + nc as usize;
+                if !visited[idx] && self.board[idx] == Some(player) {
+                    visited[idx] = true;
+                    stack.push((nr as usize, nc as usize));
+                }
+            }
+        }
+        first_edge && second_edge
+    }
+}
+
+impl Game<Action> for Hex {
+
+    /// Return a list with all allowed actions given the current game state.
+    fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.is_finished() {
+            return actions;
+        }
+
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if self.get_cell(row, col).is_none() {
+                    actions.push(Action { row: row as u8, col: col as u8 });
+                }
+            }
+        }
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.is_finished()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Action) {
+        let player = self.to_move;
+        self.place(player, action.row as usize, action.col as usize);
+    }
+
+    /// Reward from the perspective of black.
+    ///
+    /// 1 for a win, -1 for a loss and 0 for unfinished games.
+    fn reward(&self) -> f32 {
+        match self.winner {
+            Some(Player::Black) =>  1.,
+            Some(Player::White) => -1.,
+            None => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    /// Black is player 0, white is player 1.
+    fn current_player(&self) -> usize {
+        match self.to_move {
+            Player::Black => 0,
+            Player::White => 1,
+        }
+    }
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(self))
+    }
+
+    /// The rows of the board, with X for Black and O for White, and
+    /// optionally the player to move, like `X../.O./... Black`; without
+    /// it, Black moves unless Black has more stones.
+    #[cfg(feature = "serde")]
+    fn from_str(text: &str) -> Result<Hex, String> {
+        let (rows, fields) = split_notation(text);
+        let stones = |stone: char| rows.iter().map(|row| row.matches(stone).count()).sum::<usize>();
+        let to_move = match fields.as_slice() {
+            [] if stones('X') > stones('O') => "White",
+            [] => "Black",
+            [to_move] => to_move,
+            _ => return Err(format!("invalid state '{}'", text))
+        };
+        Hex::from_json(&json!({ "board": rows, "to_move": to_move }))
+    }
+}
+
+/// The board as rows like `"X.O"`, and the player to move.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for Hex {
+    fn to_json(&self) -> Value {
+        let rows = (0..self.size).map(|row| {
+            (0..self.size).map(|col| match self.get_cell(row, col) {
+                None => '.',
+                Some(Player::Black) => 'X',
+                Some(Player::White) => 'O',
+            }).collect::<String>()
+        }).collect::<Vec<_>>();
+        json!({
+            "board": rows,
+            "to_move": format!("{:?}", self.to_move),
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Hex, String> {
+        let size = try!(value["board"].as_array().map(|rows| rows.len()).ok_or("missing board".to_string()));
+        if size < 1 || size > MAX_SIZE {
+            return Err("unsupported board size".to_string());
+        }
+
+        let mut game = Hex::with_size(size);
+        for (row, cells) in try!(parse_board_rows(value, size)).iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                game.board[row * size + col] = match *cell {
+                    '.' => None,
+                    'X' => Some(Player::Black),
+                    'O' => Some(Player::White),
+                    _ => return Err(format!("invalid cell '{}'", cell))
+                };
+            }
+        }
+        game.to_move = match value["to_move"].as_str() {
+            Some("Black") => Player::Black,
+            Some("White") => Player::White,
+            _ => return Err("to_move must be \"Black\" or \"White\"".to_string())
+        };
+        game.moves = game.board.iter().filter(|cell| cell.is_some()).count();
+        for row in 0..size {
+            for col in 0..size {
+                if let Some(player) = game.get_cell(row, col) {
+                    if game.winner.is_none() && game.connects_edges(player, row, col) {
+                        game.winner = Some(player);
+                    }
+                }
+            }
+        }
+        Ok(game)
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str("   "));
+        for col in 0..self.size {
+            try!(write!(f, " {}", (b'a' + col as u8) as char));
+        }
+        try!(f.write_str("\n"));
+
+        for row in 0..self.size {
+            try!(write!(f, "{:>3}{}", row+1, " ".repeat(row)));
+            for col in 0..self.size {
+                let symbol = match self.get_cell(row, col) {
+                    None                => " .",
+                    Some(Player::Black) => " X",
+                    Some(Player::White) => " O",
+                };
+                try!(f.write_str(symbol));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use hex::*;
+
+    fn play(game: &mut Hex, moves: &[(u8, u8)]) {
+        for &(row, col) in moves {
+            game.make_move(&Action { row: row, col: col });
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let game = Hex::with_size(5);
+
+        assert_eq!(game.allowed_actions().len(), 25);
+        assert_eq!(game.to_move(), Player::Black);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.reward(), 0.);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_black_wins() {
+        // Black plays down column b, white along row 1
+        let mut game = Hex::with_size(3);
+        play(&mut game, &[(0, 1), (0, 0), (1, 1), (0, 2), (2, 1)]);
+
+        assert_eq!(game.winner(), Some(Player::Black));
+        assert_eq!(game.reward(), 1.);
+        assert_eq!(game.allowed_actions().len(), 0);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_white_wins_diagonally() {
+        // (2, 0), (1, 1), (0, 2) are connected across the board
+        let mut game = Hex::with_size(3);
+        game.place(Player::White, 2, 0);
+        game.place(Player::White, 1, 1);
+        assert_eq!(game.winner(), None);
+        game.place(Player::White, 0, 2);
+
+        assert_eq!(game.winner(), Some(Player::White));
+        assert_eq!(game.reward(), -1.);
+    }
+
+    #[test]
+    fn test_no_connection() {
+        // (0, 0) and (1, 1) are not neighbours
+        let mut game = Hex::with_size(2);
+        game.place(Player::Black, 0, 0);
+        game.place(Player::Black, 1, 1);
+        assert_eq!(game.winner(), None);
+        assert_eq!(game.to_move(), Player::White);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = Hex::with_size(3);
+        play(&mut game, &[(0, 1), (0, 0), (1, 1)]);
+
+        let value = game.to_json();
+        assert_eq!(value["board"], json!(["OX.", ".X.", "..."]));
+        assert_eq!(value["to_move"], "White");
+        assert_eq!(Hex::from_json(&value).unwrap().to_json(), value);
+
+        let won = json!({"board": ["OX.", ".X.", ".X."], "to_move": "White"});
+        assert_eq!(Hex::from_json(&won).unwrap().winner(), Some(Player::Black));
+        assert!(Hex::from_json(&json!({"board": ["X"], "to_move": "Red"})).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_from_str() {
+        let game = Hex::from_str("OX./.X./...").unwrap();
+        assert_eq!(game.to_json(), json!({"board": ["OX.", ".X.", "..."], "to_move": "White"}));
+        assert_eq!(Hex::from_str("OX./.X./... Black").unwrap().current_player(), 0);
+        assert!(Hex::from_str("OX./.X./... Red").is_err());
+    }
+
+    #[test]
+    fn test_vertex() {
+        let action = parse_vertex("C4", 11).unwrap();
+        assert_eq!(action, Action { row: 3, col: 2 });
+        assert_eq!(format_vertex(&action), "c4");
+        assert!(parse_vertex("pass", 11).is_err());
+        assert!(parse_vertex("l1", 11).is_err());
+        assert!(parse_vertex("a0", 11).is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        // Someone always wins
+        for _ in 0..20 {
+            let final_game = playout(&Hex::with_size(7));
+            assert!(final_game.winner().is_some());
+        }
+    }
+
+    #[test]
+    fn test_mcts_wins() {
+        // Black to move completes column c
+        let mut game = Hex::with_size(4);
+        play(&mut game, &[(0, 2), (0, 0), (1, 2), (1, 0), (3, 2), (3, 0)]);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(1000, 1.);
+        assert_eq!(mcts.best_action(), Some(Action { row: 2, col: 2 }));
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = Hex::new();
+        b.iter(|| playout(&game));
+    }
+}
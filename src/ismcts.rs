@@ -0,0 +1,315 @@
+//!
+//! Information set MCTS for games with hidden information.
+//!
+//! The ensemble of `MCTS` searches a fixed set of determinized copies of
+//! the game, every one in a tree of its own; each of them knows the hidden
+//! information and the trees happily exploit it. Single-observer ISMCTS
+//! [1] grows one tree over the information sets of the player to move at
+//! the root instead and draws a new determinization for every iteration.
+//!
+//!   * A node stands for the moves leading to it, which is all the
+//!     observer knows; it is shared by every state those moves can lead to.
+//!   * The moves legal at a node are the `allowed_actions` of the current
+//!     determinization. Children whose move is not legal in it are skipped,
+//!     and the selection weighs the others by how often they were available
+//!     rather than how often their parent was visited.
+//!
+//! [1] Cowling, Powley, Whitehouse: Information Set Monte Carlo Tree Search
+//!
+
+use std::f32;
+use std::mem;
+
+use rand::Rng;
+
+use mcts::{Game, GameAction, playout_with};
+use utils::{choose_random_with, with_thread_rng, RunningStats};
+
+/// A game where the players do not see all of the state.
+pub trait HiddenInfoGame<A: GameAction>: Game<A> {
+    /// Draw a state that `observer` cannot tell apart from this one.
+    ///
+    /// Everything the observer does not see, like the cards of the other
+    /// players, is redrawn consistent with what it saw; the search reseeds
+    /// the game's own randomness with `set_rng_seed` afterwards.
+    fn determinize<R: Rng>(&self, observer: usize, rng: &mut R) -> Self;
+}
+
+/// A node of the information set tree.
+#[derive(Debug)]
+pub struct InfoSetNode<A: GameAction> {
+    action: Option<A>,
+    player: usize,
+    children: Vec<InfoSetNode<A>>,
+    stats: RunningStats,
+    availability: u64,
+}
+
+impl<A: GameAction> InfoSetNode<A> {
+    fn new(action: Option<A>, player: usize) -> InfoSetNode<A> {
+        InfoSetNode {
+            action: action,
+            player: player,
+            children: Vec::new(),
+            stats: RunningStats::new(),
+            availability: 0,
+        }
+    }
+
+    /// The action leading to this node; None for the root.
+    pub fn action(&self) -> Option<A> {
+        self.action
+    }
+
+    /// The player who made the action leading to this node.
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    /// Children of this node, one per action tried so far.
+    pub fn children(&self) -> &[InfoSetNode<A>] {
+        &self.children
+    }
+
+    /// Number of iterations through this node.
+    pub fn visits(&self) -> u64 {
+        self.stats.count()
+    }
+
+    /// Mean reward of the player who moved into this node.
+    pub fn mean(&self) -> f32 {
+        self.stats.mean()
+    }
+
+    /// Number of iterations in which the action of this node was legal
+    /// at its parent.
+    pub fn availability(&self) -> u64 {
+        self.availability
+    }
+
+    /// Index of the available child with the highest UCB1 value, where
+    /// the visits of the parent are replaced by the availability.
+    fn best_available_index(&self, available: &[A], c: f32) -> Option<usize> {
+        let mut best_value = f32::NEG_INFINITY;
+        let mut best_child = None;
+        for (idx, child) in self.children.iter().enumerate() {
+            if !available.contains(&child.action.unwrap()) {
+                continue;
+            }
+            let visits = child.visits() as f32;
+            let value = child.mean() + c * ((child.availability as f32).ln() / visits).sqrt();
+            if value > best_value {
+                best_value = value;
+                best_child = Some(idx);
+            }
+        }
+        best_child
+    }
+
+    /// Perform an iteration on the determinization `game`.
+    ///
+    /// Like `Tree::iteration_with`, the selection loops and the
+    /// backpropagation follows the remembered path again. Returns the
+    /// playout reward for player 0.
+    fn iteration_with<G: Game<A>, R: Rng>(&mut self, game: &mut G, c: f32, rng: &mut R) -> f32 {
+        let mut path = Vec::new();
+        {
+            let mut node = &mut *self;
+            while !game.is_terminal() {
+                let available = game.allowed_actions();
+                for child in &mut node.children {
+                    if available.contains(&child.action.unwrap()) {
+                        child.availability += 1;
+                    }
+                }
+
+                let untried = available.iter()
+                    .filter(|&action| node.children.iter().all(|child| child.action != Some(*action)))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let idx = match choose_random_with(rng, &untried) {
+                    Some(&action) => {
+                        let mut child = InfoSetNode::new(Some(action), game.current_player());
+                        child.availability = 1;
+                        node.children.push(child);
+                        node.children.len() - 1
+                    },
+                    None => node.best_available_index(&available, c).expect("No available child")
+                };
+                let expanded = node.children[idx].visits() == 0;
+                path.push(idx);
+                node = &mut node.children[idx];
+                game.make_move(&node.action.unwrap());
+                if expanded {
+                    break;
+                }
+            }
+        }
+
+        let end = playout_with(game, rng);
+        let mut node = self;
+        node.stats.push(end.reward_for(node.player));
+        for &idx in &path {
+            node = &mut node.children[idx];
+            node.stats.push(end.reward_for(node.player));
+        }
+        end.reward_for(0)
+    }
+}
+
+/// Dropping the children one after the other would recurse as deep as the
+/// tree; take them apart level by level instead.
+impl<A: GameAction> Drop for InfoSetNode<A> {
+    fn drop(&mut self) {
+        let mut nodes = mem::replace(&mut self.children, Vec::new());
+        while let Some(mut node) = nodes.pop() {
+            nodes.extend(node.children.drain(..));
+        }
+    }
+}
+
+/// Single-observer ISMCTS from the view of the player to move.
+#[derive(Debug)]
+pub struct ISMCTS<G: HiddenInfoGame<A>, A: GameAction> {
+    root: InfoSetNode<A>,
+    game: G,
+    observer: usize,
+}
+
+impl<G: HiddenInfoGame<A>, A: GameAction> ISMCTS<G, A> {
+    /// Create a search for the player to move in `game`.
+    pub fn new(game: &G) -> ISMCTS<G, A> {
+        ISMCTS {
+            root: InfoSetNode::new(None, 0),
+            game: game.clone(),
+            observer: game.current_player(),
+        }
+    }
+
+    /// The player whose information sets the tree is made of.
+    pub fn observer(&self) -> usize {
+        self.observer
+    }
+
+    /// The root of the information set tree.
+    pub fn root(&self) -> &InfoSetNode<A> {
+        &self.root
+    }
+
+    /// Search `game` from now on, throwing away the tree.
+    pub fn advance_game(&mut self, game: &G) {
+        *self = ISMCTS::new(game);
+    }
+
+    /// Perform n_samples iterations, each on a determinization of its own.
+    pub fn search(&mut self, n_samples: usize, c: f32) {
+        with_thread_rng(|rng| self.search_with(n_samples, c, rng))
+    }
+
+    /// Perform n_samples iterations drawing the determinizations,
+    /// expansions and playouts from `rng`.
+    pub fn search_with<R: Rng>(&mut self, n_samples: usize, c: f32, rng: &mut R) {
+        for _ in 0..n_samples {
+            let mut game = self.game.determinize(self.observer, rng);
+            game.set_rng_seed(rng.next_u32());
+            self.root.iteration_with(&mut game, c, rng);
+        }
+    }
+
+    /// The most visited action at the root.
+    pub fn best_action(&self) -> Option<A> {
+        self.root.children.iter()
+            .max_by_key(|child| child.visits())
+            .map(|child| child.action.unwrap())
+    }
+
+    /// The actions tried at the root with their visits and mean reward for
+    /// the observer.
+    pub fn action_values(&self) -> Vec<(A, u64, f32)> {
+        self.root.children.iter()
+            .map(|child| (child.action.unwrap(), child.visits(), child.mean()))
+            .collect()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use mcts::*;
+    use ismcts::*;
+    use utils::seeded_rng;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum Bet { Safe, Gamble, Heads, Tails }
+    impl GameAction for Bet {}
+
+    /// Take 0.6 or gamble on a coin nobody has seen yet: guessing it wins 1.
+    #[derive(Debug, Clone)]
+    struct Coin {
+        heads: bool,
+        bets: Vec<Bet>,
+    }
+
+    impl Game<Bet> for Coin {
+        fn allowed_actions(&self) -> Vec<Bet> {
+            match self.bets.last() {
+                None => vec![Bet::Safe, Bet::Gamble],
+                Some(&Bet::Gamble) => vec![Bet::Heads, Bet::Tails],
+                _ => vec![]
+            }
+        }
+        fn make_move(&mut self, bet: &Bet) { self.bets.push(*bet) }
+        fn reward(&self) -> f32 {
+            match self.bets.last() {
+                Some(&Bet::Safe) => 0.6,
+                Some(&Bet::Heads) if self.heads => 1.,
+                Some(&Bet::Tails) if !self.heads => 1.,
+                _ => 0.
+            }
+        }
+        fn set_rng_seed(&mut self, seed: u32) { self.heads = seed % 2 == 0 }
+    }
+
+    impl HiddenInfoGame<Bet> for Coin {
+        fn determinize<R: Rng>(&self, _: usize, rng: &mut R) -> Coin {
+            Coin { heads: rng.gen(), bets: self.bets.clone() }
+        }
+    }
+
+    #[test]
+    fn test_strategy_fusion() {
+        let game = Coin { heads: true, bets: vec![] };
+
+        // Every determinized tree knows the coin and gambles
+        let mut mcts = MCTS::new(&game, 4);
+        mcts.search_with(400, 1., &mut seeded_rng(1));
+        assert_eq!(mcts.best_action(), Some(Bet::Gamble));
+
+        // One tree over the information sets sees that guessing is even
+        let mut ismcts = ISMCTS::new(&game);
+        ismcts.search_with(4000, 1., &mut seeded_rng(1));
+        assert_eq!(ismcts.best_action(), Some(Bet::Safe));
+        assert_eq!(ismcts.root().visits(), 4000);
+
+        let gamble = ismcts.root().children().iter().find(|child| child.action() == Some(Bet::Gamble)).unwrap();
+        assert_eq!(gamble.children().len(), 2);
+        assert!((gamble.mean() - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_availability() {
+        let game = Coin { heads: false, bets: vec![Bet::Gamble] };
+        let mut ismcts = ISMCTS::new(&game);
+        ismcts.search_with(10, 1., &mut seeded_rng(2));
+        let values = ismcts.action_values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values.iter().map(|&(_, visits, _)| visits).sum::<u64>(), 10);
+
+        // The second guess only exists from the second iteration on
+        let availability = ismcts.root().children().iter().map(|child| child.availability()).collect::<Vec<_>>();
+        assert_eq!(availability, vec![10, 9]);
+    }
+}
@@ -10,12 +10,105 @@
 //!
 //! [1] A Survey of Monte Carlo Tree Search Methods
 //!
+//! The search logs through the `log` facade: a summary of every search at
+//! the debug level, and the selection, expansion and backpropagation of
+//! every iteration at the trace level. The binaries print the log to
+//! stderr, e.g. with `RUST_LOG=mcts=debug`.
+//!
+//! The default `cli` feature pulls in what the binaries need. Without it
+//! the library only depends on `rand` and `time`; the `serde` feature adds
+//! the JSON and TOML formats (game states, checkpoints, records, the
+//! engine protocol) and `log` the logging.
+//!
 extern crate test;
 extern crate time;
 extern crate rand;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "serde")]
+extern crate toml;
+#[cfg(feature = "tui")]
+extern crate ratatui;
+#[cfg(feature = "tui")]
+extern crate crossterm;
+#[cfg(feature = "chess")]
+extern crate shakmaty;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "onnx")]
+extern crate tract_onnx;
+#[cfg(feature = "torch")]
+extern crate tch;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "async")]
+extern crate tokio;
+
+// Without the `log` feature the search logs nothing
+#[cfg(not(feature = "log"))]
+macro_rules! trace { ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } } }
+#[cfg(not(feature = "log"))]
+macro_rules! debug { ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } } }
+#[cfg(not(feature = "log"))]
+macro_rules! log_enabled { ($($arg:tt)*) => { false } }
 
 pub mod minigame;
 pub mod twofortyeight;
-// pub mod tictactoe;
+pub mod adv2048;
+pub mod gridworld;
+pub mod bandit;
+pub mod pentago;
+pub mod tictactoe;
+pub mod hex;
+pub mod go9;
+pub mod nim;
+#[cfg(feature = "chess")]
+pub mod chess;
+pub mod agents;
+pub mod cli;
+pub mod arena;
+pub mod timecontrol;
+pub mod ponder;
+pub mod external;
+#[cfg(feature = "serde")]
+pub mod records;
+#[cfg(feature = "serde")]
+pub mod regression;
+pub mod testkit;
+#[cfg(all(test, feature = "serde"))]
+mod solved;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+#[cfg(feature = "serde")]
+pub mod savegame;
+pub mod treedump;
+pub mod protocol;
+pub mod openspiel;
+pub mod environment;
+#[cfg(feature = "serde")]
+pub mod explore;
+#[cfg(feature = "serde")]
+pub mod config;
 pub mod mcts;
+pub mod ismcts;
+pub mod expectimax;
+#[cfg(feature = "async")]
+pub mod asynceval;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+#[cfg(feature = "torch")]
+pub mod torch;
+pub mod pipeline;
 pub mod utils;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "ffi")]
+pub mod ffi;
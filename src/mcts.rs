@@ -1,15 +1,38 @@
 
 use std::fmt;
+use std::mem;
+use std::ops::Range;
+use std::slice;
 use std::i32;
 use std::f32;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::str::FromStr;
 use std::collections::HashMap;
-use std::cmp::{min, max};
+use std::cmp::{min, max, Ordering, Reverse};
+use std::thread;
+use std::sync::Arc;
+#[cfg(feature = "serde")]
+use std::fs;
+#[cfg(feature = "serde")]
+use std::fs::File;
+#[cfg(feature = "serde")]
+use std::io::{Read, Write};
 
-use time;
+use rand::{Rng, StdRng};
+#[cfg(feature = "log")]
+use log::Level;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde_json::Value;
 
-use utils::{choose_random};
+#[cfg(feature = "serde")]
+use openspiel::ActionCodec;
+#[cfg(feature = "serde")]
+use protocol::SerializableGame;
+use utils::{choose_random_with, with_thread_rng, seeded_rng, seeded_std_rng, derive_seed, seconds, sample_symmetric_dirichlet,
+            sample_normal, RunningStats};
 
 /// A `Game` represets a game state.
 ///
@@ -20,464 +43,5056 @@ pub trait Game<A: GameAction> : Clone {
     /// Return a list with all allowed actions given the current game state.
     fn allowed_actions(&self) -> Vec<A>;
 
+    /// Whether the game is over.
+    ///
+    /// Playouts and the tree expansion ask this before enumerating the
+    /// `allowed_actions`. The default does exactly that; games with a
+    /// cheaper test override it, but must agree with an empty action list.
+    fn is_terminal(&self) -> bool {
+        self.allowed_actions().is_empty()
+    }
+
     /// Change the current game state according to the given action.
     fn make_move(&mut self, action: &A);
 
     /// Reward for the player when reaching the current game state.
+    ///
+    /// For two-player games this is the reward for player 0.
     fn reward(&self) -> f32;
 
+    /// Reward for the given player when reaching the current game state.
+    ///
+    /// The default suits single player and two-player zero-sum games:
+    /// player 0 gets `reward` and everybody else the negated reward. Games
+    /// with more players, or whose rewards do not sum to zero, override it.
+    fn reward_for(&self, player: usize) -> f32 {
+        if player == 0 { self.reward() } else { -self.reward() }
+    }
+
+    /// Whether `reward` is a running score that the moves add to, rather
+    /// than a reward that only counts once the game is over.
+    ///
+    /// The undiscounted search only looks at the reward where a playout
+    /// ends and does not care. With a discount, the moves of games with a
+    /// running score are credited with the difference they made to the
+    /// reward; otherwise the reward at the end is the only one and the
+    /// rewards of the states on the way are not even asked for.
+    fn running_reward(&self) -> bool {
+        false
+    }
+
+    /// Heuristic value of a state where a playout was cut off, see
+    /// `MctsConfig::max_playout_depth`.
+    ///
+    /// Like `reward` it is the value for player 0; the default is the
+    /// reward collected so far.
+    fn evaluate(&self) -> f32 {
+        self.reward()
+    }
+
+    /// Heuristic value of a cut off state for the given player.
+    ///
+    /// The default mirrors `reward_for`: player 0 gets `evaluate` and
+    /// everybody else the negated value. Games that override `reward_for`
+    /// override this as well.
+    fn evaluate_for(&self, player: usize) -> f32 {
+        if player == 0 { self.evaluate() } else { -self.evaluate() }
+    }
+
+    /// Prior probabilities of the allowed actions for the PUCT tree
+    /// policy, see `TreePolicy::Puct`.
+    ///
+    /// The default is uniform. Learned policies or handcrafted heuristics
+    /// give more promising actions more weight; the priors should sum to
+    /// one.
+    fn action_priors(&self) -> Vec<(A, f32)> {
+        let actions = self.allowed_actions();
+        let prior = 1. / actions.len() as f32;
+        actions.into_iter().map(|action| (action, prior)).collect()
+    }
+
+    /// The possible outcomes of a chance move with their probabilities, or
+    /// None if a player makes the next move.
+    ///
+    /// At chance states the search samples the outcome by its probability,
+    /// in the tree as well as in the playouts, instead of letting the tree
+    /// policy pick the outcome that suits the player to move best. The
+    /// outcomes should be the allowed actions and their probabilities
+    /// should sum to one. The default has no chance moves; games that roll
+    /// their dice inside `make_move` can keep it.
+    fn chance_outcomes(&self) -> Option<Vec<(A, f32)>> {
+        None
+    }
+
+    /// Make only the player's part of a move, leaving the afterstate
+    /// before the dice that `make_move` rolls; see `MctsConfig::afterstates`.
+    ///
+    /// Followed by `resolve_chance` this must amount to `make_move`. The
+    /// default makes the whole move, so nothing is left to chance.
+    fn apply_player_move(&mut self, action: &A) {
+        self.make_move(action)
+    }
+
+    /// Roll the dice that `apply_player_move` left out.
+    fn resolve_chance(&mut self) {}
+
+    /// The states `resolve_chance` may lead to from an afterstate, with
+    /// their probabilities summing to one, or None if they are too many to
+    /// list.
+    fn afterstate_outcomes(&self) -> Option<Vec<(Self, f32)>> {
+        None
+    }
+
     /// Derterminize the game
     fn set_rng_seed(&mut self, seed: u32);
+
+    /// Index of the player who makes the next move.
+    ///
+    /// Single player games can rely on the default implementation.
+    /// Two-player games return 0 or 1, games with more players count on;
+    /// the search credits every move with `reward_for` the player who made
+    /// it.
+    fn current_player(&self) -> usize {
+        0
+    }
+
+    /// A hash of the current state for the transposition table.
+    ///
+    /// States reached by different move orders must hash the same. Games
+    /// without transpositions can rely on the default implementation,
+    /// which opts out.
+    fn hash_state(&self) -> Option<u64> {
+        None
+    }
+
+    /// Parse a state from a compact, FEN-like text notation, such as the
+    /// rows of the board separated by `/` and the player to move.
+    ///
+    /// Games without a notation can rely on the default implementation,
+    /// which rejects every text.
+    fn from_str(text: &str) -> Result<Self, String> {
+        Err(format!("no text notation for states like '{}'", text))
+    }
 }
 
 /// A `GameAction` represents a move in a game.
 pub trait GameAction: Debug+Clone+Copy+Eq+Hash {}
 
+/// Values states directly, in place of the random playouts from newly
+/// expanded leaves; see `Tree::iteration_evaluated`.
+///
+/// This is where a value network or a handcrafted heuristic plugs into
+/// the search. Closures taking a state and returning its value for
+/// player 0 are evaluators.
+pub trait Evaluator<G> {
+    /// The value of a state that is not over yet for player 0.
+    fn evaluate(&self, game: &G) -> f32;
+
+    /// The value of a state for the given player.
+    ///
+    /// The default mirrors `Game::reward_for`: player 0 gets `evaluate`
+    /// and everybody else the negated value.
+    fn evaluate_for(&self, game: &G, player: usize) -> f32 {
+        if player == 0 { self.evaluate(game) } else { -self.evaluate(game) }
+    }
+
+    /// The values of a batch of states that are not over yet for player 0,
+    /// in their order; see `Tree::iteration_batch`.
+    ///
+    /// The default evaluates them one by one. Vectorized evaluators, like
+    /// a network on a GPU, value the whole batch at once instead.
+    fn evaluate_batch(&self, games: &[G]) -> Vec<f32> {
+        games.iter().map(|game| self.evaluate(game)).collect()
+    }
+}
+
+impl<G, F: Fn(&G) -> f32> Evaluator<G> for F {
+    fn evaluate(&self, game: &G) -> f32 {
+        self(game)
+    }
+}
+
+/// Values states in the background, like a remote inference server, so
+/// that the search goes on selecting leaves while the values of earlier
+/// ones are still out; see `MCTS::run_pipelined`.
+///
+/// Every state comes with a ticket, and the values come back with their
+/// tickets in any order.
+pub trait PipelinedEvaluator<G> {
+    /// Start valuing a state that is not over yet for player 0.
+    fn submit(&mut self, ticket: usize, game: G);
+
+    /// The values of submitted states that arrived so far, without waiting.
+    fn ready(&mut self) -> Vec<(usize, f32)>;
+
+    /// Wait for the value of at least one submitted state; returns all
+    /// values that arrived.
+    fn wait(&mut self) -> Vec<(usize, f32)>;
+}
+
+/// Chooses the moves of the playouts in place of uniformly random ones;
+/// see `MCTS::set_rollout_policy`.
+///
+/// This is where handcrafted playout heuristics plug into the search. At
+/// chance states the playouts draw the outcome by its probability, so the
+/// policy is only asked about the moves of the players.
+pub trait RolloutPolicy<G, A>: Debug {
+    /// Non-negative weights of the `actions` allowed in `game`; playouts
+    /// draw every action with a probability proportional to its weight,
+    /// or uniformly if all weights are zero.
+    fn weights(&self, game: &G, actions: &[A]) -> Vec<f32>;
+}
+
 
 /// Perform a random playout.
 ///
 /// Start with an initial game state and perform random actions from
-/// until a game-state is reached that does not have any `allowed_actions`.
+/// until a game-state is reached that `is_terminal`.
 pub fn playout<G: Game<A>, A: GameAction>(initial: &G) -> G {
+    with_thread_rng(|rng| playout_with(initial, rng))
+}
+
+/// A random allowed action; chance outcomes are drawn by their
+/// probability, see `Game::chance_outcomes`.
+pub fn random_action<G: Game<A>, A: GameAction>(game: &G) -> Option<A> {
+    with_thread_rng(|rng| random_action_with(game, rng))
+}
+
+/// Draw a random action like `random_action` from `rng`.
+pub fn random_action_with<G: Game<A>, A: GameAction, R: Rng>(game: &G, rng: &mut R) -> Option<A> {
+    match game.chance_outcomes() {
+        Some(outcomes) => random_outcome_with(rng, &outcomes).map(|(action, _)| action),
+        None => choose_random_with(rng, &game.allowed_actions()).cloned()
+    }
+}
+
+/// Draw one of the `outcomes` by its probability; None if there are none.
+fn random_outcome_with<A: GameAction, R: Rng>(rng: &mut R, outcomes: &[(A, f32)]) -> Option<(A, f32)> {
+    let mut u = rng.next_f32();
+    for &(action, probability) in outcomes {
+        if u < probability {
+            return Some((action, probability));
+        }
+        u -= probability;
+    }
+    // Rounding errors; fall back to the last outcome that can happen
+    outcomes.iter().rev().find(|&&(_, probability)| probability > 0.).cloned()
+}
+
+/// The next action of a playout: drawn by the weights of `policy` if there
+/// is one, like `random_action_with` otherwise.
+fn rollout_action_with<G: Game<A>, A: GameAction, R: Rng>(game: &G, policy: Option<&dyn RolloutPolicy<G, A>>,
+                                                          rng: &mut R) -> Option<A> {
+    let policy = match policy {
+        Some(policy) if game.chance_outcomes().is_none() => policy,
+        _ => return random_action_with(game, rng)
+    };
+    let actions = game.allowed_actions();
+    let weights = policy.weights(game, &actions);
+    let total = weights.iter().sum::<f32>();
+    if !(total > 0.) {
+        return choose_random_with(rng, &actions).cloned();
+    }
+    let outcomes = actions.into_iter().zip(weights.into_iter().map(|weight| weight / total)).collect::<Vec<_>>();
+    random_outcome_with(rng, &outcomes).map(|(action, _)| action)
+}
+
+/// Perform a random playout drawing the actions from `rng`.
+pub fn playout_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, rng: &mut R) -> G {
+    playout_limited_with(initial, None, rng)
+}
+
+/// Perform a random playout of at most `max_moves` moves drawing from `rng`.
+///
+/// With None the playout continues until the game is over.
+pub fn playout_limited_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, max_moves: Option<usize>,
+                                                               rng: &mut R) -> G {
+    playout_policy_with(initial, max_moves, None, false, rng).0
+}
+
+/// Perform a random playout like `playout_limited_with` and also return
+/// the moves it made, as pairs of the moving player and the action.
+pub fn playout_sequence_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, max_moves: Option<usize>,
+                                                                rng: &mut R) -> (G, Vec<(usize, A)>) {
+    playout_policy_with(initial, max_moves, None, true, rng)
+}
+
+/// A playout of at most `max_moves` moves choosing them with `policy`,
+/// returning the moves as well if `record` is set.
+fn playout_policy_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, max_moves: Option<usize>,
+                                                          policy: Option<&dyn RolloutPolicy<G, A>>, record: bool,
+                                                          rng: &mut R) -> (G, Vec<(usize, A)>) {
     let mut game = initial.clone();
+    let mut moves = Vec::new();
 
-    let mut potential_moves = game.allowed_actions();
-    while potential_moves.len() > 0 {
-        let action = choose_random(&potential_moves).clone();
-        game.make_move(&action);
-        potential_moves = game.allowed_actions();
+    let mut n_moves = 0;
+    while max_moves != Some(n_moves) && !game.is_terminal() {
+        match rollout_action_with(&game, policy, rng) {
+            Some(action) => {
+                if record {
+                    moves.push((game.current_player(), action));
+                }
+                game.make_move(&action);
+            },
+            None => break
+        }
+        n_moves += 1;
+    }
+    (game, moves)
+}
+
+/// A playout like `playout_sequence_with` that also returns its value for
+/// the players `0..players`: the reward of `initial` plus the rewards
+/// collected by every move, the one of move `t` discounted by `gamma^t`.
+/// Without a `Game::running_reward` the reward at the end is all there is.
+/// A cut off playout values its last state with `Game::evaluate_for`, a
+/// drawn one with the `contempt`.
+fn playout_discounted_with<G: Game<A>, A: GameAction, R: Rng>(initial: &G, max_moves: Option<usize>, gamma: f32,
+                                                            policy: Option<&dyn RolloutPolicy<G, A>>, players: usize,
+                                                            contempt: Contempt, rng: &mut R) -> Rollout<G, A> {
+    let mut game = initial.clone();
+    let mut moves = Vec::new();
+    let running = game.running_reward();
+    let mut rewards = (0..players).map(|player| if running { game.reward_for(player) } else { 0. })
+                                  .collect::<Vec<_>>();
+    let mut values = rewards.clone();
+    let mut weight = 1.;
+    let mut last_weight = 1.;
+
+    while max_moves != Some(moves.len()) && !game.is_terminal() {
+        match rollout_action_with(&game, policy, rng) {
+            Some(action) => {
+                moves.push((game.current_player(), action));
+                game.make_move(&action);
+            },
+            None => break
+        }
+        if running {
+            for (player, (value, reward)) in values.iter_mut().zip(rewards.iter_mut()).enumerate() {
+                let next = game.reward_for(player);
+                *value += weight * (next - *reward);
+                *reward = next;
+            }
+        }
+        last_weight = weight;
+        weight *= gamma;
+    }
+    if !running || !game.is_terminal() {
+        for (player, (value, reward)) in values.iter_mut().zip(&rewards).enumerate() {
+            *value += last_weight * (end_value(&game, player, contempt) - reward);
+        }
+    }
+    (game, moves, values)
+}
+
+/// The discounted values of the nodes on the path of an iteration for every
+/// player, from the `values` at its end and the states `trail` the moves in
+/// the tree led to.
+fn discount_trail<G: Game<A>, A: GameAction>(trail: &[G], values: Vec<f32>, gamma: f32) -> Vec<Vec<f32>> {
+    let mut discounted = vec![values];
+    for state in trail.iter().rev() {
+        let running = state.running_reward();
+        let below = discounted.last().unwrap().iter().enumerate().map(|(player, &value)| {
+            let reward = if running { state.reward_for(player) } else { 0. };
+            reward + gamma * (value - reward)
+        }).collect();
+        discounted.push(below);
+    }
+    discounted.reverse();
+    discounted
+}
+
+/// The value of the state a playout ended in for `player`: its reward if
+/// the game is over, with the `contempt` for a draw, its heuristic
+/// evaluation if the playout was cut off.
+fn end_value<G: Game<A>, A: GameAction>(end: &G, player: usize, contempt: Contempt) -> f32 {
+    if end.is_terminal() { contempt.reward_for(end, player) } else { end.evaluate_for(player) }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// How an iteration values drawn games, see `MctsConfig::contempt`.
+struct Contempt {
+    /// The player to move at the root of the iteration.
+    engine: usize,
+    contempt: f32,
+}
+
+impl Contempt {
+    /// The contempt of `config` for the games of the player to move in
+    /// `root`.
+    fn new<G: Game<A>, A: GameAction>(root: &G, config: &MctsConfig) -> Contempt {
+        Contempt { engine: root.current_player(), contempt: config.contempt }
+    }
+
+    /// The reward of the finished game `end` for `player`; a draw, where
+    /// the engine gets nothing, costs the engine the contempt and gains
+    /// it everybody else.
+    fn reward_for<G: Game<A>, A: GameAction>(&self, end: &G, player: usize) -> f32 {
+        let reward = end.reward_for(player);
+        if self.contempt == 0. || reward != 0. || end.reward_for(self.engine) != 0. {
+            reward
+        } else if player == self.engine {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+}
+
+/// Make `action` in the tree. With afterstates, the move of a player stops
+/// short of its dice roll; returns whether it did.
+fn tree_move<G: Game<A>, A: GameAction>(game: &mut G, action: &A, config: &MctsConfig, chance: bool) -> bool {
+    if config.afterstates.is_some() && !chance {
+        game.apply_player_move(action);
+        true
+    } else {
+        game.make_move(action);
+        false
+    }
+}
+
+/// The expected values for every player of an afterstate with the given
+/// `outcomes`, and with a discount the expected values of the nodes on
+/// the path to it, which the moves in the tree led to the states `trail`.
+///
+/// Every outcome is valued by the mean of its `playouts`, or like a leaf
+/// without playouts if there are none.
+fn expected_outcome<G, A, F>(outcomes: &[(G, f32)], config: &MctsConfig, trail: &[G], players: usize,
+                             evaluator: Option<&dyn Evaluator<G>>, contempt: Contempt,
+                             mut playouts: F) -> (Vec<f32>, Option<Vec<Vec<f32>>>)
+        where G: Game<A>, A: GameAction, F: FnMut(&G) -> Vec<Rollout<G, A>> {
+    let mut values = vec![0.; players];
+    let mut discounted = config.discount.map(|_| vec![vec![0.; players]; trail.len() + 1]);
+    for &(ref outcome, probability) in outcomes {
+        let rollouts = playouts(outcome);
+        // The values of the outcome for every player, and those from the
+        // outcome on to discount
+        let samples = if rollouts.is_empty() {
+            let terminal = outcome.is_terminal();
+            let value = (0..players).map(|player| match evaluator {
+                Some(evaluator) if !terminal => evaluator.evaluate_for(outcome, player),
+                _ => end_value(outcome, player, contempt)
+            }).collect::<Vec<_>>();
+            vec![(value.clone(), value)]
+        } else {
+            rollouts.into_iter()
+                .map(|(end, _, below)| ((0..players).map(|player| end_value(&end, player, contempt)).collect(), below))
+                .collect::<Vec<_>>()
+        };
+
+        let weight = probability / samples.len() as f32;
+        for (value, below) in samples {
+            for (total, value) in values.iter_mut().zip(value) {
+                *total += weight * value;
+            }
+            if let (Some(gamma), Some(discounted)) = (config.discount, discounted.as_mut()) {
+                for (totals, depth) in discounted.iter_mut().zip(discount_trail(trail, below, gamma)) {
+                    for (total, value) in totals.iter_mut().zip(depth) {
+                        *total += weight * value;
+                    }
+                }
+            }
+        }
+    }
+    (values, discounted)
+}
+
+/// The state a playout ended in, its moves if RAVE needs them and its
+/// discounted values for every player if the search discounts.
+type Rollout<G, A> = (G, Vec<(usize, A)>, Vec<f32>);
+
+/// Where the selection of an iteration left the tree, see
+/// `Tree::descend`.
+struct Descent<G, A> {
+    /// Indices of the children chosen on the way down.
+    path: Vec<usize>,
+    /// With RAVE, the moves made in the tree and their players.
+    moves: Vec<(usize, A)>,
+    /// With discounting, the states the moves in the tree led to.
+    trail: Vec<G>,
+    /// Number of players seen on the way.
+    players: usize,
+    /// With afterstates, whether the dice of the last move are still to roll.
+    pending: bool,
+    /// Whether the descent grew a new node.
+    expanded: bool,
+    /// Whether the leaf is still to be valued, by playouts or an
+    /// evaluator; false where the game ended in the tree.
+    leaf_playouts: bool,
+}
+
+/// Iterations whose leaves are out for evaluation, see
+/// `Tree::submit_leaf`.
+pub struct PendingLeaves<G, A> {
+    /// The descents of the leaves, by ticket.
+    leaves: HashMap<usize, Descent<G, A>>,
+    /// For every node on the way to a leaf, its statistics without virtual
+    /// losses and the number of leaves below it.
+    saved: HashMap<Vec<usize>, (RunningStats, usize)>,
+    next_ticket: usize,
+}
+
+impl<G, A> PendingLeaves<G, A> {
+    pub fn new() -> PendingLeaves<G, A> {
+        PendingLeaves { leaves: HashMap::new(), saved: HashMap::new(), next_ticket: 0 }
+    }
+
+    /// Number of leaves out for evaluation.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+}
+
+impl<G, A> Default for PendingLeaves<G, A> {
+    fn default() -> PendingLeaves<G, A> {
+        PendingLeaves::new()
     }
-    game
 }
 
 /// Calculate the expected reward based on random playouts.
 pub fn expected_reward<G: Game<A>, A: GameAction>(game: &G, n_samples: usize) -> f32 {
-    let mut score_sum: f32 = 0.0;
+    playout_statistics(game, n_samples).mean()
+}
+
+/// Calculate the expected reward based on random playouts drawn from `rng`.
+pub fn expected_reward_with<G: Game<A>, A: GameAction, R: Rng>(game: &G, n_samples: usize, rng: &mut R) -> f32 {
+    playout_statistics_with(game, n_samples, rng).mean()
+}
+
+/// Estimate the expected reward based on random playouts, with the
+/// uncertainty of the estimate.
+pub fn expected_reward_stats<G: Game<A>, A: GameAction>(game: &G, n_samples: usize) -> RewardEstimate {
+    RewardEstimate::from_stats(&playout_statistics(game, n_samples))
+}
+
+/// Estimate the expected reward like `expected_reward_stats` based on
+/// random playouts drawn from `rng`.
+pub fn expected_reward_stats_with<G: Game<A>, A: GameAction, R: Rng>(game: &G, n_samples: usize,
+                                                                  rng: &mut R) -> RewardEstimate {
+    RewardEstimate::from_stats(&playout_statistics_with(game, n_samples, rng))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// An estimate of an expected reward from a sample of rewards, as returned
+/// by `expected_reward_stats`.
+///
+/// Without samples the mean, minimum and maximum are NaN.
+pub struct RewardEstimate {
+    pub samples: u64,
+    pub mean: f32,
+    /// Standard error of the mean.
+    pub stderr: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl RewardEstimate {
+    /// Summarize the rewards seen by `stats`.
+    pub fn from_stats(stats: &RunningStats) -> RewardEstimate {
+        RewardEstimate {
+            samples: stats.count(),
+            mean: stats.mean(),
+            stderr: stats.stderr(),
+            min: stats.min().unwrap_or(f32::NAN),
+            max: stats.max().unwrap_or(f32::NAN),
+        }
+    }
+
+    /// Confidence interval for the expected reward.
+    ///
+    /// `z` is the number of standard errors, e.g. 1.96 for 95%.
+    pub fn interval(&self, z: f32) -> (f32, f32) {
+        let delta = z * self.stderr;
+        (self.mean - delta, self.mean + delta)
+    }
+
+    /// The difference of the expected rewards of `self` and `other` and
+    /// its confidence interval, for independent samples.
+    ///
+    /// `z` is the number of standard errors, e.g. 1.96 for 95%. If the
+    /// interval excludes 0, the difference is significant at that level.
+    pub fn difference(&self, other: &RewardEstimate, z: f32) -> (f32, f32, f32) {
+        let difference = self.mean - other.mean;
+        let delta = z * (self.stderr.powi(2) + other.stderr.powi(2)).sqrt();
+        (difference, difference - delta, difference + delta)
+    }
+}
+
+/// Shown as `0.512 [0.418, 0.606] from 100 samples in [-1, 1]` with the
+/// 95% confidence interval.
+impl fmt::Display for RewardEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (lower, upper) = self.interval(1.96);
+        write!(f, "{:.3} [{:.3}, {:.3}] from {} samples in [{}, {}]",
+               self.mean, lower, upper, self.samples, self.min, self.max)
+    }
+}
 
+/// Statistics of the rewards of `n_samples` random playouts.
+///
+/// Besides the expected reward this gives its standard error.
+pub fn playout_statistics<G: Game<A>, A: GameAction>(game: &G, n_samples: usize) -> RunningStats {
+    with_thread_rng(|rng| playout_statistics_with(game, n_samples, rng))
+}
+
+/// Statistics of the rewards of `n_samples` random playouts drawn from `rng`.
+pub fn playout_statistics_with<G: Game<A>, A: GameAction, R: Rng>(game: &G, n_samples: usize,
+                                                               rng: &mut R) -> RunningStats {
+    let mut stats = RunningStats::new();
     for _ in 0..n_samples {
-        score_sum += playout(game).reward();
+        stats.push(playout_with(game, rng).reward());
+    }
+    stats
+}
+
+
+//////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Parameters of the tree search.
+///
+/// The defaults give the plain UCT search; fill in the parameters of
+/// interest with `..MctsConfig::default()` so that code keeps compiling
+/// when parameters are added.
+pub struct MctsConfig {
+    /// Exploration constant `c` of UCT1.
+    pub exploration: f32,
+    /// How the search chooses among the children of a node.
+    pub tree_policy: TreePolicy,
+    /// Playouts stop after this many moves and score the state they
+    /// reached with `Game::evaluate_for`; None plays until the game is over.
+    pub max_playout_depth: Option<usize>,
+    /// Visits a node needs before it gets children; iterations reaching a
+    /// node with fewer visits evaluate it with playouts instead.
+    pub expansion_threshold: u64,
+    /// Number of playouts evaluating the node where an iteration leaves
+    /// the tree; every playout counts as a visit.
+    pub playouts: usize,
+    /// Share the statistics of nodes reaching the same state, see
+    /// `Tree::iteration_shared`.
+    pub transpositions: bool,
+    /// Blend all-moves-as-first statistics into the selection (RAVE) with
+    /// this equivalence parameter `k`: the weight of the AMAF mean of a
+    /// node with `n` visits is `sqrt(k / (3n + k))`.
+    pub rave: Option<f32>,
+    /// Prove wins and losses (MCTS-Solver); see `Tree::iteration_config`.
+    pub solver: bool,
+    /// How `MCTS::best_action` picks the move to play.
+    pub selection: SelectionPolicy,
+    /// Let `MCTS::search_time` stop early once the most visited action
+    /// cannot be overtaken by the runner-up in the remaining time, or the
+    /// solver proved it won.
+    pub early_stop: bool,
+    /// Let `MCTS::search_time` stop early once the most visited action has
+    /// at least this share of the visits at the root.
+    pub stop_visit_share: Option<f32>,
+    /// First play urgency: the UCT1 value of actions not tried yet. A node
+    /// with untried actions only grows a new child if none of its children
+    /// scores higher; None tries every action once before choosing among
+    /// them, as if untried actions were infinitely urgent. PUCT adds the
+    /// prior term to the urgency, which defaults to the mean reward of the
+    /// visited children there.
+    pub fpu: Option<f32>,
+    /// Discount factor `gamma` in (0, 1]: the reward collected `t` moves
+    /// below a node counts `gamma^t` times in its statistics; see
+    /// `Tree::iteration_config`. None does not discount.
+    pub discount: Option<f32>,
+    /// Dirichlet noise `(alpha, fraction)` for the priors of the root's
+    /// children, so that self-play does not repeat the same lines. The
+    /// first iteration on a fresh root expands all its children and mixes
+    /// `fraction` of noise drawn with concentration `alpha` into their
+    /// priors, which the PUCT tree policy follows. None adds no noise.
+    pub root_noise: Option<(f32, f32)>,
+    /// Let the nodes of the tree stand for afterstates, the states after a
+    /// player's move but before the dice roll of games that split their
+    /// moves, see `Game::apply_player_move`; the new leaf of an iteration
+    /// is valued as an afterstate as well. None makes whole moves.
+    pub afterstates: Option<Afterstates>,
+    /// Contempt for draws in two-player games: the player to move at the
+    /// root counts a finished game where it gets a reward of 0 as
+    /// `-contempt`, its opponents as `contempt`. Positive values avoid
+    /// draws, as the stronger side should; negative ones seek them.
+    pub contempt: f32,
+    /// The reward a visit counts with while its iteration waits for the
+    /// evaluation of a batch, see `Tree::iteration_batch`. It should be
+    /// no better than the worst reward, so that the following iterations
+    /// of the batch try other paths.
+    pub virtual_loss: f32,
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            exploration: 1.,
+            tree_policy: TreePolicy::Ucb1,
+            max_playout_depth: None,
+            expansion_threshold: 0,
+            playouts: 1,
+            transpositions: false,
+            rave: None,
+            solver: false,
+            selection: SelectionPolicy::MaxChild,
+            early_stop: false,
+            stop_visit_share: None,
+            fpu: None,
+            discount: None,
+            root_noise: None,
+            afterstates: None,
+            contempt: 0.,
+            virtual_loss: -1.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How the search values an afterstate where an iteration leaves the tree.
+///
+/// Deeper in the tree the iterations always draw the dice roll; the nodes
+/// below an afterstate average over the outcomes like those of a game that
+/// rolls its dice in `make_move`.
+pub enum Afterstates {
+    /// Draw an outcome and value that.
+    Sampled,
+    /// Value every outcome of `Game::afterstate_outcomes` and take the
+    /// expectation. All of them count as a single visit, and RAVE only
+    /// learns from the moves in the tree. Games without a list of outcomes
+    /// draw one.
+    Integrated,
+}
+
+impl FromStr for Afterstates {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Afterstates, String> {
+        match name {
+            "sampled" => Ok(Afterstates::Sampled),
+            "integrated" => Ok(Afterstates::Integrated),
+            _ => Err(format!("Unknown afterstates '{}', expected sampled or integrated", name))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Rules for picking the move to play once the search is over.
+///
+/// Actions the solver proved won always go first, actions it proved lost
+/// are only picked if there is nothing else.
+pub enum SelectionPolicy {
+    /// The action with the highest mean reward.
+    MaxChild,
+    /// The action with the most visits.
+    RobustChild,
+    /// The action with the highest mean reward if it also has the most
+    /// visits, the robust child otherwise.
+    MaxRobustChild,
+    /// The action with the highest lower bound `mean - a / sqrt(visits)`.
+    SecureChild(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Formulas for choosing the child to descend into during the search.
+///
+/// All of them try children never visited first, unless there is a first
+/// play urgency.
+pub enum TreePolicy {
+    /// UCB1: the mean reward plus `c * sqrt(2 ln(n) / n_j)` for a child
+    /// with `n_j` visits out of the `n` visits of its parent.
+    Ucb1,
+    /// UCB1-Tuned: the exploration term of UCB1 shrinks with the variance
+    /// of the rewards of a child, to `c * sqrt(ln(n) / n_j * min(1/4, V_j))`
+    /// with `V_j = variance + sqrt(2 ln(n) / n_j)`.
+    Ucb1Tuned,
+    /// The highest mean reward, no exploration.
+    Greedy,
+    /// The highest mean reward, or a random child with the given
+    /// probability.
+    EpsilonGreedy(f32),
+    /// PUCT as in AlphaZero: the mean reward plus
+    /// `c * P_j * sqrt(n) / (1 + n_j)` with the prior probability `P_j`
+    /// of the action from `Game::action_priors`. Actions not tried yet
+    /// score the first play urgency plus `c * P_j * sqrt(n)` and only get
+    /// a child once that beats the tried ones, in the order of the priors.
+    Puct,
+    /// Thompson sampling: a draw from the posterior of the mean reward,
+    /// taken as normal with mean and standard deviation `c / sqrt(1 + n_j)`;
+    /// every child wins as often as it is likely to be the best.
+    Thompson,
+}
+
+impl MctsConfig {
+    /// The default configuration with exploration constant `c`.
+    pub fn with_exploration(c: f32) -> MctsConfig {
+        MctsConfig { exploration: c, ..MctsConfig::default() }
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////
+
+/// Statistics shared by all nodes reaching the same state, keyed by the
+/// `hash_state` of the state and the player who moved into it.
+pub type Transpositions = HashMap<(u64, usize), RunningStats>;
+
+#[derive(Debug,Copy,Clone)]
+enum NodeState {
+    LeafNode, FullyExpanded, Expandable,
+    ProvenWin, ProvenLoss               // for the player who moved here, no matter what follows
+}
+
+/// A node in the arena of a `Tree`.
+#[derive(Debug,Copy,Clone)]
+struct Node<A> {
+    action: Option<A>,                  // how did we get here
+    player: usize,                      // who made that move
+    first: u32,                         // arena index of the first child
+    len: u32,                           // number of children
+    room: u32,                          // slots reserved for the children from `first` on
+    state: NodeState,                   // is this a leaf node? fully expanded?
+    stats: RunningStats,                // rewards for the player who moved here
+    amaf: RunningStats,                 // rewards whenever that player made this move later on
+    key: Option<u64>,                   // hash of the state, with transpositions
+    prior: f32,                         // prior probability of the action, with PUCT, or of the chance outcome
+    chance: bool,                       // is the next move a chance move?
+}
+
+impl<A: GameAction> Node<A> {
+
+    /// Create and initialize a new node
+    ///
+    /// Initialize the statistics to be empty, without any children, and
+    /// set the node state to Expandable.
+    fn new(action: Option<A>) -> Node<A> {
+        Node {
+            action: action,
+            player: 0,
+            first: 0,
+            len: 0,
+            room: 0,
+            state: NodeState::Expandable,
+            stats: RunningStats::new(),
+            amaf: RunningStats::new(),
+            key: None,
+            prior: 1.,
+            chance: false }
+    }
+
+    /// Arena indices of the children.
+    fn children(&self) -> Range<usize> {
+        self.first as usize..(self.first + self.len) as usize
+    }
+
+    fn is_proven_win(&self) -> bool {
+        matches!(self.state, NodeState::ProvenWin)
+    }
+
+    fn is_proven_loss(&self) -> bool {
+        matches!(self.state, NodeState::ProvenLoss)
+    }
+
+    fn is_proven(&self) -> bool {
+        self.is_proven_win() || self.is_proven_loss()
+    }
+
+    /// See `TreeNode::shared_statistics`.
+    fn shared_statistics<'a>(&'a self, table: Option<&'a Transpositions>) -> &'a RunningStats {
+        match (self.key, table) {
+            (Some(key), Some(table)) => table.get(&(key, self.player)).unwrap_or(&self.stats),
+            _ => &self.stats
+        }
     }
-    (score_sum as f32) / (n_samples as f32)
 }
 
+/// A search tree.
+///
+/// All nodes live in one arena, the root first. The children of a node
+/// sit next to each other in a block with room for all allowed actions,
+/// reserved when the first of them is expanded: the selection scans them
+/// without chasing pointers, and walking the tree is a loop over indices
+/// rather than a recursion.
+#[derive(Debug, Clone)]
+pub struct Tree<A: GameAction> {
+    nodes: Vec<Node<A>>,
+}
+
+/// A node of a `Tree`, to read its statistics and walk the subtree below it.
+#[derive(Clone, Copy)]
+pub struct TreeNode<'a, A: GameAction + 'a> {
+    tree: &'a Tree<A>,
+    id: usize,
+}
+
+impl<'a, A: GameAction> TreeNode<'a, A> {
+
+    fn data(&self) -> &'a Node<A> {
+        &self.tree.nodes[self.id]
+    }
+
+    /// The index of this node in the arena of its tree, see `Tree::node`.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The action leading to this node; None for a root.
+    pub fn action(&self) -> Option<A> {
+        self.data().action
+    }
+
+    /// The player who made the move leading to this node.
+    pub fn player(&self) -> usize {
+        self.data().player
+    }
+
+    /// The prior probability of the action leading to this node; 1 unless
+    /// the PUCT tree policy expanded it. For chance outcomes this is their
+    /// probability.
+    pub fn prior(&self) -> f32 {
+        self.data().prior
+    }
+
+    /// Whether the children of this node are the outcomes of a chance move,
+    /// see `Game::chance_outcomes`.
+    pub fn is_chance(&self) -> bool {
+        self.data().chance
+    }
+
+    /// The subtrees explored so far, in the order they were expanded.
+    pub fn children(&self) -> Vec<TreeNode<'a, A>> {
+        self.data().children().map(|id| self.tree.node(id)).collect()
+    }
+
+    /// The child reached by `action`, if it was explored.
+    pub fn child(&self, action: &A) -> Option<TreeNode<'a, A>> {
+        self.data().children()
+            .find(|&id| self.tree.nodes[id].action.as_ref() == Some(action))
+            .map(|id| self.tree.node(id))
+    }
+
+    /// The node reached by the moves in `path`, if they were explored.
+    pub fn get(&self, path: &[A]) -> Option<TreeNode<'a, A>> {
+        let mut node = *self;
+        for action in path {
+            node = node.child(action)?;
+        }
+        Some(node)
+    }
+
+    /// Iterate over this node and all nodes below it, depth first, with
+    /// their depth below this node.
+    pub fn iter(&self) -> Nodes<'a, A> {
+        Nodes { tree: self.tree, stack: vec![(0, self.id)] }
+    }
+
+    /// Number of visits of this node.
+    pub fn visits(&self) -> u64 {
+        self.data().stats.count()
+    }
+
+    /// Mean reward of this node for the player who moved into it.
+    pub fn mean(&self) -> f32 {
+        self.data().stats.mean()
+    }
+
+    /// Statistics of the rewards for the player who moved into this node.
+    pub fn statistics(&self) -> &'a RunningStats {
+        &self.data().stats
+    }
+
+    /// All-moves-as-first statistics: the rewards of every simulation
+    /// through the parent in which the player of this node made its move at
+    /// any later point. Only collected by searches using RAVE.
+    pub fn amaf(&self) -> &'a RunningStats {
+        &self.data().amaf
+    }
+
+    /// The statistics of all nodes reaching the same state if there are
+    /// any in `table`, the statistics of this node otherwise.
+    pub fn shared_statistics(&self, table: Option<&'a Transpositions>) -> &'a RunningStats {
+        self.data().shared_statistics(table)
+    }
+
+    /// The line of play along the most visited children below this node,
+    /// at most `depth` moves long.
+    pub fn pv(&self, depth: usize) -> Vec<A> {
+        let nodes = &self.tree.nodes;
+        let mut pv = Vec::new();
+        let mut node = self.data();
+        while pv.len() < depth {
+            match node.children().map(|id| &nodes[id]).max_by_key(|child| child.stats.count()) {
+                Some(child) => {
+                    pv.push(child.action.unwrap());
+                    node = child;
+                },
+                None => break
+            }
+        }
+        pv
+    }
+
+    /// Gather some statistics about this subtree, with this node at depth 0.
+    ///
+    /// The memory counts this node and the blocks reserved for the children
+    /// below it. The walk does not recurse, so deep trees cannot overflow
+    /// the stack.
+    pub fn tree_statistics(&self) -> TreeStatistics {
+        let size = mem::size_of::<Node<A>>();
+        let mut stats = TreeStatistics::empty();
+        stats.memory = size;
+
+        let mut stack = vec![(self.id, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.tree.nodes[id];
+            stats.nodes += 1;
+            stats.memory += node.room as usize * size;
+            if node.len == 0 {
+                stats.leaves += 1;
+                stats.leaf_depths += depth as i64;
+                stats.min_depth = min(stats.min_depth, depth);
+                stats.max_depth = max(stats.max_depth, depth);
+                match node.state {
+                    NodeState::LeafNode | NodeState::ProvenWin | NodeState::ProvenLoss => stats.terminal += 1,
+                    _ => {}
+                }
+            } else {
+                stats.children += node.len as i32;
+                stack.extend(node.children().map(|child| (child, depth + 1)));
+            }
+        }
+        stats
+    }
+
+    /// The two terms of the UCT1 value of this node below a parent with
+    /// `parent_visits` visits: the mean reward and the exploration bonus.
+    ///
+    /// An unvisited node has no mean yet and is explored before any
+    /// visited sibling: its terms are (0, inf) whatever `c` is. Below an
+    /// unvisited parent there is nothing to explore yet and the bonus is 0.
+    pub fn uct_terms(&self, parent_visits: u64, c: f32) -> (f32, f32) {
+        uct_terms_of(&self.data().stats, parent_visits, c)
+    }
+
+    /// Whether the solver proved that the player who moved into this node
+    /// wins.
+    pub fn is_proven_win(&self) -> bool {
+        self.data().is_proven_win()
+    }
+
+    /// Whether the solver proved that the player who moved into this node
+    /// loses.
+    pub fn is_proven_loss(&self) -> bool {
+        self.data().is_proven_loss()
+    }
+
+    /// Whether the solver proved the outcome of this node.
+    pub fn is_proven(&self) -> bool {
+        self.data().is_proven()
+    }
+
+    /// Whether there are allowed actions without a child yet.
+    pub fn is_expandable(&self) -> bool {
+        match self.data().state {
+            NodeState::Expandable => true,
+            _ => false
+        }
+    }
+
+    /// Find the best child accoring to UCT1
+    ///
+    /// Unvisited children come first, in the order they were expanded; ties
+    /// between visited children go to the first one as well. Children the
+    /// solver proved lost are never chosen.
+    pub fn best_child(&self, c: f32) -> Option<TreeNode<'a, A>> {
+        self.tree.best_child_scored(self.id, &MctsConfig::with_exploration(c), None, None)
+            .map(|(idx, _)| self.tree.node(self.tree.child(self.id, idx)))
+    }
+}
+
+/// The UCT1 terms for the given statistics of a node, see
+/// `TreeNode::uct_terms`.
+fn uct_terms_of(stats: &RunningStats, parent_visits: u64, c: f32) -> (f32, f32) {
+    if stats.count() == 0 {
+        return (0., f32::INFINITY);
+    }
+    if parent_visits == 0 {
+        return (stats.mean(), 0.);
+    }
+    let exploration = c as f64 * (2. * (parent_visits as f64).ln() / stats.count() as f64).sqrt();
+    (stats.mean(), exploration as f32)
+}
+
+impl<A> Tree<A> where A: GameAction {
+
+    /// Create a new tree that is a root alone.
+    pub fn new() -> Tree<A> {
+        Tree { nodes: vec![Node::new(None)] }
+    }
+
+    /// The root of the tree.
+    pub fn root(&self) -> TreeNode<'_, A> {
+        self.node(0)
+    }
+
+    /// The node with the given index in the arena, see `TreeNode::id`.
+    ///
+    /// A node whose block of children is full moves them to a new block
+    /// when it grows another one, so the indices of children only last
+    /// until their parent is expanded again.
+    pub fn node(&self, id: usize) -> TreeNode<'_, A> {
+        TreeNode { tree: self, id: id }
+    }
+
+    /// Arena index of the child at position `idx` among the children of
+    /// node `id`.
+    fn child(&self, id: usize, idx: usize) -> usize {
+        self.nodes[id].first as usize + idx
+    }
+
+    /// Arena index of the node reached from the root by the children at
+    /// the positions in `path`.
+    fn descendant(&self, path: &[usize]) -> usize {
+        path.iter().fold(0, |id, &idx| self.child(id, idx))
+    }
+
+    /// Arena indices of the root and of every node on `path` below it.
+    fn path_ids(&self, path: &[usize]) -> Vec<usize> {
+        let mut ids = Vec::with_capacity(path.len() + 1);
+        let mut id = 0;
+        ids.push(id);
+        for &idx in path {
+            id = self.child(id, idx);
+            ids.push(id);
+        }
+        ids
+    }
+
+    /// Append `child` to the children of node `id`; returns its index.
+    ///
+    /// The first child reserves a block with `room` slots at the end of the
+    /// arena. A full block moves there with twice the room, leaving its old
+    /// slots unused.
+    fn push_child(&mut self, id: usize, child: Node<A>, room: usize) -> usize {
+        let Node { first, len, room: reserved, .. } = self.nodes[id];
+        if len == reserved {
+            let room = max(room, 2 * len as usize).max(1);
+            let moved = self.nodes.len();
+            self.nodes.extend_from_within(first as usize..(first + len) as usize);
+            self.nodes.resize(moved + room, Node::new(None));
+            self.nodes[id].first = moved as u32;
+            self.nodes[id].room = room as u32;
+        }
+        let idx = self.child(id, self.nodes[id].len as usize);
+        self.nodes[id].len += 1;
+        self.nodes[idx] = child;
+        idx
+    }
+
+    /// A compact copy of the subtree below node `id`, with that node as
+    /// the root.
+    fn subtree(&self, id: usize) -> Tree<A> {
+        let mut nodes = vec![self.nodes[id]];
+        // Copy the blocks of children level by level, reserved room and all
+        let mut next = 0;
+        while next < nodes.len() {
+            let Node { first, room, .. } = nodes[next];
+            nodes[next].first = nodes.len() as u32;
+            nodes.extend_from_slice(&self.nodes[first as usize..(first + room) as usize]);
+            next += 1;
+        }
+        Tree { nodes: nodes }
+    }
+
+    /*
+    /// XXX
+    pub fn merge_trees(trees: Vec<Tree<A>>, depth: usize) -> Tree<A> {
+
+    }
+    */
+
+    /// Account for a visit ending in the state `end`, crediting node `id`
+    /// with the reward of the player who moved into it.
+    fn update_from<G: Game<A>>(&mut self, id: usize, end: &G, table: Option<&mut Transpositions>) {
+        let reward = end.reward_for(self.nodes[id].player);
+        self.update_shared(id, reward, table)
+    }
+
+    /// Account for a visit with the given reward for the player who moved
+    /// into node `id`, in the node and, if it has a key, in the statistics
+    /// it shares in `table`.
+    ///
+    /// Panics if the reward is not finite, which would poison the mean of
+    /// every node on the path.
+    fn update_shared(&mut self, id: usize, reward: f32, table: Option<&mut Transpositions>) {
+        assert!(reward.is_finite(), "Reward {} is not finite", reward);
+        let node = &mut self.nodes[id];
+        node.stats.push(reward);
+        if let (Some(key), Some(table)) = (node.key, table) {
+            table.entry((key, node.player)).or_insert_with(RunningStats::new).push(reward);
+        }
+    }
+
+    /// Position and value of the best child of node `id` according to the
+    /// tree policy of `config`, using the shared statistics in `table`.
+    ///
+    /// With RAVE the mean reward of visited children is blended with their
+    /// AMAF mean. Unvisited children are valued with the first play
+    /// urgency if there is one, plus their prior term under PUCT.
+    ///
+    /// Thompson sampling takes a standard normal draw per child from
+    /// `draws`; without them it is greedy.
+    fn best_child_scored(&self, id: usize, config: &MctsConfig, table: Option<&Transpositions>,
+                         draws: Option<&[f32]>) -> Option<(usize, f32)> {
+        let mut best_value :f32 = f32::NEG_INFINITY;
+        let mut best_child :Option<usize> = None;
+
+        let node = &self.nodes[id];
+        let n = node.shared_statistics(table).count();
+        let urgency = self.first_play_urgency(id, config, table);
+        for (idx, child) in self.nodes[node.children()].iter().enumerate() {
+            if child.is_proven_loss() {
+                continue;
+            }
+            let stats = child.shared_statistics(table);
+            let (mut exploitation, exploration) = uct_terms_of(stats, n, config.exploration);
+            let exploration = match config.tree_policy {
+                TreePolicy::Puct if stats.count() == 0 => {
+                    exploitation = urgency;
+                    config.exploration * child.prior * (n.max(1) as f32).sqrt()
+                },
+                _ if stats.count() == 0 => exploration,
+                TreePolicy::Ucb1 => exploration,
+                TreePolicy::Ucb1Tuned => {
+                    let log_ratio = (n.max(1) as f32).ln() / stats.count() as f32;
+                    let bound = stats.variance() + (2. * log_ratio).sqrt();
+                    config.exploration * (log_ratio * bound.min(0.25)).sqrt()
+                },
+                TreePolicy::Greedy | TreePolicy::EpsilonGreedy(_) => 0.,
+                TreePolicy::Puct => config.exploration * child.prior * (n as f32).sqrt() / (1 + stats.count()) as f32,
+                TreePolicy::Thompson => match draws {
+                    Some(draws) => config.exploration * draws[idx] / ((1 + stats.count()) as f32).sqrt(),
+                    None => 0.
+                }
+            };
+            if let Some(k) = config.rave {
+                if stats.count() > 0 && child.amaf.count() > 0 {
+                    let beta = (k / (3. * stats.count() as f32 + k)).sqrt();
+                    exploitation = (1. - beta) * exploitation + beta * child.amaf.mean();
+                }
+            }
+            let value = match (config.tree_policy, config.fpu) {
+                (TreePolicy::Puct, _) => exploitation + exploration,
+                (_, Some(fpu)) if stats.count() == 0 => fpu,
+                _ => exploitation + exploration
+            };
+            if value > best_value {
+                best_value = value;
+                best_child = Some(idx);
+            }
+        }
+        best_child.map(|idx| (idx, best_value))
+    }
+
+    /// The value of a child of node `id` not visited yet, before any
+    /// exploration bonus: the first play urgency of `config`, or under PUCT
+    /// the mean reward of the visited children, 0 if there are none.
+    fn first_play_urgency(&self, id: usize, config: &MctsConfig, table: Option<&Transpositions>) -> f32 {
+        match (config.fpu, config.tree_policy) {
+            (Some(fpu), _) => fpu,
+            (None, TreePolicy::Puct) => {
+                let (mut sum, mut visits) = (0., 0);
+                for child in &self.nodes[self.nodes[id].children()] {
+                    let stats = child.shared_statistics(table);
+                    if stats.count() == 0 {
+                        continue;
+                    }
+                    sum += stats.mean() * stats.count() as f32;
+                    visits += stats.count();
+                }
+                if visits > 0 { sum / visits as f32 } else { 0. }
+            },
+            (None, _) => 0.
+        }
+    }
+
+    /// The position of the child an iteration descends into from node
+    /// `id`, or None to grow a new child.
+    ///
+    /// Fully expanded nodes descend into their best child, or with the
+    /// epsilon-greedy tree policy sometimes into a random one. Nodes with
+    /// untried actions grow a new child, unless the configuration has a
+    /// first play urgency and a tried child scores higher than that. Under
+    /// PUCT a new child scores like an unvisited one with the highest
+    /// prior `untried` of the untried actions.
+    fn selected_child<R: Rng>(&self, id: usize, config: &MctsConfig, table: Option<&Transpositions>,
+                              untried: Option<f32>, rng: &mut R) -> Option<usize> {
+        let node = &self.nodes[id];
+        let children = &self.nodes[node.children()];
+        if let (TreePolicy::EpsilonGreedy(epsilon), NodeState::FullyExpanded) = (config.tree_policy, node.state) {
+            if rng.next_f32() < epsilon {
+                let candidates = (0..children.len())
+                    .filter(|&idx| !children[idx].is_proven_loss())
+                    .collect::<Vec<_>>();
+                return choose_random_with(rng, &candidates).cloned();
+            }
+        }
+        let draws = match config.tree_policy {
+            TreePolicy::Thompson => Some(children.iter().map(|_| sample_normal(rng, 0., 1.)).collect::<Vec<_>>()),
+            _ => None
+        };
+        let best = self.best_child_scored(id, config, table, draws.as_deref());
+        let growth = match (config.tree_policy, untried) {
+            (TreePolicy::Puct, Some(prior)) => {
+                let n = node.shared_statistics(table).count();
+                Some(self.first_play_urgency(id, config, table) + config.exploration * prior * (n.max(1) as f32).sqrt())
+            },
+            _ => config.fpu
+        };
+        match node.state {
+            NodeState::FullyExpanded => Some(best.expect("No child to descend into").0),
+            _ => match (growth, best) {
+                (Some(growth), Some((idx, value))) if value > growth => Some(idx),
+                _ => None
+            }
+        }
+    }
+
+    /// Add a child to node `id` with an previously unexplored action;
+    /// returns the index of the child.
+    ///
+    /// XXX Use HashSet? Use iterators? XXX
+    pub fn expand<G: Game<A>>(&mut self, id: usize, game: &G) -> Option<usize> {
+        with_thread_rng(move |rng| self.expand_with(id, game, rng))
+    }
+
+    /// Expand like `expand`, choosing the action with `rng`.
+    pub fn expand_with<G: Game<A>, R: Rng>(&mut self, id: usize, game: &G, rng: &mut R) -> Option<usize> {
+        self.expand_action(id, game, None, rng)
+    }
+
+    /// Expand like `expand_with`, but with the untried action of highest
+    /// prior probability, which the new child remembers.
+    fn expand_by_prior<G: Game<A>, R: Rng>(&mut self, id: usize, game: &G, rng: &mut R) -> Option<usize> {
+        match self.untried_prior(id, game) {
+            Some((action, prior)) => {
+                let child = self.expand_action(id, game, Some(action), rng)?;
+                self.nodes[child].prior = prior;
+                Some(child)
+            },
+            None => self.expand_action(id, game, None, rng)
+        }
+    }
+
+    /// The untried action of highest prior probability at node `id`, with
+    /// its prior.
+    fn untried_prior<G: Game<A>>(&self, id: usize, game: &G) -> Option<(A, f32)> {
+        let children = &self.nodes[self.nodes[id].children()];
+        let mut best: Option<(A, f32)> = None;
+        for (action, prior) in game.action_priors() {
+            let untried = children.iter().all(|child| child.action != Some(action));
+            let better = match best {
+                Some((_, best_prior)) => prior > best_prior,
+                None => true
+            };
+            if untried && better {
+                best = Some((action, prior));
+            }
+        }
+        best
+    }
+
+    /// Expand all actions of the root and mix Dirichlet noise with
+    /// concentration `alpha` into the priors of its children.
+    ///
+    /// The children get the prior of their action first, which the noise
+    /// replaces by `fraction`; see `MctsConfig::root_noise`.
+    pub fn add_root_noise_with<G: Game<A>, R: Rng>(&mut self, game: &G, alpha: f32, fraction: f32, rng: &mut R) {
+        for (action, prior) in game.action_priors() {
+            match self.root().child(&action).map(|child| child.id) {
+                Some(child) => self.nodes[child].prior = prior,
+                None => if let Some(child) = self.expand_action(0, game, Some(action), rng) {
+                    self.nodes[child].prior = prior;
+                }
+            }
+        }
+        let children = self.nodes[0].children();
+        if children.is_empty() {
+            return;
+        }
+        let noise = sample_symmetric_dirichlet(rng, alpha, children.len());
+        for (child, noise) in self.nodes[children].iter_mut().zip(noise) {
+            child.prior = (1. - fraction) * child.prior + fraction * noise;
+        }
+    }
+
+    /// Add a child to node `id` for `action`, or for a random unexplored
+    /// action if None; returns the index of the child.
+    ///
+    /// Returns None if the game is over or the action was explored already.
+    fn expand_action<G: Game<A>, R: Rng>(&mut self, id: usize, game: &G, action: Option<A>,
+                                         rng: &mut R) -> Option<usize> {
+
+        if self.nodes[id].is_proven() {
+            return None;
+        }
+
+        if game.is_terminal() {
+            self.nodes[id].state = NodeState::LeafNode;
+            return None;
+        }
+
+        // What are our options given the current game state?
+        let allowed_actions = game.allowed_actions();
+
+        // Get a list with all the actions we tried alreday
+        let mut child_actions : Vec<A> = Vec::new();
+        for child in &self.nodes[self.nodes[id].children()] {
+                child_actions.push(child.action.expect("Child node without action"));
+        }
+
+        // Find untried actions
+        let mut candidate_actions = Vec::new();
+        for action in &allowed_actions {
+            if !child_actions.contains(action) {
+                candidate_actions.push(action);
+            }
+        }
+
+        // Select the given or a random action
+        let action = match action {
+            Some(action) if candidate_actions.contains(&&action) => action,
+            Some(_) => return None,
+            None => match choose_random_with(rng, &candidate_actions) {
+                Some(action) => **action,
+                None => return None,
+            },
+        };
+
+        if candidate_actions.len() == 1 {
+            self.nodes[id].state = NodeState::FullyExpanded;
+        }
+
+        // Siblings share a block; make room for all of them at once so that
+        // the block rarely has to move
+        let mut child = Node::new(Some(action));
+        child.player = game.current_player();
+        Some(self.push_child(id, child, allowed_actions.len()))
+    }
+
+    /// Perform an MCTS iteration.
+    ///
+    /// Returns the reward for player 0; each node accumulates the reward
+    /// of the player who moved into it, see `Game::reward_for`.
+    pub fn iteration<G: Game<A>>(&mut self, game: &mut G, c: f32) -> f32 {
+        with_thread_rng(|rng| self.iteration_with(game, c, rng))
+    }
+
+    /// Perform an MCTS iteration drawing expansions and playouts from `rng`.
+    ///
+    /// The selection descends in a loop and remembers the positions of the
+    /// children it chose; the backpropagation follows them again from the
+    /// root. Neither recurses, so deep trees cannot overflow the stack.
+    pub fn iteration_with<G: Game<A>, R: Rng>(&mut self, game: &mut G, c: f32, rng: &mut R) -> f32 {
+        self.iteration_shared(game, c, rng, None)
+    }
+
+    /// Perform an MCTS iteration with a transposition table.
+    ///
+    /// New nodes are keyed by the `hash_state` of their state; nodes with
+    /// the same key share their statistics in `table`, which the selection
+    /// uses instead of the statistics of the nodes themselves. The root
+    /// keeps its own statistics.
+    pub fn iteration_shared<G: Game<A>, R: Rng>(&mut self, game: &mut G, c: f32, rng: &mut R,
+                                                table: Option<&mut Transpositions>) -> f32 {
+        self.iteration_config(game, &MctsConfig::with_exploration(c), rng, table)
+    }
+
+    /// Perform an MCTS iteration with the given parameters.
+    ///
+    /// Returns the mean reward of the playouts for player 0. The
+    /// `transpositions` flag of the configuration is up to the caller, who
+    /// passes a `table` or not.
+    ///
+    /// With RAVE the iteration keeps track of the moves made in the tree
+    /// and in the playouts; every node on the path updates the AMAF
+    /// statistics of those children whose move its player made later on.
+    ///
+    /// With the solver, game ends with a positive reward for the player who
+    /// moved there are proven wins and those with a negative reward proven
+    /// losses. A node is proven once one of its children is a proven win
+    /// for the player to move, or all of them are proven losses; the
+    /// solver assumes that a win for one player is a loss for the other.
+    /// Proven lost children are never selected, and iterations on a proven
+    /// root do nothing but return its mean reward.
+    ///
+    /// With a discount, a node is credited with the reward of the state it
+    /// leads to plus the rewards collected by the moves below it, every
+    /// further move discounted by `gamma` once more; the iteration returns
+    /// this value for the root and player 0. Without a discount this is the
+    /// reward at the end of the playout. Games without a running reward
+    /// only collect that at the end, see `Game::running_reward`.
+    ///
+    /// With root noise, the first iteration on this node as the root adds
+    /// the noise; see `MctsConfig::root_noise`.
+    ///
+    /// At chance states the iteration draws the outcome by its probability
+    /// instead of selecting a child, and grows a child for it if it is new.
+    /// The mean reward of a node before a chance move thus estimates the
+    /// expectation over the outcomes, expectimax-style, and not the value
+    /// of the outcome the player to move likes best.
+    ///
+    /// With afterstates, the moves of the players in the tree leave out
+    /// their dice roll, which follows once the iteration goes on below; see
+    /// `MctsConfig::afterstates`.
+    pub fn iteration_config<G: Game<A>, R: Rng>(&mut self, game: &mut G, config: &MctsConfig, rng: &mut R,
+                                                table: Option<&mut Transpositions>) -> f32 {
+        self.iteration_policy(game, config, None, rng, table, &mut Growth::default())
+    }
+
+    /// Perform an MCTS iteration like `iteration_config`, choosing the
+    /// moves of the playouts with `policy` instead of at random.
+    pub fn iteration_rollout<G: Game<A>, R: Rng>(&mut self, game: &mut G, config: &MctsConfig,
+                                                 policy: &dyn RolloutPolicy<G, A>, rng: &mut R,
+                                                 table: Option<&mut Transpositions>) -> f32 {
+        self.iteration_policy(game, config, Some(policy), rng, table, &mut Growth::default())
+    }
+
+    /// Perform an MCTS iteration with playouts following `policy`, or
+    /// random playouts without one, recording the growth of the tree.
+    fn iteration_policy<G: Game<A>, R: Rng>(&mut self, game: &mut G, config: &MctsConfig,
+                                            policy: Option<&dyn RolloutPolicy<G, A>>, rng: &mut R,
+                                            table: Option<&mut Transpositions>, growth: &mut Growth) -> f32 {
+        self.iteration_playouts(game, config, rng, table, None, growth, |game, players, contempt, rng| {
+            (0..max(config.playouts, 1))
+                .map(|_| Tree::<A>::playout(game, config, policy, players, contempt, rng))
+                .collect()
+        })
+    }
+
+    /// Perform an MCTS iteration like `iteration_config`, but value the new
+    /// leaf with `evaluator` instead of running playouts from it.
+    ///
+    /// Leaves where the game is over get their reward as usual. Without
+    /// playouts there are no moves beyond the tree for RAVE, and the
+    /// `playouts` and `max_playout_depth` of the configuration do not
+    /// matter.
+    pub fn iteration_evaluated<G, E, R>(&mut self, game: &mut G, config: &MctsConfig, evaluator: &E,
+                                        rng: &mut R, table: Option<&mut Transpositions>) -> f32
+            where G: Game<A>, E: Evaluator<G>, R: Rng {
+        self.iteration_playouts(game, config, rng, table, Some(evaluator), &mut Growth::default(), |_, _, _, _| Vec::new())
+    }
+
+    /// Perform `batch_size` MCTS iterations like `iteration_evaluated`,
+    /// valuing their new leaves together with `Evaluator::evaluate_batch`.
+    ///
+    /// The iterations descend one after the other. Until the batch is
+    /// valued, every node an iteration went through counts an extra visit
+    /// with the reward `config.virtual_loss`, so that the following
+    /// iterations spread out over the tree instead of all reaching the same
+    /// leaf. The virtual losses are then taken back, the leaves where the
+    /// game is not over are valued in a single call and every iteration is
+    /// backpropagated. Returns the values of the iterations for player 0.
+    ///
+    /// Players other than 0 get the negated value of the evaluator, as in
+    /// a zero-sum game. Afterstate leaves roll their dice as with
+    /// `Afterstates::Sampled`. The virtual losses do not reach statistics
+    /// shared through the transposition table.
+    pub fn iteration_batch<G, E, R>(&mut self, game: &G, config: &MctsConfig, evaluator: &E, batch_size: usize,
+                                    rng: &mut R, mut table: Option<&mut Transpositions>) -> Vec<f32>
+            where G: Game<A>, E: Evaluator<G>, R: Rng {
+        let contempt = Contempt::new(game, config);
+        if let Some((alpha, fraction)) = config.root_noise {
+            if self.root().visits() == 0 && !self.root().is_proven() {
+                self.add_root_noise_with(game, alpha, fraction, rng);
+            }
+        }
+
+        // Descend with virtual losses, keeping the statistics they replace
+        let mut saved: HashMap<Vec<usize>, RunningStats> = HashMap::new();
+        let mut leaves = Vec::new();
+        for _ in 0..batch_size {
+            if self.root().is_proven() {
+                break;
+            }
+            let mut leaf = game.clone();
+            let descent = self.descend(&mut leaf, config, rng, table.as_deref());
+            if descent.pending {
+                leaf.resolve_chance();
+            }
+            if config.solver && !descent.leaf_playouts {
+                self.propagate_proof(&descent.path);
+            }
+            for (depth, id) in self.path_ids(&descent.path).into_iter().enumerate() {
+                let stats = &mut self.nodes[id].stats;
+                saved.entry(descent.path[..depth].to_vec()).or_insert(*stats);
+                stats.push(config.virtual_loss);
+            }
+            leaves.push((leaf, descent));
+        }
+        for (path, stats) in saved {
+            let id = self.descendant(&path);
+            self.nodes[id].stats = stats;
+        }
+
+        let open = leaves.iter()
+            .filter(|&(leaf, _)| !leaf.is_terminal())
+            .map(|(leaf, _)| leaf.clone())
+            .collect::<Vec<_>>();
+        let evaluated = evaluator.evaluate_batch(&open);
+        assert_eq!(evaluated.len(), open.len(), "Evaluator returned {} values for a batch of {}",
+                   evaluated.len(), open.len());
+        let mut evaluated = evaluated.into_iter();
+
+        let mut values = Vec::with_capacity(leaves.len());
+        for (leaf, descent) in leaves {
+            let value = if leaf.is_terminal() {
+                self.backpropagate_leaf(Some(&leaf), &descent, 0., config, contempt, table.as_deref_mut())
+            } else {
+                self.backpropagate_leaf(None, &descent, evaluated.next().unwrap(), config, contempt, table.as_deref_mut())
+            };
+            values.push(value);
+        }
+        values
+    }
+
+    /// Start an iteration like those of `iteration_batch`, whose leaf is
+    /// valued in the background; see `MCTS::run_pipelined`.
+    ///
+    /// Until `complete_leaf` takes it back, every node on the way counts a
+    /// virtual loss, so that the iterations started meanwhile spread out
+    /// over the tree. Returns the ticket and the state of the leaf, or None
+    /// if the game ended in the tree; that iteration is already
+    /// backpropagated.
+    pub fn submit_leaf<G, R>(&mut self, game: &G, config: &MctsConfig, pending: &mut PendingLeaves<G, A>,
+                             rng: &mut R, table: Option<&mut Transpositions>) -> Option<(usize, G)>
+            where G: Game<A>, R: Rng {
+        if let Some((alpha, fraction)) = config.root_noise {
+            if self.root().visits() == 0 && !self.root().is_proven() {
+                self.add_root_noise_with(game, alpha, fraction, rng);
+            }
+        }
+
+        let mut leaf = game.clone();
+        let descent = self.descend(&mut leaf, config, rng, table.as_deref());
+        if descent.pending {
+            leaf.resolve_chance();
+        }
+        if config.solver && !descent.leaf_playouts {
+            self.propagate_proof(&descent.path);
+        }
+        if leaf.is_terminal() {
+            let contempt = Contempt::new(game, config);
+            self.backpropagate_pending(Some(&leaf), &descent, 0., config, contempt, &mut pending.saved, false, table);
+            return None;
+        }
+
+        for (depth, id) in self.path_ids(&descent.path).into_iter().enumerate() {
+            let stats = &mut self.nodes[id].stats;
+            pending.saved.entry(descent.path[..depth].to_vec()).or_insert((*stats, 0)).1 += 1;
+            stats.push(config.virtual_loss);
+        }
+        let ticket = pending.next_ticket;
+        pending.next_ticket += 1;
+        pending.leaves.insert(ticket, descent);
+        Some((ticket, leaf))
+    }
+
+    /// Finish the iteration of the leaf with the given ticket, see
+    /// `submit_leaf`, whose state is worth `value` for player 0; returns
+    /// the value of the iteration for player 0.
+    pub fn complete_leaf<G: Game<A>>(&mut self, game: &G, config: &MctsConfig, pending: &mut PendingLeaves<G, A>,
+                                     ticket: usize, value: f32, table: Option<&mut Transpositions>) -> f32 {
+        let descent = pending.leaves.remove(&ticket).expect("No pending leaf with this ticket");
+        let contempt = Contempt::new(game, config);
+        self.backpropagate_pending(None, &descent, value, config, contempt, &mut pending.saved, true, table)
+    }
+
+    /// Backpropagate like `backpropagate_leaf` while other leaves are
+    /// pending: the virtual losses on the path are taken back first, and
+    /// put back for the leaves still pending afterwards. With `release`
+    /// the one of this iteration is gone for good.
+    fn backpropagate_pending<G: Game<A>>(&mut self, end: Option<&G>, descent: &Descent<G, A>, estimate: f32,
+                                         config: &MctsConfig, contempt: Contempt,
+                                         saved: &mut HashMap<Vec<usize>, (RunningStats, usize)>, release: bool,
+                                         table: Option<&mut Transpositions>) -> f32 {
+        let path = &descent.path;
+        let ids = self.path_ids(path);
+        for (depth, &id) in ids.iter().enumerate() {
+            if let Some(&(stats, _)) = saved.get(&path[..depth]) {
+                self.nodes[id].stats = stats;
+            }
+        }
+
+        let value = self.backpropagate_leaf(end, descent, estimate, config, contempt, table);
+
+        for (depth, &id) in ids.iter().enumerate() {
+            let stats = &mut self.nodes[id].stats;
+            let below = match saved.get_mut(&path[..depth]) {
+                Some(entry) => {
+                    entry.0 = *stats;
+                    if release {
+                        entry.1 -= 1;
+                    }
+                    entry.1
+                },
+                None => continue
+            };
+            if below == 0 {
+                saved.remove(&path[..depth]);
+            }
+            for _ in 0..below {
+                stats.push(config.virtual_loss);
+            }
+        }
+        value
+    }
+
+    /// Backpropagate the iteration of `descent`, whose leaf is worth
+    /// `estimate` for player 0 unless the game is over there in `end`;
+    /// returns the value of the iteration for player 0.
+    fn backpropagate_leaf<G: Game<A>>(&mut self, end: Option<&G>, descent: &Descent<G, A>, estimate: f32,
+                                      config: &MctsConfig, contempt: Contempt,
+                                      table: Option<&mut Transpositions>) -> f32 {
+        let value = |player: usize| match (end, player) {
+            (Some(end), _) => end_value(end, player, contempt),
+            (None, 0) => estimate,
+            (None, _) => -estimate
+        };
+        let amaf = if config.rave.is_some() { Some(&descent.moves[..]) } else { None };
+        let discounted = config.discount.map(|gamma| {
+            let values = (0..descent.players).map(value).collect();
+            discount_trail(&descent.trail, values, gamma)
+        });
+        self.backpropagate(&descent.path, amaf, &value, discounted.as_deref(), table);
+        match discounted {
+            Some(discounted) => discounted[0][0],
+            None => value(0)
+        }
+    }
+
+    /// Perform an MCTS iteration like `iteration_config`, running the
+    /// playouts from the new leaf in parallel on the rayon thread pool
+    /// (leaf parallelization).
+    ///
+    /// Every playout draws from a generator of its own, seeded from `rng`,
+    /// so the result does not depend on the number of threads. Only the
+    /// calling thread touches the tree. This pays off with many `playouts`
+    /// per iteration.
+    #[cfg(feature = "rayon")]
+    pub fn iteration_leaf_parallel<G, R>(&mut self, game: &mut G, config: &MctsConfig, rng: &mut R,
+                                         table: Option<&mut Transpositions>) -> f32
+            where G: Game<A> + Send + Sync, A: Send, R: Rng {
+        self.iteration_playouts(game, config, rng, table, None, &mut Growth::default(), |game, players, contempt, rng| {
+            let master = rng.next_u32();
+            (0..max(config.playouts, 1) as u32).into_par_iter().map(|i| {
+                let mut rng = seeded_rng(derive_seed(master, i));
+                Tree::<A>::playout(game, config, None, players, contempt, &mut rng)
+            }).collect()
+        })
+    }
+
+    /// Perform an MCTS iteration, evaluating the new leaf with `playouts`
+    /// for the given number of players and contempt, or with `evaluator`
+    /// if there are none. The depth of the leaf and a new node go into
+    /// `growth`.
+    fn iteration_playouts<G, R, F>(&mut self, game: &mut G, config: &MctsConfig, rng: &mut R,
+                                   mut table: Option<&mut Transpositions>, evaluator: Option<&dyn Evaluator<G>>,
+                                   growth: &mut Growth, mut playouts: F) -> f32
+            where G: Game<A>, R: Rng, F: FnMut(&G, usize, Contempt, &mut R) -> Vec<Rollout<G, A>> {
+        if self.root().is_proven() {
+            return self.root().mean();
+        }
+        let contempt = Contempt::new(game, config);
+        if let Some((alpha, fraction)) = config.root_noise {
+            if self.root().visits() == 0 {
+                self.add_root_noise_with(game, alpha, fraction, rng);
+            }
+        }
+        let Descent { path, moves, trail, players, pending, expanded, leaf_playouts } =
+            self.descend(game, config, rng, table.as_deref());
+        growth.record(path.len(), expanded);
+
+        // The leaf is an afterstate: integrate over the dice or roll them
+        if pending {
+            let outcomes = match config.afterstates {
+                Some(Afterstates::Integrated) => game.afterstate_outcomes(),
+                _ => None
+            };
+            if let Some(outcomes) = outcomes {
+                let amaf = if config.rave.is_some() { Some(&moves[..]) } else { None };
+                let (values, discounted) = expected_outcome(&outcomes, config, &trail, players, evaluator, contempt,
+                                                            |game| {
+                    if leaf_playouts { playouts(game, players, contempt, rng) } else { Vec::new() }
+                });
+                self.backpropagate(&path, amaf, &|player| values[player], discounted.as_deref(), table);
+                return match discounted {
+                    Some(discounted) => discounted[0][0],
+                    None => values[0]
+                };
+            }
+            game.resolve_chance();
+        }
+
+        // Without playouts the game ended in the tree, or the evaluator
+        // values the leaf
+        let playouts = if leaf_playouts { playouts(game, players, contempt, rng) } else { Vec::new() };
+        if playouts.is_empty() {
+            let terminal = game.is_terminal();
+            let value = |player: usize| match evaluator {
+                Some(evaluator) if !terminal => evaluator.evaluate_for(game, player),
+                _ => end_value(game, player, contempt)
+            };
+            let amaf = if config.rave.is_some() { Some(&moves[..]) } else { None };
+            let discounted = config.discount.map(|gamma| {
+                let values = (0..players).map(value).collect();
+                discount_trail(&trail, values, gamma)
+            });
+            self.backpropagate(&path, amaf, &value, discounted.as_deref(), table);
+            if config.solver {
+                self.propagate_proof(&path);
+            }
+            return match discounted {
+                Some(discounted) => discounted[0][0],
+                None => value(0)
+            };
+        }
+        let n_playouts = playouts.len();
+        let mut total = 0.;
+        for (end, sequence, values) in playouts {
+            let discounted = config.discount.map(|gamma| discount_trail(&trail, values, gamma));
+            let value = |player: usize| end_value(&end, player, contempt);
+            if config.rave.is_some() {
+                let all_moves = moves.iter().chain(&sequence).cloned().collect::<Vec<_>>();
+                self.backpropagate(&path, Some(&all_moves), &value, discounted.as_deref(), table.as_deref_mut());
+            } else {
+                self.backpropagate(&path, None, &value, discounted.as_deref(), table.as_deref_mut());
+            }
+            total += match discounted {
+                Some(discounted) => discounted[0][0],
+                None => end_value(&end, 0, contempt)
+            };
+        }
+        total / n_playouts as f32
+    }
+
+    /// Descend from the root to where an iteration leaves the tree,
+    /// making the moves on the way in `game` and growing a new node if
+    /// there is one to grow.
+    fn descend<G: Game<A>, R: Rng>(&mut self, game: &mut G, config: &MctsConfig, rng: &mut R,
+                                   table: Option<&Transpositions>) -> Descent<G, A> {
+        let mut path = Vec::new();
+        let mut moves = Vec::new();
+        // With discounting, the states the moves in the tree led to
+        let mut trail = Vec::new();
+        let mut players = self.nodes[0].player + 1;
+        // With afterstates, whether the dice of the last move are still to roll
+        let mut pending = false;
+        let mut expanded = false;
+        let leaf_playouts = {
+            let mut id = 0;
+            loop {
+                match self.nodes[id].state {
+                    NodeState::LeafNode => {
+                        break false;
+                    },
+                    NodeState::ProvenWin | NodeState::ProvenLoss => {
+                        unreachable!("Descended into a proven node");
+                    },
+                    NodeState::Expandable if self.nodes[id].stats.count() < config.expansion_threshold => {
+                        // Not visited often enough to grow the tree here
+                        break true;
+                    },
+                    NodeState::FullyExpanded | NodeState::Expandable => {
+                        if pending {
+                            game.resolve_chance();
+                            pending = false;
+                        }
+                        // Chance picks the outcome, the tree policy everything else
+                        let outcome = game.chance_outcomes().map(|outcomes| random_outcome_with(rng, &outcomes));
+                        self.nodes[id].chance = outcome.is_some();
+                        let selected = match outcome {
+                            Some(Some((action, _))) => self.nodes[id].children()
+                                .position(|child| self.nodes[child].action == Some(action)),
+                            Some(None) => None,
+                            None => {
+                                let node = &self.nodes[id];
+                                let untried = match (config.tree_policy, node.state) {
+                                    (TreePolicy::Puct, NodeState::Expandable) if node.len > 0 =>
+                                        self.untried_prior(id, game).map(|(_, prior)| prior),
+                                    _ => None
+                                };
+                                self.selected_child(id, config, table, untried, rng)
+                            }
+                        };
+                        match selected {
+                            Some(idx) => {      // Descend into the chosen child...
+                                path.push(idx);
+                                let chance = self.nodes[id].chance;
+                                id = self.child(id, idx);
+                                let node = &self.nodes[id];
+                                pending = tree_move(game, &node.action.unwrap(), config, chance);
+                                if config.rave.is_some() {
+                                    moves.push((node.player, node.action.unwrap()));
+                                }
+                                if config.discount.is_some() {
+                                    trail.push(game.clone());
+                                }
+                                players = max(players, node.player + 1);
+                                trace!("Selected {:?} at depth {}", node.action.unwrap(), path.len());
+                            },
+                            None => {
+                                let child = match (outcome, config.tree_policy) {
+                                    (Some(Some((action, prior))), _) => match self.expand_action(id, game, Some(action), rng) {
+                                        Some(child) => {
+                                            self.nodes[child].prior = prior;
+                                            Some(child)
+                                        },
+                                        None => None
+                                    },
+                                    (Some(None), _) => None,
+                                    (None, TreePolicy::Puct) => self.expand_by_prior(id, game, rng),
+                                    (None, _) => self.expand_with(id, game, rng)
+                                };
+                                match child {
+                                    Some(child) => {           // We expanded our current node...
+                                        let Node { action, player, .. } = self.nodes[child];
+                                        let action = action.unwrap();
+                                        if config.rave.is_some() {
+                                            moves.push((player, action));
+                                        }
+                                        pending = tree_move(game, &action, config, self.nodes[id].chance);
+                                        if table.is_some() {
+                                            self.nodes[child].key = game.hash_state();
+                                        }
+                                        if config.discount.is_some() {
+                                            trail.push(game.clone());
+                                        }
+                                        players = max(players, player + 1);
+                                        path.push(self.nodes[id].len as usize - 1);
+                                        expanded = true;
+                                        trace!("Expanded {:?} at depth {}", action, path.len());
+                                        break true;
+                                    },
+                                    None => {  // Could not expand, current node is a leaf node!
+                                        if config.solver {
+                                            self.prove_end(id, game);
+                                        }
+                                        break false;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        Descent {
+            path: path,
+            moves: moves,
+            trail: trail,
+            players: players,
+            pending: pending,
+            expanded: expanded,
+            leaf_playouts: leaf_playouts,
+        }
+    }
+
+    /// Prove the outcome of node `id`, where the game ended.
+    fn prove_end<G: Game<A>>(&mut self, id: usize, game: &G) {
+        let node = &mut self.nodes[id];
+        let reward = game.reward_for(node.player);
+        if reward > 0. {
+            node.state = NodeState::ProvenWin;
+        } else if reward < 0. {
+            node.state = NodeState::ProvenLoss;
+        }
+    }
+
+    /// Prove node `id` from the proofs of its children; returns whether
+    /// it is proven now.
+    ///
+    /// Chance nodes are never proven: a proven child is just one of the
+    /// outcomes.
+    fn prove(&mut self, id: usize) -> bool {
+        let node = &self.nodes[id];
+        if node.chance {
+            return false;
+        }
+        let children = &self.nodes[node.children()];
+        let mover = match children.first() {
+            Some(child) => child.player,
+            None => return false
+        };
+        let mover_wins = children.iter().any(|child| child.is_proven_win());
+        let mover_loses = match node.state {
+            NodeState::FullyExpanded => children.iter().all(|child| child.is_proven_loss()),
+            _ => false
+        };
+        if mover_wins || mover_loses {
+            let win = mover_wins == (node.player == mover);
+            self.nodes[id].state = if win { NodeState::ProvenWin } else { NodeState::ProvenLoss };
+        }
+        self.nodes[id].is_proven()
+    }
+
+    /// Prove the nodes on `path` bottom up, as far as the proof of the
+    /// node at its end carries.
+    fn propagate_proof(&mut self, path: &[usize]) {
+        let ids = self.path_ids(path);
+        for (depth, &id) in ids.iter().enumerate().rev() {
+            let proven = if depth == path.len() { self.nodes[id].is_proven() } else { self.prove(id) };
+            if !proven {
+                break;
+            }
+        }
+    }
+
+    /// A playout evaluating `game` with the moves `policy` chooses, with
+    /// its moves if RAVE needs them and its discounted values for the first
+    /// `players` players if the search discounts.
+    fn playout<G: Game<A>, R: Rng>(game: &G, config: &MctsConfig, policy: Option<&dyn RolloutPolicy<G, A>>,
+                                   players: usize, contempt: Contempt, rng: &mut R) -> Rollout<G, A> {
+        match config.discount {
+            Some(gamma) => playout_discounted_with(game, config.max_playout_depth, gamma, policy, players,
+                                                   contempt, rng),
+            None => {
+                let (end, moves) = playout_policy_with(game, config.max_playout_depth, policy,
+                                                       config.rave.is_some(), rng);
+                (end, moves, Vec::new())
+            }
+        }
+    }
+
+    /// Account for a visit worth `value` for every player in the root and
+    /// the nodes on `path` below it.
+    ///
+    /// With the `moves` of the whole simulation, starting with the move out
+    /// of the root, also update the AMAF statistics of the children of
+    /// every node on the path. With the `discounted` values of every depth
+    /// and player the nodes get those instead of `value`; the AMAF
+    /// statistics keep the latter.
+    fn backpropagate<F: Fn(usize) -> f32>(&mut self, path: &[usize], moves: Option<&[(usize, A)]>, value: &F,
+                                          discounted: Option<&[Vec<f32>]>, mut table: Option<&mut Transpositions>) {
+        // Where every move was made for the last time
+        let mut last = HashMap::new();
+        for (i, &mv) in moves.unwrap_or(&[]).iter().enumerate() {
+            last.insert(mv, i);
+        }
+
+        let node_value = |depth: usize, player: usize| match discounted {
+            Some(discounted) => discounted[depth][player],
+            None => value(player)
+        };
+
+        trace!("Backpropagating {:.3} for player 0 from depth {}", node_value(path.len(), 0), path.len());
+        let mut id = 0;
+        let player = self.nodes[id].player;
+        self.update_shared(id, node_value(0, player), None);
+        for (depth, &idx) in path.iter().enumerate() {
+            self.update_amaf(id, &last, depth, value);
+            id = self.child(id, idx);
+            let player = self.nodes[id].player;
+            self.update_shared(id, node_value(depth + 1, player), table.as_deref_mut());
+        }
+        self.update_amaf(id, &last, path.len(), value);
+    }
+
+    /// Update the AMAF statistics of the children of node `id` whose move
+    /// was made at `depth` or later according to `last`.
+    fn update_amaf<F: Fn(usize) -> f32>(&mut self, id: usize, last: &HashMap<(usize, A), usize>, depth: usize,
+                                        value: &F) {
+        if last.is_empty() {
+            return;
+        }
+        let children = self.nodes[id].children();
+        for child in &mut self.nodes[children] {
+            match last.get(&(child.player, child.action.unwrap())) {
+                Some(&i) if i >= depth => child.amaf.push(value(child.player)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Force the expansion of the node reached from the root by `path`.
+    ///
+    /// Adds a child for `action` (or a random unexplored action), evaluates
+    /// it with a playout and updates the statistics along the path like an
+    /// iteration would. Returns the playout reward for player 0, or None if
+    /// the path does not exist or there is nothing to expand.
+    pub fn expand_at<G: Game<A>>(&mut self, game: &mut G, path: &[A], action: Option<A>) -> Option<f32> {
+        with_thread_rng(|rng| self.expand_at_with(game, path, action, rng))
+    }
+
+    /// Force an expansion like `expand_at`, drawing from `rng`.
+    pub fn expand_at_with<G: Game<A>, R: Rng>(&mut self, game: &mut G, path: &[A], action: Option<A>,
+                                              rng: &mut R) -> Option<f32> {
+        // The root and the nodes on the path
+        let mut ids = vec![0];
+        for action in path {
+            let child = self.node(ids[ids.len() - 1]).child(action)?.id;
+            game.make_move(action);
+            ids.push(child);
+        }
+        let child = self.expand_action(ids[ids.len() - 1], game, action, rng)?;
+        game.make_move(&self.nodes[child].action.unwrap());
+        let end = playout_with(game, rng);
+        ids.push(child);
+        for &id in &ids {
+            self.update_from(id, &end, None);
+        }
+        Some(end.reward_for(0))
+    }
+}
+
+impl<A: GameAction> Default for Tree<A> {
+    fn default() -> Tree<A> {
+        Tree::new()
+    }
+}
+
+/// Depth first iterator over the nodes of a subtree, see `TreeNode::iter`.
+///
+/// Keeps a stack of nodes to visit rather than recursing, so deep trees
+/// are fine.
+pub struct Nodes<'a, A: GameAction + 'a> {
+    tree: &'a Tree<A>,
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a, A: GameAction> Iterator for Nodes<'a, A> {
+    type Item = (usize, TreeNode<'a, A>);
+
+    fn next(&mut self) -> Option<(usize, TreeNode<'a, A>)> {
+        let (depth, id) = self.stack.pop()?;
+        self.stack.extend(self.tree.nodes[id].children().rev().map(|child| (depth + 1, child)));
+        Some((depth, self.tree.node(id)))
+    }
+}
+
+impl<'a, A: GameAction> fmt::Debug for TreeNode<'a, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.data().fmt(f)
+    }
+}
+
+impl<'a, A: GameAction> fmt::Display for TreeNode<'a, A> {
+
+    /// Output a nicely indented tree
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (depth, node) in self.iter() {
+            for _ in 0..depth {
+                try!(f.write_str("    "));
+            }
+            match node.action() {
+                Some(a)  => try!(writeln!(f, "{:?} mean={} n={}", a, node.mean(), node.visits())),
+                None     => try!(writeln!(f, "Root mean={} n={}", node.mean(), node.visits()))
+            }
+        }
+        write!(f, "")
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Statistics of an action at the root, merged over the ensemble.
+pub struct ActionStats<A> {
+    pub action: A,
+    /// Visits in all trees of the ensemble.
+    pub visits: u64,
+    /// Mean reward for the player to move.
+    pub mean: f32,
+    /// Standard error of the mean.
+    pub stderr: f32,
+    /// Whether the solver proved the action won (or lost) in every tree
+    /// that explored it.
+    pub proven: Option<bool>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// A snapshot of a running search, as passed to the observer of
+/// `MCTS::search_observed` and `MCTS::search_time_observed`.
+pub struct SearchProgress<A> {
+    /// Iterations per ensemble member so far.
+    pub iterations: usize,
+    /// The best action so far, see `MCTS::best_action`.
+    pub best_action: Option<A>,
+    /// The value of the root so far, see `MCTS::root_value`.
+    pub root_value: Option<f32>,
+    /// Nodes the search added to the trees of the ensemble so far.
+    pub nodes: usize,
+    /// Seconds since the search started.
+    pub elapsed: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// What a search did, as returned by `MCTS::search`, `MCTS::search_time`
+/// and their relatives.
+pub struct SearchReport<A> {
+    /// Iterations per ensemble member.
+    pub iterations: usize,
+    /// Wall-clock time of the search in seconds.
+    pub elapsed: f32,
+    /// Nodes the search added to the trees of the ensemble.
+    pub nodes: usize,
+    /// Depth of the deepest leaf the iterations reached.
+    pub max_depth: usize,
+    /// The root actions after the search, ranked like
+    /// `MCTS::action_statistics`.
+    pub actions: Vec<ActionStats<A>>,
+}
+
+/// Shown as `1000 iterations in 0.120 s, 958 new nodes, depth 7: Up 0.512
+/// (640), Left 0.498 (250), ...` with the mean reward and visits of every
+/// action.
+impl<A: GameAction> fmt::Display for SearchReport<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} iterations in {:.3} s, {} new nodes, depth {}:",
+                    self.iterations, self.elapsed, self.nodes, self.max_depth));
+        for (i, stats) in self.actions.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            try!(write!(f, "{}{:?} {:.3} ({})", separator, stats.action, stats.mean, stats.visits));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+/// How much the iterations of a search grew the trees.
+struct Growth {
+    nodes: usize,
+    max_depth: usize,
+}
+
+impl Growth {
+    /// Account for an iteration that reached a leaf at `depth`, and added
+    /// a node if `expanded`.
+    fn record(&mut self, depth: usize, expanded: bool) {
+        if expanded {
+            self.nodes += 1;
+        }
+        self.max_depth = max(self.max_depth, depth);
+    }
+
+    fn merge(&mut self, other: Growth) {
+        self.nodes += other.nodes;
+        self.max_depth = max(self.max_depth, other.max_depth);
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Store and process some simple statistical information about NodeTrees.
+///
+/// For an ensemble the statistics cover all its trees, with every root at
+/// depth 0.
+pub struct TreeStatistics {
+    nodes: i32,
+    leaves: i32,
+    terminal: i32,
+    children: i32,
+    min_depth: i32,
+    max_depth: i32,
+    leaf_depths: i64,
+    memory: usize,
+}
+
+impl TreeStatistics {
+    /// Number of nodes in the tree, the root included.
+    pub fn nodes(&self) -> i32 {
+        self.nodes
+    }
+
+    /// Number of nodes without children.
+    pub fn leaves(&self) -> i32 {
+        self.leaves
+    }
+
+    /// Number of leaves where the game is over.
+    pub fn terminal_nodes(&self) -> i32 {
+        self.terminal
+    }
+
+    /// Depth of the shallowest leaf.
+    pub fn min_depth(&self) -> i32 {
+        self.min_depth
+    }
+
+    /// Depth of the deepest leaf.
+    pub fn max_depth(&self) -> i32 {
+        self.max_depth
+    }
+
+    /// Mean depth of the leaves.
+    pub fn average_depth(&self) -> f32 {
+        if self.leaves == 0 {
+            return 0.;
+        }
+        self.leaf_depths as f32 / self.leaves as f32
+    }
+
+    /// Mean number of children of the nodes that have any.
+    pub fn branching_factor(&self) -> f32 {
+        let inner = self.nodes - self.leaves;
+        if inner == 0 {
+            return 0.;
+        }
+        self.children as f32 / inner as f32
+    }
+
+    /// Approximate memory taken by the nodes, in bytes: the nodes
+    /// themselves and the spare capacity of the vectors of children,
+    /// without the statistics of the transposition tables.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+
+    /// Statistics of an empty forest.
+    fn empty() -> TreeStatistics {
+        TreeStatistics {
+            nodes: 0,
+            leaves: 0,
+            terminal: 0,
+            children: 0,
+            min_depth: i32::MAX,
+            max_depth: 0,
+            leaf_depths: 0,
+            memory: 0,
+        }
+    }
+
+    /// Statistics of the trees of `self` and `other` together.
+    fn merge(&self, other: &TreeStatistics) -> TreeStatistics {
+        TreeStatistics {
+            nodes: self.nodes + other.nodes,
+            leaves: self.leaves + other.leaves,
+            terminal: self.terminal + other.terminal,
+            children: self.children + other.children,
+            min_depth: min(self.min_depth, other.min_depth),
+            max_depth: max(self.max_depth, other.max_depth),
+            leaf_depths: self.leaf_depths + other.leaf_depths,
+            memory: self.memory + other.memory,
+        }
+    }
+}
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Statistics of a single tree of an ensemble, as returned by
+/// `MCTS::per_root_statistics`.
+pub struct RootStatistics<A> {
+    /// The shape of the tree.
+    pub tree: TreeStatistics,
+    /// Visits of the root.
+    pub visits: u64,
+    /// The action this tree would play on its own.
+    pub best_action: Option<A>,
+    /// The mean reward of that action in this tree.
+    pub value: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// How much the trees of an ensemble agree on the action to play, as
+/// returned by `MCTS::agreement`.
+///
+/// Trees that keep disagreeing hint that the ensemble is too small to
+/// average out the determinizations; trees that always agree, that it
+/// could be smaller.
+pub struct Agreement<A> {
+    /// Every action some tree would play, with the number of trees that
+    /// would play it, most votes first.
+    pub votes: Vec<(A, usize)>,
+    /// Share of the voting trees behind the most popular action: 1 if
+    /// they all agree, 0 if none voted.
+    pub agreement: f32,
+    /// Entropy of the votes in bits: 0 if all trees agree, up to the
+    /// binary logarithm of the ensemble size if they all differ.
+    pub entropy: f32,
+}
+
+/// Shown as `agreement 0.750, entropy 0.811 bits: Up 3, Left 1`.
+impl<A: GameAction> fmt::Display for Agreement<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "agreement {:.3}, entropy {:.3} bits:", self.agreement, self.entropy));
+        for (i, &(action, votes)) in self.votes.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            try!(write!(f, "{}{:?} {}", separator, action, votes));
+        }
+        Ok(())
+    }
+}
+
+//////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+/// Represents an ensemble of MCTS trees.
+///
+/// For many applications we need to work with ensembles because we use
+/// determinization.
+pub struct MCTS<G: Game<A>, A: GameAction> {
+    trees: Vec<Tree<A>>,
+    games: Vec<G>,
+    tables: Vec<Option<Transpositions>>,
+    config: MctsConfig,
+    rollout: Option<Arc<dyn RolloutPolicy<G, A> + Send + Sync>>,
+    rng: Option<StdRng>,
+    iterations_per_s: f32,
+}
+
+impl<G: Game<A>, A: GameAction> MCTS<G, A> {
+
+    /// Create a new MCTS solver.
+    pub fn new(game: &G, ensemble_size: usize) -> MCTS<G, A> {
+        MCTS::with_transpositions(game, ensemble_size, false)
+    }
+
+    /// Create a new MCTS solver, sharing the statistics of nodes reaching
+    /// the same state if `transpositions` is set.
+    ///
+    /// Every ensemble member has a transposition table of its own; see
+    /// `Tree::iteration_shared`. Games have to implement `hash_state`
+    /// for this to have any effect.
+    pub fn with_transpositions(game: &G, ensemble_size: usize, transpositions: bool) -> MCTS<G, A> {
+        MCTS::with_config(game, ensemble_size, MctsConfig { transpositions: transpositions, ..MctsConfig::default() })
+    }
+
+    /// Create a new MCTS solver searching with the given parameters.
+    ///
+    /// `run` searches with these parameters; `search` and its variants
+    /// replace the exploration constant with their `c`.
+    pub fn with_config(game: &G, ensemble_size: usize, config: MctsConfig) -> MCTS<G, A> {
+        let mut trees = Vec::new();
+        let mut games = Vec::new();
+        for i in 0..ensemble_size {
+            let mut game = game.clone();
+            game.set_rng_seed(i as u32);
+            games.push(game);
+            trees.push(Tree::new());
+        }
+        let tables = (0..ensemble_size)
+            .map(|_| if config.transpositions { Some(Transpositions::new()) } else { None })
+            .collect();
+        MCTS {
+            trees: trees,
+            games: games,
+            tables: tables,
+            config: config,
+            rollout: None,
+            rng: None,
+            iterations_per_s: 1.
+        }
+    }
+
+    /// The parameters of the search.
+    pub fn config(&self) -> &MctsConfig {
+        &self.config
+    }
+
+    /// Let the playouts of all further searches choose their moves with
+    /// `policy` instead of at random; see `Tree::iteration_rollout`.
+    ///
+    /// Only the leaf parallel search keeps its random playouts.
+    pub fn set_rollout_policy<P>(&mut self, policy: P)
+            where P: RolloutPolicy<G, A> + Send + Sync + 'static {
+        self.rollout = Some(Arc::new(policy));
+    }
+
+    /// Let the methods without a generator of their own draw from a
+    /// `StdRng` seeded with `seed` instead of the thread generator, so that
+    /// the same seed grows the same trees and picks the same actions.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Some(seeded_std_rng(seed));
+    }
+
+    /// Run `f` with the generator set by `set_seed`, or the thread
+    /// generator without one.
+    fn with_rng<T, F: FnOnce(&mut Self, &mut dyn Rng) -> T>(&mut self, f: F) -> T {
+        match self.rng.take() {
+            Some(mut rng) => {
+                let result = f(self, &mut rng);
+                self.rng = Some(rng);
+                result
+            },
+            None => with_thread_rng(|rng| f(self, rng))
+        }
+    }
+
+    /// The configured parameters with exploration constant `c`.
+    fn config_with(&self, c: f32) -> MctsConfig {
+        MctsConfig { exploration: c, ..self.config }
+    }
+
+    /// The number of trees in the ensemble.
+    pub fn ensemble_size(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Grow or shrink the ensemble to `ensemble_size` trees.
+    ///
+    /// Shrinking drops the last trees with their statistics. New trees
+    /// start empty, on a copy of the game of the first tree reseeded like
+    /// the members `with_config` creates.
+    ///
+    /// Panics when growing an empty ensemble, which has no game to copy.
+    pub fn set_ensemble_size(&mut self, ensemble_size: usize) {
+        if ensemble_size <= self.trees.len() {
+            self.trees.truncate(ensemble_size);
+            self.games.truncate(ensemble_size);
+            self.tables.truncate(ensemble_size);
+            return;
+        }
+
+        assert!(!self.games.is_empty(), "Cannot grow an empty ensemble");
+        for i in self.trees.len()..ensemble_size {
+            let mut game = self.games[0].clone();
+            game.set_rng_seed(i as u32);
+            self.games.push(game);
+            self.trees.push(Tree::new());
+            self.tables.push(if self.config.transpositions { Some(Transpositions::new()) } else { None });
+        }
+    }
+
+    /// The root of every tree in the ensemble.
+    pub fn roots(&self) -> Vec<TreeNode<'_, A>> {
+        self.trees.iter().map(|tree| tree.root()).collect()
+    }
+
+    /// The determinized game of every ensemble member.
+    pub fn games(&self) -> &[G] {
+        &self.games
+    }
+
+    /// The transposition table of an ensemble member; None if the solver
+    /// does not use transpositions.
+    pub fn transpositions(&self, member: usize) -> Option<&Transpositions> {
+        self.tables[member].as_ref()
+    }
+
+    /// Force an expansion below `path` in the given ensemble member; see
+    /// `Tree::expand_at`.
+    pub fn expand_at(&mut self, member: usize, path: &[A], action: Option<A>) -> Option<f32> {
+        self.with_rng(|mcts, mut rng| mcts.expand_at_with(member, path, action, &mut rng))
+    }
+
+    /// Force an expansion like `expand_at`, drawing from `rng`.
+    pub fn expand_at_with<R: Rng>(&mut self, member: usize, path: &[A], action: Option<A>,
+                                  rng: &mut R) -> Option<f32> {
+        let mut game = self.games[member].clone();
+        self.trees[member].expand_at_with(&mut game, path, action, rng)
+    }
+
+    /// Return basic statistical data about the trees of the ensemble.
+    ///
+    /// Counts are summed over the trees; depths are measured from the
+    /// roots, as if every tree were the only one.
+    pub fn tree_statistics(&self) -> TreeStatistics {
+        let stats = self.trees.iter()
+            .fold(TreeStatistics::empty(), |stats, tree| stats.merge(&tree.root().tree_statistics()));
+        if stats.nodes == 0 {
+            return TreeStatistics { min_depth: 0, ..stats };
+        }
+        stats
+    }
+    /// Set a new game state for this solver.
+    pub fn advance_game(&mut self, game: &G) {
+        let ensemble_size = self.games.len();
+
+        let mut trees = Vec::new();
+        let mut games = Vec::new();
+        for i in 0..ensemble_size {
+            let mut game = game.clone();
+            game.set_rng_seed(i as u32);
+            games.push(game);
+            trees.push(Tree::new());
+        }
+        self.games = games;
+        self.trees = trees;
+        for table in self.tables.iter_mut().flat_map(|table| table.as_mut()) {
+            table.clear();
+        }
+    }
+
+    /// Play `action` and keep the subtrees below it as the new roots.
+    ///
+    /// Every ensemble member applies the action to its own determinized
+    /// game, so for stochastic games the state may differ from the real
+    /// one; use `advance_game` there. Returns whether any statistics were
+    /// kept.
+    pub fn advance(&mut self, action: &A) -> bool {
+        let mut kept = false;
+        for e in 0..self.games.len() {
+            self.games[e].make_move(action);
+
+            let child = self.trees[e].root().child(action).map(|child| child.id());
+            self.trees[e] = match child {
+                Some(child) => {
+                    kept = true;
+                    self.trees[e].subtree(child)
+                },
+                None => Tree::new()
+            };
+        }
+        kept
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters.
+    pub fn run(&mut self, n_samples: usize) {
+        self.with_rng(|mcts, mut rng| mcts.run_with(n_samples, &mut rng))
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters,
+    /// drawing from `rng`.
+    pub fn run_with<R: Rng>(&mut self, n_samples: usize, rng: &mut R) {
+        let config = self.config;
+        self.search_config_with(n_samples, &config, rng);
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters,
+    /// valuing new leaves with `evaluator` instead of playouts; see
+    /// `Tree::iteration_evaluated`.
+    pub fn run_evaluated<E: Evaluator<G>>(&mut self, n_samples: usize, evaluator: &E) {
+        self.with_rng(|mcts, mut rng| mcts.run_evaluated_with(n_samples, evaluator, &mut rng))
+    }
+
+    /// Run like `run_evaluated`, drawing from `rng`.
+    pub fn run_evaluated_with<E: Evaluator<G>, R: Rng>(&mut self, n_samples: usize, evaluator: &E, rng: &mut R) {
+        let config = self.config;
+        for e in 0..self.games.len() {
+            let game = &self.games[e];
+            let tree = &mut self.trees[e];
+            let table = &mut self.tables[e];
+
+            for _ in 0..n_samples {
+                let mut this_game = game.clone();
+                tree.iteration_evaluated(&mut this_game, &config, evaluator, rng, table.as_mut());
+            }
+        }
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters,
+    /// valuing new leaves with `evaluator` in batches of up to `batch_size`;
+    /// see `Tree::iteration_batch`.
+    pub fn run_batched<E: Evaluator<G>>(&mut self, n_samples: usize, batch_size: usize, evaluator: &E) {
+        self.with_rng(|mcts, mut rng| mcts.run_batched_with(n_samples, batch_size, evaluator, &mut rng))
+    }
+
+    /// Run like `run_batched`, drawing from `rng`.
+    pub fn run_batched_with<E: Evaluator<G>, R: Rng>(&mut self, n_samples: usize, batch_size: usize,
+                                                    evaluator: &E, rng: &mut R) {
+        let config = self.config;
+        let batch_size = max(batch_size, 1);
+        for e in 0..self.games.len() {
+            let game = &self.games[e];
+            let tree = &mut self.trees[e];
+            let table = &mut self.tables[e];
+
+            let mut done = 0;
+            while done < n_samples {
+                let size = min(batch_size, n_samples - done);
+                tree.iteration_batch(game, &config, evaluator, size, rng, table.as_mut());
+                done += size;
+            }
+        }
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters,
+    /// valuing new leaves with `evaluator` in the background while up to
+    /// `max_pending` of them are out; see `Tree::submit_leaf`.
+    ///
+    /// The search only waits for a value once `max_pending` leaves are out
+    /// or all iterations are started.
+    pub fn run_pipelined<E: PipelinedEvaluator<G>>(&mut self, n_samples: usize, max_pending: usize, evaluator: &mut E) {
+        self.with_rng(|mcts, mut rng| mcts.run_pipelined_with(n_samples, max_pending, evaluator, &mut rng))
+    }
+
+    /// Run like `run_pipelined`, drawing from `rng`.
+    pub fn run_pipelined_with<E: PipelinedEvaluator<G>, R: Rng>(&mut self, n_samples: usize, max_pending: usize,
+                                                               evaluator: &mut E, rng: &mut R) {
+        let config = self.config;
+        let max_pending = max(max_pending, 1);
+        for e in 0..self.games.len() {
+            let game = &self.games[e];
+            let tree = &mut self.trees[e];
+            let table = &mut self.tables[e];
+
+            let mut pending = PendingLeaves::new();
+            let mut started = 0;
+            loop {
+                while started < n_samples && pending.len() < max_pending && !tree.root().is_proven() {
+                    if let Some((ticket, leaf)) = tree.submit_leaf(game, &config, &mut pending, rng, table.as_mut()) {
+                        evaluator.submit(ticket, leaf);
+                    }
+                    started += 1;
+                    for (ticket, value) in evaluator.ready() {
+                        tree.complete_leaf(game, &config, &mut pending, ticket, value, table.as_mut());
+                    }
+                }
+                if pending.is_empty() {
+                    break;
+                }
+                for (ticket, value) in evaluator.wait() {
+                    tree.complete_leaf(game, &config, &mut pending, ticket, value, table.as_mut());
+                }
+            }
+        }
+    }
+
+    /// Perform n_samples MCTS iterations with the configured parameters,
+    /// running the playouts of every iteration in parallel; see
+    /// `Tree::iteration_leaf_parallel`.
+    #[cfg(feature = "rayon")]
+    pub fn run_leaf_parallel(&mut self, n_samples: usize)
+            where G: Send + Sync, A: Send {
+        self.with_rng(|mcts, mut rng| mcts.run_leaf_parallel_with(n_samples, &mut rng))
+    }
+
+    /// Run like `run_leaf_parallel`, seeding the playouts from `rng`.
+    #[cfg(feature = "rayon")]
+    pub fn run_leaf_parallel_with<R: Rng>(&mut self, n_samples: usize, rng: &mut R)
+            where G: Send + Sync, A: Send {
+        let config = self.config;
+        for e in 0..self.games.len() {
+            let game = &self.games[e];
+            let tree = &mut self.trees[e];
+            let table = &mut self.tables[e];
+
+            for _ in 0..n_samples {
+                let mut this_game = game.clone();
+                tree.iteration_leaf_parallel(&mut this_game, &config, rng, table.as_mut());
+            }
+        }
+    }
+
+    /// Perform n_samples MCTS iterations.
+    pub fn search(&mut self, n_samples: usize, c: f32) -> SearchReport<A> {
+        self.with_rng(|mcts, mut rng| mcts.search_with(n_samples, c, &mut rng))
+    }
+
+    /// Perform n_samples MCTS iterations drawing from `rng`.
+    pub fn search_with<R: Rng>(&mut self, n_samples: usize, c: f32, rng: &mut R) -> SearchReport<A> {
+        let t0 = seconds();
+        let config = self.config_with(c);
+        let growth = self.search_config_with(n_samples, &config, rng);
+        self.report(n_samples, t0, growth)
+    }
+
+    fn search_config_with<R: Rng>(&mut self, n_samples: usize, config: &MctsConfig, rng: &mut R) -> Growth {
+        let ensemble_size = self.games.len();
+        let mut growth = Growth::default();
+
+        // Iterate over ensemble and perform MCTS iterations
+        let policy = self.rollout.as_ref().map(|policy| &**policy as &dyn RolloutPolicy<G, A>);
+        for e in 0..ensemble_size {
+            let game = &self.games[e];
+            let tree = &mut self.trees[e];
+            let table = &mut self.tables[e];
+
+            // Perform MCTS iterations
+            for _ in 0..n_samples {
+                let mut this_game = game.clone();
+                tree.iteration_policy(&mut this_game, config, policy, rng, table.as_mut(), &mut growth);
+            }
+        }
+        growth
+    }
+
+    /// Perform n_samples MCTS iterations in every tree, searching the trees
+    /// of the ensemble in parallel on up to `threads` threads.
+    ///
+    /// With `threads` 0 there is one thread per available core. Every tree
+    /// draws from its own generator, seeded from the thread RNG, so the
+    /// result does not depend on the number of threads.
+    pub fn search_parallel(&mut self, n_samples: usize, c: f32, threads: usize) -> SearchReport<A>
+            where G: Send, A: Send {
+        self.with_rng(|mcts, mut rng| mcts.search_parallel_with(n_samples, c, threads, &mut rng))
+    }
+
+    /// Search in parallel like `search_parallel`, seeding the trees from `rng`.
+    pub fn search_parallel_with<R: Rng>(&mut self, n_samples: usize, c: f32, threads: usize,
+                                        rng: &mut R) -> SearchReport<A>
+            where G: Send, A: Send {
+        let t0 = seconds();
+        let ensemble_size = self.games.len();
+        if ensemble_size == 0 {
+            return self.report(0, t0, Growth::default());
+        }
+        let threads = match threads {
+            0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            threads => threads
+        };
+        let per_thread = (ensemble_size + threads - 1) / threads;
+
+        let config = self.config_with(c);
+        let config = &config;
+        let rollout = self.rollout.clone();
+        let rollout = &rollout;
+        let master = rng.next_u32();
+        let seeds = (0..ensemble_size).map(|e| derive_seed(master, e as u32)).collect::<Vec<_>>();
+
+        // The games are borrowed mutably only so that they need not be Sync
+        let growth = thread::scope(|scope| {
+            let chunks = self.trees.chunks_mut(per_thread)
+                .zip(self.games.chunks_mut(per_thread))
+                .zip(self.tables.chunks_mut(per_thread))
+                .zip(seeds.chunks(per_thread));
+            let handles = chunks.map(|(((trees, games), tables), seeds)| {
+                scope.spawn(move || {
+                    let mut growth = Growth::default();
+                    let members = trees.iter_mut().zip(games.iter()).zip(tables.iter_mut()).zip(seeds);
+                    for (((tree, game), table), &seed) in members {
+                        let mut rng = seeded_rng(seed);
+                        for _ in 0..n_samples {
+                            let mut this_game = game.clone();
+                            let policy = rollout.as_ref().map(|policy| &**policy as &dyn RolloutPolicy<G, A>);
+                            tree.iteration_policy(&mut this_game, config, policy, &mut rng, table.as_mut(),
+                                                  &mut growth);
+                        }
+                    }
+                    growth
+                })
+            }).collect::<Vec<_>>();
+            let mut growth = Growth::default();
+            for handle in handles {
+                growth.merge(handle.join().expect("Search thread panicked"));
+            }
+            growth
+        });
+        self.report(n_samples, t0, growth)
+    }
+
+    /// Perform MCTS iterations for the given time budget (in s).
+    pub fn search_time(&mut self, budget_seconds: f32, c: f32) -> SearchReport<A> {
+        self.with_rng(|mcts, mut rng| mcts.search_time_with(budget_seconds, c, &mut rng))
+    }
+
+    /// Search for the given time budget like `search_time`, drawing from
+    /// `rng`.
+    ///
+    /// The number of iterations depends on the speed of the machine, so
+    /// only the random choices are reproducible. The search runs in slices
+    /// of at most 100 iterations; after every slice it checks whether the
+    /// move is decided according to `early_stop` and `stop_visit_share`.
+    pub fn search_time_with<R: Rng>(&mut self, budget_seconds: f32, c: f32, rng: &mut R) -> SearchReport<A> {
+        self.search_time_observed_with(budget_seconds, c, usize::MAX, |_| true, rng)
+    }
+
+    /// Perform n_samples MCTS iterations, calling `observer` with the
+    /// progress after every `every` iterations and at the end.
+    ///
+    /// The search stops early once `observer` returns false, e.g. for a
+    /// custom stopping rule.
+    pub fn search_observed<F>(&mut self, n_samples: usize, c: f32, every: usize, observer: F) -> SearchReport<A>
+            where F: FnMut(&SearchProgress<A>) -> bool {
+        self.with_rng(|mcts, mut rng| mcts.search_observed_with(n_samples, c, every, observer, &mut rng))
+    }
+
+    /// Search like `search_observed`, drawing from `rng`.
+    pub fn search_observed_with<F, R>(&mut self, n_samples: usize, c: f32, every: usize, mut observer: F,
+                                      rng: &mut R) -> SearchReport<A>
+            where F: FnMut(&SearchProgress<A>) -> bool, R: Rng {
+        let every = every.max(1);
+        let config = self.config_with(c);
+        let t0 = seconds();
+        let mut samples_total = 0;
+        let mut growth = Growth::default();
+        while samples_total < n_samples {
+            let slice = every.min(n_samples - samples_total);
+            growth.merge(self.search_config_with(slice, &config, rng));
+            samples_total += slice;
+            if !observer(&self.progress(samples_total, t0, growth)) {
+                break;
+            }
+        }
+        self.report(samples_total, t0, growth)
+    }
+
+    /// Perform MCTS iterations for the given time budget (in s), calling
+    /// `observer` with the progress after every `every` iterations and at
+    /// the end.
+    ///
+    /// The search stops early once `observer` returns false, or once the
+    /// move is decided like in `search_time`.
+    pub fn search_time_observed<F>(&mut self, budget_seconds: f32, c: f32, every: usize,
+                                   observer: F) -> SearchReport<A>
+            where F: FnMut(&SearchProgress<A>) -> bool {
+        self.with_rng(|mcts, mut rng| mcts.search_time_observed_with(budget_seconds, c, every, observer, &mut rng))
+    }
+
+    /// Search like `search_time_observed`, drawing from `rng`.
+    pub fn search_time_observed_with<F, R>(&mut self, budget_seconds: f32, c: f32, every: usize,
+                                           mut observer: F, rng: &mut R) -> SearchReport<A>
+            where F: FnMut(&SearchProgress<A>) -> bool, R: Rng {
+        let every = every.max(1);
+        let config = self.config_with(c);
+        let mut next_report = every;
+        let mut samples_total = 0;
+        let mut growth = Growth::default();
+        let t0 = seconds();
+
+        let mut n_samples = (self.iterations_per_s*budget_seconds).max(10.).min(100.) as usize;
+        while n_samples >= 5 {
+            // Slices end at the reports
+            let slice = n_samples.min(next_report - samples_total);
+            growth.merge(self.search_config_with(slice, &config, rng));
+            samples_total += slice;
+
+            let time_spend = (seconds() - t0) as f32;
+            self.iterations_per_s = (samples_total as f32) / time_spend;
+
+            if samples_total == next_report {
+                next_report = next_report.saturating_add(every);
+                if !observer(&self.progress(samples_total, t0, growth)) {
+                    return self.report(samples_total, t0, growth);
+                }
+            }
+
+            let time_left = budget_seconds - time_spend;
+            if self.decided(self.iterations_per_s*time_left.max(0.)) {
+                break;
+            }
+            n_samples = (self.iterations_per_s*time_left).max(0.).min(100.) as usize;
+
+        }
+        // The last slice ended between two reports
+        if samples_total != next_report - every {
+            observer(&self.progress(samples_total, t0, growth));
+        }
+        self.report(samples_total, t0, growth)
+    }
+
+    /// The report of a search that started at `t0`, ran `iterations`
+    /// iterations per tree and grew the trees by `growth`; logs a summary
+    /// with the statistics of every action at the root.
+    fn report(&self, iterations: usize, t0: f64, growth: Growth) -> SearchReport<A> {
+        let report = SearchReport {
+            iterations: iterations,
+            elapsed: (seconds() - t0) as f32,
+            nodes: growth.nodes,
+            max_depth: growth.max_depth,
+            actions: self.action_statistics(),
+        };
+        if log_enabled!(Level::Debug) {
+            debug!("Searched {} iterations in {} trees in {:.3} s: best {:?}, value {:?}, {} new nodes, depth {}",
+                   iterations, self.trees.len(), report.elapsed, self.best_action(), self.root_value(),
+                   report.nodes, report.max_depth);
+            for stats in &report.actions {
+                debug!("  {:?}: {} visits, mean {:.3} +/- {:.3}", stats.action, stats.visits, stats.mean, stats.stderr);
+            }
+        }
+        report
+    }
+
+    /// The progress of a search that started at `t0`, ran `iterations`
+    /// iterations so far and grew the trees by `growth`.
+    fn progress(&self, iterations: usize, t0: f64, growth: Growth) -> SearchProgress<A> {
+        SearchProgress {
+            iterations: iterations,
+            best_action: self.best_action(),
+            root_value: self.root_value(),
+            nodes: growth.nodes,
+            elapsed: (seconds() - t0) as f32,
+        }
+    }
+
+    /// Whether further search cannot change the most visited action, with
+    /// `remaining` more iterations in every tree.
+    fn decided(&self, remaining: f32) -> bool {
+        if !self.config.early_stop && self.config.stop_visit_share.is_none() {
+            return false;
+        }
+        let stats = self.action_statistics();
+        let mut visits = stats.iter().map(|stats| stats.visits).collect::<Vec<_>>();
+        visits.sort_by(|a, b| b.cmp(a));
+        let (best, second) = match visits.len() {
+            0 => return false,
+            1 => (visits[0], 0),
+            _ => (visits[0], visits[1])
+        };
+
+        if self.config.early_stop {
+            let proven = stats[0].proven == Some(true);
+            let catch_up = remaining as f64 * self.trees.len() as f64;
+            if proven || (best - second) as f64 > catch_up {
+                return true;
+            }
+        }
+        match self.config.stop_visit_share {
+            Some(share) => {
+                let total = visits.iter().sum::<u64>();
+                best as f32 >= share * total as f32
+            },
+            None => false
+        }
+    }
+
+    /// Return the best action found so far by averaging over the ensemble,
+    /// chosen by the configured `SelectionPolicy`.
+    pub fn best_action(&self) -> Option<A> {
+        self.best_action_by(self.config.selection)
+    }
+
+    /// Return the best action found so far according to `policy`.
+    pub fn best_action_by(&self, policy: SelectionPolicy) -> Option<A> {
+        self.select_action(policy).map(|stats| stats.action)
+    }
+
+    /// Return the estimated value of the current game state.
+    ///
+    /// This is the mean reward of the best action found so far, averaged
+    /// over the ensemble.
+    pub fn root_value(&self) -> Option<f32> {
+        self.select_action(self.config.selection).map(|stats| stats.mean)
+    }
+
+    /// Return the visit count and mean reward of every action at the root,
+    /// sorted like `action_statistics`.
+    pub fn action_values(&self) -> Vec<(A, u64, f32)> {
+        self.action_statistics().iter()
+            .map(|stats| (stats.action, stats.visits, stats.mean))
+            .collect()
+    }
+
+    /// Return the statistics of every action at the root.
+    ///
+    /// The statistics are merged over the ensemble and the result is
+    /// sorted by mean reward, best action first. Actions the solver proved
+    /// won in every tree that explored them come before all others, those
+    /// proved lost in every tree after all others.
+    pub fn action_statistics(&self) -> Vec<ActionStats<A>> {
+        merge_action_statistics(&self.roots())
+    }
+
+    /// Return the statistics of every tree of the ensemble on its own, in
+    /// the order of `roots`.
+    ///
+    /// The best action of a tree is the one it would play on its own, by
+    /// the configured `SelectionPolicy`.
+    pub fn per_root_statistics(&self) -> Vec<RootStatistics<A>> {
+        self.roots().iter()
+            .map(|root| {
+                let best = choose_action(&merge_action_statistics(slice::from_ref(root)), self.config.selection);
+                RootStatistics {
+                    tree: root.tree_statistics(),
+                    visits: root.visits(),
+                    best_action: best.map(|stats| stats.action),
+                    value: best.map(|stats| stats.mean),
+                }
+            })
+            .collect()
+    }
+
+    /// Return how much the trees of the ensemble agree on the action to
+    /// play, judging by their best actions in `per_root_statistics`.
+    ///
+    /// Trees that explored no action yet do not vote.
+    pub fn agreement(&self) -> Agreement<A> {
+        let mut votes: Vec<(A, usize)> = Vec::new();
+        for action in self.per_root_statistics().iter().filter_map(|stats| stats.best_action) {
+            match votes.iter_mut().find(|&&mut (a, _)| a == action) {
+                Some(entry) => entry.1 += 1,
+                None => votes.push((action, 1))
+            }
+        }
+        // Most votes first; the first tree to vote wins ties
+        votes.sort_by_key(|&(_, n)| Reverse(n));
+
+        let total = votes.iter().map(|&(_, n)| n).sum::<usize>() as f32;
+        let entropy = votes.iter()
+            .map(|&(_, n)| n as f32 / total)
+            .map(|p| -p * p.log2())
+            .sum::<f32>();
+        Agreement {
+            agreement: votes.first().map_or(0., |&(_, n)| n as f32 / total),
+            entropy: entropy.max(0.),
+            votes: votes,
+        }
+    }
+
+    /// Return the most promising line of play found so far, at most
+    /// `depth` moves long.
+    ///
+    /// Starts with `best_action` and continues with the action visited
+    /// most often, summing the visits over all ensemble members that
+    /// explored the line so far.
+    pub fn principal_variation(&self, depth: usize) -> Vec<A> {
+        let action = match self.best_action() {
+            Some(action) if depth > 0 => action,
+            _ => return Vec::new()
+        };
+
+        let mut pv = vec![action];
+        let mut nodes = self.roots().iter()
+            .filter_map(|root| root.child(&action))
+            .collect::<Vec<_>>();
+        while pv.len() < depth {
+            let mut visits: Vec<(A, u64)> = Vec::new();
+            for child in nodes.iter().flat_map(|node| node.children()) {
+                let action = child.action().unwrap();
+                match visits.iter_mut().find(|&&mut (a, _)| a == action) {
+                    Some(entry) => entry.1 += child.visits(),
+                    None => visits.push((action, child.visits()))
+                }
+            }
+            let action = match visits.iter().max_by_key(|&&(_, n)| n) {
+                Some(&(action, _)) => action,
+                None => break
+            };
+            pv.push(action);
+            nodes = nodes.iter()
+                .filter_map(|node| node.child(&action))
+                .collect();
+        }
+        pv
+    }
+
+    /// Find the action to play according to `policy`.
+    fn select_action(&self, policy: SelectionPolicy) -> Option<ActionStats<A>> {
+        choose_action(&self.action_statistics(), policy)
+    }
+}
+
+/// Merge the statistics of the root actions of `roots`, see
+/// `MCTS::action_statistics`.
+fn merge_action_statistics<A: GameAction>(roots: &[TreeNode<A>]) -> Vec<ActionStats<A>> {
+    // Merge ensemble results; keep actions in the order we first see them
+    let mut index = HashMap::<A, usize>::new();
+    let mut values: Vec<(A, RunningStats, i32)> = Vec::new();
+
+    for root in roots {
+        for child in root.children() {
+            let action = child.action().unwrap();
+            let proof = if child.is_proven_win() { 1 } else if child.is_proven_loss() { -1 } else { 0 };
+
+            let idx = *index.entry(action).or_insert(values.len());
+            if idx == values.len() {
+                values.push((action, RunningStats::new(), proof));
+            }
+            values[idx].1.merge(child.statistics());
+            if values[idx].2 != proof {
+                values[idx].2 = 0;
+            }
+        }
+    }
+
+    values.sort_by(|a, b| b.2.cmp(&a.2)
+        .then(b.1.mean().partial_cmp(&a.1.mean()).unwrap_or(Ordering::Equal)));
+    values.iter()
+        .map(|&(action, ref stats, proof)| ActionStats {
+            action: action,
+            visits: stats.count(),
+            mean: stats.mean(),
+            stderr: stats.stderr(),
+            proven: match proof { 1 => Some(true), -1 => Some(false), _ => None },
+        })
+        .collect()
+}
+
+/// Find the action to play among the ranked `stats` according to `policy`.
+fn choose_action<A: GameAction>(stats: &[ActionStats<A>], policy: SelectionPolicy) -> Option<ActionStats<A>> {
+    let mut candidates = stats.iter()
+        .filter(|stats| stats.proven != Some(false))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() || candidates[0].proven == Some(true) {
+        return stats.first().cloned();
+    }
+
+    // Sorted by mean reward; the first of equally good actions wins
+    let robust = candidates.iter()
+        .fold(candidates[0], |best, &stats| if stats.visits > best.visits { stats } else { best });
+    let chosen = match policy {
+        SelectionPolicy::MaxChild => candidates[0],
+        SelectionPolicy::RobustChild => robust,
+        SelectionPolicy::MaxRobustChild => {
+            if candidates[0].visits == robust.visits { candidates[0] } else { robust }
+        },
+        SelectionPolicy::SecureChild(a) => {
+            let bound = |stats: &ActionStats<A>| stats.mean - a / (stats.visits as f32).sqrt();
+            candidates.retain(|stats| stats.visits > 0);
+            match candidates.first() {
+                Some(&first) => candidates.iter()
+                    .fold(first, |best, &stats| if bound(stats) > bound(best) { stats } else { best }),
+                None => return None
+            }
+        }
+    };
+    Some(*chosen)
+}
+
+
+/// Checkpoints of a search, for resuming it in another process (with the
+/// `serde` feature).
+///
+/// Actions are stored by their `ActionCodec` ids and games by their
+/// `SerializableGame` JSON. The trees are flat lists of nodes in depth
+/// first order, each with its depth, so that deep trees neither recurse
+/// nor hit the nesting limit of the JSON parser.
+#[cfg(feature = "serde")]
+impl<G: SerializableGame<A> + ActionCodec<A>, A: GameAction> MCTS<G, A> {
+    /// The complete search as JSON: the configuration, and for every
+    /// ensemble member its game, its tree and its transposition table.
+    pub fn to_json(&self) -> Value {
+        let members = (0..self.trees.len()).map(|e| {
+            let game = &self.games[e];
+            let nodes = self.trees[e].root().iter().map(|(depth, node)| {
+                let node = node.data();
+                json!({
+                    "depth": depth,
+                    "action": node.action.map(|action| game.encode(&action)),
+                    "player": node.player,
+                    "state": format!("{:?}", node.state),
+                    "stats": node.stats.to_json(),
+                    "amaf": node.amaf.to_json(),
+                    "key": node.key,
+                    "prior": node.prior,
+                })
+            }).collect::<Vec<_>>();
+            let table = self.tables[e].as_ref().map(|table| {
+                table.iter()
+                    .map(|(&(key, player), stats)| json!([key, player, stats.to_json()]))
+                    .collect::<Vec<_>>()
+            });
+            json!({ "game": game.to_json(), "nodes": nodes, "transpositions": table })
+        }).collect::<Vec<_>>();
+        json!({
+            "config": config_to_json(&self.config),
+            "iterations_per_s": self.iterations_per_s,
+            "members": members,
+        })
+    }
+
+    /// Restore a search saved with `to_json`.
+    ///
+    /// Like a new ensemble, member `i` reseeds its game with `i`. The
+    /// rollout policy and the generator of `set_seed` are not saved; set
+    /// them again if there were any.
+    pub fn from_json(value: &Value) -> Result<MCTS<G, A>, String> {
+        let config = try!(config_from_json(&value["config"]));
+        let members = try!(value["members"].as_array().ok_or("expected a list of members"));
+        let mut trees = Vec::new();
+        let mut games = Vec::new();
+        let mut tables = Vec::new();
+        for (i, member) in members.iter().enumerate() {
+            let mut game = try!(G::from_json(&member["game"]));
+            let nodes = try!(member["nodes"].as_array().ok_or("expected a list of nodes"));
+            trees.push(try!(tree_from_json(nodes, &game)));
+            game.set_rng_seed(i as u32);
+            games.push(game);
+
+            tables.push(match member["transpositions"].as_array() {
+                Some(entries) => {
+                    let mut table = Transpositions::new();
+                    for entry in entries {
+                        let key = try!(entry[0].as_u64().ok_or("expected a transposition key"));
+                        let player = try!(entry[1].as_u64().ok_or("expected a transposition player"));
+                        table.insert((key, player as usize), try!(RunningStats::from_json(&entry[2])));
+                    }
+                    Some(table)
+                },
+                None => None
+            });
+        }
+        Ok(MCTS {
+            trees: trees,
+            games: games,
+            tables: tables,
+            config: config,
+            rollout: None,
+            rng: None,
+            iterations_per_s: value["iterations_per_s"].as_f64().unwrap_or(1.) as f32,
+        })
+    }
+
+    /// Write the search to a checkpoint file.
+    ///
+    /// The file is written next to its destination first and then moved
+    /// there, so an interrupted save never clobbers an older checkpoint.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let tmp = format!("{}.tmp", path);
+        File::create(&tmp)
+            .and_then(|mut file| writeln!(file, "{}", self.to_json()))
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| format!("Failed to save {}: {}", path, e))
+    }
+
+    /// Resume a search from a checkpoint file.
+    pub fn load(path: &str) -> Result<MCTS<G, A>, String> {
+        let mut text = String::new();
+        try!(File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e)));
+        let value = try!(text.parse::<Value>().map_err(|e| format!("{}: invalid JSON: {}", path, e)));
+        MCTS::from_json(&value).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Rebuild a tree from its flat list of nodes, see `MCTS::to_json`.
+#[cfg(feature = "serde")]
+fn tree_from_json<G: ActionCodec<A>, A: GameAction>(nodes: &[Value], game: &G) -> Result<Tree<A>, String> {
+    // The nodes in the order of the list, with the positions of their children
+    let mut parsed: Vec<(Node<A>, Vec<usize>)> = Vec::new();
+    // Nodes whose children are still to come, one per depth
+    let mut open: Vec<usize> = Vec::new();
+    for (i, value) in nodes.iter().enumerate() {
+        let invalid = |what: &str| format!("node {}: expected {}", i, what);
+        let depth = try!(value["depth"].as_u64().ok_or_else(|| invalid("a depth"))) as usize;
+        if depth > open.len() || (depth == 0) != (i == 0) {
+            return Err(invalid("a child of the node before or one of its ancestors"));
+        }
+        open.truncate(depth);
+        if let Some(&parent) = open.last() {
+            parsed[parent].1.push(i);
+        }
+
+        let action = match value["action"].as_i64() {
+            Some(id) => Some(try!(game.decode(id).ok_or_else(|| invalid("a valid action id")))),
+            None => None
+        };
+        let mut node = Node::new(action);
+        node.player = try!(value["player"].as_u64().ok_or_else(|| invalid("a player"))) as usize;
+        node.state = match value["state"].as_str() {
+            Some("LeafNode") => NodeState::LeafNode,
+            Some("FullyExpanded") => NodeState::FullyExpanded,
+            Some("Expandable") => NodeState::Expandable,
+            Some("ProvenWin") => NodeState::ProvenWin,
+            Some("ProvenLoss") => NodeState::ProvenLoss,
+            _ => return Err(invalid("a node state"))
+        };
+        node.stats = try!(RunningStats::from_json(&value["stats"]));
+        node.amaf = try!(RunningStats::from_json(&value["amaf"]));
+        node.key = value["key"].as_u64();
+        node.prior = value["prior"].as_f64().unwrap_or(1.) as f32;
+        parsed.push((node, Vec::new()));
+        open.push(i);
+    }
+    if parsed.is_empty() {
+        return Err("expected a root node".to_string());
+    }
+
+    // Give the children of every node a block of their own, level by level
+    let mut tree = Tree { nodes: vec![parsed[0].0] };
+    let mut order = vec![0];
+    let mut next = 0;
+    while next < order.len() {
+        let children = &parsed[order[next]].1;
+        let first = tree.nodes.len();
+        let node = &mut tree.nodes[next];
+        node.first = first as u32;
+        node.len = children.len() as u32;
+        node.room = children.len() as u32;
+        for &child in children {
+            tree.nodes.push(parsed[child].0);
+            order.push(child);
+        }
+        next += 1;
+    }
+    Ok(tree)
+}
+
+#[cfg(feature = "serde")]
+fn config_to_json(config: &MctsConfig) -> Value {
+    let selection = match config.selection {
+        SelectionPolicy::MaxChild => json!("MaxChild"),
+        SelectionPolicy::RobustChild => json!("RobustChild"),
+        SelectionPolicy::MaxRobustChild => json!("MaxRobustChild"),
+        SelectionPolicy::SecureChild(a) => json!({ "SecureChild": a }),
+    };
+    let tree_policy = match config.tree_policy {
+        TreePolicy::Ucb1 => json!("Ucb1"),
+        TreePolicy::Ucb1Tuned => json!("Ucb1Tuned"),
+        TreePolicy::Greedy => json!("Greedy"),
+        TreePolicy::EpsilonGreedy(epsilon) => json!({ "EpsilonGreedy": epsilon }),
+        TreePolicy::Puct => json!("Puct"),
+        TreePolicy::Thompson => json!("Thompson"),
+    };
+    json!({
+        "exploration": config.exploration,
+        "tree_policy": tree_policy,
+        "max_playout_depth": config.max_playout_depth,
+        "expansion_threshold": config.expansion_threshold,
+        "playouts": config.playouts,
+        "transpositions": config.transpositions,
+        "rave": config.rave,
+        "solver": config.solver,
+        "selection": selection,
+        "early_stop": config.early_stop,
+        "stop_visit_share": config.stop_visit_share,
+        "fpu": config.fpu,
+        "discount": config.discount,
+        "root_noise": config.root_noise.map(|(alpha, fraction)| [alpha, fraction]),
+        "afterstates": config.afterstates.map(|afterstates| format!("{:?}", afterstates)),
+        "contempt": config.contempt,
+        "virtual_loss": config.virtual_loss,
+    })
+}
+
+/// Parameters saved with `config_to_json`; missing ones keep their defaults.
+#[cfg(feature = "serde")]
+fn config_from_json(value: &Value) -> Result<MctsConfig, String> {
+    if !value.is_object() {
+        return Err("expected a configuration".to_string());
+    }
+    let default = MctsConfig::default();
+    let number = |key: &str| value[key].as_f64().map(|x| x as f32);
+    let count = |key: &str| value[key].as_u64().map(|x| x as usize);
+    let flag = |key: &str, default: bool| value[key].as_bool().unwrap_or(default);
+    let selection = match (value["selection"].as_str(), value["selection"]["SecureChild"].as_f64()) {
+        (Some("MaxChild"), _) => SelectionPolicy::MaxChild,
+        (Some("RobustChild"), _) => SelectionPolicy::RobustChild,
+        (Some("MaxRobustChild"), _) => SelectionPolicy::MaxRobustChild,
+        (None, Some(a)) => SelectionPolicy::SecureChild(a as f32),
+        _ if value["selection"].is_null() => default.selection,
+        _ => return Err(format!("unknown selection policy {}", value["selection"]))
+    };
+    let tree_policy = match (value["tree_policy"].as_str(), value["tree_policy"]["EpsilonGreedy"].as_f64()) {
+        (Some("Ucb1"), _) => TreePolicy::Ucb1,
+        (Some("Ucb1Tuned"), _) => TreePolicy::Ucb1Tuned,
+        (Some("Greedy"), _) => TreePolicy::Greedy,
+        (Some("Puct"), _) => TreePolicy::Puct,
+        (Some("Thompson"), _) => TreePolicy::Thompson,
+        (None, Some(epsilon)) => TreePolicy::EpsilonGreedy(epsilon as f32),
+        _ if value["tree_policy"].is_null() => default.tree_policy,
+        _ => return Err(format!("unknown tree policy {}", value["tree_policy"]))
+    };
+    let root_noise = match (value["root_noise"][0].as_f64(), value["root_noise"][1].as_f64()) {
+        (Some(alpha), Some(fraction)) => Some((alpha as f32, fraction as f32)),
+        _ if value["root_noise"].is_null() => None,
+        _ => return Err(format!("invalid root noise {}", value["root_noise"]))
+    };
+    let afterstates = match value["afterstates"].as_str() {
+        Some("Sampled") => Some(Afterstates::Sampled),
+        Some("Integrated") => Some(Afterstates::Integrated),
+        _ if value["afterstates"].is_null() => None,
+        _ => return Err(format!("unknown afterstates {}", value["afterstates"]))
+    };
+    Ok(MctsConfig {
+        exploration: number("exploration").unwrap_or(default.exploration),
+        tree_policy: tree_policy,
+        max_playout_depth: count("max_playout_depth"),
+        expansion_threshold: value["expansion_threshold"].as_u64().unwrap_or(default.expansion_threshold),
+        playouts: count("playouts").unwrap_or(default.playouts),
+        transpositions: flag("transpositions", default.transpositions),
+        rave: number("rave"),
+        solver: flag("solver", default.solver),
+        selection: selection,
+        early_stop: flag("early_stop", default.early_stop),
+        stop_visit_share: number("stop_visit_share"),
+        fpu: number("fpu"),
+        discount: number("discount"),
+        root_noise: root_noise,
+        afterstates: afterstates,
+        contempt: number("contempt").unwrap_or(default.contempt),
+        virtual_loss: number("virtual_loss").unwrap_or(default.virtual_loss),
+    })
+}
+
+impl<G: Game<A>, A: GameAction> fmt::Display for MCTS<G, A> {
+
+    /// Output a nicely indented tree
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "Ensemble of {} trees:", self.trees.len()));
+        //for root in self.roots() {
+        //    try!(root.fmt(f));
+        //}
+        write!(f, "")
+    }
+}
+
+
+/////////////////////////////////////////////////////////////////////////////
+// Unittests
+
+#[cfg(test)]
+mod tests {
+    use std::mem;
+    use time;
+    //use std::num::traits::*;
+    use test::Bencher;
+
+    use mcts::*;
+    use minigame::{MiniGame, Action as MiniAction};
+    use twofortyeight::TwoFortyEight;
+    use utils::{seed_thread_rng, seeded_rng, seeded_std_rng};
+
+    /*
+    // Are the given
+    fn allmost_equal<T: Float>(a: T, b: T) -> bool {
+        let rtol = 1e-6;
+
+        // Shortcut for inf and neg_inf
+        if (a == b) { return true };
+
+        let a_abs = a.abs();
+        let b_abd = b.abs();
+        let diff = (a-b).abs();
+
+        diff <= tol * a_abs.max(b_abs)
+    }
+    */
+
+    #[test]
+    fn test_playout() {
+        let game = MiniGame::new();
+        let game = playout(&game);
+        println!("Final: {:?}", game);
+    }
+
+    #[test]
+    fn test_expected_reward_stats() {
+        let game = MiniGame::new();
+        let estimate = expected_reward_stats_with(&game, 200, &mut seeded_rng(1));
+        assert_eq!(estimate.samples, 200);
+        assert!(estimate.min <= estimate.mean && estimate.mean <= estimate.max);
+        assert!(estimate.stderr > 0.);
+        let (lower, upper) = estimate.interval(1.96);
+        assert!((estimate.mean - lower - 1.96 * estimate.stderr).abs() < 1e-4);
+        assert!((upper - estimate.mean - 1.96 * estimate.stderr).abs() < 1e-4);
+        assert!(format!("{}", estimate).ends_with(&format!("from 200 samples in [{}, {}]", estimate.min, estimate.max)));
+
+        // Differences add up the variances of both estimates
+        let other = RewardEstimate { mean: estimate.mean + 1., ..estimate };
+        let (difference, lower, upper) = other.difference(&estimate, 2.);
+        assert!((difference - 1.).abs() < 1e-4);
+        assert!((upper - difference - 2. * 2f32.sqrt() * estimate.stderr).abs() < 1e-4);
+        assert!(lower < difference);
+
+        let empty = expected_reward_stats(&game, 0);
+        assert_eq!(empty.samples, 0);
+        assert!(empty.mean.is_nan() && empty.min.is_nan() && empty.max.is_nan());
+    }
+
+    #[test]
+    fn test_expand() {
+        let game = MiniGame::new();
+        let mut tree = Tree::new();
+
+        tree.expand(0, &game);
+        tree.expand(0, &game);
+        let v = tree.expand(0, &game).unwrap();
+        tree.expand(v, &game);
+
+        println!("After some expands:\n{}", tree.root());
+    }
+
+    #[test]
+    fn test_full_block() {
+        let mut tree = Tree::new();
+        let mut rng = seeded_rng(2);
+
+        // The first child reserves room for all seven actions of the root
+        for _ in 0..7 {
+            tree.expand_with(0, &Picks(vec![1]), &mut rng);
+        }
+        let child = tree.root().children()[0].id();
+        let first = tree.node(child).action().unwrap();
+        let grandchild = tree.expand_with(child, &Picks(vec![1, first.0]), &mut rng).unwrap();
+        let second = tree.node(grandchild).action().unwrap();
+
+        // An eighth action does not fit; the siblings move with their subtrees
+        assert_eq!(tree.expand_with(0, &Picks(vec![]), &mut rng).map(|id| tree.node(id).action()), Some(Some(Pick(1))));
+        let root = tree.root();
+        assert_eq!(root.children().len(), 8);
+        assert!(root.child(&first).unwrap().id() != child);
+        assert_eq!(root.get(&[first, second]).map(|node| node.action()), Some(Some(second)));
+        assert_eq!(root.tree_statistics().nodes(), 10);
+    }
+
+    #[test]
+    fn test_first_visits() {
+        let game = MiniGame::new();
+        let mut tree = Tree::new();
+        let n_actions = game.allowed_actions().len();
+
+        // Unvisited children below an unvisited parent
+        for _ in 0..n_actions {
+            tree.expand(0, &game);
+        }
+        let node = tree.root();
+        assert!(!node.is_expandable());
+        for child in node.children() {
+            assert_eq!(child.uct_terms(node.visits(), 1.), (0., f32::INFINITY));
+            assert_eq!(child.uct_terms(node.visits(), 0.), (0., f32::INFINITY));
+        }
+        let first = node.children()[0].action();
+        assert_eq!(node.best_child(1.).unwrap().action(), first);
+
+        // Visits go to the unvisited children in turn
+        for i in 0..n_actions {
+            let mut game = game.clone();
+            tree.iteration(&mut game, 1.);
+            assert_eq!(tree.root().children()[i].visits(), 1);
+            assert!(tree.root().children()[i].mean().is_finite());
+        }
+        let node = tree.root();
+        for child in node.children() {
+            let (exploitation, exploration) = child.uct_terms(node.visits(), 1.);
+            assert!(exploitation.is_finite() && exploration.is_finite());
+        }
+
+        // A visited child below an unvisited parent gets no bonus
+        let mut parent = Tree::new();
+        let child = parent.expand(0, &game).unwrap();
+        parent.update_shared(child, 1., None);
+        assert_eq!(parent.root().children()[0].uct_terms(0, 1.), (1., 0.));
+    }
+
+    #[test]
+    #[should_panic(expected = "not finite")]
+    fn test_nan_reward() {
+        let mut tree = Tree::<::minigame::Action>::new();
+        tree.update_shared(0, f32::NAN, None);
+    }
+
+    /// A single line of `length` moves.
+    #[derive(Clone)]
+    struct Line {
+        moves: usize,
+        length: usize,
+    }
+
+    impl Game<::minigame::Action> for Line {
+        fn allowed_actions(&self) -> Vec<::minigame::Action> {
+            if self.moves < self.length { vec![MiniGame::new().allowed_actions()[0]] } else { vec![] }
+        }
+        fn make_move(&mut self, _: &::minigame::Action) { self.moves += 1 }
+        fn reward(&self) -> f32 { self.moves as f32 }
+        fn running_reward(&self) -> bool { true }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    /// A `Line` whose cut off playouts are valued by the moves to go.
+    #[derive(Clone)]
+    struct Horizon(Line);
+
+    impl Game<::minigame::Action> for Horizon {
+        fn allowed_actions(&self) -> Vec<::minigame::Action> { self.0.allowed_actions() }
+        fn make_move(&mut self, action: &::minigame::Action) { self.0.make_move(action) }
+        fn reward(&self) -> f32 { self.0.reward() }
+        fn running_reward(&self) -> bool { true }
+        fn evaluate(&self) -> f32 { -((self.0.length - self.0.moves) as f32) }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let game = Horizon(Line { moves: 0, length: 10 });
+        let mut rng = seeded_rng(3);
+
+        // The playout stops three moves in and is evaluated
+        let config = MctsConfig { max_playout_depth: Some(2), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), -7.);
+        assert_eq!(tree.root().children()[0].mean(), -7.);
+
+        // Playouts reaching the end of the game get the reward
+        let config = MctsConfig { max_playout_depth: Some(20), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), 10.);
+    }
+
+    #[test]
+    fn test_evaluator() {
+        let game = Line { moves: 0, length: 10 };
+        let evaluator = |line: &Line| 100. - line.moves as f32;
+        let config = MctsConfig::default();
+        let mut rng = seeded_rng(3);
+
+        // The new leaf is valued without playing on
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_evaluated(&mut game.clone(), &config, &evaluator, &mut rng, None), 99.);
+        assert_eq!(tree.iteration_evaluated(&mut game.clone(), &config, &evaluator, &mut rng, None), 98.);
+        assert_eq!(tree.root().children()[0].visits(), 2);
+        assert_eq!(tree.root().children()[0].mean(), 98.5);
+
+        // Leaves where the game is over get their reward
+        let mut tree = Tree::new();
+        let mut short = Line { moves: 0, length: 1 };
+        assert_eq!(tree.iteration_evaluated(&mut short, &config, &evaluator, &mut rng, None), 1.);
+
+        // Beyond the reward of the move, the value counts half
+        let config = MctsConfig { discount: Some(0.5), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_evaluated(&mut game.clone(), &config, &evaluator, &mut rng, None), 50.);
+
+        // Every iteration grows the tree down to the end of the game
+        let mut mcts = MCTS::new(&game, 2);
+        mcts.run_evaluated_with(20, &evaluator, &mut rng);
+        assert!(mcts.roots().iter().all(|root| root.visits() == 20 && root.iter().count() == 11));
+    }
+
+    #[test]
+    fn test_evaluator_playouts() {
+        // Evaluators may run playouts on the generator of the thread
+        let mut mcts = MCTS::new(&MiniGame::new(), 1);
+        mcts.run_evaluated(10, &|game: &MiniGame| expected_reward(game, 2));
+        assert_eq!(mcts.roots()[0].visits(), 10);
+    }
+
+    /// Three moves of three branches each, remembering the branches taken.
+    #[derive(Clone)]
+    struct Branches(Vec<usize>);
+
+    impl Game<::minigame::Action> for Branches {
+        fn allowed_actions(&self) -> Vec<::minigame::Action> {
+            if self.0.len() < 3 { MiniGame::new().allowed_actions() } else { vec![] }
+        }
+        fn make_move(&mut self, action: &::minigame::Action) {
+            let branch = MiniGame::new().allowed_actions().iter().position(|a| a == action).unwrap();
+            self.0.push(branch);
+        }
+        fn reward(&self) -> f32 { 0. }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    /// Values `Branches` by the first branch and records the batch sizes.
+    struct Batches(::std::cell::RefCell<Vec<usize>>);
+
+    impl Evaluator<Branches> for Batches {
+        fn evaluate(&self, game: &Branches) -> f32 {
+            game.0[0] as f32 / 10.
+        }
+        fn evaluate_batch(&self, games: &[Branches]) -> Vec<f32> {
+            self.0.borrow_mut().push(games.len());
+            games.iter().map(|game| self.evaluate(game)).collect()
+        }
+    }
+
+    #[test]
+    fn test_iteration_batch() {
+        let game = Branches(Vec::new());
+        let evaluator = Batches(::std::cell::RefCell::new(Vec::new()));
+        let config = MctsConfig { tree_policy: TreePolicy::Greedy, virtual_loss: -5., ..MctsConfig::default() };
+        let mut rng = seeded_rng(3);
+
+        // The first batch expands the branches, which the evaluator values
+        let mut tree = Tree::new();
+        let mut values = tree.iteration_batch(&game, &config, &evaluator, 3, &mut rng, None);
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(values, vec![0., 0.1, 0.2]);
+        assert_eq!(*evaluator.0.borrow(), vec![3]);
+
+        // The greedy policy would take the best branch every time; the
+        // virtual losses spread the batch over all of them
+        tree.iteration_batch(&game, &config, &evaluator, 6, &mut rng, None);
+        assert_eq!(tree.root().visits(), 9);
+        assert!(tree.root().children().iter().all(|child| child.visits() >= 2));
+        assert!(tree.root().iter().all(|(_, node)| node.statistics().min().unwrap() >= 0.));
+
+        // Without a loss they all go the same way
+        let config = MctsConfig { virtual_loss: 1., ..config };
+        let mut tree = Tree::new();
+        tree.iteration_batch(&game, &config, &evaluator, 3, &mut rng, None);
+        tree.iteration_batch(&game, &config, &evaluator, 6, &mut rng, None);
+        assert_eq!(tree.root().children().iter().map(|child| child.visits()).max(), Some(7));
+
+        // A search takes three batches per tree; the leaves where the game
+        // is over need no evaluation
+        let mut mcts = MCTS::with_config(&game, 2, MctsConfig { virtual_loss: -5., ..MctsConfig::default() });
+        evaluator.0.borrow_mut().clear();
+        mcts.run_batched_with(20, 8, &evaluator, &mut rng);
+        let batches = evaluator.0.borrow();
+        assert_eq!(batches.len(), 6);
+        assert!(batches[0] == 8 && batches.iter().all(|&size| size <= 8));
+        assert!(mcts.roots().iter().all(|root| root.visits() == 20));
+    }
+
+    #[test]
+    fn test_batches_of_one() {
+        // One at a time, the batched search is the evaluated one
+        let game = MiniGame::new();
+        let evaluator = |_: &MiniGame| 0.25;
+        let mut evaluated = MCTS::new(&game, 1);
+        evaluated.run_evaluated_with(100, &evaluator, &mut seeded_rng(5));
+        let mut batched = MCTS::new(&game, 1);
+        batched.run_batched_with(100, 1, &evaluator, &mut seeded_rng(5));
+        assert_eq!(evaluated.action_values(), batched.action_values());
+        assert_eq!(evaluated.tree_statistics(), batched.tree_statistics());
+    }
+
+    /// Values states with `value` in the background, one at a time in the
+    /// order they came in, and remembers the most states out at once.
+    struct Delayed<G> {
+        value: fn(&G) -> f32,
+        out: Vec<(usize, G)>,
+        most: usize,
+    }
+
+    impl<G> PipelinedEvaluator<G> for Delayed<G> {
+        fn submit(&mut self, ticket: usize, game: G) {
+            self.out.push((ticket, game));
+            self.most = self.most.max(self.out.len());
+        }
+        fn ready(&mut self) -> Vec<(usize, f32)> {
+            Vec::new()
+        }
+        fn wait(&mut self) -> Vec<(usize, f32)> {
+            let (ticket, game) = self.out.remove(0);
+            vec![(ticket, (self.value)(&game))]
+        }
+    }
+
+    #[test]
+    fn test_run_pipelined() {
+        // Leaves stay out until the pipeline is full, and their virtual
+        // losses are gone once they all came back
+        let game = Branches(Vec::new());
+        let mut evaluator = Delayed { value: |game: &Branches| game.0[0] as f32 / 10., out: Vec::new(), most: 0 };
+        let config = MctsConfig { tree_policy: TreePolicy::Greedy, virtual_loss: -5., ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 2, config);
+        mcts.run_pipelined_with(20, 4, &mut evaluator, &mut seeded_rng(3));
+        assert_eq!(evaluator.most, 4);
+        assert!(evaluator.out.is_empty());
+        for root in mcts.roots() {
+            assert_eq!(root.visits(), 20);
+            assert!(root.children().iter().all(|child| child.visits() >= 2));
+            assert!(root.iter().all(|(_, node)| node.statistics().min().unwrap() >= 0.));
+        }
+
+        // One at a time, the pipelined search is the evaluated one
+        let game = MiniGame::new();
+        let mut evaluated = MCTS::new(&game, 1);
+        evaluated.run_evaluated_with(100, &|_: &MiniGame| 0.25, &mut seeded_rng(5));
+        let mut pipelined = MCTS::new(&game, 1);
+        let mut evaluator = Delayed { value: |_: &MiniGame| 0.25, out: Vec::new(), most: 0 };
+        pipelined.run_pipelined_with(100, 1, &mut evaluator, &mut seeded_rng(5));
+        assert_eq!(evaluated.action_values(), pipelined.action_values());
+        assert_eq!(evaluated.tree_statistics(), pipelined.tree_statistics());
+    }
+
+    /// A `Line` whose reward only counts at the end.
+    #[derive(Clone)]
+    struct Final(Line);
+
+    impl Game<::minigame::Action> for Final {
+        fn allowed_actions(&self) -> Vec<::minigame::Action> { self.0.allowed_actions() }
+        fn make_move(&mut self, action: &::minigame::Action) { self.0.make_move(action) }
+        fn reward(&self) -> f32 { self.0.reward() }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_discount() {
+        let game = Line { moves: 0, length: 3 };
+        let mut rng = seeded_rng(4);
+
+        // Every move collects 1, the second one below the root counts half
+        let config = MctsConfig { discount: Some(0.5), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), 1.75);
+        assert_eq!(tree.root().children()[0].mean(), 2.5);
+        for _ in 0..3 {
+            tree.iteration_config(&mut game.clone(), &config, &mut rng, None);
+        }
+        assert_eq!(tree.root().mean(), 1.75);
+
+        // A cut off playout ends with the evaluation
+        let config = MctsConfig { discount: Some(0.5), max_playout_depth: Some(1), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        let game = Horizon(Line { moves: 0, length: 10 });
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), -3.5);
+        assert_eq!(tree.root().children()[0].mean(), -8.);
+
+        // Without a running reward only the end counts, discounted by the moves to it
+        let config = MctsConfig { discount: Some(0.5), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        let game = Final(Line { moves: 0, length: 3 });
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), 0.75);
+        assert_eq!(tree.root().children()[0].mean(), 1.5);
+    }
+
+    #[test]
+    fn test_deep_tree() {
+        // Every iteration adds a node at the bottom of the line; a recursive
+        // iteration or drop would need far more stack than this thread has
+        let deep = ::std::thread::Builder::new().stack_size(64 * 1024).spawn(|| {
+            let game = Line { moves: 0, length: 3000 };
+            let mut tree = Tree::new();
+            let mut rng = seeded_rng(1);
+            for _ in 0..3001 {
+                let delta = tree.iteration_with(&mut game.clone(), 1., &mut rng);
+                assert_eq!(delta, 3000.);
+            }
+            let mut node = tree.root();
+            let mut depth = 0;
+            while let Some(&child) = node.children().first() {
+                assert_eq!(child.visits(), 3001 - depth);
+                node = child;
+                depth += 1;
+            }
+            assert_eq!(depth, 3000);
+            assert_eq!(tree.root().visits(), 3001);
+            assert_eq!(tree.root().iter().map(|(depth, _)| depth).max(), Some(3000));
+        }).unwrap();
+        deep.join().unwrap();
+    }
+
+    #[test]
+    fn test_config() {
+        let game = Line { moves: 0, length: 10 };
+        let mut rng = seeded_rng(2);
+
+        // Playouts stop after the given number of moves
+        let config = MctsConfig { max_playout_depth: Some(2), playouts: 3, ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), 3.);
+        assert_eq!(tree.root().visits(), 3);
+        assert_eq!(tree.root().children()[0].visits(), 3);
+
+        // Nodes grow children only after enough visits
+        let config = MctsConfig { expansion_threshold: 2, ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        for _ in 0..2 {
+            assert_eq!(tree.iteration_config(&mut game.clone(), &config, &mut rng, None), 10.);
+            assert!(tree.root().children().is_empty());
+        }
+        tree.iteration_config(&mut game.clone(), &config, &mut rng, None);
+        assert_eq!(tree.root().children().len(), 1);
+        tree.iteration_config(&mut game.clone(), &config, &mut rng, None);
+        assert!(tree.root().children()[0].children().is_empty());
+
+        // The solver runs with its configuration; search only changes c
+        let config = MctsConfig { playouts: 2, ..MctsConfig::with_exploration(2.) };
+        let mut mcts = MCTS::with_config(&Line { moves: 0, length: 100 }, 2, config);
+        assert_eq!(*mcts.config(), config);
+        mcts.run_with(10, &mut rng);
+        assert!(mcts.roots().iter().all(|root| root.visits() == 20));
+        mcts.search_with(10, 1., &mut rng);
+        assert!(mcts.roots().iter().all(|root| root.visits() == 40));
+        assert_eq!(*mcts.config(), config);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_leaf_parallel() {
+        let config = MctsConfig { playouts: 8, ..MctsConfig::default() };
+        let search = |threads: usize| {
+            let pool = ::rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let mut mcts = MCTS::with_config(&TwoFortyEight::new(), 2, config);
+            pool.install(|| mcts.run_leaf_parallel_with(20, &mut seeded_rng(7)));
+            mcts
+        };
+
+        // Every playout counts as a visit; threads do not change the result
+        let mcts = search(1);
+        assert!(mcts.roots().iter().all(|root| root.visits() == 160));
+        assert_eq!(mcts.action_values(), search(4).action_values());
+    }
+
+    /// Three players move once each: the first action takes 1 for the
+    /// player, the second shares 2 with everybody.
+    #[derive(Clone)]
+    struct Share {
+        scores: [f32; 3],
+        moves: usize,
+    }
+
+    impl Game<::minigame::Action> for Share {
+        fn allowed_actions(&self) -> Vec<::minigame::Action> {
+            if self.moves < 3 { MiniGame::new().allowed_actions()[..2].to_vec() } else { vec![] }
+        }
+        fn make_move(&mut self, action: &::minigame::Action) {
+            if *action == MiniGame::new().allowed_actions()[0] {
+                self.scores[self.moves] += 1.;
+            } else {
+                for score in self.scores.iter_mut() { *score += 2. }
+            }
+            self.moves += 1;
+        }
+        fn reward(&self) -> f32 { self.scores[0] }
+        fn reward_for(&self, player: usize) -> f32 { self.scores[player] }
+        fn current_player(&self) -> usize { self.moves % 3 }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_multi_player() {
+        let actions = MiniGame::new().allowed_actions();
+        let mut game = Share { scores: [0.; 3], moves: 0 };
+        game.make_move(&actions[0]);
+        assert_eq!(game.current_player(), 1);
+
+        // Sharing helps player 0 as well, which a zero-sum search would avoid
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search_with(200, 1., &mut seeded_rng(5));
+        assert_eq!(mcts.best_action(), Some(actions[1]));
+
+        // Nodes hold the reward of the player who moved into them
+        let root = mcts.trees[0].root();
+        let share = root.children().into_iter().find(|child| child.action() == Some(actions[1])).unwrap();
+        assert_eq!(share.player(), 1);
+        assert!(share.mean() > 3.);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Pick(usize);
+    impl GameAction for Pick {}
+
+    /// Pick three of eight numbers in any order; picking 0 wins.
+    #[derive(Clone)]
+    struct Picks(Vec<usize>);
+
+    impl Game<Pick> for Picks {
+        fn allowed_actions(&self) -> Vec<Pick> {
+            if self.0.len() < 3 { (0..8).filter(|i| !self.0.contains(i)).map(Pick).collect() } else { vec![] }
+        }
+        fn make_move(&mut self, action: &Pick) { self.0.push(action.0) }
+        fn reward(&self) -> f32 { if self.0.contains(&0) { 1. } else { 0. } }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    /// Never picks the given number in playouts.
+    #[derive(Debug)]
+    struct Avoid(usize);
+
+    impl RolloutPolicy<Picks, Pick> for Avoid {
+        fn weights(&self, _: &Picks, actions: &[Pick]) -> Vec<f32> {
+            actions.iter().map(|action| if action.0 == self.0 { 0. } else { 1. }).collect()
+        }
+    }
+
+    /// Gives no weight to any move.
+    #[derive(Debug)]
+    struct Indifferent;
+
+    impl RolloutPolicy<Picks, Pick> for Indifferent {
+        fn weights(&self, _: &Picks, actions: &[Pick]) -> Vec<f32> {
+            vec![0.; actions.len()]
+        }
+    }
+
+    #[test]
+    fn test_rollout_policy() {
+        let mut rng = seeded_rng(1);
+        for _ in 0..100 {
+            let (end, moves) = playout_policy_with(&Picks(vec![]), None, Some(&Avoid(0)), true, &mut rng);
+            assert!(!end.0.contains(&0));
+            assert_eq!(moves.len(), 3);
+        }
+        // Without any weight the playouts fall back to random moves
+        let ends = (0..100).map(|_| playout_policy_with(&Picks(vec![]), None, Some(&Indifferent), false, &mut rng).0)
+            .filter(|end| end.0.contains(&0))
+            .count();
+        assert!(ends > 0);
+
+        // Every child of the roots is evaluated by a single playout first,
+        // which only finds the reward if the child picked it already
+        let mut mcts = MCTS::new(&Picks(vec![]), 2);
+        mcts.set_rollout_policy(Avoid(0));
+        mcts.search_with(8, 1., &mut rng);
+        let mut parallel = MCTS::new(&Picks(vec![]), 2);
+        parallel.set_rollout_policy(Avoid(0));
+        parallel.search_parallel_with(8, 1., 2, &mut rng);
+        for root in mcts.roots().into_iter().chain(parallel.roots()) {
+            for child in root.children() {
+                let expected = if child.action() == Some(Pick(0)) { 1. } else { 0. };
+                assert_eq!(child.mean(), expected, "{:?}", child.action());
+            }
+        }
+    }
+
+    /// Pick(0) pays 0.5, Pick(1) 0 or 1.2 by the roll of a die after the
+    /// move; the rolls alternate.
+    #[derive(Clone, Debug)]
+    struct Dice {
+        pick: Option<usize>,
+        roll: Option<bool>,
+        seed: u32,
+    }
+
+    impl Game<Pick> for Dice {
+        fn allowed_actions(&self) -> Vec<Pick> {
+            if self.pick.is_none() { vec![Pick(0), Pick(1)] } else { vec![] }
+        }
+        fn make_move(&mut self, action: &Pick) {
+            self.apply_player_move(action);
+            self.resolve_chance();
+        }
+        fn reward(&self) -> f32 {
+            match (self.pick, self.roll) {
+                (Some(0), Some(_)) => 0.5,
+                (Some(1), Some(true)) => 1.2,
+                _ => 0.
+            }
+        }
+        fn set_rng_seed(&mut self, seed: u32) { self.seed = seed }
+        fn apply_player_move(&mut self, action: &Pick) { self.pick = Some(action.0) }
+        fn resolve_chance(&mut self) {
+            self.roll = Some(self.seed % 2 == 0);
+            self.seed += 1;
+        }
+        fn afterstate_outcomes(&self) -> Option<Vec<(Dice, f32)>> {
+            Some([false, true].iter().map(|&roll| (Dice { roll: Some(roll), ..self.clone() }, 0.5)).collect())
+        }
+    }
+
+    #[test]
+    fn test_afterstates() {
+        let game = Dice { pick: None, roll: None, seed: 0 };
+        let mut rng = seeded_rng(1);
+
+        // The first visit of either pick integrates over the die
+        let config = MctsConfig { afterstates: Some(Afterstates::Integrated), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        for _ in 0..2 {
+            tree.iteration_config(&mut game.clone(), &config, &mut rng, None);
+        }
+        assert_eq!(tree.root().child(&Pick(0)).unwrap().mean(), 0.5);
+        assert!((tree.root().child(&Pick(1)).unwrap().mean() - 0.6).abs() < 1e-6);
+
+        // So does the evaluator, which gets the states after the roll
+        let evaluator = |dice: &Dice| { assert!(dice.roll.is_some()); 0. };
+        let mut tree = Tree::new();
+        for _ in 0..2 {
+            tree.iteration_evaluated(&mut game.clone(), &config, &evaluator, &mut rng, None);
+        }
+        assert!((tree.root().child(&Pick(1)).unwrap().mean() - 0.6).abs() < 1e-6);
+
+        // Drawing the die, the first visit gets one of the outcomes
+        let config = MctsConfig { afterstates: Some(Afterstates::Sampled), ..MctsConfig::default() };
+        let mut tree = Tree::new();
+        let mut dice = game.clone();
+        for _ in 0..2 {
+            tree.iteration_config(&mut dice.clone(), &config, &mut rng, None);
+            dice.seed += 1;
+        }
+        assert!([0., 1.2].contains(&tree.root().child(&Pick(1)).unwrap().mean()));
+
+        // Later visits roll the die before going deeper, where the game is over
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        mcts.run_with(100, &mut rng);
+        assert_eq!(mcts.best_action(), Some(Pick(1)));
+        assert!(mcts.roots()[0].children().iter().all(|child| child.children().is_empty()));
+        assert_eq!("integrated".parse(), Ok(Afterstates::Integrated));
+        assert!("expected".parse::<Afterstates>().is_err());
+    }
+
+    #[test]
+    fn test_fpu() {
+        let search = |fpu: Option<f32>| {
+            let config = MctsConfig { fpu: fpu, ..MctsConfig::default() };
+            let mut tree = Tree::new();
+            let mut rng = seeded_rng(7);
+            for _ in 0..50 {
+                tree.iteration_config(&mut Picks(vec![]), &config, &mut rng, None);
+            }
+            tree
+        };
+
+        // Untried actions have to beat the tried ones
+        assert_eq!(search(None).root().children().len(), 8);
+        assert_eq!(search(Some(10.)).root().children().len(), 8);
+        let greedy = search(Some(-1.));
+        assert_eq!(greedy.root().children().len(), 1);
+        assert_eq!(greedy.root().children()[0].visits(), 50);
+    }
+
+    #[test]
+    fn test_tree_policy() {
+        let search = |tree_policy: TreePolicy| {
+            let config = MctsConfig { tree_policy: tree_policy, ..MctsConfig::default() };
+            let mut tree = Tree::new();
+            let mut rng = seeded_rng(3);
+            for _ in 0..400 {
+                tree.iteration_config(&mut Picks(vec![]), &config, &mut rng, None);
+            }
+            tree
+        };
+        let visits_of_zero = |tree: &Tree<Pick>| tree.root().child(&Pick(0)).unwrap().visits();
+
+        // Picking 0 wins every time, so the variance-aware bound explores less
+        let ucb1 = search(TreePolicy::Ucb1);
+        let tuned = search(TreePolicy::Ucb1Tuned);
+        assert!(visits_of_zero(&tuned) > visits_of_zero(&ucb1));
+
+        // Without exploration everything goes to the best child once all were tried
+        let greedy = search(TreePolicy::Greedy);
+        assert_eq!(greedy.root().children().len(), 8);
+        assert!(visits_of_zero(&greedy) > 300);
+        let epsilon = search(TreePolicy::EpsilonGreedy(0.5));
+        assert!(visits_of_zero(&epsilon) < visits_of_zero(&greedy));
+        assert!(epsilon.root().children().iter().all(|child| child.visits() > 10));
+    }
+
+    /// `Picks` with a hint which action to pick.
+    #[derive(Clone)]
+    struct Hinted(Picks, usize);
+
+    impl Game<Pick> for Hinted {
+        fn allowed_actions(&self) -> Vec<Pick> { self.0.allowed_actions() }
+        fn make_move(&mut self, action: &Pick) { self.0.make_move(action) }
+        fn reward(&self) -> f32 { self.0.reward() }
+        fn action_priors(&self) -> Vec<(Pick, f32)> {
+            let actions = self.allowed_actions();
+            let rest = 0.2 / actions.len() as f32;
+            actions.into_iter().map(|action| (action, if action.0 == self.1 { 0.8 + rest } else { rest })).collect()
+        }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_puct() {
+        let search_n = |hint: usize, n: usize| {
+            let config = MctsConfig { tree_policy: TreePolicy::Puct, ..MctsConfig::default() };
+            let mut tree = Tree::new();
+            let mut rng = seeded_rng(4);
+            for _ in 0..n {
+                tree.iteration_config(&mut Hinted(Picks(vec![]), hint), &config, &mut rng, None);
+            }
+            tree
+        };
+        let search = |hint: usize| search_n(hint, 200);
+
+        // The hinted action is tried first and remembers its prior
+        let good = search(0);
+        assert_eq!(good.root().children()[0].action(), Some(Pick(0)));
+        assert!((good.root().children()[0].prior() - 0.825).abs() < 1e-6);
+        assert!((good.root().children()[1].prior() - 0.025).abs() < 1e-6);
+        assert!(good.root().child(&Pick(0)).unwrap().visits() > 150);
+
+        // A bad hint is followed first, but the other actions get tried
+        let bad = search(5);
+        assert_eq!(bad.root().children()[0].action(), Some(Pick(5)));
+        assert_eq!(bad.root().children().len(), 8);
+        assert_eq!(bad.root().child(&Pick(0)).unwrap().mean(), 1.);
+        assert!(good.root().child(&Pick(0)).unwrap().visits() > bad.root().child(&Pick(0)).unwrap().visits());
+
+        // Untried actions wait for the hinted one to lose its urgency
+        let early = search_n(5, 30);
+        assert!(early.root().children().len() < 8);
+        assert!(early.root().child(&Pick(5)).unwrap().visits() > 20);
+
+        // Without a hint the priors are uniform
+        assert_eq!(Picks(vec![1]).action_priors(), (0..8).filter(|&i| i != 1).map(|i| (Pick(i), 1. / 7.)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_root_noise() {
+        let game = Hinted(Picks(vec![]), 0);
+        let config = MctsConfig { tree_policy: TreePolicy::Puct, root_noise: Some((0.3, 0.25)), ..MctsConfig::default() };
+        let priors = |seed: u32| {
+            let mut tree = Tree::new();
+            tree.iteration_config(&mut game.clone(), &config, &mut seeded_rng(seed), None);
+            tree.root().children().iter().map(|child| child.prior()).collect::<Vec<_>>()
+        };
+
+        // All children exist right away and their noisy priors still sum to one
+        let first = priors(1);
+        assert_eq!(first.len(), 8);
+        assert!((first.iter().sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(first[0] >= 0.75 * 0.825);
+        assert_eq!(first, priors(1));
+        assert!(first != priors(2));
+
+        // Fresh roots get fresh noise, kept subtrees keep theirs
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        let mut rng = seeded_rng(3);
+        mcts.run_with(10, &mut rng);
+        let before = mcts.roots()[0].children().iter().map(|child| child.prior()).collect::<Vec<_>>();
+        mcts.run_with(10, &mut rng);
+        assert_eq!(mcts.roots()[0].children().iter().map(|child| child.prior()).collect::<Vec<_>>(), before);
+        mcts.advance_game(&game);
+        mcts.run_with(10, &mut rng);
+        assert!(mcts.roots()[0].children().iter().map(|child| child.prior()).collect::<Vec<_>>() != before);
+
+        // The visits follow the noisy priors
+        let search = |root_noise: Option<(f32, f32)>| {
+            let config = MctsConfig { tree_policy: TreePolicy::Puct, root_noise: root_noise, ..MctsConfig::default() };
+            let mut tree = Tree::new();
+            let mut rng = seeded_rng(6);
+            for _ in 0..40 {
+                tree.iteration_config(&mut Hinted(Picks(vec![]), 5), &config, &mut rng, None);
+            }
+            tree
+        };
+        let plain = search(None);
+        let noisy = search(Some((0.3, 0.5)));
+        let boosted = noisy.root().children().into_iter().filter(|child| child.action() != Some(Pick(5)))
+            .max_by(|a, b| a.prior().partial_cmp(&b.prior()).unwrap()).unwrap();
+        assert!(boosted.visits() > plain.root().child(&boosted.action().unwrap()).map_or(0, |child| child.visits()));
+        let visited = |tree: &Tree<Pick>| tree.root().children().iter().filter(|child| child.visits() > 0).count();
+        assert!(visited(&noisy) > visited(&plain));
+    }
+
+    #[test]
+    fn test_tree_access() {
+        let mut tree = Tree::new();
+        let mut rng = seeded_rng(5);
+        for _ in 0..200 {
+            tree.iteration_with(&mut Picks(vec![]), 1., &mut rng);
+        }
+
+        // Every node shows up once, right after its parent
+        let nodes = tree.root().iter().collect::<Vec<_>>();
+        assert_eq!(nodes[0].0, 0);
+        assert_eq!(nodes.iter().filter(|&&(depth, _)| depth == 1).count(), 8);
+        assert_eq!(nodes.iter().filter(|&&(depth, _)| depth == 1).map(|&(_, node)| node.visits()).sum::<u64>(), 200);
+        assert_eq!(nodes.len() as u64, 1 + tree.root().children().iter().map(|child| child.iter().count() as u64).sum::<u64>());
+        assert!(nodes.iter().all(|&(depth, _)| depth <= 3));
+
+        let first = tree.root().children()[0].action().unwrap();
+        let second = tree.root().children()[0].children()[0].action().unwrap();
+        assert_eq!(tree.root().child(&first).map(|child| child.visits()), Some(tree.root().children()[0].visits()));
+        assert_eq!(tree.root().get(&[first, second]).map(|node| node.action()), Some(Some(second)));
+        assert!(tree.root().get(&[]).is_some());
+        assert!(tree.root().get(&[first, first]).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_checkpoint() {
+        use std::env;
+        use std::fs;
+        use tictactoe::TicTacToe;
+
+        let config = MctsConfig { transpositions: true, solver: true, rave: Some(50.),
+                                  selection: SelectionPolicy::SecureChild(0.5),
+                                  afterstates: Some(Afterstates::Sampled), contempt: 0.2, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&TicTacToe::new(), 2, config);
+        mcts.run_with(300, &mut seeded_rng(8));
+
+        let path = env::temp_dir().join(format!("mcts-checkpoint-{}.json", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        mcts.save(path).unwrap();
+        let mut resumed = MCTS::<TicTacToe, _>::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let nodes = |mcts: &MCTS<TicTacToe, _>, member: usize| mcts.roots()[member].iter()
+            .map(|(depth, node)| (depth, node.action(), node.player(), *node.statistics(), *node.amaf(),
+                                  node.is_proven_win(), node.is_proven_loss()))
+            .collect::<Vec<_>>();
+        assert_eq!(*resumed.config(), config);
+        for member in 0..2 {
+            assert_eq!(nodes(&resumed, member), nodes(&mcts, member));
+            assert_eq!(resumed.transpositions(member), mcts.transpositions(member));
+        }
+        assert_eq!(resumed.action_statistics(), mcts.action_statistics());
+
+        // The search goes on where it stopped
+        resumed.run_with(10, &mut seeded_rng(9));
+        assert_eq!(resumed.roots()[0].visits(), mcts.roots()[0].visits() + 10);
+
+        assert!(MCTS::<TicTacToe, _>::from_json(&json!({ "members": [] })).is_err());
+        assert!(MCTS::<TicTacToe, _>::load(path).is_err());
+    }
+
+    #[test]
+    fn test_playout_sequence() {
+        let (end, moves) = playout_sequence_with(&Picks(vec![]), Some(2), &mut seeded_rng(4));
+        assert_eq!(moves.iter().map(|&(_, Pick(i))| i).collect::<Vec<_>>(), end.0);
+        assert!(moves.iter().all(|&(player, _)| player == 0));
+        assert_eq!(moves.len(), 2);
+    }
+
+    #[test]
+    fn test_rave() {
+        let game = Picks(vec![]);
+        let mut rng = seeded_rng(6);
+
+        // Without RAVE there are no AMAF statistics
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search_with(50, 1., &mut rng);
+        assert!(mcts.roots()[0].children().iter().all(|child| child.amaf().count() == 0));
+
+        // Moves count for every node they were playable from
+        let config = MctsConfig { rave: Some(100.), ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        mcts.run_with(50, &mut rng);
+        let root = &mcts.roots()[0];
+        assert!(root.children().iter().all(|child| child.amaf().count() >= child.visits()));
+        assert!(root.children().iter().map(|child| child.amaf().count()).sum::<u64>() > root.visits());
+
+        // A winning move wins as first or later move
+        let best = root.children().into_iter().max_by_key(|child| child.visits()).unwrap();
+        assert_eq!(best.action(), Some(Pick(0)));
+        assert_eq!(best.amaf().mean(), 1.);
+        assert_eq!(mcts.best_action(), Some(Pick(0)));
+    }
+
+    /// Two players take one or two stones in turn; who takes the last wins.
+    #[derive(Clone)]
+    struct Nim {
+        stones: usize,
+        moves: usize,
+    }
+
+    impl Game<Pick> for Nim {
+        fn allowed_actions(&self) -> Vec<Pick> {
+            (1..3).filter(|&n| n <= self.stones).map(Pick).collect()
+        }
+        fn make_move(&mut self, action: &Pick) {
+            self.stones -= action.0;
+            self.moves += 1;
+        }
+        fn reward(&self) -> f32 {
+            match (self.stones, self.moves % 2) {
+                (0, 1) => 1.,
+                (0, _) => -1.,
+                _ => 0.
+            }
+        }
+        fn current_player(&self) -> usize { self.moves % 2 }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    /// Take 0.6 for sure with Pick(0), or gamble with Pick(1): chance pays
+    /// 1 with Pick(2) three times out of ten and nothing with Pick(3).
+    #[derive(Clone)]
+    struct Gamble {
+        moves: Vec<usize>,
+        chance: bool,
+    }
+
+    impl Game<Pick> for Gamble {
+        fn allowed_actions(&self) -> Vec<Pick> {
+            match self.moves.as_slice() {
+                [] => vec![Pick(0), Pick(1)],
+                [1] => vec![Pick(2), Pick(3)],
+                _ => vec![]
+            }
+        }
+        fn make_move(&mut self, action: &Pick) { self.moves.push(action.0) }
+        fn reward(&self) -> f32 {
+            match self.moves.as_slice() {
+                [0] => 0.6,
+                [1, 2] => 1.,
+                _ => 0.
+            }
+        }
+        fn chance_outcomes(&self) -> Option<Vec<(Pick, f32)>> {
+            match self.moves.as_slice() {
+                [1] if self.chance => Some(vec![(Pick(2), 0.3), (Pick(3), 0.7)]),
+                _ => None
+            }
+        }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_chance() {
+        // Chance pays 0.3 on average, which is worse than the sure thing
+        let game = Gamble { moves: vec![], chance: true };
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search_with(1000, 1., &mut seeded_rng(1));
+        assert_eq!(mcts.best_action(), Some(Pick(0)));
+
+        let gamble = mcts.roots()[0].child(&Pick(1)).unwrap();
+        assert!(gamble.is_chance());
+        assert!((gamble.mean() - 0.3).abs() < 0.1, "{}", gamble.mean());
+        let win = gamble.child(&Pick(2)).unwrap();
+        assert!((win.prior() - 0.3).abs() < 1e-6);
+        assert!((win.visits() as f32 / gamble.visits() as f32 - 0.3).abs() < 0.1);
+
+        // Playouts draw by probability as well
+        let mut rng = seeded_rng(2);
+        let wins = (0..1000).filter(|_| playout_with(&game.clone(), &mut rng).moves == vec![1, 2]).count();
+        assert!(wins > 100 && wins < 200, "{}", wins);
+
+        // The solver does not take the lucky outcome for granted
+        let config = MctsConfig { solver: true, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        mcts.run_with(200, &mut seeded_rng(3));
+        assert!(!mcts.roots()[0].child(&Pick(1)).unwrap().is_proven());
+        assert_eq!(mcts.best_action(), Some(Pick(0)));
+
+        // If the gambler could choose the outcome, gambling would win
+        let game = Gamble { moves: vec![], chance: false };
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search_with(1000, 1., &mut seeded_rng(1));
+        assert_eq!(mcts.best_action(), Some(Pick(1)));
+        assert!(!mcts.roots()[0].child(&Pick(1)).unwrap().is_chance());
+    }
+
+    #[test]
+    fn test_solver() {
+        // Taking one stone leaves the opponent with a lost position
+        let game = Nim { stones: 4, moves: 0 };
+        let config = MctsConfig { solver: true, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        let mut rng = seeded_rng(3);
+        mcts.run_with(200, &mut rng);
 
-//////////////////////////////////////////////////////////////////////////
+        let root = &mcts.roots()[0];
+        assert!(root.is_proven_win());
+        for child in root.children() {
+            assert_eq!(child.is_proven_win(), child.action() == Some(Pick(1)));
+            assert_eq!(child.is_proven_loss(), child.action() == Some(Pick(2)));
+        }
+        assert_eq!(mcts.best_action(), Some(Pick(1)));
+        assert_eq!(mcts.principal_variation(1), vec![Pick(1)]);
+        let proven = mcts.action_statistics().iter().map(|s| (s.action, s.proven)).collect::<Vec<_>>();
+        assert_eq!(proven, vec![(Pick(1), Some(true)), (Pick(2), Some(false))]);
 
-#[derive(Debug,Copy,Clone)]
-enum NodeState {
-    LeafNode, FullyExpanded, Expandable
-}
+        // Nothing left to search
+        let visits = mcts.roots()[0].visits();
+        mcts.run_with(10, &mut rng);
+        assert_eq!(mcts.roots()[0].visits(), visits);
 
-#[derive(Debug)]
-pub struct TreeNode<A: GameAction> {
-    action: Option<A>,                  // how did we get here
-    children: Vec<TreeNode<A>>,         // next steps we investigated
-    state: NodeState,                   // is this a leaf node? fully expanded?
-    n: f32, q: f32                      // statistics for this game state
-}
+        // Without the solver nothing gets proven
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search_with(200, 1., &mut rng);
+        assert!(!mcts.roots()[0].is_proven());
+    }
+
+    #[test]
+    fn test_tree_statistics() {
+        let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 2);
 
-impl<A> TreeNode<A> where A: GameAction {
+        // Fresh trees are their roots alone
+        let stats = mcts.tree_statistics();
+        assert_eq!((stats.nodes(), stats.leaves(), stats.terminal_nodes()), (2, 2, 0));
+        assert_eq!((stats.min_depth(), stats.max_depth()), (0, 0));
+        assert_eq!((stats.average_depth(), stats.branching_factor()), (0., 0.));
+        assert_eq!(stats.memory(), 2 * mem::size_of::<Node<MiniAction>>());
 
-    /// Create and initialize a new TreeNode
-    ///
-    /// Initialize q and n t to be zero; childeren list to
-    /// be empty and set the node state to Expandable.
-    pub fn new(action: Option<A>) -> TreeNode<A> {
-        TreeNode::<A> {
-            action: action,
-            children: Vec::new(),
-            state: NodeState::Expandable,
-            n: 0., q: 0. }
-    }
+        mcts.search_with(300, 1., &mut seeded_rng(3));
+        let stats = mcts.tree_statistics();
+        let nodes = |node: &TreeNode<MiniAction>| node.tree_statistics().nodes();
+        assert_eq!(stats.nodes(), nodes(&mcts.roots()[0]) + nodes(&mcts.roots()[1]));
+        assert_eq!(stats, mcts.roots()[0].tree_statistics().merge(&mcts.roots()[1].tree_statistics()));
 
-    /// Gather some statistics about this subtree
-    pub fn tree_statistics(&self) -> TreeStatistics {
-        let child_stats = self.children.iter()
-                .map(|c| c.tree_statistics())
-                .collect::<Vec<_>>();
-        TreeStatistics::merge(child_stats)
+        // Every node but the roots is somebody's child
+        let inner = stats.nodes() - stats.leaves();
+        assert!((stats.branching_factor() * inner as f32 - (stats.nodes() - 2) as f32).abs() < 1e-3);
+        assert!(stats.branching_factor() >= 1.);
+        assert!(stats.min_depth() as f32 <= stats.average_depth());
+        assert!(stats.average_depth() <= stats.max_depth() as f32);
+        assert!(stats.terminal_nodes() > 0 && stats.terminal_nodes() <= stats.leaves());
+        assert!(stats.memory() >= stats.nodes() as usize * mem::size_of::<Node<MiniAction>>());
     }
 
     /*
-    /// XXX
-    pub fn merge_nodes(nodes: Vec<TreeNode<A>>, depth: usize) -> TreeNode<A> {
+    #[test]
+    fn test_mcts() {
+        let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 1);
+        //println!("MCTS on new game: {:?}", mcts);
 
-    }
-    */
 
-    /// Find the best child accoring to UCT1
-    pub fn best_child(&mut self, c: f32) -> Option<&mut TreeNode<A>> {
-        let mut best_value :f32 = f32::NEG_INFINITY;
-        let mut best_child :Option<&mut TreeNode<A>> = None;
 
-        for child in &mut self.children {
-            let value = child.q / child.n + c*(2.*self.n.ln()/child.n).sqrt();
-            if value > best_value {
-                best_value = value;
-                best_child = Some(child);
-            }
+        for i in 0..5 {
+            mcts.root.iteration(&mut game.clone(), 1.0);
+            println!("After {} iteration(s):\n{}", i, mcts);
         }
-        best_child
-    }
+    }*/
 
-    /// Add a child to the current node with an previously unexplored action.
-    ///
-    /// XXX Use HashSet? Use iterators? XXX
-    pub fn expand<G: Game<A>>(&mut self, game: &G) -> Option<&mut TreeNode<A>> {
+    #[test]
+    fn test_search() {
+        let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 2);
 
-        // What are our options given the current game state?
-        let allowed_actions = game.allowed_actions();
-        if allowed_actions.len() == 0 {
-            self.state = NodeState::LeafNode;
-            return None;
-        }
+        mcts.search(50, 1.);
 
-        // Get a list with all the actions we tried alreday
-        let mut child_actions : Vec<A> = Vec::new();
-        for child in &self.children {
-                child_actions.push(child.action.expect("Child node without action"));
-        }
+        println!("Search result: {:?}", mcts.best_action());
+    }
 
-        // Find untried actions
-        let mut candidate_actions = Vec::new();
-        for action in &allowed_actions {
-            if !child_actions.contains(action) {
-                candidate_actions.push(action);
-            }
-        }
+    #[test]
+    fn test_search_reproducible() {
+        let game = TwoFortyEight::with_seed(3);
+        let search = || {
+            seed_thread_rng(11);
+            let mut mcts = MCTS::new(&game, 2);
+            mcts.search(100, 1.);
+            mcts.action_values()
+        };
 
-        if candidate_actions.len() == 1 {
-            self.state = NodeState::FullyExpanded;
-        }
+        assert_eq!(search(), search());
+    }
 
-        // Select random actions
-        let action = *choose_random(&candidate_actions).clone();
+    #[test]
+    fn test_search_with() {
+        let game = TwoFortyEight::with_seed(3);
+        let search = |seed| {
+            let mut rng = seeded_rng(seed);
+            let mut mcts = MCTS::new(&game, 2);
+            mcts.search_with(100, 1., &mut rng);
+            mcts.action_values()
+        };
 
-        self.children.push(TreeNode::new(Some(action)));
-        self.children.last_mut()
-    }
+        assert_eq!(search(5), search(5));
+        assert!(search(5) != search(6));
 
-    /// Recursively perform an MCTS iteration.
-    ///
-    /// XXX A non-recursive implementation would probably be faster.
-    /// XXX But how to keep &mut pointers to all our parents while
-    /// XXX we fiddle with our leaf node?
-    pub fn iteration<G: Game<A>>(&mut self, game: &mut G, c: f32) -> f32 {
-        let delta = match self.state {
-            NodeState::LeafNode => {
-                game.reward()
-            },
-            NodeState::FullyExpanded => {
-                // Choose and recurse into child...
-                let child = self.best_child(c).unwrap();
-                game.make_move(&child.action.unwrap());
-                child.iteration(game, c)
-            },
-            NodeState::Expandable => {
-                let child = self.expand(game);
-                match child {
-                    Some(child) => {           // We expanded our current node...
-                        game.make_move(&child.action.unwrap());
-                        let delta = playout(game).reward();
-                        child.n += 1.;
-                        child.q += delta;
-                        delta
-                    },
-                    None => game.reward()      // Could not expand, current node is a leaf node!
-                }
-            }
+        let mut rng = seeded_rng(1);
+        let a = playout_statistics_with(&game, 10, &mut rng);
+        let mut rng = seeded_rng(1);
+        assert_eq!(a, playout_statistics_with(&game, 10, &mut rng));
+        let mut rng = seeded_rng(1);
+        assert_eq!(expected_reward_stats_with(&game, 10, &mut rng), RewardEstimate::from_stats(&a));
+
+        // Forced expansions draw from the given generator as well
+        let expanded = |seed| {
+            let mut rng = seeded_rng(seed);
+            let mut mcts = MCTS::new(&game, 1);
+            while mcts.expand_at_with(0, &[], None, &mut rng).is_some() {}
+            mcts.action_values()
         };
-        self.n += 1.;
-        self.q += delta;
-        delta
+        assert_eq!(expanded(2), expanded(2));
     }
-}
 
+    #[test]
+    fn test_set_seed() {
+        let game = TwoFortyEight::with_seed(3);
+        let search = |seed: u64| {
+            let mut mcts = MCTS::new(&game, 2);
+            mcts.set_seed(seed);
+            mcts.search(100, 1.);
+            mcts.run(50);
+            (mcts.action_values(), mcts.best_action())
+        };
 
-impl<A: GameAction> fmt::Display for TreeNode<A> {
+        // The seed of the solver decides, whatever the thread generator does
+        seed_thread_rng(1);
+        let first = search(1 << 40);
+        seed_thread_rng(2);
+        assert_eq!(search(1 << 40), first);
+        assert!(search((1 << 40) + 1) != first);
 
-    /// Output a nicely indented tree
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The same as searching with the seeded generator
+        let mut mcts = MCTS::new(&game, 2);
+        let mut rng = seeded_std_rng(1 << 40);
+        mcts.search_with(100, 1., &mut rng);
+        mcts.run_with(50, &mut rng);
+        assert_eq!((mcts.action_values(), mcts.best_action()), first);
+    }
 
-        // Nested definition for recursive formatting
-        fn fmt_subtree<M: GameAction>(f: &mut fmt::Formatter, node: &TreeNode<M>, indent_level :i32) -> fmt::Result {
-            for _ in (0..indent_level) {
-                try!(f.write_str("    "));
-            }
-            match node.action {
-                Some(a)  => try!(writeln!(f, "{:?} q={} n={}", a, node.q, node.n)),
-                None     => try!(writeln!(f, "Root q={} n={}", node.q, node.n))
-            }
-            for child in &node.children {
-                try!(fmt_subtree(f, child, indent_level+1));
-            }
-            write!(f, "")
+    #[test]
+    fn test_search_parallel() {
+        let game = MiniGame::new();
+        let searched = |threads| {
+            let mut mcts = MCTS::new(&game, 5);
+            mcts.search_parallel_with(100, 1., threads, &mut seeded_rng(3));
+            mcts
+        };
+
+        let mcts = searched(2);
+        for root in mcts.roots() {
+            assert_eq!(root.visits(), 100);
         }
+        let visits = mcts.action_values().iter().map(|&(_, n, _)| n).sum::<u64>();
+        assert_eq!(visits, 500);
+        assert!(mcts.best_action().is_some());
 
-        fmt_subtree(f, self, 0)
+        // The same trees whatever the number of threads
+        for &threads in &[1, 5, 8, 0] {
+            assert_eq!(searched(threads).action_values(), mcts.action_values());
+        }
     }
-}
-
-#[derive(Debug, Copy, Clone)]
-/// Store and process some simple statistical information about NodeTrees.
-pub struct TreeStatistics {
-    nodes: i32,
-    min_depth: i32,
-    max_depth: i32,
-}
 
-impl TreeStatistics {
-    fn merge(child_stats: Vec<TreeStatistics>) -> TreeStatistics {
-        if child_stats.len() == 0 {
-            TreeStatistics {
-                nodes: 1,
-                min_depth: 0,
-                max_depth: 0,
-            }
-        } else {
-            TreeStatistics {
-                nodes: child_stats.iter()
-                        .fold(0, |sum, child| sum + child.nodes),
-                min_depth: 1 + child_stats.iter()
-                        .fold(i32::MAX, |depth, child| min(depth, child.min_depth)),
-                max_depth: 1 + child_stats.iter()
-                        .fold(0, |depth, child| max(depth, child.max_depth)),
+    /// Sum up the visits of all nodes below `node` by key; returns the
+    /// number of nodes with a key.
+    fn visits_by_key<A: GameAction>(node: &TreeNode<A>, visits: &mut HashMap<(u64, usize), u64>) -> usize {
+        let mut nodes = 0;
+        for child in node.children() {
+            if let Some(key) = child.data().key {
+                *visits.entry((key, child.player())).or_insert(0) += child.visits();
+                nodes += 1;
             }
+            nodes += visits_by_key(&child, visits);
         }
+        nodes
     }
-}
-//////////////////////////////////////////////////////////////////////////
-
-#[derive(Debug)]
-/// Represents an ensamble of MCTS trees.
-///
-/// For many applications we need to work with ensambles because we use
-/// determinization.
-pub struct MCTS<G: Game<A>, A: GameAction> {
-    roots: Vec<TreeNode<A>>,
-    games: Vec<G>,
-    iterations_per_s: f32,
-}
 
-impl<G: Game<A>, A: GameAction> MCTS<G, A> {
+    #[test]
+    fn test_contempt() {
+        use tictactoe::{TicTacToe, Action};
 
-    /// Create a new MCTS solver.
-    pub fn new(game: &G, ensamble_size: usize) -> MCTS<G, A> {
-        let mut roots = Vec::new();
-        let mut games = Vec::new();
-        for i in 0..ensamble_size {
-            let mut game = game.clone();
-            game.set_rng_seed(i as u32);
-            games.push(game);
-            roots.push(TreeNode::new(None));
+        // O to move can only draw: (2, 1) draws, (0, 1) lets X complete
+        // the bottom row
+        let mut game = TicTacToe::new();
+        for &(row, col) in &[(0, 0), (1, 1), (2, 2), (0, 2), (2, 0), (1, 0), (1, 2)] {
+            game.make_move(&Action { row: row, col: col });
         }
-        MCTS {
-            roots: roots,
-            games: games,
-            iterations_per_s: 1.
+        let draw = Action { row: 2, col: 1 };
+        let search = |game: &TicTacToe, contempt: f32| {
+            let config = MctsConfig { contempt: contempt, ..MctsConfig::default() };
+            let mut mcts = MCTS::with_config(game, 1, config);
+            mcts.search_with(200, 1., &mut seeded_rng(1));
+            mcts
+        };
+        for &contempt in &[0., 0.25, -0.25] {
+            let mcts = search(&game, contempt);
+            assert_eq!(mcts.best_action(), Some(draw));
+            assert_eq!(mcts.root_value(), Some(-contempt));
         }
-    }
 
-    /// Return basic statistical data about the current MCTS tree.
-    ///
-    /// XXX Note: The current implementation considers the ensemble
-    /// to be a tree layer. In other words tree depth and number of
-    /// nodes are all one too large.
-    pub fn tree_statistics(&self) -> TreeStatistics {
-        let child_stats = self.roots.iter()
-                    .map(|c| c.tree_statistics())
-                    .collect::<Vec<_>>();
-        TreeStatistics::merge(child_stats)
-    }
-    /// Set a new game state for this solver.
-    pub fn advance_game(&mut self, game: &G) {
-        let ensamble_size = self.games.len();
+        // The contempt is that of whoever is to move at the root, X now
+        game.make_move(&draw);
+        assert_eq!(search(&game, 0.25).root_value(), Some(-0.25));
 
-        let mut roots = Vec::new();
-        let mut games = Vec::new();
-        for i in 0..ensamble_size {
-            let mut game = game.clone();
-            game.set_rng_seed(i as u32);
-            games.push(game);
-            roots.push(TreeNode::new(None));
-        }
-        self.games = games;
-        self.roots = roots;
+        // Most random games of an empty board are won by somebody, but
+        // draws weigh in
+        let game = TicTacToe::new();
+        let value = |contempt| search(&game, contempt).root_value().unwrap();
+        assert!(value(1.) < value(0.));
+        assert!(value(-1.) > value(0.));
     }
 
-    /// Perform n_samples MCTS iterations.
-    pub fn search(&mut self, n_samples: usize, c: f32) {
-        let ensamble_size = self.games.len();
+    #[test]
+    fn test_transpositions() {
+        use tictactoe::{TicTacToe, Action};
 
-        // Iterate over ensamble and perform MCTS iterations
-        for e in 0..ensamble_size {
-            let game = &self.games[e];
-            let root = &mut self.roots[e];
+        let mut game = TicTacToe::new();
+        assert!(MCTS::new(&game, 1).transpositions(0).is_none());
 
-            // Perform MCTS iterations
-            for _ in 0..n_samples {
-                let mut this_game = game.clone();
-                root.iteration(&mut this_game, c);
+        let mut mcts = MCTS::with_transpositions(&game, 2, true);
+        mcts.search_with(500, 1., &mut seeded_rng(1));
+        for e in 0..2 {
+            // Every visit of a node with a key is counted once in the table
+            let table = mcts.transpositions(e).unwrap();
+            let mut visits = HashMap::new();
+            let nodes = visits_by_key(&mcts.roots()[e], &mut visits);
+            assert_eq!(visits.len(), table.len());
+            for (key, stats) in table {
+                assert_eq!(visits[key], stats.count());
             }
+            // ... and some states were reached by different move orders
+            assert!(table.len() < nodes);
         }
-    }
 
-    /// Perform MCTS iterations for the given time budget (in s).
-    pub fn search_time(&mut self, budget_seconds: f32, c: f32) {
-        let mut samples_total = 0;
-        let t0 = time::now();
+        mcts.search_parallel_with(100, 1., 2, &mut seeded_rng(2));
+        assert_eq!(mcts.roots()[1].visits(), 600);
 
-        let mut n_samples = (self.iterations_per_s*budget_seconds).max(10.).min(100.) as usize;
-        while n_samples >= 5 {
-            self.search(n_samples, c);
-            samples_total += n_samples;
+        for &(row, col) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            game.make_move(&Action { row: row, col: col });
+        }
+        let mut mcts = MCTS::with_transpositions(&game, 1, true);
+        mcts.search_with(300, 1., &mut seeded_rng(3));
+        assert_eq!(mcts.best_action(), Some(Action { row: 0, col: 2 }));
 
-            let time_spend = (time::now()-t0).num_milliseconds() as f32 / 1000.;
-            self.iterations_per_s = (samples_total as f32) / time_spend;
+        mcts.advance_game(&game);
+        assert!(mcts.transpositions(0).unwrap().is_empty());
+    }
 
-            let time_left = budget_seconds - time_spend;
-            n_samples = (self.iterations_per_s*time_left).max(0.).min(100.) as usize;
+    #[test]
+    fn test_action_values() {
+        let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 2);
 
-        }
-    }
+        mcts.search(50, 1.);
 
-    /// Return the best action found so far by averaging over the ensamble.
-    pub fn best_action(&self) -> Option<A> {
-        let ensamble_size = self.games.len();
+        let values = mcts.action_values();
+        assert_eq!(values.len(), game.allowed_actions().len());
 
-        // Merge ensamble results
-        let mut n_values = HashMap::<A, f32>::new();
-        let mut q_values = HashMap::<A, f32>::new();
+        // Every iteration visits exactly one root action
+        let visits = values.iter().map(|&(_, n, _)| n).sum::<u64>();
+        assert_eq!(visits, 100);
 
-        for e in 0..ensamble_size {
-            let root = &self.roots[e];
+        // Sorted by value, best action first
+        for pair in values.windows(2) {
+            assert!(pair[0].2 >= pair[1].2);
+        }
+        assert_eq!(mcts.best_action(), Some(values[0].0));
+    }
 
-            for child in &root.children {
-                let action = child.action.unwrap();
+    #[test]
+    fn test_action_statistics() {
+        let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 3);
+        assert_eq!(mcts.action_statistics(), vec![]);
 
-                let n = n_values.entry(action).or_insert(0.);
-                let q = q_values.entry(action).or_insert(0.);
+        mcts.search_with(40, 1., &mut seeded_rng(2));
 
-                *n += child.n;
-                *q += child.q;
+        // The same ranking as the values, with merged spreads
+        let stats = mcts.action_statistics();
+        let values = mcts.action_values();
+        assert_eq!(stats.iter().map(|s| (s.action, s.visits, s.mean)).collect::<Vec<_>>(), values);
+        for s in &stats {
+            let mut merged = RunningStats::new();
+            for root in mcts.roots() {
+                if let Some(child) = root.children().iter().find(|child| child.action() == Some(s.action)) {
+                    merged.merge(child.statistics());
+                }
             }
+            assert_eq!(s.stderr, merged.stderr());
+            assert_eq!(s.proven, None);
         }
+    }
 
-        // Find best action
-        let mut best_action: Option<A> = None;
-        let mut best_value: f32 = f32::NEG_INFINITY;
-        for (action, n) in &n_values {
-            let q = q_values.get(action).unwrap();
-            let value = q / n;
-            if value > best_value {
-                best_action = Some(*action);
-                best_value = value;
-            }
+    #[test]
+    fn test_per_root_statistics() {
+        let game = TwoFortyEight::with_seed(3);
+        let mut mcts = MCTS::new(&game, 4);
+        let agreement = mcts.agreement();
+        assert_eq!((agreement.votes, agreement.agreement, agreement.entropy), (vec![], 0., 0.));
+
+        mcts.search_with(50, 1., &mut seeded_rng(5));
+        let roots = mcts.per_root_statistics();
+        assert_eq!(roots.len(), 4);
+        let merged = roots.iter().skip(1).fold(roots[0].tree, |merged, root| merged.merge(&root.tree));
+        assert_eq!(merged, mcts.tree_statistics());
+        for (root, stats) in mcts.roots().iter().zip(&roots) {
+            assert_eq!(stats.visits, root.visits());
+            let best = root.children().into_iter()
+                .find(|child| child.action() == stats.best_action)
+                .unwrap();
+            assert_eq!(stats.value, Some(best.mean()));
         }
 
-        best_action
+        // Every tree votes, for its own best action
+        let agreement = mcts.agreement();
+        assert_eq!(agreement.votes.iter().map(|&(_, n)| n).sum::<usize>(), 4);
+        assert!(agreement.votes.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(agreement.agreement, agreement.votes[0].1 as f32 / 4.);
+        let entropy = agreement.votes.iter()
+            .map(|&(_, n)| -(n as f32 / 4.) * (n as f32 / 4.).log2())
+            .sum::<f32>();
+        assert!((agreement.entropy - entropy).abs() < 1e-6);
+        assert!(agreement.entropy <= 2.);
+        let line = format!("{}", agreement);
+        assert!(line.starts_with(&format!("agreement {:.3}, entropy ", agreement.agreement)), "{}", line);
+
+        // A single tree agrees with itself and with the ensemble
+        let mut single = MCTS::new(&game, 1);
+        single.search_with(50, 1., &mut seeded_rng(5));
+        assert_eq!(single.per_root_statistics()[0].best_action, single.best_action());
+        let agreement = single.agreement();
+        assert_eq!((agreement.agreement, agreement.entropy), (1., 0.));
     }
-}
 
+    #[test]
+    fn test_set_ensemble_size() {
+        let game = TwoFortyEight::with_seed(3);
+        let config = MctsConfig { transpositions: true, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 2, config);
+        mcts.search_with(30, 1., &mut seeded_rng(1));
+        let visits = mcts.roots()[0].visits();
 
-impl<G: Game<A>, A: GameAction> fmt::Display for MCTS<G, A> {
+        // Growing keeps the trees and adds empty ones, seeded like those of
+        // a new ensemble
+        mcts.set_ensemble_size(4);
+        assert_eq!(mcts.ensemble_size(), 4);
+        assert_eq!(mcts.roots()[0].visits(), visits);
+        assert_eq!(mcts.roots()[3].visits(), 0);
+        assert!(mcts.transpositions(3).is_some());
+        let action = game.allowed_actions()[0];
+        let after = |game: &TwoFortyEight| {
+            let mut game = game.clone();
+            game.make_move(&action);
+            format!("{}", game)
+        };
+        let fresh = MCTS::<TwoFortyEight, _>::new(&game, 4);
+        assert_eq!(after(&mcts.games()[3]), after(&fresh.games()[3]));
 
-    /// Output a nicely indented tree
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(f, "Ensable of {} trees:", self.roots.len()));
-        //for root in &self.roots {
-        //    try!(root.fmt(f));
-        //}
-        write!(f, "")
+        mcts.search_with(30, 1., &mut seeded_rng(2));
+        assert!(mcts.roots()[3].visits() > 0);
+
+        // Shrinking drops the last trees
+        mcts.set_ensemble_size(1);
+        assert_eq!(mcts.ensemble_size(), 1);
+        assert_eq!(mcts.games().len(), 1);
+        assert_eq!(mcts.per_root_statistics().len(), 1);
+        assert!(mcts.roots()[0].visits() > visits);
     }
-}
 
+    #[test]
+    fn test_selection_policies() {
+        let game = MiniGame::new();
+        let config = MctsConfig { selection: SelectionPolicy::RobustChild, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 2, config);
+        mcts.search_with(60, 1., &mut seeded_rng(4));
 
-/////////////////////////////////////////////////////////////////////////////
-// Unittests
+        let stats = mcts.action_statistics();
+        let visits = |action| stats.iter().find(|s| s.action == action).unwrap().visits;
+        let max_visits = stats.iter().map(|s| s.visits).max().unwrap();
 
-#[cfg(test)]
-mod tests {
-    use time;
-    //use std::num::traits::*;
-    use test::Bencher;
+        let max = mcts.best_action_by(SelectionPolicy::MaxChild).unwrap();
+        let robust = mcts.best_action_by(SelectionPolicy::RobustChild).unwrap();
+        assert_eq!(max, stats[0].action);
+        assert_eq!(visits(robust), max_visits);
+        assert_eq!(mcts.best_action(), Some(robust));
+        assert_eq!(mcts.root_value(), Some(stats.iter().find(|s| s.action == robust).unwrap().mean));
 
-    use mcts::*;
-    use minigame::MiniGame;
+        let max_robust = mcts.best_action_by(SelectionPolicy::MaxRobustChild).unwrap();
+        assert_eq!(max_robust, if visits(max) == max_visits { max } else { robust });
 
-    /*
-    // Are the given
-    fn allmost_equal<T: Float>(a: T, b: T) -> bool {
-        let rtol = 1e-6;
+        // Without a penalty the secure child is the max child; with a huge
+        // one it is the robust child
+        assert_eq!(mcts.best_action_by(SelectionPolicy::SecureChild(0.)), Some(max));
+        assert_eq!(visits(mcts.best_action_by(SelectionPolicy::SecureChild(1e6)).unwrap()), max_visits);
+    }
 
-        // Shortcut for inf and neg_inf
-        if (a == b) { return true };
+    #[test]
+    fn test_early_stop() {
+        let game = Line { moves: 0, length: 50 };
+        let mut mcts = MCTS::new(&game, 2);
+        mcts.search_with(10, 1., &mut seeded_rng(1));
+        assert!(!mcts.decided(0.));
 
-        let a_abs = a.abs();
-        let b_abd = b.abs();
-        let diff = (a-b).abs();
+        // The only action leads by 20 visits
+        let config = MctsConfig { early_stop: true, ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 2, config);
+        mcts.search_with(10, 1., &mut seeded_rng(1));
+        assert!(mcts.decided(9.));
+        assert!(!mcts.decided(10.));
 
-        diff <= tol * a_abs.max(b_abs)
+        // Forced moves have all the visits
+        let config = MctsConfig { stop_visit_share: Some(0.9), ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 2, config);
+        mcts.search_with(10, 1., &mut seeded_rng(1));
+        assert!(mcts.decided(1e9));
+        let t0 = time::precise_time_s();
+        mcts.search_time(5., 1.);
+        assert!(time::precise_time_s() - t0 < 2.5);
     }
-    */
 
     #[test]
-    fn test_playout() {
+    fn test_principal_variation() {
         let game = MiniGame::new();
-        let game = playout(&game);
-        println!("Final: {:?}", game);
+        let mut mcts = MCTS::new(&game, 2);
+        assert_eq!(mcts.principal_variation(5), vec![]);
+
+        mcts.search(50, 1.);
+
+        // A legal sequence of moves starting with the best action
+        let pv = mcts.principal_variation(usize::MAX);
+        assert_eq!(pv.first(), mcts.best_action().as_ref());
+        let mut game = game.clone();
+        for action in &pv {
+            assert!(game.allowed_actions().contains(action));
+            game.make_move(action);
+        }
+        assert_eq!(mcts.principal_variation(2), pv[..2].to_vec());
+        assert_eq!(mcts.principal_variation(0), vec![]);
+
+        // A single tree follows its most visited children
+        let mut mcts = MCTS::new(&MiniGame::new(), 1);
+        mcts.search(50, 1.);
+        let root = &mcts.roots()[0];
+        let pv = mcts.principal_variation(usize::MAX);
+        let first = root.children().into_iter().find(|child| child.action() == Some(pv[0])).unwrap();
+        assert_eq!(first.pv(usize::MAX), pv[1..].to_vec());
+        let most_visited = root.children().into_iter().max_by_key(|child| child.visits()).unwrap();
+        assert_eq!(root.pv(1), vec![most_visited.action().unwrap()]);
     }
 
     #[test]
-    fn test_expand() {
+    fn test_advance() {
         let game = MiniGame::new();
-        let mut node = TreeNode::new(None);
+        let mut mcts = MCTS::new(&game, 2);
+        mcts.search(50, 1.);
 
-        node.expand(&game);
-        node.expand(&game);
-        {
-            let v = node.expand(&game).unwrap();
-            v.expand(&game);
-        }
+        // The subtree of the best action carries its visits along
+        let action = mcts.best_action().unwrap();
+        let visits = mcts.action_values().iter()
+            .find(|&&(a, _, _)| a == action)
+            .map(|&(_, n, _)| n).unwrap();
+        assert!(mcts.advance(&action));
+        let kept = mcts.action_values().iter().map(|&(_, n, _)| n).sum::<u64>();
+        assert!(kept > 0 && kept < visits);
 
-        println!("After some expands:\n{}", node);
+        let mut game = game.clone();
+        game.make_move(&action);
+        for (a, _, _) in mcts.action_values() {
+            assert!(game.allowed_actions().contains(&a));
+        }
     }
 
     #[test]
-    fn test_tree_statistics() {
+    fn test_expand_at() {
         let game = MiniGame::new();
-        let mut mcts = MCTS::new(&game, 2);
+        let mut mcts = MCTS::new(&game, 1);
+        let action = game.allowed_actions()[1];
 
-        mcts.search(50, 1.);
+        // Expand a chosen action at the root, then below it
+        assert!(mcts.expand_at(0, &[], Some(action)).is_some());
+        assert!(mcts.expand_at(0, &[], Some(action)).is_none());
+        assert!(mcts.expand_at(0, &[action], None).is_some());
 
-        let stats = mcts.tree_statistics();
+        let root = &mcts.roots()[0];
+        assert_eq!(root.visits(), 2);
+        assert_eq!(root.children().len(), 1);
+        assert_eq!(root.children()[0].action(), Some(action));
+        assert_eq!(root.children()[0].visits(), 2);
+        assert_eq!(root.children()[0].children().len(), 1);
 
-        println!("{:?}", stats);
+        // Paths must exist in the tree
+        let other = game.allowed_actions()[0];
+        assert!(mcts.expand_at(0, &[other], None).is_none());
     }
 
-    /*
     #[test]
-    fn test_mcts() {
+    fn test_search_observed() {
         let game = MiniGame::new();
+        let mut mcts = MCTS::new(&game, 2);
+        let mut reports = Vec::new();
+        let report = mcts.search_observed_with(250, 1., 100, |progress| {
+            reports.push(*progress);
+            true
+        }, &mut seeded_rng(1));
+        assert_eq!(reports.iter().map(|p| p.iterations).collect::<Vec<_>>(), vec![100, 200, 250]);
+        assert!(reports.windows(2).all(|w| w[0].nodes <= w[1].nodes));
+        let last = reports.last().unwrap();
+        assert_eq!(last.best_action, mcts.best_action());
+        assert_eq!(last.root_value, mcts.root_value());
+        assert_eq!(last.nodes, report.nodes);
+        assert_eq!(report.iterations, 250);
+
+        // With a single tree, the same search as without an observer
+        let mut observed = MCTS::new(&game, 1);
+        observed.search_observed_with(250, 1., 100, |_| true, &mut seeded_rng(1));
+        let mut plain = MCTS::new(&game, 1);
+        plain.search_with(250, 1., &mut seeded_rng(1));
+        assert_eq!(plain.action_values(), observed.action_values());
+
+        // The observer stops the search
         let mut mcts = MCTS::new(&game, 1);
-        //println!("MCTS on new game: {:?}", mcts);
+        mcts.search_observed(1000, 1., 10, |progress| progress.iterations < 30);
+        assert_eq!(mcts.roots()[0].visits(), 30);
 
+        let mut mcts = MCTS::new(&game, 1);
+        let mut iterations = Vec::new();
+        let t0 = time::precise_time_s();
+        mcts.search_time_observed(5., 1., 20, |progress| {
+            iterations.push(progress.iterations);
+            progress.iterations < 100
+        });
+        assert!(time::precise_time_s() - t0 < 2.5);
+        assert_eq!(iterations, vec![20, 40, 60, 80, 100]);
 
+        // The observer sees the end of the search, but only once
+        let mut mcts = MCTS::new(&game, 1);
+        let mut reports = Vec::new();
+        let report = mcts.search_time_observed_with(0.05, 1., 1000000, |progress| {
+            reports.push(*progress);
+            true
+        }, &mut seeded_rng(2));
+        assert_eq!(reports.iter().map(|p| p.iterations).collect::<Vec<_>>(), vec![report.iterations]);
+        assert_eq!(reports[0].nodes, report.nodes);
+        let mut iterations = Vec::new();
+        let report = mcts.search_time_observed_with(0.05, 1., 1, |progress| {
+            iterations.push(progress.iterations);
+            true
+        }, &mut seeded_rng(2));
+        assert_eq!(iterations, (1..report.iterations + 1).collect::<Vec<_>>());
+    }
 
-        for i in 0..5 {
-            mcts.root.iteration(&mut game.clone(), 1.0);
-            println!("After {} iteration(s):\n{}", i, mcts);
+    #[test]
+    fn test_search_report() {
+        // Nodes below the root and the depth of the deepest one
+        fn size<A: GameAction>(node: &TreeNode<A>) -> (usize, usize) {
+            node.children().iter().map(size).fold((0, 0), |(nodes, depth), (n, d)| (nodes + n + 1, max(depth, d + 1)))
+        }
+        fn sizes<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>) -> (usize, usize) {
+            mcts.roots().iter().map(size).fold((0, 0), |(nodes, depth), (n, d)| (nodes + n, max(depth, d)))
         }
-    }*/
 
-    #[test]
-    fn test_search() {
         let game = MiniGame::new();
         let mut mcts = MCTS::new(&game, 2);
+        let report = mcts.search_with(300, 1., &mut seeded_rng(3));
+        assert_eq!(report.iterations, 300);
+        assert_eq!((report.nodes, report.max_depth), sizes(&mcts));
+        assert!(report.nodes > 0 && report.nodes <= 600);
+        assert_eq!(report.actions, mcts.action_statistics());
+        assert!(report.elapsed >= 0.);
 
-        mcts.search(50, 1.);
+        // A continued search only counts its own nodes
+        let (nodes, _) = sizes(&mcts);
+        let report = mcts.search_with(100, 1., &mut seeded_rng(4));
+        assert_eq!(report.nodes, sizes(&mcts).0 - nodes);
 
-        println!("Search result: {:?}", mcts.best_action());
+        let mut parallel = MCTS::new(&game, 3);
+        let report = parallel.search_parallel_with(50, 1., 2, &mut seeded_rng(5));
+        assert_eq!((report.nodes, report.max_depth), sizes(&parallel));
+
+        let report = MCTS::new(&game, 1).search_time(0.05, 1.);
+        assert!(report.iterations > 0);
+        let line = format!("{}", report);
+        assert!(line.starts_with(&format!("{} iterations in ", report.iterations)), "{}", line);
+        assert!(line.contains(&format!("{:?} ", report.actions[0].action)), "{}", line);
     }
 
     #[test]
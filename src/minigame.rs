@@ -13,6 +13,7 @@
 
 use std::fmt;
 use mcts::{GameAction, Game};
+use utils::hash_value;
 
 const WINNING_SUM :u32 = 11;
 const DRAW_MIN :u32 = 3;
@@ -54,6 +55,10 @@ impl Game<Action> for MiniGame {
         moves
     }
 
+    fn is_terminal(&self) -> bool {
+        self.sum >= WINNING_SUM
+    }
+
     /// Change the current game state according to the given action.
     fn reward(&self) -> f32 {
              if self.sum <  WINNING_SUM {  0. }
@@ -69,4 +74,8 @@ impl Game<Action> for MiniGame {
 
     /// Derterminize the game
     fn set_rng_seed(&mut self, _: u32) { }
+
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(self))
+    }
 }
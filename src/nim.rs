@@ -0,0 +1,277 @@
+//!
+//! Implementation of Nim.
+//!
+//! Two players take turns removing any positive number of stones from one
+//! heap. In normal play whoever takes the last stone wins, in misère play
+//! whoever takes the last stone loses.
+//!
+//! Nim is solved: in normal play the player to move loses iff the nim-sum,
+//! the XOR of all heap sizes, is zero. Misère play follows the same rule
+//! until only heaps of one stone are left; then the player to move loses iff
+//! their number is odd. `winning_actions` uses this to name the optimal
+//! moves, which makes Nim a good check of the search.
+//!
+//! Player 0 moves first; rewards are given from player 0's perspective.
+//!
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game};
+use utils::hash_value;
+#[cfg(feature = "serde")]
+use protocol::SerializableGame;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Take `take` stones from heap number `heap`.
+pub struct Action {
+    pub heap: u8,
+    pub take: u8,
+}
+impl GameAction for Action {}
+
+#[derive(Debug, Clone, Hash)]
+/// Implementation of the Nim game mechanics.
+pub struct Nim {
+    heaps: Vec<usize>,
+    misere: bool,
+    pub moves: usize,
+}
+
+/// Does the player to move lose the position with these heaps?
+fn is_lost(heaps: &[usize], misere: bool) -> bool {
+    let nim_sum = heaps.iter().fold(0, |sum, &heap| sum ^ heap);
+    if misere && heaps.iter().all(|&heap| heap <= 1) {
+        nim_sum == 1
+    } else {
+        nim_sum == 0
+    }
+}
+
+impl Nim {
+    /// Create a new game in normal play.
+    pub fn new(heaps: &[usize]) -> Nim {
+        Nim {
+            heaps: heaps.to_vec(),
+            misere: false,
+            moves: 0,
+        }
+    }
+
+    /// Create a new game in misère play.
+    pub fn misere(heaps: &[usize]) -> Nim {
+        Nim { misere: true, ..Nim::new(heaps) }
+    }
+
+    /// The stones left on every heap.
+    pub fn heaps(&self) -> &[usize] {
+        &self.heaps
+    }
+
+    /// Does taking the last stone lose?
+    pub fn is_misere(&self) -> bool {
+        self.misere
+    }
+
+    /// The XOR of all heap sizes.
+    pub fn nim_sum(&self) -> usize {
+        self.heaps.iter().fold(0, |sum, &heap| sum ^ heap)
+    }
+
+    /// Is the game over?
+    pub fn is_finished(&self) -> bool {
+        self.heaps.iter().all(|&heap| heap == 0)
+    }
+
+    /// The player who won a finished game.
+    pub fn winner(&self) -> Option<usize> {
+        if !self.is_finished() || self.moves == 0 {
+            return None;
+        }
+        let last = (self.moves - 1) % 2;
+        Some(if self.misere { 1 - last } else { last })
+    }
+
+    /// The moves that leave the opponent with a lost position; empty if the
+    /// player to move loses against perfect play.
+    pub fn winning_actions(&self) -> Vec<Action> {
+        self.allowed_actions().into_iter().filter(|action| {
+            let mut heaps = self.heaps.clone();
+            heaps[action.heap as usize] -= action.take as usize;
+            is_lost(&heaps, self.misere)
+        }).collect()
+    }
+}
+
+impl Game<Action> for Nim {
+    fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for (heap, &stones) in self.heaps.iter().enumerate() {
+            for take in 1..(stones+1) {
+                actions.push(Action { heap: heap as u8, take: take as u8 });
+            }
+        }
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.is_finished()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Action) {
+        let heap = action.heap as usize;
+        assert!(heap < self.heaps.len(), "No such heap");
+        assert!(action.take >= 1 && action.take as usize <= self.heaps[heap], "Illegal number of stones");
+        self.heaps[heap] -= action.take as usize;
+        self.moves += 1;
+    }
+
+    /// Reward from the perspective of player 0.
+    ///
+    /// 1 for a win, -1 for a loss and 0 while the game is in progress.
+    fn reward(&self) -> f32 {
+        match self.winner() {
+            Some(0) =>  1.,
+            Some(_) => -1.,
+            None => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    fn current_player(&self) -> usize {
+        self.moves % 2
+    }
+
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(self))
+    }
+}
+
+/// The heaps, the kind of play and the number of moves made.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for Nim {
+    fn to_json(&self) -> Value {
+        json!({
+            "heaps": self.heaps,
+            "misere": self.misere,
+            "moves": self.moves,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Nim, String> {
+        let heaps = try!(value["heaps"].as_array().ok_or("missing heaps".to_string()));
+        let mut game = Nim::new(&[]);
+        for heap in heaps {
+            match heap.as_u64() {
+                Some(stones) if stones <= 255 => game.heaps.push(stones as usize),
+                _ => return Err(format!("invalid heap '{}'", heap))
+            }
+        }
+        game.misere = value["misere"].as_bool().unwrap_or(false);
+        game.moves = value["moves"].as_u64().unwrap_or(0) as usize;
+        Ok(game)
+    }
+}
+
+impl fmt::Display for Nim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (heap, &stones) in self.heaps.iter().enumerate() {
+            try!(writeln!(f, "{:>3}: {}", heap+1, "|".repeat(stones)));
+        }
+        write!(f, "Player {} to move{}", self.current_player(), if self.misere { " (misère)" } else { "" })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use nim::*;
+    #[cfg(feature = "serde")]
+    use protocol::SerializableGame;
+
+    #[test]
+    fn test_new() {
+        let game = Nim::new(&[3, 4, 5]);
+
+        assert_eq!(game.allowed_actions().len(), 12);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.nim_sum(), 2);
+        assert_eq!(game.reward(), 0.);
+        assert!(!game.is_finished());
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_rules() {
+        // Whoever takes the last stone wins, or loses in misère play
+        for &misere in &[false, true] {
+            let mut game = if misere { Nim::misere(&[2, 1]) } else { Nim::new(&[2, 1]) };
+            game.make_move(&Action { heap: 0, take: 2 });
+            assert_eq!(game.winner(), None);
+            game.make_move(&Action { heap: 1, take: 1 });
+            assert!(game.is_finished());
+            assert!(game.allowed_actions().is_empty());
+            assert_eq!(game.winner(), Some(if misere { 0 } else { 1 }));
+            assert_eq!(game.reward(), if misere { 1. } else { -1. });
+        }
+    }
+
+    #[test]
+    fn test_winning_actions() {
+        // Normal play evens out the nim-sum
+        assert_eq!(Nim::new(&[3, 4, 5]).winning_actions(), vec![Action { heap: 0, take: 2 }]);
+        assert!(Nim::new(&[1, 2, 3]).winning_actions().is_empty());
+
+        // Misère play leaves an odd number of single stones instead
+        assert_eq!(Nim::new(&[1, 1, 3]).winning_actions(), vec![Action { heap: 2, take: 3 }]);
+        assert_eq!(Nim::misere(&[1, 1, 3]).winning_actions(), vec![Action { heap: 2, take: 2 }]);
+        assert!(Nim::misere(&[1]).winning_actions().is_empty());
+        assert!(Nim::misere(&[2, 2]).winning_actions().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = Nim::misere(&[3, 4, 5]);
+        game.make_move(&Action { heap: 1, take: 4 });
+
+        let value = game.to_json();
+        assert_eq!(value, json!({"heaps": [3, 0, 5], "misere": true, "moves": 1}));
+        let restored = Nim::from_json(&value).unwrap();
+        assert_eq!(restored.to_json(), value);
+        assert_eq!(restored.current_player(), 1);
+        assert!(Nim::from_json(&json!({"heaps": [-1]})).is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        let final_game = playout(&Nim::new(&[3, 4, 5]));
+        assert!(final_game.is_finished());
+        assert!(final_game.winner().is_some());
+    }
+
+    #[test]
+    fn test_mcts_wins() {
+        // Taking the whole heap of three stones loses in misère play
+        let game = Nim::misere(&[1, 1, 3]);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(1000, 1.);
+        assert_eq!(mcts.best_action(), Some(Action { heap: 2, take: 2 }));
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = Nim::new(&[3, 4, 5]);
+        b.iter(|| playout(&game));
+    }
+}
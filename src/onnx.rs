@@ -0,0 +1,218 @@
+//!
+//! Leaf evaluation with a trained network in the ONNX format (with the
+//! `onnx` feature).
+//!
+//! The network takes a batch of states, each encoded as `features`
+//! numbers, with the shape `[batch, features]`. Its first output holds the
+//! value of every state for player 0, with the shape `[batch]` or
+//! `[batch, 1]`. An optional second output holds the logits of a policy
+//! over the action ids of `ActionCodec`, `[batch, num_distinct_actions]`.
+//!
+//! ```ignore
+//! let net = OnnxEvaluator::load("2048.onnx", 16, |game: &TwoFortyEight| log2_tiles(game))?;
+//! mcts.run_batched(1000, 32, &net);
+//! ```
+//!
+//! The batches of `MCTS::run_batched` go through the network in a single
+//! run. For the PUCT tree policy, games answer `Game::action_priors` with
+//! the policy of the network, see `OnnxEvaluator::priors`.
+//!
+
+use std::f32;
+use std::slice;
+
+use tract_onnx::prelude::*;
+
+use mcts::{Evaluator, GameAction};
+use openspiel::ActionCodec;
+
+/// An `Evaluator` running an ONNX network on states encoded by `encode`.
+pub struct OnnxEvaluator<F> {
+    plan: TypedSimplePlan<TypedModel>,
+    features: usize,
+    encode: F,
+}
+
+impl<F> OnnxEvaluator<F> {
+    /// Load the network from an ONNX file; `encode` turns a state into
+    /// `features` numbers.
+    pub fn load(path: &str, features: usize, encode: F) -> Result<OnnxEvaluator<F>, String> {
+        let model = try!(tract_onnx::onnx().model_for_path(path)
+            .map_err(|e| format!("Failed to load {}: {}", path, e)));
+        OnnxEvaluator::new(model, features, encode).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Prepare a network that was already read, e.g. with
+    /// `tract_onnx::onnx().model_for_read`, for batches of any size.
+    pub fn new(model: InferenceModel, features: usize, encode: F) -> Result<OnnxEvaluator<F>, String> {
+        let batch = model.symbol_table.sym("batch");
+        let fact = InferenceFact::dt_shape(f32::datum_type(), tvec!(batch.to_dim(), features.to_dim()));
+        let plan = try!(model.with_input_fact(0, fact)
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(|e| format!("Unsuitable network: {}", e)));
+        Ok(OnnxEvaluator { plan: plan, features: features, encode: encode })
+    }
+
+    /// Run the network on a batch of states; returns their values and, if
+    /// the network has a policy, its logits.
+    fn run<G>(&self, games: &[G]) -> (Vec<f32>, Option<Vec<Vec<f32>>>) where F: Fn(&G) -> Vec<f32> {
+        let mut input = Vec::with_capacity(games.len() * self.features);
+        for game in games {
+            let features = (self.encode)(game);
+            assert_eq!(features.len(), self.features, "Encoded {} features instead of {}",
+                       features.len(), self.features);
+            input.extend(features);
+        }
+        let input = Tensor::from_shape(&[games.len(), self.features], &input).expect("Invalid batch");
+        let outputs = self.plan.run(tvec!(input.into())).unwrap_or_else(|e| panic!("Network failed: {}", e));
+
+        let values = outputs[0].as_slice::<f32>().expect("Expected values of type f32").to_vec();
+        assert_eq!(values.len(), games.len(), "Network returned {} values for a batch of {}",
+                   values.len(), games.len());
+        let logits = outputs.get(1).map(|logits| {
+            let logits = logits.as_slice::<f32>().expect("Expected logits of type f32");
+            logits.chunks(logits.len() / games.len().max(1)).map(|row| row.to_vec()).collect()
+        });
+        (values, logits)
+    }
+
+    /// Prior probabilities of the allowed actions of `game`: the softmax
+    /// of the policy logits over them, or uniform if the network has no
+    /// policy.
+    pub fn priors<G, A>(&self, game: &G) -> Vec<(A, f32)>
+            where G: ActionCodec<A>, A: GameAction, F: Fn(&G) -> Vec<f32> {
+        let actions = game.allowed_actions();
+        let logits = match self.run(slice::from_ref(game)).1 {
+            Some(mut logits) => logits.remove(0),
+            None => return actions.iter().map(|&action| (action, 1. / actions.len() as f32)).collect()
+        };
+        let logits = actions.iter().map(|action| logits[game.encode(action) as usize]).collect::<Vec<_>>();
+        let most = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let weights = logits.iter().map(|logit| (logit - most).exp()).collect::<Vec<_>>();
+        let total: f32 = weights.iter().sum();
+        actions.into_iter().zip(weights).map(|(action, weight)| (action, weight / total)).collect()
+    }
+}
+
+impl<G, F: Fn(&G) -> Vec<f32>> Evaluator<G> for OnnxEvaluator<F> {
+    fn evaluate(&self, game: &G) -> f32 {
+        self.run(slice::from_ref(game)).0[0]
+    }
+
+    /// The whole batch goes through the network at once.
+    fn evaluate_batch(&self, games: &[G]) -> Vec<f32> {
+        if games.is_empty() {
+            return Vec::new();
+        }
+        self.run(games).0
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use tract_onnx::pb::*;
+    use tract_onnx::pb::type_proto::Value as TypeValue;
+    use tract_onnx::pb::tensor_shape_proto::dimension::Value as DimValue;
+    use tract_onnx::prelude::*;
+
+    use mcts::*;
+    use onnx::*;
+    use twofortyeight::{self, TwoFortyEight};
+
+    fn float_tensor(name: &str, dims: Vec<i64>, data: Vec<f32>) -> TensorProto {
+        TensorProto { name: name.to_string(), dims: dims, data_type: 1, float_data: data, ..TensorProto::default() }
+    }
+
+    fn matrix_info(name: &str, columns: i64) -> ValueInfoProto {
+        let dims = vec![DimValue::DimParam("batch".to_string()), DimValue::DimValue(columns)];
+        let shape = TensorShapeProto {
+            dim: dims.into_iter().map(|dim| tensor_shape_proto::Dimension { value: Some(dim), ..Default::default() }).collect()
+        };
+        let tensor = type_proto::Tensor { elem_type: 1, shape: Some(shape) };
+        ValueInfoProto { name: name.to_string(), r#type: Some(TypeProto { value: Some(TypeValue::TensorType(tensor)), ..Default::default() }),
+                         ..ValueInfoProto::default() }
+    }
+
+    fn matmul(input: &str, weights: &str, output: &str) -> NodeProto {
+        NodeProto { input: vec![input.to_string(), weights.to_string()], output: vec![output.to_string()],
+                    op_type: "MatMul".to_string(), ..NodeProto::default() }
+    }
+
+    /// A linear network on the 16 tiles of a 2048 board: the value is the
+    /// sum of the tiles over 100, and the logit of every move the tile in
+    /// one of the corners.
+    fn network(policy: bool) -> InferenceModel {
+        let mut initializer = vec![float_tensor("v", vec![16, 1], vec![0.01; 16])];
+        let mut nodes = vec![matmul("x", "v", "value")];
+        let mut output = vec![matrix_info("value", 1)];
+        if policy {
+            let mut weights = vec![0.; 16 * 4];
+            for (action, &cell) in [0, 15, 12, 3].iter().enumerate() {
+                weights[cell * 4 + action] = 1.;
+            }
+            initializer.push(float_tensor("p", vec![16, 4], weights));
+            nodes.push(matmul("x", "p", "policy"));
+            output.push(matrix_info("policy", 4));
+        }
+        let graph = GraphProto {
+            node: nodes, initializer: initializer, input: vec![matrix_info("x", 16)], output: output,
+            ..GraphProto::default()
+        };
+        let model = ModelProto {
+            ir_version: 7,
+            opset_import: vec![OperatorSetIdProto { domain: String::new(), version: 13 }],
+            graph: Some(graph),
+            ..ModelProto::default()
+        };
+        tract_onnx::onnx().model_for_proto_model(&model).unwrap()
+    }
+
+    fn tiles(game: &TwoFortyEight) -> Vec<f32> {
+        (0..16).map(|i| game.get_tile(i / 4, i % 4) as f32).collect()
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let net = OnnxEvaluator::new(network(false), 16, tiles).unwrap();
+        let games = (0..5).map(TwoFortyEight::with_seed).collect::<Vec<_>>();
+        let values = net.evaluate_batch(&games);
+        for (game, value) in games.iter().zip(values) {
+            let sum: f32 = tiles(game).iter().sum();
+            assert!((value - sum / 100.).abs() < 1e-5);
+            assert_eq!(net.evaluate(game), value);
+        }
+        assert!(net.evaluate_batch(&[]).is_empty());
+
+        let game = TwoFortyEight::with_seed(1);
+        let priors = net.priors(&game);
+        assert_eq!(priors.len(), game.allowed_actions().len());
+        assert!(priors.iter().all(|&(_, p)| p == 1. / priors.len() as f32));
+        assert!(OnnxEvaluator::load("/nonexistent/net.onnx", 16, tiles).is_err());
+    }
+
+    #[test]
+    fn test_priors() {
+        let net = OnnxEvaluator::new(network(true), 16, tiles).unwrap();
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 1, 2);
+        game.set_tile(3, 0, 4);
+        let priors = net.priors(&game);
+        let total: f32 = priors.iter().map(|&(_, p)| p).sum();
+        assert!((total - 1.).abs() < 1e-5);
+        // The 4 goes to the bottom left corner, nothing reaches the others
+        let best = priors.iter().cloned().fold(None, |best: Option<(twofortyeight::Action, f32)>, (action, p)| match best {
+            Some((_, q)) if q >= p => best,
+            _ => Some((action, p))
+        });
+        assert_eq!(best.map(|(action, _)| action), Some(twofortyeight::Action::Left));
+
+        // A batched search values its leaves with the network
+        let game = TwoFortyEight::with_seed(2);
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.run_batched(64, 8, &net);
+        assert_eq!(mcts.roots()[0].visits(), 64);
+    }
+}
@@ -0,0 +1,433 @@
+//!
+//! Adapter between OpenSpiel's state conventions and the `Game` trait.
+//!
+//! OpenSpiel states identify actions by integers, mark chance nodes and
+//! terminal states with special player ids and report a returns vector
+//! with one entry per player. `SpielState` mirrors that interface:
+//!
+//!   * `SpielGame` wraps a `SpielState` so it can be searched with the MCTS;
+//!     chance nodes are resolved by sampling inside `make_move`, just like
+//!     the tile spawns in 2048.
+//!   * `GameState` wraps a `Game` with an `ActionCodec` so it can be driven
+//!     through the OpenSpiel interface, e.g. by algorithms ported from
+//!     OpenSpiel.
+//!
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use rand::{Rng, XorShiftRng};
+
+use mcts::{Game, GameAction};
+use twofortyeight::{self, TwoFortyEight};
+use tictactoe::{self, TicTacToe};
+use hex::{self, Hex};
+use utils::seeded_rng;
+
+/// Player id of chance nodes.
+pub const CHANCE_PLAYER: i32 = -1;
+
+/// Player id of terminal states.
+pub const TERMINAL_PLAYER: i32 = -4;
+
+/// A game state following the OpenSpiel conventions.
+pub trait SpielState: Clone {
+    /// Number of players; the returns vector has one entry per player.
+    fn num_players(&self) -> usize;
+
+    /// Player to move, `CHANCE_PLAYER` or `TERMINAL_PLAYER`.
+    fn current_player(&self) -> i32;
+
+    /// Legal actions of the player to move; empty for chance nodes and
+    /// terminal states.
+    fn legal_actions(&self) -> Vec<i64>;
+
+    /// Possible outcomes of a chance node with their probabilities.
+    fn chance_outcomes(&self) -> Vec<(i64, f64)> {
+        Vec::new()
+    }
+
+    /// Apply a legal action or a chance outcome.
+    fn apply_action(&mut self, action: i64);
+
+    /// Whether the game is over.
+    fn is_terminal(&self) -> bool {
+        self.current_player() == TERMINAL_PLAYER
+    }
+
+    /// Whether the next action is drawn by chance.
+    fn is_chance_node(&self) -> bool {
+        self.current_player() == CHANCE_PLAYER
+    }
+
+    /// Total reward of every player so far.
+    fn returns(&self) -> Vec<f64>;
+
+    /// Human-readable description of the state from `player`'s view.
+    fn observation_string(&self, player: i32) -> String;
+
+    /// Human-readable name of an action.
+    fn action_to_string(&self, _player: i32, action: i64) -> String {
+        action.to_string()
+    }
+}
+
+/// Games whose actions can be identified with integers.
+///
+/// Ids run from 0 to `num_distinct_actions() - 1`.
+pub trait ActionCodec<A: GameAction> : Game<A> {
+    /// Number of different action ids.
+    fn num_distinct_actions(&self) -> usize;
+
+    /// Id of the given action.
+    fn encode(&self, action: &A) -> i64;
+
+    /// Action for the given id; None for ids out of range.
+    fn decode(&self, action: i64) -> Option<A>;
+}
+
+impl ActionCodec<twofortyeight::Action> for TwoFortyEight {
+    fn num_distinct_actions(&self) -> usize {
+        4
+    }
+
+    fn encode(&self, action: &twofortyeight::Action) -> i64 {
+        match *action {
+            twofortyeight::Action::Up => 0,
+            twofortyeight::Action::Down => 1,
+            twofortyeight::Action::Left => 2,
+            twofortyeight::Action::Right => 3,
+        }
+    }
+
+    fn decode(&self, action: i64) -> Option<twofortyeight::Action> {
+        match action {
+            0 => Some(twofortyeight::Action::Up),
+            1 => Some(twofortyeight::Action::Down),
+            2 => Some(twofortyeight::Action::Left),
+            3 => Some(twofortyeight::Action::Right),
+            _ => None
+        }
+    }
+}
+
+impl ActionCodec<tictactoe::Action> for TicTacToe {
+    fn num_distinct_actions(&self) -> usize {
+        tictactoe::SIZE * tictactoe::SIZE
+    }
+
+    fn encode(&self, action: &tictactoe::Action) -> i64 {
+        (action.row as usize * tictactoe::SIZE + action.col as usize) as i64
+    }
+
+    fn decode(&self, action: i64) -> Option<tictactoe::Action> {
+        let size = tictactoe::SIZE as i64;
+        if action < 0 || action >= size * size {
+            return None;
+        }
+        Some(tictactoe::Action { row: (action / size) as u8, col: (action % size) as u8 })
+    }
+}
+
+impl ActionCodec<hex::Action> for Hex {
+    fn num_distinct_actions(&self) -> usize {
+        self.size() * self.size()
+    }
+
+    fn encode(&self, action: &hex::Action) -> i64 {
+        (action.row as usize * self.size() + action.col as usize) as i64
+    }
+
+    fn decode(&self, action: i64) -> Option<hex::Action> {
+        let size = self.size() as i64;
+        if action < 0 || action >= size * size {
+            return None;
+        }
+        Some(hex::Action { row: (action / size) as u8, col: (action % size) as u8 })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// OpenSpiel states as Games
+
+/// Action of a `SpielGame`: the OpenSpiel action id.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SpielAction(pub i64);
+impl GameAction for SpielAction {}
+
+/// A `SpielState` searchable with the MCTS.
+///
+/// The reward is the return of player 0, following the convention for
+/// two-player zero-sum games; `reward_for` gives the return of any player.
+#[derive(Clone)]
+pub struct SpielGame<S: SpielState> {
+    pub state: S,
+    rng: XorShiftRng,
+}
+
+impl<S: SpielState> SpielGame<S> {
+    /// Wrap the given state, resolving pending chance nodes.
+    pub fn new(state: S) -> SpielGame<S> {
+        let mut game = SpielGame { state: state, rng: seeded_rng(0) };
+        game.resolve_chance();
+        game
+    }
+
+    /// Sample chance outcomes until a decision or terminal state is reached.
+    fn resolve_chance(&mut self) {
+        while self.state.is_chance_node() {
+            let outcomes = self.state.chance_outcomes();
+            assert!(outcomes.len() > 0, "Chance node without outcomes");
+
+            let mut u = self.rng.gen::<f64>();
+            let mut chosen = outcomes[outcomes.len()-1].0;
+            for &(action, probability) in &outcomes {
+                if u < probability {
+                    chosen = action;
+                    break;
+                }
+                u -= probability;
+            }
+            self.state.apply_action(chosen);
+        }
+    }
+}
+
+impl<S: SpielState> Game<SpielAction> for SpielGame<S> {
+    fn allowed_actions(&self) -> Vec<SpielAction> {
+        if self.state.is_terminal() {
+            return Vec::new();
+        }
+        self.state.legal_actions().into_iter().map(SpielAction).collect()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.state.is_terminal()
+    }
+
+    fn make_move(&mut self, action: &SpielAction) {
+        self.state.apply_action(action.0);
+        self.resolve_chance();
+    }
+
+    fn reward(&self) -> f32 {
+        self.state.returns()[0] as f32
+    }
+
+    /// OpenSpiel returns add up the rewards so far.
+    fn running_reward(&self) -> bool {
+        true
+    }
+
+    fn reward_for(&self, player: usize) -> f32 {
+        self.state.returns()[player] as f32
+    }
+
+    fn evaluate_for(&self, player: usize) -> f32 {
+        self.reward_for(player)
+    }
+
+    fn set_rng_seed(&mut self, seed: u32) {
+        self.rng = seeded_rng(seed);
+    }
+
+    fn current_player(&self) -> usize {
+        let player = self.state.current_player();
+        if player < 0 { 0 } else { player as usize }
+    }
+}
+
+impl<S: SpielState> fmt::Display for SpielGame<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.state.observation_string(self.state.current_player()))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Games as OpenSpiel states
+
+/// A `Game` driven through the OpenSpiel interface.
+///
+/// Randomness inside `make_move` (like the tile spawns in 2048) is not
+/// exposed as chance nodes. The returns are the `reward_for` every player,
+/// `[r, -r]` for two-player zero-sum games with `r` the reward of player 0.
+#[derive(Clone)]
+pub struct GameState<G: ActionCodec<A>, A: GameAction> {
+    pub game: G,
+    num_players: usize,
+    _action: PhantomData<A>,
+}
+
+impl<G: ActionCodec<A>, A: GameAction> GameState<G, A> {
+    /// Wrap a single-player game.
+    pub fn single_player(game: G) -> GameState<G, A> {
+        GameState { game: game, num_players: 1, _action: PhantomData }
+    }
+
+    /// Wrap a two-player zero-sum game.
+    pub fn two_player(game: G) -> GameState<G, A> {
+        GameState { game: game, num_players: 2, _action: PhantomData }
+    }
+
+    /// Wrap a game with the given number of players.
+    pub fn multi_player(game: G, num_players: usize) -> GameState<G, A> {
+        GameState { game: game, num_players: num_players, _action: PhantomData }
+    }
+
+    /// Number of different action ids.
+    pub fn num_distinct_actions(&self) -> usize {
+        self.game.num_distinct_actions()
+    }
+}
+
+impl<G: ActionCodec<A> + fmt::Display, A: GameAction> SpielState for GameState<G, A> {
+    fn num_players(&self) -> usize {
+        self.num_players
+    }
+
+    fn current_player(&self) -> i32 {
+        if self.game.is_terminal() {
+            TERMINAL_PLAYER
+        } else {
+            self.game.current_player() as i32
+        }
+    }
+
+    fn legal_actions(&self) -> Vec<i64> {
+        let mut actions = self.game.allowed_actions().iter()
+            .map(|action| self.game.encode(action))
+            .collect::<Vec<_>>();
+        actions.sort();
+        actions
+    }
+
+    fn apply_action(&mut self, action: i64) {
+        let action = self.game.decode(action).expect("Action id out of range");
+        assert!(self.game.allowed_actions().contains(&action), "Illegal action {:?}", action);
+        self.game.make_move(&action);
+    }
+
+    fn returns(&self) -> Vec<f64> {
+        (0..self.num_players).map(|player| self.game.reward_for(player) as f64).collect()
+    }
+
+    fn observation_string(&self, _player: i32) -> String {
+        format!("{}", self.game)
+    }
+
+    fn action_to_string(&self, _player: i32, action: i64) -> String {
+        match self.game.decode(action) {
+            Some(action) => format!("{:?}", action),
+            None => action.to_string()
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+    use mcts::*;
+    use openspiel::*;
+    use tictactoe::TicTacToe;
+
+    /// A die is rolled, then the player guesses whether it was even.
+    #[derive(Clone)]
+    struct GuessParity {
+        roll: Option<i64>,
+        guess: Option<i64>,
+    }
+
+    impl SpielState for GuessParity {
+        fn num_players(&self) -> usize {
+            1
+        }
+
+        fn current_player(&self) -> i32 {
+            match (self.roll, self.guess) {
+                (None, _) => CHANCE_PLAYER,
+                (Some(_), None) => 0,
+                _ => TERMINAL_PLAYER,
+            }
+        }
+
+        fn legal_actions(&self) -> Vec<i64> {
+            if self.current_player() == 0 { vec![0, 1] } else { vec![] }
+        }
+
+        fn chance_outcomes(&self) -> Vec<(i64, f64)> {
+            // Loaded die: mostly odd rolls
+            vec![(1, 0.3), (2, 0.1), (3, 0.3), (4, 0.1), (5, 0.1), (6, 0.1)]
+        }
+
+        fn apply_action(&mut self, action: i64) {
+            if self.roll.is_none() { self.roll = Some(action) } else { self.guess = Some(action) }
+        }
+
+        fn returns(&self) -> Vec<f64> {
+            match (self.roll, self.guess) {
+                (Some(roll), Some(guess)) if roll % 2 == guess => vec![1.],
+                _ => vec![0.],
+            }
+        }
+
+        fn observation_string(&self, _player: i32) -> String {
+            format!("guess={:?}", self.guess)
+        }
+    }
+
+    #[test]
+    fn test_spiel_game() {
+        let mut game = SpielGame::new(GuessParity { roll: None, guess: None });
+        assert!(game.state.roll.is_some());
+        assert_eq!(game.allowed_actions(), vec![SpielAction(0), SpielAction(1)]);
+
+        game.make_move(&SpielAction(1));
+        assert!(game.allowed_actions().is_empty());
+        assert_eq!(format!("{}", game), "guess=Some(1)");
+    }
+
+    #[test]
+    fn test_spiel_search() {
+        let mut state = GuessParity { roll: None, guess: None };
+        state.apply_action(3);
+
+        let mut mcts = MCTS::new(&SpielGame::new(state), 1);
+        mcts.search(50, 1.);
+        assert_eq!(mcts.best_action(), Some(SpielAction(1)));
+        assert_eq!(mcts.root_value(), Some(1.));
+    }
+
+    #[test]
+    fn test_game_state() {
+        let mut state = GameState::two_player(TicTacToe::new());
+        assert_eq!(state.num_players(), 2);
+        assert_eq!(state.num_distinct_actions(), 9);
+        assert_eq!(state.legal_actions(), (0..9).collect::<Vec<_>>());
+        assert_eq!(state.action_to_string(0, 4), "Action { row: 1, col: 1 }");
+
+        // X: 0, 1, 2; O: 4, 3
+        for &action in &[0, 4, 1, 3] {
+            state.apply_action(action);
+        }
+        assert_eq!(state.current_player(), 0);
+        assert!(!state.is_terminal() && !state.is_chance_node());
+
+        state.apply_action(2);
+        assert!(state.is_terminal());
+        assert_eq!(state.returns(), vec![1., -1.]);
+
+        // And back again
+        let mut game = SpielGame::new(GameState::two_player(TicTacToe::new()));
+        game.make_move(&SpielAction(4));
+        assert_eq!(game.current_player(), 1);
+        assert_eq!(game.allowed_actions().len(), 8);
+    }
+
+    #[bench]
+    fn bench_spiel_playout(b: &mut Bencher) {
+        let game = SpielGame::new(GameState::two_player(TicTacToe::new()));
+        b.iter(|| playout(&game))
+    }
+}
@@ -0,0 +1,531 @@
+//!
+//! Implementation of Pentago.
+//!
+//! Pentago is played on a 6x6 board that is divided into four 3x3 quadrants.
+//! A move consists of two phases: the player first places a marble on an
+//! empty cell and then twists one of the quadrants by 90 degrees, either
+//! clockwise or counterclockwise. Both phases are combined into a single
+//! composite `Action`.
+//!
+//! A player wins with five marbles in a row, horizontally, vertically or
+//! diagonally. As in most computer implementations, lines are only checked
+//! after the twist; if both players have five in a row at that point, or if
+//! the board is full, the game is a draw.
+//!
+//! The rules are invariant under rotating and mirroring the whole board,
+//! so each position has up to eight equivalent representations;
+//! `canonical_key` maps all of them to the same number, which is also the
+//! hash of the state, so transpositions share equivalent positions.
+//!
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game};
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, parse_board_rows};
+
+pub const SIZE: usize = 6;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Player {
+    White, Black
+}
+
+impl Player {
+    /// The other player.
+    pub fn opponent(&self) -> Player {
+        match *self {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Place a marble on (row, col), then twist a quadrant.
+///
+/// Quadrants are numbered 0 (top left), 1 (top right), 2 (bottom left)
+/// and 3 (bottom right).
+pub struct Action {
+    pub row: u8,
+    pub col: u8,
+    pub quadrant: u8,
+    pub clockwise: bool,
+}
+impl GameAction for Action {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Outcome of a finished game.
+pub enum Outcome {
+    Win(Player),
+    Draw,
+}
+
+#[derive(Clone, Hash)]
+/// Implementation of the Pentago game mechanics.
+pub struct Pentago {
+    board: [Option<Player>; SIZE*SIZE],
+    to_move: Player,
+    outcome: Option<Outcome>,
+    pub moves: usize,
+}
+
+impl Pentago {
+    /// Create a new game with an empty board; white moves first.
+    pub fn new() -> Pentago {
+        Pentago {
+            board: [None; SIZE*SIZE],
+            to_move: Player::White,
+            outcome: None,
+            moves: 0,
+        }
+    }
+
+    /// The marble on the given cell, if any.
+    pub fn get_cell(&self, row: usize, col: usize) -> Option<Player> {
+        self.board[row * SIZE + col]
+    }
+
+    /// Put a marble on the given cell (or remove it).
+    ///
+    /// This does not change the player to move nor check for a winner.
+    pub fn set_cell(&mut self, row: usize, col: usize, cell: Option<Player>) {
+        self.board[row * SIZE + col] = cell;
+    }
+
+    /// The player who makes the next move.
+    pub fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    /// The outcome of the game, or `None` while it is still running.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// Twist the given quadrant by 90 degrees.
+    fn twist(board: &mut [Option<Player>; SIZE*SIZE], quadrant: usize, clockwise: bool) {
+        let row0 = 3 * (quadrant / 2);
+        let col0 = 3 * (quadrant % 2);
+
+        let old = *board;
+        for r in 0..3 {
+            for c in 0..3 {
+                let (src_r, src_c) = if clockwise { (2-c, r) } else { (c, 2-r) };
+                board[(row0+r)*SIZE + col0+c] = old[(row0+src_r)*SIZE + col0+src_c];
+            }
+        }
+    }
+
+    /// Does the given player have five marbles in a row?
+    fn has_five(&self, player: Player) -> bool {
+        let directions: [(isize, isize); 4] = [(0, 1), (1, 0), (1, 1), (1, -1)];
+        let size = SIZE as isize;
+
+        for row in 0..size {
+            for col in 0..size {
+                for &(dr, dc) in &directions {
+                    let end_r = row + 4*dr;
+                    let end_c = col + 4*dc;
+                    if end_r < 0 || end_r >= size || end_c < 0 || end_c >= size {
+                        continue;
+                    }
+                    let five = (0..5).all(|i| {
+                        let idx = (row + i*dr) * size + col + i*dc;
+                        self.board[idx as usize] == Some(player)
+                    });
+                    if five {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Determine the outcome from the marbles on the board.
+    fn update_outcome(&mut self) {
+        let white = self.has_five(Player::White);
+        let black = self.has_five(Player::Black);
+        self.outcome = match (white, black) {
+            (true, true)   => Some(Outcome::Draw),
+            (true, false)  => Some(Outcome::Win(Player::White)),
+            (false, true)  => Some(Outcome::Win(Player::Black)),
+            (false, false) if self.moves == SIZE*SIZE => Some(Outcome::Draw),
+            _ => None
+        };
+    }
+
+    /// Encode the board as a base-3 number.
+    fn encode(board: &[Option<Player>; SIZE*SIZE]) -> u64 {
+        board.iter().fold(0, |key, cell| {
+            3*key + match *cell {
+                None                => 0,
+                Some(Player::White) => 1,
+                Some(Player::Black) => 2,
+            }
+        })
+    }
+
+    /// A number that uniquely identifies the current board.
+    pub fn key(&self) -> u64 {
+        Pentago::encode(&self.board)
+    }
+
+    /// A number that identifies the current board up to rotations and
+    /// reflections of the whole board.
+    pub fn canonical_key(&self) -> u64 {
+        let mut best = u64::max_value();
+        let mut board = self.board;
+        for _ in 0..4 {
+            // Rotate the whole board clockwise by 90 degrees
+            let old = board;
+            for r in 0..SIZE {
+                for c in 0..SIZE {
+                    board[r*SIZE + c] = old[(SIZE-1-c)*SIZE + r];
+                }
+            }
+            // ... and consider its mirror image as well
+            let mut mirrored = board;
+            for r in 0..SIZE {
+                for c in 0..SIZE {
+                    mirrored[r*SIZE + c] = board[r*SIZE + SIZE-1-c];
+                }
+            }
+            best = best.min(Pentago::encode(&board)).min(Pentago::encode(&mirrored));
+        }
+        best
+    }
+}
+
+impl Game<Action> for Pentago {
+
+    /// Return a list with all allowed actions given the current game state.
+    fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.outcome.is_some() {
+            return actions;
+        }
+
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                if self.get_cell(row, col).is_some() {
+                    continue;
+                }
+                for quadrant in 0..4 {
+                    for &clockwise in &[true, false] {
+                        actions.push(Action {
+                            row: row as u8,
+                            col: col as u8,
+                            quadrant: quadrant,
+                            clockwise: clockwise,
+                        });
+                    }
+                }
+            }
+        }
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.outcome.is_some()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Action) {
+        let (row, col) = (action.row as usize, action.col as usize);
+        assert!(self.outcome.is_none(), "Game is already over");
+        assert!(self.get_cell(row, col).is_none(), "Illegal move");
+
+        let player = self.to_move;
+        self.set_cell(row, col, Some(player));
+        Pentago::twist(&mut self.board, action.quadrant as usize, action.clockwise);
+        self.moves += 1;
+        self.to_move = player.opponent();
+        self.update_outcome();
+    }
+
+    /// Reward from the perspective of the white player.
+    ///
+    /// 1 for a win, -1 for a loss and 0 for draws and unfinished games.
+    fn reward(&self) -> f32 {
+        match self.outcome {
+            Some(Outcome::Win(Player::White)) =>  1.,
+            Some(Outcome::Win(Player::Black)) => -1.,
+            _ => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    /// White is player 0, black is player 1.
+    fn current_player(&self) -> usize {
+        match self.to_move {
+            Player::White => 0,
+            Player::Black => 1,
+        }
+    }
+
+    /// Equivalent boards hash the same, see `canonical_key`; the stones on
+    /// the board determine the player to move.
+    fn hash_state(&self) -> Option<u64> {
+        Some(self.canonical_key())
+    }
+}
+
+/// The board as rows of `O` (white), `X` (black) and `.`, plus the
+/// player to move (`"White"` or `"Black"`).
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for Pentago {
+    fn to_json(&self) -> Value {
+        let rows = (0..SIZE).map(|row| {
+            (0..SIZE).map(|col| match self.get_cell(row, col) {
+                None                => '.',
+                Some(Player::White) => 'O',
+                Some(Player::Black) => 'X',
+            }).collect::<String>()
+        }).collect::<Vec<_>>();
+        json!({ "board": rows, "to_move": format!("{:?}", self.to_move) })
+    }
+
+    fn from_json(value: &Value) -> Result<Pentago, String> {
+        let mut game = Pentago::new();
+        for (row, cells) in try!(parse_board_rows(value, SIZE)).iter().enumerate() {
+            for (col, &cell) in cells.iter().enumerate() {
+                let cell = match cell {
+                    '.' => None,
+                    'O' => Some(Player::White),
+                    'X' => Some(Player::Black),
+                    _ => return Err(format!("invalid cell '{}'", cell))
+                };
+                game.set_cell(row, col, cell);
+                if cell.is_some() {
+                    game.moves += 1;
+                }
+            }
+        }
+        game.to_move = match value["to_move"].as_str() {
+            Some("White") => Player::White,
+            Some("Black") => Player::Black,
+            _ => return Err("to_move must be \"White\" or \"Black\"".to_string())
+        };
+        game.update_outcome();
+        Ok(game)
+    }
+}
+
+impl fmt::Display for Pentago {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "Moves={} To move: {:?}", self.moves, self.to_move));
+        for row in 0..SIZE {
+            if row == 3 {
+                try!(writeln!(f, "-------+-------"));
+            }
+            for col in 0..SIZE {
+                if col == 3 {
+                    try!(f.write_str(" |"));
+                }
+                let symbol = match self.get_cell(row, col) {
+                    None                => " .",
+                    Some(Player::White) => " O",
+                    Some(Player::Black) => " X",
+                };
+                try!(f.write_str(symbol));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("")
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    #[cfg(feature = "serde")]
+    use protocol::SerializableGame;
+    use pentago::*;
+
+    fn action(row: u8, col: u8, quadrant: u8, clockwise: bool) -> Action {
+        Action { row: row, col: col, quadrant: quadrant, clockwise: clockwise }
+    }
+
+    #[test]
+    fn test_new() {
+        let game = Pentago::new();
+
+        assert_eq!(game.allowed_actions().len(), 36 * 8);
+        assert_eq!(game.to_move(), Player::White);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.outcome(), None);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_twist() {
+        let mut game = Pentago::new();
+
+        // Top left corner of the bottom right quadrant moves to its top right corner
+        game.make_move(&action(3, 3, 3, true));
+        assert_eq!(game.get_cell(3, 5), Some(Player::White));
+        assert_eq!(game.get_cell(3, 3), None);
+        assert_eq!(game.to_move(), Player::Black);
+        assert_eq!(game.current_player(), 1);
+
+        // Twisting back and forth is the identity
+        let before = game.key();
+        let mut board = game.board;
+        Pentago::twist(&mut board, 3, true);
+        Pentago::twist(&mut board, 3, false);
+        assert_eq!(Pentago::encode(&board), before);
+
+        // Four twists into the same direction are the identity as well
+        for _ in 0..4 {
+            Pentago::twist(&mut board, 3, false);
+        }
+        assert_eq!(Pentago::encode(&board), before);
+    }
+
+    #[test]
+    fn test_five_in_a_row() {
+        let mut game = Pentago::new();
+        for col in 0..4 {
+            game.set_cell(0, col, Some(Player::White));
+        }
+
+        // Twisting the bottom left quadrant does not disturb the top row
+        game.make_move(&action(0, 4, 2, true));
+        assert_eq!(game.outcome(), Some(Outcome::Win(Player::White)));
+        assert_eq!(game.reward(), 1.);
+        assert_eq!(game.allowed_actions().len(), 0);
+    }
+
+    #[test]
+    fn test_diagonal() {
+        let mut game = Pentago::new();
+        for i in 1..5 {
+            game.set_cell(i, i, Some(Player::Black));
+        }
+        game.to_move = Player::Black;
+
+        // The twist of the top right quadrant keeps the diagonal intact
+        game.make_move(&action(5, 5, 1, false));
+        assert_eq!(game.outcome(), Some(Outcome::Win(Player::Black)));
+        assert_eq!(game.reward(), -1.);
+    }
+
+    #[test]
+    fn test_twist_completes_line() {
+        let mut game = Pentago::new();
+        game.set_cell(0, 3, Some(Player::White));
+        game.set_cell(0, 4, Some(Player::White));
+
+        // Twisting clockwise turns the left column of a quadrant into its top row
+        for row in 0..3 {
+            game.set_cell(row, 0, Some(Player::White));
+        }
+
+        game.make_move(&action(5, 5, 0, true));
+        assert_eq!(game.outcome(), Some(Outcome::Win(Player::White)));
+    }
+
+    #[test]
+    fn test_both_five_is_draw() {
+        let mut game = Pentago::new();
+        for col in 0..4 {
+            game.set_cell(0, col, Some(Player::White));
+        }
+
+        // Black completes row 4 when (5, 4) is twisted onto (4, 3)
+        for &col in &[0, 1, 2, 4] {
+            game.set_cell(4, col, Some(Player::Black));
+        }
+        game.set_cell(5, 4, Some(Player::Black));
+
+        game.make_move(&action(0, 4, 3, true));
+        assert_eq!(game.outcome(), Some(Outcome::Draw));
+        assert_eq!(game.reward(), 0.);
+    }
+
+    #[test]
+    fn test_canonical_key() {
+        let mut game = Pentago::new();
+        game.set_cell(0, 1, Some(Player::White));
+        game.set_cell(2, 4, Some(Player::Black));
+
+        // Rotate the whole board counterclockwise
+        let mut rotated = Pentago::new();
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                rotated.set_cell(SIZE-1-col, row, game.get_cell(row, col));
+            }
+        }
+        // Mirror it
+        let mut mirrored = Pentago::new();
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                mirrored.set_cell(row, SIZE-1-col, game.get_cell(row, col));
+            }
+        }
+
+        assert!(game.key() != rotated.key());
+        assert!(game.key() != mirrored.key());
+        assert_eq!(game.canonical_key(), rotated.canonical_key());
+        assert_eq!(game.canonical_key(), mirrored.canonical_key());
+        assert!(game.canonical_key() != Pentago::new().canonical_key());
+        assert_eq!(game.hash_state(), rotated.hash_state());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = Pentago::new();
+        game.make_move(&action(0, 0, 3, true));
+        game.make_move(&action(1, 1, 3, true));
+
+        let value = game.to_json();
+        assert_eq!(value["board"][1], ".X....");
+        assert_eq!(value["to_move"], "White");
+        let restored = Pentago::from_json(&value).unwrap();
+        assert_eq!(restored.key(), game.key());
+        assert_eq!(restored.moves, 2);
+
+        let won = json!({"board": ["OOOOO.", "XXXX..", "......", "......", "......", "......"],
+                         "to_move": "Black"});
+        assert_eq!(Pentago::from_json(&won).unwrap().outcome(), Some(Outcome::Win(Player::White)));
+        assert!(Pentago::from_json(&json!({"board": ["O"], "to_move": "White"})).is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        let game = Pentago::new();
+        let final_game = playout(&game);
+
+        assert!(final_game.outcome().is_some());
+        assert!(final_game.moves <= SIZE*SIZE);
+        println!("{}", final_game);
+    }
+
+    #[test]
+    fn test_mcts() {
+        let game = Pentago::new();
+        let mut mcts = MCTS::new(&game, 1);
+
+        mcts.search(50, 1.);
+        let action = mcts.best_action();
+        action.expect("should give some action");
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = Pentago::new();
+        b.iter(|| playout(&game));
+    }
+}
@@ -0,0 +1,535 @@
+//!
+//! AlphaZero-style training loop.
+//!
+//! `Pipeline` coordinates the parts of a generation: self-play workers
+//! play games with the search valuing its leaves with the current network,
+//! every position goes into a `ReplayBuffer` along with the visit shares of
+//! its search and the final outcome, a `Trainer` fits a candidate network
+//! to batches drawn from the buffer, and the candidate plays a gating match
+//! against the current network. It only takes over if it scores at least
+//! `PipelineConfig::gate`; the networks that take over are checkpointed by
+//! the trainer.
+//!
+//! The crate has no networks of its own. The trainer brings them as
+//! `Evaluator`s, e.g. `torch::TorchEvaluator`, which trains in-process,
+//! or `onnx::OnnxEvaluator` with the fitting done in another framework;
+//! `value_loss` and `policy_loss` are the usual targets. Policies reach
+//! the search through `Game::action_priors`.
+//!
+//! Gating matches assume two players taking turns, like the zero-sum games
+//! of the crate. Every game is seeded from the master seed, so the results
+//! do not depend on the number of workers.
+//!
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::f32;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use rand::Rng;
+
+use arena::MatchStats;
+use mcts::{Game, GameAction, Evaluator, MctsConfig, MCTS};
+use utils::{seeded_rng, derive_seed, sample_indices_with, sample_softmax_with};
+
+/// A position of a self-play game with its training targets.
+#[derive(Debug, Clone)]
+pub struct Sample<G, A> {
+    pub game: G,
+    /// Visit shares of the actions at the root of the search.
+    pub policy: Vec<(A, f32)>,
+    /// Final outcome of the game for player 0.
+    pub outcome: f32,
+}
+
+/// The most recent self-play samples.
+pub struct ReplayBuffer<G, A> {
+    capacity: usize,
+    samples: VecDeque<Sample<G, A>>,
+}
+
+impl<G, A> ReplayBuffer<G, A> {
+    /// Create a buffer that keeps up to `capacity` samples.
+    pub fn new(capacity: usize) -> ReplayBuffer<G, A> {
+        ReplayBuffer { capacity: capacity, samples: VecDeque::new() }
+    }
+
+    /// Add a sample, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, sample: Sample<G, A>) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        if self.capacity > 0 {
+            self.samples.push_back(sample);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The samples, oldest first.
+    pub fn iter(&self) -> ::std::collections::vec_deque::Iter<'_, Sample<G, A>> {
+        self.samples.iter()
+    }
+
+    /// Up to `size` different samples, drawn uniformly with `rng`.
+    pub fn batch_with<R: Rng>(&self, size: usize, rng: &mut R) -> Vec<&Sample<G, A>> {
+        sample_indices_with(rng, self.samples.len(), size).into_iter().map(|idx| &self.samples[idx]).collect()
+    }
+}
+
+/// Fits networks to self-play samples, see `Pipeline`.
+pub trait Trainer<G, A> {
+    /// The networks, which value the leaves of the searches.
+    type Net: Evaluator<G>;
+
+    /// A network trained from `net` on one batch of samples.
+    fn train(&mut self, net: &Self::Net, batch: &[&Sample<G, A>]) -> Self::Net;
+
+    /// Keep the network of the given generation, which took over from its
+    /// predecessor. The default keeps nothing.
+    fn checkpoint(&mut self, _net: &Self::Net, _generation: usize) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Squared error of a network's `value` against the `outcome` of a game.
+pub fn value_loss(outcome: f32, value: f32) -> f32 {
+    (outcome - value) * (outcome - value)
+}
+
+/// Cross-entropy of a network's `priors` against the search `policy`.
+pub fn policy_loss<A: GameAction>(policy: &[(A, f32)], priors: &[(A, f32)]) -> f32 {
+    policy.iter().filter(|&&(_, share)| share > 0.).map(|&(action, share)| {
+        let prior = priors.iter().find(|&&(a, _)| a == action).map_or(0., |&(_, p)| p);
+        -share * prior.max(f32::MIN_POSITIVE).ln()
+    }).sum()
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Parameters of the training loop.
+pub struct PipelineConfig {
+    /// Self-play games per generation.
+    pub games: usize,
+    /// Threads playing the games of a generation.
+    pub workers: usize,
+    /// Search iterations per move.
+    pub iterations: usize,
+    /// Leaves the network values at once, see `MCTS::run_batched`.
+    pub batch_size: usize,
+    /// Moves at the start of a game that are sampled by their visit
+    /// shares; the most visited move is played afterwards.
+    pub sampled_moves: usize,
+    /// Moves after which self-play and gating games stop and score the
+    /// state they reached with `Game::reward_for`, so that games which do
+    /// not finish cannot stall a generation.
+    pub max_moves: usize,
+    /// Samples the replay buffer keeps.
+    pub buffer_size: usize,
+    /// Training batches per generation.
+    pub training_steps: usize,
+    /// Samples per training batch.
+    pub training_batch: usize,
+    /// Games of the gating match.
+    pub gating_games: usize,
+    /// Score the candidate needs in the gating match to take over.
+    pub gate: f32,
+    /// The search; its root noise only applies to self-play.
+    pub mcts: MctsConfig,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> PipelineConfig {
+        PipelineConfig {
+            games: 100,
+            workers: 1,
+            iterations: 200,
+            batch_size: 8,
+            sampled_moves: 4,
+            max_moves: 1000,
+            buffer_size: 100000,
+            training_steps: 100,
+            training_batch: 256,
+            gating_games: 40,
+            gate: 0.55,
+            mcts: MctsConfig::default(),
+        }
+    }
+}
+
+/// What happened in a generation, see `Pipeline::step`.
+#[derive(Debug, Clone)]
+pub struct GenerationReport {
+    /// The generation of the network after this one.
+    pub generation: usize,
+    pub games: usize,
+    pub samples: usize,
+    /// Mean losses of the previous network on the training batches.
+    pub value_loss: f32,
+    pub policy_loss: f32,
+    /// Results of the candidate against the previous network.
+    pub gating: MatchStats,
+    pub accepted: bool,
+}
+
+impl fmt::Display for GenerationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Generation {}: {} games, {} samples, value loss {:.4}, policy loss {:.4}; gating {} ({})",
+               self.generation, self.games, self.samples, self.value_loss, self.policy_loss, self.gating,
+               if self.accepted { "accepted" } else { "rejected" })
+    }
+}
+
+/// Play a move out of `game`: sample a chance outcome, or search with
+/// `net` and play the most visited move, or one sampled by the visit
+/// shares if `sampled`. Returns the visit shares of a search.
+fn play_move<G, A, E, R>(game: &mut G, net: &E, config: &PipelineConfig, mcts: MctsConfig, sampled: bool,
+                         rng: &mut R) -> Option<Vec<(A, f32)>>
+        where G: Game<A>, A: GameAction, E: Evaluator<G>, R: Rng {
+    if let Some(outcomes) = game.chance_outcomes() {
+        let weights = outcomes.iter().map(|&(_, p)| p.ln()).collect::<Vec<_>>();
+        let idx = sample_softmax_with(rng, &weights, 1.).expect("No chance outcomes");
+        game.make_move(&outcomes[idx].0);
+        return None;
+    }
+
+    let mut search = MCTS::with_config(game, 1, mcts);
+    search.run_batched_with(config.iterations, config.batch_size, net, rng);
+    let values = search.action_values();
+    let total = values.iter().map(|&(_, visits, _)| visits).sum::<u64>().max(1) as f32;
+    let policy = values.iter().map(|&(action, visits, _)| (action, visits as f32 / total)).collect::<Vec<_>>();
+    let idx = if sampled {
+        let scores = policy.iter().map(|&(_, share)| share.ln()).collect::<Vec<_>>();
+        sample_softmax_with(rng, &scores, 1.).expect("No moves to play")
+    } else {
+        (0..values.len()).max_by_key(|&idx| values[idx].1).expect("No moves to play")
+    };
+    game.make_move(&values[idx].0);
+    Some(policy)
+}
+
+/// Play a self-play game with `net` for up to `PipelineConfig::max_moves`
+/// moves; returns every position where a player moved, with the visit
+/// shares of its search, as a sample.
+pub fn self_play_with<G, A, E, R>(initial: &G, net: &E, config: &PipelineConfig, rng: &mut R) -> Vec<Sample<G, A>>
+        where G: Game<A>, A: GameAction, E: Evaluator<G>, R: Rng {
+    let mut game = initial.clone();
+    let mut samples = Vec::new();
+    while !game.is_terminal() && samples.len() < config.max_moves {
+        let position = game.clone();
+        let sampled = samples.len() < config.sampled_moves;
+        if let Some(policy) = play_move(&mut game, net, config, config.mcts, sampled, rng) {
+            samples.push(Sample { game: position, policy: policy, outcome: 0. });
+        }
+    }
+    let outcome = game.reward_for(0);
+    for sample in &mut samples {
+        sample.outcome = outcome;
+    }
+    samples
+}
+
+/// Play a game of `candidate` against `current` without root noise for up
+/// to `PipelineConfig::max_moves` moves, the candidate moving as `seat`;
+/// returns the reward of the candidate.
+pub fn gating_game_with<G, A, E, R>(initial: &G, candidate: &E, current: &E, seat: usize,
+                                    config: &PipelineConfig, rng: &mut R) -> f32
+        where G: Game<A>, A: GameAction, E: Evaluator<G>, R: Rng {
+    let mcts = MctsConfig { root_noise: None, ..config.mcts };
+    let mut game = initial.clone();
+    let mut moves = 0;
+    while !game.is_terminal() && moves < config.max_moves {
+        let net = if game.current_player() == seat { candidate } else { current };
+        let sampled = moves < config.sampled_moves;
+        if play_move(&mut game, net, config, mcts, sampled, rng).is_some() {
+            moves += 1;
+        }
+    }
+    game.reward_for(seat)
+}
+
+/// Call `play` for the games 0 to `n` on `workers` threads; returns the
+/// results in the order of the games.
+fn play_games<T: Send, F: Fn(usize) -> T + Sync>(n: usize, workers: usize, play: F) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..n).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let g = next.fetch_add(1, Ordering::SeqCst);
+                    if g >= n {
+                        break;
+                    }
+                    let result = play(g);
+                    results.lock().unwrap()[g] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+}
+
+/// The training loop for one game, see the module documentation.
+pub struct Pipeline<G, A, T: Trainer<G, A>> {
+    pub config: PipelineConfig,
+    initial: G,
+    trainer: T,
+    net: T::Net,
+    buffer: ReplayBuffer<G, A>,
+    generation: usize,
+    round: u32,
+    seed: u32,
+}
+
+impl<G, A, T> Pipeline<G, A, T>
+        where G: Game<A> + Send + Sync, A: GameAction + Send + Sync, T: Trainer<G, A>, T::Net: Sync {
+    /// Start training from `net` on games starting at `initial`, seeding
+    /// all games from `seed`.
+    pub fn new(initial: &G, trainer: T, net: T::Net, config: PipelineConfig, seed: u32) -> Pipeline<G, A, T> {
+        Pipeline {
+            config: config,
+            initial: initial.clone(),
+            trainer: trainer,
+            net: net,
+            buffer: ReplayBuffer::new(config.buffer_size),
+            generation: 0,
+            round: 0,
+            seed: seed,
+        }
+    }
+
+    /// The current network.
+    pub fn net(&self) -> &T::Net {
+        &self.net
+    }
+
+    /// Number of networks that took over so far.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn buffer(&self) -> &ReplayBuffer<G, A> {
+        &self.buffer
+    }
+
+    pub fn trainer(&self) -> &T {
+        &self.trainer
+    }
+
+    /// Run a generation: self-play, training and the gating match. A
+    /// candidate that takes over is checkpointed; fails if that fails.
+    pub fn step(&mut self) -> Result<GenerationReport, String> {
+        let config = self.config;
+        let master = derive_seed(self.seed, self.round);
+        self.round += 1;
+
+        let games = {
+            let (initial, net) = (&self.initial, &self.net);
+            play_games(config.games, config.workers, |g| {
+                self_play_with(initial, net, &config, &mut seeded_rng(derive_seed(master, 2*g as u32)))
+            })
+        };
+        let samples = games.iter().map(|samples| samples.len()).sum();
+        for sample in games.into_iter().flatten() {
+            self.buffer.push(sample);
+        }
+
+        let mut rng = seeded_rng(derive_seed(master, 2*config.games as u32));
+        let (mut value_error, mut policy_error, mut count) = (0., 0., 0);
+        let mut candidate: Option<T::Net> = None;
+        for _ in 0..config.training_steps {
+            let batch = self.buffer.batch_with(config.training_batch, &mut rng);
+            let states = batch.iter().map(|sample| sample.game.clone()).collect::<Vec<_>>();
+            for (sample, value) in batch.iter().zip(self.net.evaluate_batch(&states)) {
+                value_error += value_loss(sample.outcome, value);
+                policy_error += policy_loss(&sample.policy, &sample.game.action_priors());
+                count += 1;
+            }
+            let trained = self.trainer.train(candidate.as_ref().unwrap_or(&self.net), &batch);
+            candidate = Some(trained);
+        }
+
+        let mut gating = MatchStats::new();
+        if let Some(candidate) = candidate {
+            let rewards = {
+                let (initial, current, candidate) = (&self.initial, &self.net, &candidate);
+                play_games(config.gating_games, config.workers, |g| {
+                    let mut rng = seeded_rng(derive_seed(master, 2*g as u32 + 1));
+                    gating_game_with(initial, candidate, current, g % 2, &config, &mut rng)
+                })
+            };
+            for reward in rewards {
+                gating.add_result(reward);
+            }
+            if gating.games() > 0 && gating.score() >= config.gate {
+                self.generation += 1;
+                try!(self.trainer.checkpoint(&candidate, self.generation));
+                self.net = candidate;
+            }
+        }
+
+        let count = count.max(1) as f32;
+        Ok(GenerationReport {
+            generation: self.generation,
+            games: config.games,
+            samples: samples,
+            value_loss: value_error / count,
+            policy_loss: policy_error / count,
+            gating: gating,
+            accepted: gating.games() > 0 && gating.score() >= config.gate,
+        })
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use pipeline::*;
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+    struct Take(usize);
+    impl GameAction for Take {}
+
+    /// Players take 1 to 3 stones in turn; whoever takes the last one wins.
+    #[derive(Debug, Clone)]
+    struct Stones {
+        left: usize,
+        moves: usize,
+    }
+
+    impl Game<Take> for Stones {
+        fn allowed_actions(&self) -> Vec<Take> {
+            (1..4).filter(|&n| n <= self.left).map(Take).collect()
+        }
+
+        fn make_move(&mut self, action: &Take) {
+            self.left -= action.0;
+            self.moves += 1;
+        }
+
+        fn reward(&self) -> f32 {
+            match (self.left, self.moves % 2) {
+                (0, 1) => 1.,
+                (0, _) => -1.,
+                _ => 0.
+            }
+        }
+
+        fn set_rng_seed(&mut self, _: u32) {}
+
+        fn current_player(&self) -> usize {
+            self.moves % 2
+        }
+    }
+
+    /// Values the positions perfectly, or the opposite way for `Net(-1)`:
+    /// the player to move loses if the stones left are a multiple of 4.
+    struct Net(f32);
+
+    impl Evaluator<Stones> for Net {
+        fn evaluate(&self, game: &Stones) -> f32 {
+            let to_move = match game.left % 4 { 0 => -1., _ => 1. };
+            let value = if game.current_player() == 0 { to_move } else { -to_move };
+            self.0 * value
+        }
+    }
+
+    /// Always comes up with the perfect network; remembers the batch sizes
+    /// and checkpoints.
+    #[derive(Default)]
+    struct Oracle {
+        batches: Vec<usize>,
+        checkpoints: Vec<usize>,
+    }
+
+    impl Trainer<Stones, Take> for Oracle {
+        type Net = Net;
+
+        fn train(&mut self, _: &Net, batch: &[&Sample<Stones, Take>]) -> Net {
+            self.batches.push(batch.len());
+            Net(1.)
+        }
+
+        fn checkpoint(&mut self, _: &Net, generation: usize) -> Result<(), String> {
+            self.checkpoints.push(generation);
+            Ok(())
+        }
+    }
+
+    fn config(workers: usize) -> PipelineConfig {
+        PipelineConfig {
+            games: 6, workers: workers, iterations: 40, batch_size: 4, sampled_moves: 2, max_moves: 20, buffer_size: 30,
+            training_steps: 3, training_batch: 16, gating_games: 10, gate: 0.55,
+            mcts: MctsConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_losses() {
+        assert_eq!(value_loss(1., 0.5), 0.25);
+        let policy = vec![(Take(1), 0.5), (Take(2), 0.5), (Take(3), 0.)];
+        let entropy = 2f32.ln();
+        assert!((policy_loss(&policy, &policy) - entropy).abs() < 1e-6);
+        assert!(policy_loss(&policy, &[(Take(1), 0.9), (Take(2), 0.1)]) > entropy);
+        assert!(policy_loss(&policy, &[(Take(1), 1.)]) > 10.);
+    }
+
+    #[test]
+    fn test_replay_buffer() {
+        let mut buffer = ReplayBuffer::new(3);
+        for left in 0..5 {
+            buffer.push(Sample { game: Stones { left: left, moves: 0 }, policy: vec![(Take(1), 1.)], outcome: 0. });
+        }
+        assert_eq!(buffer.iter().map(|sample| sample.game.left).collect::<Vec<_>>(), vec![2, 3, 4]);
+        let batch = buffer.batch_with(5, &mut ::utils::seeded_rng(1));
+        assert_eq!(batch.len(), 3);
+    }
+
+    #[test]
+    fn test_max_moves() {
+        let game = Stones { left: 100, moves: 0 };
+        let config = PipelineConfig { max_moves: 5, ..config(1) };
+        let mut rng = ::utils::seeded_rng(3);
+        let samples = self_play_with(&game, &Net(1.), &config, &mut rng);
+        assert_eq!(samples.len(), 5);
+        assert!(samples.iter().all(|sample| sample.outcome == 0.));
+        assert_eq!(gating_game_with(&game, &Net(1.), &Net(-1.), 0, &config, &mut rng), 0.);
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let game = Stones { left: 10, moves: 0 };
+        let mut pipeline = Pipeline::new(&game, Oracle::default(), Net(-1.), config(2), 7);
+        let report = pipeline.step().unwrap();
+        assert!(report.accepted, "{}", report);
+        assert_eq!(pipeline.generation(), 1);
+        assert_eq!(pipeline.net().0, 1.);
+        assert_eq!(pipeline.trainer().checkpoints, vec![1]);
+        assert_eq!(pipeline.trainer().batches, vec![16; 3]);
+        // The backwards network got most outcomes wrong
+        assert!(report.value_loss > 2.);
+
+        assert_eq!(pipeline.buffer().len(), report.samples.min(30));
+        for sample in pipeline.buffer().iter() {
+            let total: f32 = sample.policy.iter().map(|&(_, p)| p).sum();
+            assert!((total - 1.).abs() < 1e-5);
+            assert!(sample.outcome == 1. || sample.outcome == -1.);
+            assert!(sample.game.left > 0);
+        }
+
+        // The results do not depend on the number of workers
+        let mut single = Pipeline::new(&game, Oracle::default(), Net(-1.), config(1), 7);
+        let other = single.step().unwrap();
+        assert_eq!((other.samples, other.gating), (report.samples, report.gating));
+    }
+}
@@ -0,0 +1,137 @@
+//!
+//! Searching on the opponent's time.
+//!
+//! After making its move, an engine predicts the opponent's reply and
+//! keeps searching the position after it in a background thread. When the
+//! opponent's actual move arrives, the search is stopped: on a "ponder
+//! hit" the tree is kept and the next search continues from it, otherwise
+//! it is thrown away.
+//!
+//! Pondering only pays off for deterministic two-player games, where
+//! `MCTS::advance` keeps valid statistics.
+//!
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+
+use mcts::{Game, GameAction, MCTS};
+
+/// Iterations between checks for the stop signal.
+const SLICE: usize = 20;
+
+/// A search running in the background until it is stopped.
+pub struct BackgroundSearch<G: Game<A>, A: GameAction> {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<MCTS<G, A>>,
+}
+
+impl<G, A> BackgroundSearch<G, A>
+        where G: Game<A> + Send + 'static, A: GameAction + Send + 'static {
+    /// Continue searching `mcts` in a new thread.
+    pub fn start(mut mcts: MCTS<G, A>, c: f32) -> BackgroundSearch<G, A> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let signal = stop.clone();
+        let handle = thread::spawn(move || {
+            while !signal.load(Ordering::Relaxed) {
+                mcts.search(SLICE, c);
+            }
+            mcts
+        });
+        BackgroundSearch { stop: stop, handle: handle }
+    }
+
+    /// Stop the search and return the tree.
+    pub fn stop(self) -> MCTS<G, A> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("Background search panicked")
+    }
+}
+
+/// Pondering on a predicted reply.
+pub struct Ponder<G: Game<A>, A: GameAction> {
+    pub predicted: A,
+    search: BackgroundSearch<G, A>,
+}
+
+impl<G, A> Ponder<G, A>
+        where G: Game<A> + Send + 'static, A: GameAction + Send + 'static {
+    /// Ponder on the best reply in `mcts`, the tree of the position after
+    /// our move; None if there is no reply.
+    pub fn start(mut mcts: MCTS<G, A>, c: f32) -> Option<Ponder<G, A>> {
+        let predicted = match mcts.best_action() {
+            Some(action) => action,
+            None => return None
+        };
+        mcts.advance(&predicted);
+        Some(Ponder { predicted: predicted, search: BackgroundSearch::start(mcts, c) })
+    }
+
+    /// Stop pondering once the opponent played `actual`.
+    ///
+    /// Returns the tree to continue from on a ponder hit, None otherwise.
+    pub fn resolve(self, actual: &A) -> Option<MCTS<G, A>> {
+        let mcts = self.search.stop();
+        if *actual == self.predicted { Some(mcts) } else { None }
+    }
+
+    /// Stop pondering and discard the tree.
+    pub fn cancel(self) {
+        self.search.stop();
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use mcts::*;
+    use ponder::*;
+    use tictactoe::*;
+
+    fn ponder_after_first_move() -> (TicTacToe, Ponder<TicTacToe, Action>) {
+        let mut game = TicTacToe::new();
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(200, 1.);
+
+        let action = mcts.best_action().unwrap();
+        game.make_move(&action);
+        mcts.advance(&action);
+        (game, Ponder::start(mcts, 1.).unwrap())
+    }
+
+    #[test]
+    fn test_background_search() {
+        let search = BackgroundSearch::start(MCTS::new(&TicTacToe::new(), 1), 1.);
+        thread::sleep(Duration::from_millis(20));
+        let mcts = search.stop();
+        assert!(mcts.action_values().len() > 0);
+    }
+
+    #[test]
+    fn test_ponder_hit() {
+        let (mut game, ponder) = ponder_after_first_move();
+        let predicted = ponder.predicted;
+        assert!(game.allowed_actions().contains(&predicted));
+        thread::sleep(Duration::from_millis(20));
+
+        let mcts = ponder.resolve(&predicted).unwrap();
+        game.make_move(&predicted);
+        let visits = mcts.action_values().iter().map(|&(_, n, _)| n).sum::<u64>();
+        assert!(visits > 0);
+        for (action, _, _) in mcts.action_values() {
+            assert!(game.allowed_actions().contains(&action));
+        }
+    }
+
+    #[test]
+    fn test_ponder_miss() {
+        let (game, ponder) = ponder_after_first_move();
+        let other = *game.allowed_actions().iter().find(|&&a| a != ponder.predicted).unwrap();
+        assert!(ponder.resolve(&other).is_none());
+    }
+}
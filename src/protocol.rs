@@ -0,0 +1,333 @@
+//!
+//! A simple line-based engine protocol for any game.
+//!
+//! External harnesses and GUIs drive the engine by sending one command per
+//! line; the engine answers every command with exactly one line:
+//!
+//!   * `newgame` -- reset to the initial position; answers `ok`
+//!   * `state` -- answers the current state as JSON
+//!   * `state <json>` -- set the current state; answers `ok`
+//!   * `state <text>` -- set the current state given in the text notation
+//!     of `Game::from_str`, like `XX./OO./... X`; answers `ok`
+//!   * `actions` -- answers the allowed actions as a JSON array
+//!   * `move <action>` -- make a move; answers `ok`
+//!   * `go [iterations N | time SECONDS]` -- search the current state;
+//!     answers `bestmove <action>` or `bestmove none`
+//!   * `bestmove` -- repeat the answer of the last search
+//!   * `stats` -- statistics of the last search as JSON
+//!   * `quit` -- stop the engine
+//!
+//! Actions are written the way they are printed with `{:?}`, e.g. `Up` or
+//! `Action { row: 1, col: 2 }`. Failed commands answer `error <message>`.
+//!
+//! Everything JSON, the protocol itself included, needs the `serde`
+//! feature.
+//!
+
+#[cfg(feature = "serde")]
+use std::io;
+#[cfg(feature = "serde")]
+use std::io::{BufRead, Write};
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game, MCTS, SearchReport};
+use agents::Budget;
+#[cfg(feature = "serde")]
+use records::search_json;
+
+/// Games whose state can be exchanged as JSON.
+#[cfg(feature = "serde")]
+pub trait SerializableGame<A: GameAction> : Game<A> {
+
+    /// The current state as JSON.
+    fn to_json(&self) -> Value;
+
+    /// Restore a state from its JSON representation.
+    fn from_json(value: &Value) -> Result<Self, String>;
+}
+
+/// Search parameters used by the engine.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchSettings {
+    pub budget: Budget,
+    pub ensemble_size: usize,
+    pub c: f32,
+}
+
+impl SearchSettings {
+    /// Search the given game state within the budget.
+    pub fn search<G: Game<A>, A: GameAction>(&self, game: &G) -> MCTS<G, A> {
+        let mut mcts = MCTS::new(game, self.ensemble_size);
+        self.run(&mut mcts);
+        mcts
+    }
+
+    /// Continue the search of a prepared MCTS within the budget; returns
+    /// the report of the search.
+    pub fn run<G: Game<A>, A: GameAction>(&self, mcts: &mut MCTS<G, A>) -> SearchReport<A> {
+        match self.budget {
+            Budget::Iterations(n) => mcts.search(n, self.c),
+            Budget::Time(t) => mcts.search_time(t, self.c),
+        }
+    }
+}
+
+/// Find the allowed action that is printed as `text`.
+pub fn parse_action<G: Game<A>, A: GameAction>(game: &G, text: &str) -> Result<A, String> {
+    game.allowed_actions().into_iter()
+        .find(|action| format!("{:?}", action) == text)
+        .ok_or(format!("illegal action '{}'", text))
+}
+
+/// Read the `"board"` field of a JSON state: `size` strings of `size` characters.
+#[cfg(feature = "serde")]
+pub fn parse_board_rows(value: &Value, size: usize) -> Result<Vec<Vec<char>>, String> {
+    let invalid = || format!("expected a board of {} strings with {} characters", size, size);
+    let rows = try!(value["board"].as_array().ok_or_else(&invalid));
+    if rows.len() != size {
+        return Err(invalid());
+    }
+    rows.iter()
+        .map(|row| match row.as_str() {
+            Some(row) if row.chars().count() == size => Ok(row.chars().collect()),
+            _ => Err(invalid())
+        })
+        .collect()
+}
+
+/// Split a FEN-like notation, see `Game::from_str`, into the rows of the
+/// board, separated by `/`, and the fields following it.
+pub fn split_notation(text: &str) -> (Vec<&str>, Vec<&str>) {
+    let mut fields = text.split_whitespace();
+    let rows = fields.next().map_or(Vec::new(), |board| board.split('/').collect());
+    (rows, fields.collect())
+}
+
+/// Read a state given as JSON, or as a string in the text notation of
+/// `Game::from_str`.
+#[cfg(feature = "serde")]
+pub fn parse_state<G: SerializableGame<A>, A: GameAction>(state: &Value) -> Result<G, String> {
+    match state.as_str() {
+        Some(text) => G::from_str(text),
+        None => G::from_json(state)
+    }
+}
+
+/// Parse the arguments of the `go` command.
+#[cfg(feature = "serde")]
+fn parse_budget(arg: &str, default: Budget) -> Result<Budget, String> {
+    let words = arg.split_whitespace().collect::<Vec<_>>();
+    let invalid = || format!("invalid budget '{}'", arg);
+    match words.as_slice() {
+        [] => Ok(default),
+        ["iterations", n] => n.parse().map(Budget::Iterations).map_err(|_| invalid()),
+        ["time", t] => t.parse().map(Budget::Time).map_err(|_| invalid()),
+        _ => Err(invalid())
+    }
+}
+
+/// Engine state between commands.
+#[cfg(feature = "serde")]
+struct Session<G: Game<A>, A: GameAction> {
+    game: G,
+    mcts: Option<MCTS<G, A>>,
+}
+
+#[cfg(feature = "serde")]
+impl<G: SerializableGame<A>, A: GameAction> Session<G, A> {
+    /// The answer of the last search.
+    fn bestmove(&self) -> Result<String, String> {
+        match self.mcts {
+            Some(ref mcts) => Ok(match mcts.best_action() {
+                Some(action) => format!("bestmove {:?}", action),
+                None => "bestmove none".to_string(),
+            }),
+            None => Err("no search yet".to_string())
+        }
+    }
+
+    /// Execute a single command; returns the answer.
+    fn execute(&mut self, initial: &G, settings: &SearchSettings,
+               command: &str, arg: &str) -> Result<String, String> {
+        match command {
+            "newgame" => {
+                self.game = initial.clone();
+                self.mcts = None;
+                Ok("ok".to_string())
+            },
+            "state" if arg.is_empty() => Ok(self.game.to_json().to_string()),
+            "state" if !arg.starts_with('{') => {
+                self.game = try!(G::from_str(arg));
+                self.mcts = None;
+                Ok("ok".to_string())
+            },
+            "state" => {
+                let value = try!(arg.parse::<Value>().map_err(|e| format!("invalid JSON: {}", e)));
+                self.game = try!(G::from_json(&value));
+                self.mcts = None;
+                Ok("ok".to_string())
+            },
+            "actions" => {
+                let actions = self.game.allowed_actions().iter()
+                    .map(|action| json!(format!("{:?}", action)))
+                    .collect::<Vec<_>>();
+                Ok(Value::Array(actions).to_string())
+            },
+            "move" => {
+                let action = try!(parse_action(&self.game, arg));
+                self.game.make_move(&action);
+                self.mcts = None;
+                Ok("ok".to_string())
+            },
+            "go" => {
+                let budget = try!(parse_budget(arg, settings.budget));
+                let settings = SearchSettings { budget: budget, ..*settings };
+                self.mcts = Some(settings.search(&self.game));
+                self.bestmove()
+            },
+            "bestmove" => self.bestmove(),
+            "stats" => match self.mcts {
+                Some(ref mcts) => Ok(search_json(mcts).to_string()),
+                None => Err("no search yet".to_string())
+            },
+            _ => Err(format!("unknown command '{}'", command))
+        }
+    }
+}
+
+/// Search the state given as JSON, or as a string in the text notation of
+/// `Game::from_str`, and report the result.
+///
+/// The answer contains the `best_action` along with everything
+/// `records::search_json` reports.
+#[cfg(feature = "serde")]
+pub fn analyze<G, A>(state: &Value, settings: &SearchSettings) -> Result<Value, String>
+        where G: SerializableGame<A>, A: GameAction {
+    let game: G = try!(parse_state(state));
+    let mcts = settings.search(&game);
+
+    let mut result = search_json(&mcts);
+    result["best_action"] = match mcts.best_action() {
+        Some(action) => json!(format!("{:?}", action)),
+        None => Value::Null,
+    };
+    Ok(result)
+}
+
+/// Answer the commands read from `input` until `quit` or EOF.
+///
+/// `initial` is the state for `newgame` and at startup.
+#[cfg(feature = "serde")]
+pub fn run<G, A, R, W>(initial: &G, settings: &SearchSettings, input: R, mut output: W) -> io::Result<()>
+        where G: SerializableGame<A>, A: GameAction, R: BufRead, W: Write {
+    let mut session = Session { game: initial.clone(), mcts: None };
+
+    for line in input.lines() {
+        let line = try!(line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let command = parts.next().unwrap();
+        let arg = parts.next().unwrap_or("").trim();
+        if command == "quit" {
+            break;
+        }
+
+        match session.execute(initial, settings, command, arg) {
+            Ok(answer) => try!(writeln!(output, "{}", answer)),
+            Err(msg) => try!(writeln!(output, "error {}", msg)),
+        }
+        try!(output.flush());
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::io::Cursor;
+    use serde_json::Value;
+
+    use agents::Budget;
+    use protocol::*;
+    use tictactoe::*;
+
+    fn session(commands: &str) -> Vec<String> {
+        let settings = SearchSettings { budget: Budget::Iterations(200), ensemble_size: 1, c: 1. };
+        let mut output = Vec::new();
+        run(&TicTacToe::new(), &settings, Cursor::new(commands), &mut output).unwrap();
+        String::from_utf8(output).unwrap().lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_session() {
+        let answers = session("move Action { row: 1, col: 1 }\n\
+                               go iterations 300\n\
+                               bestmove\n\
+                               stats\n\
+                               newgame\n\
+                               actions\n\
+                               quit\n\
+                               state\n");
+        assert_eq!(answers.len(), 6);
+        assert_eq!(answers[0], "ok");
+        assert!(answers[1].starts_with("bestmove Action"));
+        assert_eq!(answers[2], answers[1]);
+
+        let stats = answers[3].parse::<Value>().unwrap();
+        assert_eq!(stats["actions"].as_array().unwrap().len(), 8);
+        assert!(stats["pv"].as_array().unwrap().len() >= 1);
+
+        assert_eq!(answers[4], "ok");
+        assert_eq!(answers[5].parse::<Value>().unwrap().as_array().unwrap().len(), 9);
+    }
+
+    #[test]
+    fn test_state() {
+        let answers = session("state {\"board\": [\"XX.\", \"OO.\", \"...\"], \"to_move\": \"X\"}\n\
+                               go\n\
+                               state\n");
+        assert_eq!(answers[0], "ok");
+        assert_eq!(answers[1], "bestmove Action { row: 0, col: 2 }");
+        let state = answers[2].parse::<Value>().unwrap();
+        assert_eq!(state["board"][0], "XX.");
+        assert_eq!(state["to_move"], "X");
+
+        let answers = session("state XX./OO./...\nstate\nstate XX\n");
+        assert_eq!(answers[0], "ok");
+        assert_eq!(answers[1], "{\"board\":[\"XX.\",\"OO.\",\"...\"],\"to_move\":\"X\"}");
+        assert!(answers[2].starts_with("error "));
+    }
+
+    #[test]
+    fn test_analyze() {
+        let settings = SearchSettings { budget: Budget::Iterations(300), ensemble_size: 1, c: 1. };
+        let state = json!({"board": ["XX.", "OO.", "..."], "to_move": "O"});
+
+        let result = analyze::<TicTacToe, Action>(&state, &settings).unwrap();
+        assert_eq!(result["best_action"], "Action { row: 1, col: 2 }");
+        assert_eq!(result["actions"].as_array().unwrap().len(), 5);
+
+        let finished = json!({"board": ["XXX", "OO.", "..."], "to_move": "O"});
+        assert_eq!(analyze::<TicTacToe, Action>(&finished, &settings).unwrap()["best_action"], Value::Null);
+        assert!(analyze::<TicTacToe, Action>(&json!({}), &settings).is_err());
+
+        // States may be given in the text notation
+        let result = analyze::<TicTacToe, Action>(&json!("XX./OO./... O"), &settings).unwrap();
+        assert_eq!(result["best_action"], "Action { row: 1, col: 2 }");
+        assert!(analyze::<TicTacToe, Action>(&json!("XX./OO."), &settings).is_err());
+    }
+
+    #[test]
+    fn test_errors() {
+        let answers = session("bestmove\nmove Up\nstate {\nstate {}\ngo time abc\nfoo\n");
+        assert_eq!(answers.len(), 6);
+        assert!(answers.iter().all(|a| a.starts_with("error ")));
+    }
+}
@@ -0,0 +1,230 @@
+//!
+//! Machine-readable output for the binaries.
+//!
+//! Records are written as JSON lines: one JSON object per line, each with
+//! a `"type"` field telling what kind of record it is (e.g. `"move"`,
+//! `"game"` or `"summary"`).
+//!
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+
+use serde_json::Value;
+
+use mcts::{GameAction, Game, MCTS, SearchReport};
+use expectimax::Expectimax;
+use twofortyeight::TwoFortyEight;
+
+/// Writes JSON records to stdout or to a file.
+pub struct RecordWriter {
+    out: Box<dyn Write>,
+}
+
+impl RecordWriter {
+    /// Write to the given file; "-" means stdout.
+    pub fn open(path: &str) -> io::Result<RecordWriter> {
+        let out: Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(BufWriter::new(try!(File::create(path))))
+        };
+        Ok(RecordWriter { out: out })
+    }
+
+    /// Write a record on a line of its own.
+    pub fn write(&mut self, record: &Value) -> io::Result<()> {
+        try!(writeln!(self.out, "{}", record));
+        self.out.flush()
+    }
+}
+
+/// Visit counts and mean rewards of all root actions.
+pub fn action_values_json<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>) -> Value {
+    let values = mcts.action_values().iter().map(|&(action, visits, value)| json!({
+        "action": format!("{:?}", action),
+        "visits": visits,
+        "value": value,
+    })).collect::<Vec<_>>();
+    Value::Array(values)
+}
+
+/// Values of all actions of an expectimax search.
+pub fn expectimax_values_json<A: GameAction>(values: &[(A, f32)]) -> Value {
+    let values = values.iter().map(|&(action, value)| json!({
+        "action": format!("{:?}", action),
+        "value": value,
+    })).collect::<Vec<_>>();
+    Value::Array(values)
+}
+
+/// Summary of a finished search: value, ranked actions, principal
+/// variation and tree size.
+pub fn search_json<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>) -> Value {
+    let stats = mcts.tree_statistics();
+    let pv = mcts.principal_variation(usize::MAX).iter()
+        .map(|action| json!(format!("{:?}", action)))
+        .collect::<Vec<_>>();
+    json!({
+        "value": mcts.root_value(),
+        "actions": action_values_json(mcts),
+        "pv": pv,
+        "nodes": stats.nodes(),
+        "max_depth": stats.max_depth(),
+    })
+}
+
+/// Engine-style analysis of a finished search.
+///
+/// Shown as an info line like `info depth 4 nodes 812 nps 4060 time 200
+/// value 0.250 pv Up Left Up Up`, where depth is the length of the
+/// principal variation and time is given in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub depth: usize,
+    pub nodes: i32,
+    pub nps: u64,
+    pub time: f32,
+    pub value: Option<f32>,
+    pub pv: Vec<String>,
+}
+
+impl Analysis {
+    /// Analyse a search that took `elapsed` seconds.
+    pub fn new<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>, elapsed: f32) -> Analysis {
+        Analysis::with_nodes(mcts, mcts.tree_statistics().nodes(), elapsed)
+    }
+
+    /// Analyse a search from its `report`; the nodes are those the search
+    /// added to the trees.
+    pub fn from_report<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>, report: &SearchReport<A>) -> Analysis {
+        Analysis::with_nodes(mcts, report.nodes as i32, report.elapsed)
+    }
+
+    fn with_nodes<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>, nodes: i32, elapsed: f32) -> Analysis {
+        let pv = mcts.principal_variation(usize::MAX).iter()
+            .map(|action| format!("{:?}", action))
+            .collect::<Vec<_>>();
+        Analysis {
+            depth: pv.len(),
+            nodes: nodes,
+            nps: (nodes as f32 / elapsed.max(0.001)) as u64,
+            time: elapsed,
+            value: mcts.root_value(),
+            pv: pv,
+        }
+    }
+
+    /// Analyse an expectimax search that took `elapsed` seconds and found
+    /// the `best` action with its value; that is the principal variation.
+    pub fn expectimax<A: GameAction>(expectimax: &Expectimax, best: Option<(A, f32)>, elapsed: f32) -> Analysis {
+        let nodes = expectimax.nodes() as i32;
+        Analysis {
+            depth: expectimax.depth,
+            nodes: nodes,
+            nps: (nodes as f32 / elapsed.max(0.001)) as u64,
+            time: elapsed,
+            value: best.map(|(_, value)| value),
+            pv: best.iter().map(|&(action, _)| format!("{:?}", action)).collect(),
+        }
+    }
+
+    /// The analysis as fields of a move record.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "depth": self.depth,
+            "nodes": self.nodes,
+            "nps": self.nps,
+            "time": self.time,
+            "value": self.value,
+            "pv": self.pv,
+        })
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "info depth {} nodes {} nps {} time {}",
+                    self.depth, self.nodes, self.nps, (self.time * 1000.) as u64));
+        match self.value {
+            Some(value) => try!(write!(f, " value {:.3}", value)),
+            None => try!(write!(f, " value none")),
+        }
+        write!(f, " pv {}", self.pv.join(" "))
+    }
+}
+
+/// The 2048 board as an array of rows.
+pub fn board_json(game: &TwoFortyEight) -> Value {
+    let rows = (0..game.height()).map(|row| {
+        (0..game.width()).map(|col| game.get_tile(row, col)).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    json!(rows)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use records::*;
+    use expectimax::best_value;
+    use twofortyeight::*;
+
+    #[test]
+    fn test_board_json() {
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 1, 2);
+        game.set_tile(3, 3, 1024);
+
+        let board = board_json(&game);
+        assert_eq!(board[0], json!([0, 2, 0, 0]));
+        assert_eq!(board[3][3], json!(1024));
+    }
+
+    #[test]
+    fn test_analysis() {
+        let game = TwoFortyEight::new();
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(50, 1.);
+
+        let analysis = Analysis::new(&mcts, 0.5);
+        assert_eq!(analysis.depth, analysis.pv.len());
+        assert_eq!(analysis.nps, analysis.nodes as u64 * 2);
+        assert_eq!(analysis.to_json()["pv"][0], json!(analysis.pv[0]));
+
+        let line = format!("{}", analysis);
+        assert!(line.starts_with(&format!("info depth {} nodes {} nps {} time 500 value ",
+                                          analysis.depth, analysis.nodes, analysis.nps)));
+        assert!(line.ends_with(&format!("pv {}", analysis.pv.join(" "))));
+    }
+
+    #[test]
+    fn test_expectimax() {
+        let game = TwoFortyEight::with_seed(3);
+        let mut expectimax = Expectimax::new(1);
+        let values = expectimax.action_values(&game);
+        let best = best_value(&values, 0);
+
+        let analysis = Analysis::expectimax(&expectimax, best, 0.5);
+        assert_eq!(analysis.depth, 1);
+        assert_eq!(analysis.nodes as u64, expectimax.nodes());
+        assert_eq!(analysis.value, best.map(|(_, value)| value));
+        assert_eq!(analysis.pv, vec![format!("{:?}", best.unwrap().0)]);
+        assert_eq!(expectimax_values_json(&values).as_array().unwrap().len(), values.len());
+    }
+
+    #[test]
+    fn test_action_values_json() {
+        let game = TwoFortyEight::new();
+        let mut mcts = MCTS::new(&game, 2);
+        mcts.search(20, 1.);
+
+        let values = action_values_json(&mcts);
+        let values = values.as_array().unwrap();
+        assert_eq!(values.len(), game.allowed_actions().len());
+        assert!(values.iter().all(|v| v["visits"].as_f64().unwrap() > 0.));
+        println!("{}", json!(values));
+    }
+}
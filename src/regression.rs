@@ -0,0 +1,333 @@
+//!
+//! Regression tests of the search against recorded baselines.
+//!
+//! A suite is a JSON lines file with one case per line: a named position of
+//! some game, the seed and budget to search it with, and what the search
+//! found when the case was recorded -- the move and root value it chose,
+//! the visits and mean reward of every root action and the shape of the
+//! tree:
+//!
+//! ```text
+//! {"name": "ttt-win", "game": "tictactoe", "state": {...}, "seed": 1,
+//!  "iterations": 200, "ensemble_size": 1, "c": 1.0,
+//!  "expected": {"best_action": "Action { row: 0, col: 2 }", "value": 0.98,
+//!               "actions": [{"action": "Action { row: 0, col: 2 }", "visits": 180, "value": 0.98}, ...],
+//!               "nodes": 412, "max_depth": 6}}
+//! ```
+//!
+//! With an iteration budget and a seeded search the results are fully
+//! reproducible, so any difference means the behavior of the search
+//! changed. Intended changes are accepted by recording the suite again.
+//! Cases recorded without root actions or tree shape only compare the
+//! move and value. The suite in `regression/suite.jsonl` is replayed by
+//! the tests as well.
+//!
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use serde_json::Value;
+
+use agents::Budget;
+use protocol::{SearchSettings, analyze};
+use utils::seed_thread_rng;
+use twofortyeight::TwoFortyEight;
+use tictactoe::TicTacToe;
+use hex::Hex;
+use pentago::Pentago;
+
+/// The result of searching a case.
+///
+/// `actions` holds the root actions with their visits and mean reward as
+/// ranked by `MCTS::action_values`; `nodes` and `max_depth` describe the
+/// tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub best_action: Option<String>,
+    pub value: Option<f32>,
+    pub actions: Option<Vec<(String, f32, f32)>>,
+    pub nodes: Option<i32>,
+    pub max_depth: Option<i32>,
+}
+
+impl Outcome {
+    fn from_json(value: &Value) -> Outcome {
+        let actions = value["actions"].as_array().map(|actions| actions.iter().map(|action| (
+            action["action"].as_str().unwrap_or("").to_string(),
+            action["visits"].as_f64().unwrap_or(0.) as f32,
+            action["value"].as_f64().unwrap_or(0.) as f32,
+        )).collect());
+        Outcome {
+            best_action: value["best_action"].as_str().map(|action| action.to_string()),
+            value: value["value"].as_f64().map(|value| value as f32),
+            actions: actions,
+            nodes: value["nodes"].as_i64().map(|nodes| nodes as i32),
+            max_depth: value["max_depth"].as_i64().map(|depth| depth as i32),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let actions = self.actions.as_ref().map(|actions| actions.iter().map(|&(ref action, visits, value)| json!({
+            "action": action,
+            "visits": visits,
+            "value": value,
+        })).collect::<Vec<_>>());
+        json!({
+            "best_action": self.best_action,
+            "value": self.value,
+            "actions": actions,
+            "nodes": self.nodes,
+            "max_depth": self.max_depth,
+        })
+    }
+}
+
+/// Whether `b` is within `tolerance` of `a`, relative to `a` but at least
+/// absolute.
+fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() <= tolerance * a.abs().max(1.)
+}
+
+/// A position with its search settings and recorded outcome.
+#[derive(Debug, Clone)]
+pub struct Case {
+    pub name: String,
+    pub game: String,
+    pub state: Value,
+    pub seed: u32,
+    pub settings: SearchSettings,
+    pub expected: Option<Outcome>,
+}
+
+impl Case {
+    pub fn from_json(value: &Value) -> Result<Case, String> {
+        let name = try!(value["name"].as_str().ok_or("case without a name")).to_string();
+        let field = |key: &str| value[key].as_u64().ok_or(format!("case {}: missing {}", name, key));
+        let game = try!(value["game"].as_str().ok_or(format!("case {}: missing game", name))).to_string();
+        let seed = try!(field("seed")) as u32;
+        let iterations = try!(field("iterations")) as usize;
+        let ensemble_size = value["ensemble_size"].as_u64().unwrap_or(1) as usize;
+        let c = value["c"].as_f64().unwrap_or(1.) as f32;
+        let expected = match value["expected"] {
+            Value::Null => None,
+            ref expected => Some(Outcome::from_json(expected)),
+        };
+        Ok(Case {
+            name: name,
+            game: game,
+            state: value["state"].clone(),
+            seed: seed,
+            settings: SearchSettings { budget: Budget::Iterations(iterations), ensemble_size: ensemble_size, c: c },
+            expected: expected,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        let iterations = match self.settings.budget {
+            Budget::Iterations(n) => n,
+            Budget::Time(_) => panic!("Regression cases need an iteration budget"),
+        };
+        json!({
+            "name": self.name,
+            "game": self.game,
+            "state": self.state,
+            "seed": self.seed,
+            "iterations": iterations,
+            "ensemble_size": self.settings.ensemble_size,
+            "c": self.settings.c,
+            "expected": self.expected.as_ref().map(Outcome::to_json),
+        })
+    }
+
+    /// Search the position with the case's seed and budget.
+    pub fn run(&self) -> Result<Outcome, String> {
+        seed_thread_rng(self.seed);
+        let result = match self.game.as_ref() {
+            "2048" => analyze::<TwoFortyEight, _>(&self.state, &self.settings),
+            "tictactoe" => analyze::<TicTacToe, _>(&self.state, &self.settings),
+            "hex" => analyze::<Hex, _>(&self.state, &self.settings),
+            "pentago" => analyze::<Pentago, _>(&self.state, &self.settings),
+            _ => Err(format!("unknown game '{}'", self.game))
+        };
+        result.map(|result| Outcome::from_json(&result))
+            .map_err(|msg| format!("case {}: {}", self.name, msg))
+    }
+
+    /// Differences between the recorded and the actual outcome.
+    ///
+    /// Values, visits and tree sizes may differ by `tolerance` relative to
+    /// the recorded ones; whatever was not recorded is not compared.
+    pub fn diff(&self, actual: &Outcome, tolerance: f32) -> Vec<String> {
+        let expected = match self.expected {
+            Some(ref expected) => expected,
+            None => return vec![format!("{}: no recorded outcome", self.name)],
+        };
+
+        let mut diff = Vec::new();
+        if expected.best_action != actual.best_action {
+            diff.push(format!("{}: best action {:?} -> {:?}", self.name, expected.best_action, actual.best_action));
+        }
+        let same_value = match (expected.value, actual.value) {
+            (Some(a), Some(b)) => close(a, b, tolerance),
+            (a, b) => a == b,
+        };
+        if !same_value {
+            diff.push(format!("{}: value {:?} -> {:?}", self.name, expected.value, actual.value));
+        }
+
+        if let (&Some(ref expected), &Some(ref actual)) = (&expected.actions, &actual.actions) {
+            if expected.len() != actual.len() {
+                diff.push(format!("{}: {} root actions -> {}", self.name, expected.len(), actual.len()));
+            }
+            for (&(ref a, a_visits, a_value), &(ref b, b_visits, b_value)) in expected.iter().zip(actual) {
+                if a != b {
+                    diff.push(format!("{}: root action {} -> {}", self.name, a, b));
+                } else if !close(a_visits, b_visits, tolerance) || !close(a_value, b_value, tolerance) {
+                    diff.push(format!("{}: {} visits={} value={} -> visits={} value={}",
+                                      self.name, a, a_visits, a_value, b_visits, b_value));
+                }
+            }
+        }
+
+        let shape = |outcome: &Outcome| outcome.nodes.and_then(|nodes| outcome.max_depth.map(|depth| (nodes, depth)));
+        if let (Some((a_nodes, a_depth)), Some((b_nodes, b_depth))) = (shape(expected), shape(actual)) {
+            if !close(a_nodes as f32, b_nodes as f32, tolerance) || a_depth != b_depth {
+                diff.push(format!("{}: tree nodes={} max_depth={} -> nodes={} max_depth={}",
+                                  self.name, a_nodes, a_depth, b_nodes, b_depth));
+            }
+        }
+        diff
+    }
+}
+
+/// Read a suite; blank lines and lines starting with `#` are skipped.
+pub fn load_suite(path: &str) -> Result<Vec<Case>, String> {
+    let file = try!(File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e)));
+    let mut cases = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = try!(line.map_err(|e| format!("Failed to read {}: {}", path, e)));
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let value = try!(line.parse::<Value>().map_err(|e| format!("{}:{}: invalid JSON: {}", path, i+1, e)));
+        cases.push(try!(Case::from_json(&value).map_err(|msg| format!("{}:{}: {}", path, i+1, msg))));
+    }
+    Ok(cases)
+}
+
+/// Write a suite, one case per line.
+pub fn save_suite(path: &str, cases: &[Case]) -> Result<(), String> {
+    let mut file = try!(File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e)));
+    for case in cases {
+        try!(writeln!(file, "{}", case.to_json()).map_err(|e| format!("Failed to write {}: {}", path, e)));
+    }
+    Ok(())
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use regression::*;
+
+    fn case() -> Case {
+        Case::from_json(&json!({
+            "name": "ttt-win",
+            "game": "tictactoe",
+            "state": {"board": ["XX.", "OO.", "..."], "to_move": "X"},
+            "seed": 3,
+            "iterations": 200,
+        })).unwrap()
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let mut case = case();
+        assert!(case.expected.is_none());
+        let outcome = case.run().unwrap();
+        assert_eq!(outcome.best_action, Some("Action { row: 0, col: 2 }".to_string()));
+        assert_eq!(case.diff(&outcome, 0.).len(), 1);
+
+        case.expected = Some(outcome);
+        assert!(case.diff(&case.run().unwrap(), 0.).is_empty());
+    }
+
+    fn outcome(best_action: &str, value: f32) -> Outcome {
+        Outcome { best_action: Some(best_action.to_string()), value: Some(value),
+                  actions: None, nodes: None, max_depth: None }
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut case = case();
+        case.expected = Some(outcome("Up", 10.));
+        let actual = outcome("Up", 10.5);
+        assert!(case.diff(&actual, 0.1).is_empty());
+        assert_eq!(case.diff(&actual, 0.01), vec!["ttt-win: value Some(10.0) -> Some(10.5)"]);
+
+        let actual = Outcome { best_action: None, value: None, ..actual };
+        assert_eq!(case.diff(&actual, 0.1).len(), 2);
+    }
+
+    #[test]
+    fn test_diff_root_and_tree() {
+        let mut case = case();
+        let recorded = Outcome {
+            actions: Some(vec![("Up".to_string(), 90., 10.), ("Left".to_string(), 10., 4.)]),
+            nodes: Some(100),
+            max_depth: Some(5),
+            ..outcome("Up", 10.)
+        };
+        case.expected = Some(recorded.clone());
+        assert!(case.diff(&recorded, 0.).is_empty());
+
+        // Not recorded, not compared
+        assert!(case.diff(&outcome("Up", 10.), 0.).is_empty());
+
+        let actual = Outcome {
+            actions: Some(vec![("Up".to_string(), 91., 10.), ("Down".to_string(), 9., 4.)]),
+            nodes: Some(104),
+            ..recorded.clone()
+        };
+        assert_eq!(case.diff(&actual, 0.05), vec!["ttt-win: root action Left -> Down"]);
+        assert_eq!(case.diff(&actual, 0.), vec![
+            "ttt-win: Up visits=90 value=10 -> visits=91 value=10",
+            "ttt-win: root action Left -> Down",
+            "ttt-win: tree nodes=100 max_depth=5 -> nodes=104 max_depth=5",
+        ]);
+
+        let actual = Outcome { actions: Some(vec![]), max_depth: Some(6), ..recorded };
+        assert_eq!(case.diff(&actual, 0.1).len(), 2);
+    }
+
+    #[test]
+    fn test_golden_suite() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/regression/suite.jsonl");
+        for case in load_suite(path).unwrap() {
+            assert!(case.expected.as_ref().map_or(false, |expected| expected.actions.is_some()),
+                    "{}: no recorded root actions", case.name);
+            let diff = case.diff(&case.run().unwrap(), 1e-4);
+            assert!(diff.is_empty(), "{}", diff.join("\n"));
+        }
+    }
+
+    #[test]
+    fn test_suite() {
+        let mut case = case();
+        case.expected = Some(outcome("Action { row: 0, col: 2 }", 1.));
+        let path = env::temp_dir().join(format!("mcts-suite-{}.jsonl", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        save_suite(path, &[case.clone(), case]).unwrap();
+        let cases = load_suite(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[1].expected.as_ref().unwrap().value, Some(1.));
+        assert!(Case::from_json(&json!({"name": "x", "game": "hex"})).is_err());
+        assert!(Case { game: "go".to_string(), ..cases[0].clone() }.run().is_err());
+    }
+}
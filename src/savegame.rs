@@ -0,0 +1,185 @@
+//!
+//! Saving and resuming 2048 games.
+//!
+//! Long runs with a high search budget can take hours; the 2048 and
+//! adv2048 binaries write the game in progress to a save file when they
+//! are interrupted, and `--resume` continues from there. The save file is a JSON object with the
+//! game state, the state of its tile spawn generator, the moves played so
+//! far and the master seed and index of the game.
+//!
+
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use serde_json::Value;
+
+use mcts::GameAction;
+use protocol::SerializableGame;
+use twofortyeight::{TwoFortyEight, Action};
+
+/// Games of the 2048 family that can be saved.
+pub trait Saveable<A: GameAction> : SerializableGame<A> {
+    /// The board, whose tile spawn generator is saved along with the state.
+    fn board(&self) -> &TwoFortyEight;
+
+    fn board_mut(&mut self) -> &mut TwoFortyEight;
+
+    /// Read back a move of the history, as printed with `{:?}`.
+    fn parse_move(&self, text: &str) -> Result<A, String>;
+}
+
+impl Saveable<Action> for TwoFortyEight {
+    fn board(&self) -> &TwoFortyEight {
+        self
+    }
+
+    fn board_mut(&mut self) -> &mut TwoFortyEight {
+        self
+    }
+
+    fn parse_move(&self, text: &str) -> Result<Action, String> {
+        match text {
+            "Up" => Ok(Action::Up),
+            "Down" => Ok(Action::Down),
+            "Left" => Ok(Action::Left),
+            "Right" => Ok(Action::Right),
+            _ => Err(format!("invalid move '{}'", text))
+        }
+    }
+}
+
+/// A 2048 game in progress.
+#[derive(Clone)]
+pub struct SavedGame<G = TwoFortyEight, A = Action> {
+    pub game: G,
+    pub history: Vec<A>,
+    pub seed: Option<u32>,
+    pub index: usize,
+}
+
+impl<G: Saveable<A>, A: GameAction> SavedGame<G, A> {
+    /// Start saving a fresh game.
+    pub fn new(game: G, seed: Option<u32>, index: usize) -> SavedGame<G, A> {
+        SavedGame { game: game, history: Vec::new(), seed: seed, index: index }
+    }
+
+    /// Record a move that was just made in `game`.
+    pub fn record(&mut self, game: &G, action: A) {
+        self.game = game.clone();
+        self.history.push(action);
+    }
+
+    pub fn to_json(&self) -> Value {
+        let (rng_seed, rng_draws) = self.game.board().rng_state();
+        let history = self.history.iter()
+            .map(|action| json!(format!("{:?}", action)))
+            .collect::<Vec<_>>();
+        json!({
+            "game": self.game.to_json(),
+            "rng": { "seed": rng_seed, "draws": rng_draws },
+            "history": history,
+            "seed": self.seed,
+            "index": self.index,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<SavedGame<G, A>, String> {
+        let mut game = try!(G::from_json(&value["game"]));
+
+        let invalid_rng = || "expected an rng with a seed of 4 integers and draws".to_string();
+        let seed = try!(value["rng"]["seed"].as_array().ok_or_else(&invalid_rng));
+        if seed.len() != 4 {
+            return Err(invalid_rng());
+        }
+        let mut rng_seed = [0; 4];
+        for (i, word) in seed.iter().enumerate() {
+            rng_seed[i] = try!(word.as_u64().ok_or_else(&invalid_rng)) as u32;
+        }
+        let rng_draws = try!(value["rng"]["draws"].as_u64().ok_or_else(&invalid_rng));
+        game.board_mut().set_rng_state(rng_seed, rng_draws);
+
+        let moves = try!(value["history"].as_array().ok_or("expected a history of moves"));
+        let mut history = Vec::new();
+        for action in moves {
+            history.push(try!(game.parse_move(action.as_str().unwrap_or(""))));
+        }
+
+        Ok(SavedGame {
+            game: game,
+            history: history,
+            seed: value["seed"].as_u64().map(|seed| seed as u32),
+            index: value["index"].as_u64().unwrap_or(0) as usize,
+        })
+    }
+
+    /// Write the save file.
+    ///
+    /// The file is written next to its destination first and then moved
+    /// there, so an interrupted save never clobbers an older one.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let tmp = format!("{}.tmp", path);
+        try!(File::create(&tmp)
+            .and_then(|mut file| writeln!(file, "{}", self.to_json()))
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| format!("Failed to save {}: {}", path, e)));
+        Ok(())
+    }
+
+    /// Read a save file.
+    pub fn load(path: &str) -> Result<SavedGame<G, A>, String> {
+        let mut text = String::new();
+        try!(File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(|e| format!("Failed to read {}: {}", path, e)));
+        let value = try!(text.parse::<Value>().map_err(|e| format!("{}: invalid JSON: {}", path, e)));
+        SavedGame::from_json(&value).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use mcts::*;
+    use savegame::*;
+
+    #[test]
+    fn test_save_load() {
+        let mut game = TwoFortyEight::with_seed(3);
+        let mut saved = SavedGame::new(game.clone(), Some(3), 1);
+        for _ in 0..4 {
+            let action = game.allowed_actions()[0];
+            game.make_move(&action);
+            saved.record(&game, action);
+        }
+
+        let path = env::temp_dir().join(format!("mcts-savegame-{}.json", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        saved.save(path).unwrap();
+        let mut loaded: SavedGame = SavedGame::load(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.history, saved.history);
+        assert_eq!(loaded.seed, Some(3));
+        assert_eq!(loaded.index, 1);
+        assert_eq!(loaded.game.to_json(), game.to_json());
+
+        // Tile spawns continue as if the game had never been interrupted
+        let action = game.allowed_actions()[0];
+        game.make_move(&action);
+        loaded.game.make_move(&action);
+        assert_eq!(loaded.game.to_json(), game.to_json());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut value = SavedGame::new(TwoFortyEight::new(), None, 0).to_json();
+        value["history"] = json!(["Sideways"]);
+        assert!(SavedGame::<TwoFortyEight>::from_json(&value).is_err());
+        assert!(SavedGame::<TwoFortyEight>::load("/nonexistent/mcts.save").is_err());
+    }
+}
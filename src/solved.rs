@@ -0,0 +1,123 @@
+//!
+//! Correctness of the search on tiny solved games.
+//!
+//! Every case is a position of a two-player game small enough to solve
+//! exactly by negamax. The search has to find one of the optimal moves
+//! with a fixed seed and budget, so a change that breaks the search in a
+//! way the regression baselines would only record shows up as a failure.
+//!
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use mcts::{GameAction, Game, MCTS};
+use protocol::SerializableGame;
+use utils::seeded_rng;
+use nim::Nim;
+use tictactoe::TicTacToe;
+
+/// The value of `game` for the player to move under perfect play.
+fn negamax<G: Game<A>, A: GameAction>(game: &G, table: &mut HashMap<u64, f32>) -> f32 {
+    let player = game.current_player();
+    if game.is_terminal() || game.allowed_actions().is_empty() {
+        return game.reward_for(player);
+    }
+    if let Some(value) = game.hash_state().and_then(|hash| table.get(&hash).cloned()) {
+        return value;
+    }
+
+    let value = game.allowed_actions().iter()
+        .map(|action| value_after(game, action, player, table))
+        .fold(f32::NEG_INFINITY, f32::max);
+    if let Some(hash) = game.hash_state() {
+        table.insert(hash, value);
+    }
+    value
+}
+
+/// The value of making `action` in `game` for `player`.
+fn value_after<G: Game<A>, A: GameAction>(game: &G, action: &A, player: usize, table: &mut HashMap<u64, f32>) -> f32 {
+    let mut child = game.clone();
+    child.make_move(action);
+    let value = negamax(&child, table);
+    if child.current_player() == player { value } else { -value }
+}
+
+/// The moves that keep the value of `game` for the player to move.
+fn optimal_actions<G: Game<A>, A: GameAction>(game: &G) -> Vec<A> {
+    let mut table = HashMap::new();
+    let player = game.current_player();
+    let best = negamax(game, &mut table);
+    game.allowed_actions().into_iter()
+        .filter(|action| value_after(game, action, player, &mut table) == best)
+        .collect()
+}
+
+/// Search `game` and check that the search picks an optimal move.
+fn assert_converges<G: Game<A>, A: GameAction>(game: &G, iterations: usize, seed: u32) {
+    let optimal = optimal_actions(game);
+    assert!(optimal.len() < game.allowed_actions().len(), "every move is optimal");
+
+    let mut mcts = MCTS::new(game, 1);
+    mcts.search_with(iterations, 1., &mut seeded_rng(seed));
+    let best = mcts.best_action().unwrap();
+    assert!(optimal.contains(&best), "{:?} is not one of the optimal moves {:?}", best, optimal);
+}
+
+fn tictactoe(rows: &[&str], to_move: &str) -> TicTacToe {
+    let state: Value = json!({"board": rows, "to_move": to_move});
+    TicTacToe::from_json(&state).unwrap()
+}
+
+#[test]
+fn test_nim_theory() {
+    // The closed-form solution agrees with the exhaustive one
+    for &misere in &[false, true] {
+        for a in 0..4 {
+            for b in 0..4 {
+                for c in 0..5 {
+                    let game = if misere { Nim::misere(&[a, b, c]) } else { Nim::new(&[a, b, c]) };
+                    if game.is_finished() {
+                        continue;
+                    }
+                    let winning = game.winning_actions();
+                    let optimal = optimal_actions(&game);
+                    if winning.is_empty() {
+                        assert_eq!(optimal, game.allowed_actions());
+                    } else {
+                        assert_eq!(optimal, winning);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_nim() {
+    assert_converges(&Nim::new(&[1, 2, 4]), 2000, 1);
+    assert_converges(&Nim::new(&[2, 5]), 2000, 2);
+    assert_converges(&Nim::new(&[2, 3]), 1000, 3);
+}
+
+#[test]
+fn test_misere_nim() {
+    assert_converges(&Nim::misere(&[1, 1, 3]), 2000, 1);
+    assert_converges(&Nim::misere(&[1, 2, 4]), 2000, 2);
+    assert_converges(&Nim::misere(&[2, 5]), 2000, 3);
+}
+
+#[test]
+fn test_tictactoe() {
+    // Win at once
+    assert_converges(&tictactoe(&["XX.", "OO.", "..."], "X"), 500, 1);
+    // Block the open row
+    assert_converges(&tictactoe(&["XX.", ".O.", "..."], "O"), 500, 2);
+    // Avoid the corners, which let X fork
+    assert_converges(&tictactoe(&["X..", ".O.", "..X"], "O"), 500, 3);
+    // Take the center against a corner opening
+    assert_converges(&tictactoe(&["X..", "...", "..."], "O"), 2000, 4);
+    // Force a win after the reply in the neighbouring corner
+    assert_converges(&tictactoe(&["X.O", "...", "..."], "X"), 2000, 5);
+}
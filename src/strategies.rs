@@ -0,0 +1,219 @@
+//!
+//! Property based testing support (with the `proptest` feature).
+//!
+//! Strategies generate random action sequences and the states they reach
+//! for any `Game`, plus random boards of the bundled games. The invariant
+//! predicates below hold for every state and move of the bundled games, so
+//! property tests of both the games and the search can be written as:
+//!
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn slides_conserve_tiles(reached in twofortyeight_state(50)) {
+//!         for action in reached.game.allowed_actions() {
+//!             prop_assert!(slide_conserves_tiles(&reached.game, &action));
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Actions are generated as arbitrary indices into the allowed actions of
+//! the state at hand, so shrinking a failure leads towards shorter
+//! sequences of first allowed actions.
+//!
+
+use std::fmt;
+
+use proptest::prelude::*;
+use proptest::collection::vec;
+
+use mcts::{Game, GameAction, TreeNode};
+use twofortyeight::{TwoFortyEight, Action as TwoFortyEightAction};
+use tictactoe::{TicTacToe, Player};
+
+/// A state reached by playing `history` from a seeded initial state.
+#[derive(Clone)]
+pub struct Reached<G, A> {
+    pub game: G,
+    pub history: Vec<A>,
+    pub seed: u32,
+}
+
+/// Games need not be `Debug`; the seed and history identify the state.
+impl<G, A: GameAction> fmt::Debug for Reached<G, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reached {{ seed: {}, history: {:?} }}", self.seed, self.history)
+    }
+}
+
+/// States reached by up to `max_moves` random moves from `initial`.
+///
+/// The game is seeded before the first move, so stochastic games are
+/// reproducible as well.
+pub fn reachable<G, A>(initial: G, max_moves: usize) -> BoxedStrategy<Reached<G, A>>
+        where G: Game<A> + 'static, A: GameAction + 'static {
+    (any::<u32>(), vec(any::<usize>(), 0..max_moves + 1))
+        .prop_map(move |(seed, choices)| {
+            let mut game = initial.clone();
+            game.set_rng_seed(seed);
+            let mut history = Vec::new();
+            for choice in choices {
+                let actions = game.allowed_actions();
+                if actions.is_empty() {
+                    break;
+                }
+                let action = actions[choice % actions.len()];
+                game.make_move(&action);
+                history.push(action);
+            }
+            Reached { game: game, history: history, seed: seed }
+        })
+        .boxed()
+}
+
+/// Sequences of up to `max_moves` allowed actions from `initial`.
+pub fn action_sequence<G, A>(initial: G, max_moves: usize) -> BoxedStrategy<Vec<A>>
+        where G: Game<A> + 'static, A: GameAction + 'static {
+    reachable(initial, max_moves).prop_map(|reached| reached.history).boxed()
+}
+
+/// 2048 states reached by up to `max_moves` moves from a new game.
+pub fn twofortyeight_state(max_moves: usize) -> BoxedStrategy<Reached<TwoFortyEight, TwoFortyEightAction>> {
+    reachable(TwoFortyEight::new(), max_moves)
+}
+
+/// Arbitrary 2048 boards of 2x2 up to 6x6 cells with tiles up to 2048,
+/// not necessarily reachable.
+pub fn twofortyeight_board() -> BoxedStrategy<TwoFortyEight> {
+    (2usize..7, 2usize..7)
+        .prop_flat_map(|(width, height)| {
+            vec(0u32..12, width * height).prop_map(move |exponents| {
+                let mut game = TwoFortyEight::empty(width, height);
+                for (i, &exponent) in exponents.iter().enumerate() {
+                    let tile = if exponent == 0 { 0 } else { 1 << exponent };
+                    game.set_tile(i / width, i % width, tile);
+                }
+                game
+            })
+        })
+        .boxed()
+}
+
+/// TicTacToe positions reached from the empty board.
+pub fn tictactoe_state() -> BoxedStrategy<Reached<TicTacToe, ::tictactoe::Action>> {
+    reachable(TicTacToe::new(), 9)
+}
+
+/// The tiles of a 2048 board.
+fn tiles(game: &TwoFortyEight) -> Vec<u16> {
+    (0..game.height()).flat_map(|row| (0..game.width()).map(move |col| (row, col)))
+        .map(|(row, col)| game.get_tile(row, col))
+        .collect()
+}
+
+/// Every tile is empty or a power of two of at least 2.
+pub fn powers_of_two(game: &TwoFortyEight) -> bool {
+    tiles(game).iter().all(|&tile| tile == 0 || (tile >= 2 && tile.is_power_of_two()))
+}
+
+/// Sliding keeps the sum of the tiles, and the number of tiles only
+/// decreases by merging, which scores points. The action must be allowed.
+pub fn slide_conserves_tiles(before: &TwoFortyEight, action: &TwoFortyEightAction) -> bool {
+    let mut after = before.clone();
+    after.slide(action);
+
+    let (old, new) = (tiles(before), tiles(&after));
+    let sum = |tiles: &[u16]| tiles.iter().map(|&t| t as u32).sum::<u32>();
+    let count = |tiles: &[u16]| tiles.iter().filter(|&&t| t > 0).count();
+    let merges = count(&old).saturating_sub(count(&new));
+    let gained = after.score - before.score;
+
+    sum(&old) == sum(&new) && count(&new) <= count(&old)
+        && (merges > 0) == (gained > 0.) && gained >= 4. * merges as f32
+}
+
+/// An allowed move slides the tiles and spawns a single 2 or 4.
+pub fn move_spawns_one_tile(before: &TwoFortyEight, action: &TwoFortyEightAction) -> bool {
+    let mut slid = before.clone();
+    slid.slide(action);
+    let mut after = before.clone();
+    after.make_move(action);
+
+    let added = tiles(&slid).iter().zip(tiles(&after))
+        .filter(|&(&old, new)| old != new)
+        .map(|(&old, new)| (old, new))
+        .collect::<Vec<_>>();
+    (added == vec![(0, 2)] || added == vec![(0, 4)]) && after.moves == before.moves + 1
+}
+
+/// X moves first, so X has as many marks as O or one more, and the player
+/// to move follows from the counts.
+pub fn tictactoe_balanced(game: &TicTacToe) -> bool {
+    let cells = (0..3).flat_map(|row| (0..3).map(move |col| (row, col)))
+        .map(|(row, col)| game.get_cell(row, col))
+        .collect::<Vec<_>>();
+    let xs = cells.iter().filter(|&&cell| cell == Some(Player::X)).count();
+    let os = cells.iter().filter(|&&cell| cell == Some(Player::O)).count();
+    let to_move = if xs == os { Player::X } else { Player::O };
+    (xs == os || xs == os + 1) && game.to_move() == to_move && game.moves == xs + os
+}
+
+/// Every node of the tree was visited at least as often as all its
+/// children together, and every child at least once with a finite mean.
+pub fn tree_consistent<A: GameAction>(node: &TreeNode<A>) -> bool {
+    let child_visits = node.children().iter().map(|child| child.visits()).sum::<u64>();
+    child_visits <= node.visits() && node.children().iter().all(|child| {
+        child.visits() >= 1 && child.mean().is_finite() && tree_consistent(child)
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use mcts::*;
+    use strategies::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_reachable_boards(reached in twofortyeight_state(100)) {
+            prop_assert!(powers_of_two(&reached.game));
+            prop_assert_eq!(reached.game.moves, reached.history.len());
+        }
+
+        #[test]
+        fn prop_slides(game in twofortyeight_board()) {
+            for action in game.allowed_actions() {
+                prop_assert!(slide_conserves_tiles(&game, &action), "{:?}", action);
+            }
+        }
+
+        #[test]
+        fn prop_moves(reached in twofortyeight_state(50)) {
+            for action in reached.game.allowed_actions() {
+                prop_assert!(move_spawns_one_tile(&reached.game, &action), "{:?}", action);
+            }
+        }
+
+        #[test]
+        fn prop_tictactoe(reached in tictactoe_state()) {
+            prop_assert!(tictactoe_balanced(&reached.game));
+        }
+
+        #[test]
+        fn prop_search_tree(reached in tictactoe_state()) {
+            let mut mcts = MCTS::new(&reached.game, 1);
+            mcts.search(30, 1.);
+            prop_assert!(tree_consistent(&mcts.roots()[0]));
+        }
+
+        #[test]
+        fn prop_action_sequences(actions in action_sequence(::tictactoe::TicTacToe::new(), 4)) {
+            prop_assert!(actions.len() <= 4);
+        }
+    }
+}
@@ -0,0 +1,616 @@
+//!
+//! Conformance checks for `Game` implementations.
+//!
+//! The search trusts the games it is given: a game that is not
+//! deterministic, shares state between clones or never ends does not make
+//! the search fail, it just makes it play badly. The functions here play
+//! seeded random trajectories through a game and report the first
+//! violation they find, so that authors of new games can call them from
+//! their own tests:
+//!
+//! ```ignore
+//! let limits = Limits::default();
+//! check_game(&MyGame::new(), |game| game.to_json(), &all_actions, &limits).unwrap();
+//! ```
+//!
+//! Games have no notion of equality, so the checks compare what `observe`
+//! returns for a state, together with its allowed actions, reward and
+//! current player.
+//!
+//! Bugs that only show up deep in a search are easier to catch with
+//! `Validated`, which wraps a game and checks every call the search makes:
+//!
+//! ```ignore
+//! let game = Validated::new(MyGame::new()).with_fingerprint(|game| hash_state(game));
+//! let mut mcts = MCTS::new(&game, 1);
+//! mcts.search(1000, 1.);
+//! ```
+//!
+
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::panic;
+use std::sync::Mutex;
+use std::thread;
+
+use mcts::{Game, GameAction};
+use utils::{choose_random_with, seeded_rng, derive_seed, hash_value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// How much of the game the checks explore.
+pub struct Limits {
+    /// Number of random trajectories per check.
+    pub playouts: usize,
+    /// Playouts longer than this are considered not to terminate.
+    pub max_moves: usize,
+    /// Master seed for the trajectories and the games.
+    pub seed: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits { playouts: 20, max_moves: 10000, seed: 0 }
+    }
+}
+
+/// Everything the checks can see of a state.
+#[derive(Debug, PartialEq)]
+struct Snapshot<A, S> {
+    observation: S,
+    allowed_actions: Vec<A>,
+    reward: f32,
+    current_player: usize,
+}
+
+fn snapshot<G, A, S, F>(game: &G, observe: &F) -> Snapshot<A, S>
+        where G: Game<A>, A: GameAction, F: Fn(&G) -> S {
+    Snapshot {
+        observation: observe(game),
+        allowed_actions: game.allowed_actions(),
+        reward: game.reward(),
+        current_player: game.current_player(),
+    }
+}
+
+/// A seeded copy of the game and the generator for trajectory `i`.
+fn trajectory_start<G: Game<A>, A: GameAction>(game: &G, limits: &Limits, i: usize) -> G {
+    let mut game = game.clone();
+    game.set_rng_seed(derive_seed(limits.seed, 2 * i as u32));
+    game
+}
+
+/// Play random trajectories; `step` is called with the state before every
+/// move, the move and the history so far.
+fn for_each_move<G, A, F>(game: &G, limits: &Limits, mut step: F) -> Result<(), String>
+        where G: Game<A>, A: GameAction, F: FnMut(&G, &A, &[A]) -> Result<(), String> {
+    for i in 0..limits.playouts {
+        let mut rng = seeded_rng(derive_seed(limits.seed, 2 * i as u32 + 1));
+        let mut game = trajectory_start(game, limits, i);
+        let mut history = Vec::new();
+        while let Some(action) = choose_random_with(&mut rng, &game.allowed_actions()).cloned() {
+            if history.len() >= limits.max_moves {
+                break;
+            }
+            try!(step(&game, &action, &history));
+            game.make_move(&action);
+            history.push(action);
+        }
+    }
+    Ok(())
+}
+
+/// Identically seeded games must stay identical under identical moves.
+pub fn check_determinism<G, A, S, F>(game: &G, observe: F, limits: &Limits) -> Result<(), String>
+        where G: Game<A>, A: GameAction, S: PartialEq + Debug, F: Fn(&G) -> S {
+    for i in 0..limits.playouts {
+        let mut rng = seeded_rng(derive_seed(limits.seed, 2 * i as u32 + 1));
+        let mut a = trajectory_start(game, limits, i);
+        let mut b = trajectory_start(game, limits, i);
+        let mut history = Vec::new();
+        loop {
+            let (snapshot_a, snapshot_b) = (snapshot(&a, &observe), snapshot(&b, &observe));
+            if snapshot_a != snapshot_b {
+                return Err(format!("identical games differ after {:?}: {:?} vs {:?}",
+                                   history, snapshot_a, snapshot_b));
+            }
+            let action = match choose_random_with(&mut rng, &snapshot_a.allowed_actions) {
+                Some(&action) if history.len() < limits.max_moves => action,
+                _ => break
+            };
+            a.make_move(&action);
+            b.make_move(&action);
+            history.push(action);
+        }
+    }
+    Ok(())
+}
+
+/// Run `f` and catch its panic without printing the message.
+fn catch_silently<F: FnOnce()>(f: F) -> thread::Result<()> {
+    static HOOK: Mutex<()> = Mutex::new(());
+    let _guard = HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result
+}
+
+/// Run `f`; false if it panicked. The panic message is not printed.
+fn completes<F: FnOnce()>(f: F) -> bool {
+    catch_silently(f).is_ok()
+}
+
+/// Moves that are not allowed must not change the state.
+///
+/// `actions` lists every action of the game; `make_move` may either panic
+/// for those that are not allowed or ignore them. Allowed actions must not
+/// panic.
+pub fn check_illegal_moves<G, A, S, F>(game: &G, observe: F, actions: &[A], limits: &Limits) -> Result<(), String>
+        where G: Game<A>, A: GameAction, S: PartialEq + Debug, F: Fn(&G) -> S {
+    for_each_move(game, limits, |game, _, history| {
+        let allowed = game.allowed_actions();
+        let before = snapshot(game, &observe);
+        for action in actions {
+            let mut after = game.clone();
+            let completed = completes(|| after.make_move(action));
+            if allowed.contains(action) {
+                if !completed {
+                    return Err(format!("allowed action {:?} panicked after {:?}", action, history));
+                }
+            } else if completed && snapshot(&after, &observe) != before {
+                return Err(format!("action {:?} is not allowed after {:?} but changed the state",
+                                   action, history));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Rewards must be finite in every state.
+pub fn check_rewards_finite<G: Game<A>, A: GameAction>(game: &G, limits: &Limits) -> Result<(), String> {
+    try!(for_each_move(game, limits, |game, _, history| {
+        if !game.reward().is_finite() {
+            return Err(format!("reward {} after {:?}", game.reward(), history));
+        }
+        Ok(())
+    }));
+    // The loop above skips the final states
+    for i in 0..limits.playouts {
+        let mut rng = seeded_rng(derive_seed(limits.seed, 2 * i as u32 + 1));
+        let mut game = trajectory_start(game, limits, i);
+        let mut moves = 0;
+        while let Some(action) = choose_random_with(&mut rng, &game.allowed_actions()).cloned() {
+            if moves >= limits.max_moves {
+                break;
+            }
+            game.make_move(&action);
+            moves += 1;
+        }
+        if !game.reward().is_finite() {
+            return Err(format!("final reward {} of trajectory {}", game.reward(), i));
+        }
+    }
+    Ok(())
+}
+
+/// Random playouts must end within `limits.max_moves` moves.
+pub fn check_termination<G: Game<A>, A: GameAction>(game: &G, limits: &Limits) -> Result<(), String> {
+    for i in 0..limits.playouts {
+        let mut rng = seeded_rng(derive_seed(limits.seed, 2 * i as u32 + 1));
+        let mut game = trajectory_start(game, limits, i);
+        for _ in 0..limits.max_moves {
+            match choose_random_with(&mut rng, &game.allowed_actions()).cloned() {
+                Some(action) => game.make_move(&action),
+                None => break
+            }
+        }
+        if !game.allowed_actions().is_empty() {
+            return Err(format!("playout {} did not end within {} moves", i, limits.max_moves));
+        }
+    }
+    Ok(())
+}
+
+/// Moves on a clone must not affect the original and vice versa.
+pub fn check_clone_independence<G, A, S, F>(game: &G, observe: F, limits: &Limits) -> Result<(), String>
+        where G: Game<A>, A: GameAction, S: PartialEq + Debug, F: Fn(&G) -> S {
+    for_each_move(game, limits, |game, action, history| {
+        let original = game.clone();
+        let before = snapshot(&original, &observe);
+
+        let mut copy = original.clone();
+        copy.make_move(action);
+        if snapshot(&original, &observe) != before {
+            return Err(format!("moving a clone changed the original after {:?}", history));
+        }
+
+        let copy_before = snapshot(&copy, &observe);
+        let mut original = original;
+        original.make_move(action);
+        if snapshot(&copy, &observe) != copy_before {
+            return Err(format!("moving the original changed a clone after {:?}", history));
+        }
+        Ok(())
+    })
+}
+
+/// A game that checks the wrapped game whenever it is used.
+///
+/// Panics as soon as the game lists an action twice, is asked to make a
+/// move it does not allow, returns a reward that is not finite or -- if a
+/// fingerprint is given -- a move leaves its fingerprint unchanged. The
+/// message tells the moves that led to the offending state from the one
+/// that was wrapped.
+#[derive(Clone)]
+pub struct Validated<G, A> {
+    game: G,
+    path: Vec<A>,
+    fingerprint: Option<fn(&G) -> u64>,
+}
+
+impl<G: Game<A>, A: GameAction> Validated<G, A> {
+    pub fn new(game: G) -> Validated<G, A> {
+        Validated { game: game, path: Vec::new(), fingerprint: None }
+    }
+
+    /// Also check that every move changes `fingerprint` of the state.
+    pub fn with_fingerprint(self, fingerprint: fn(&G) -> u64) -> Validated<G, A> {
+        Validated { fingerprint: Some(fingerprint), ..self }
+    }
+
+    /// The wrapped game.
+    pub fn inner(&self) -> &G {
+        &self.game
+    }
+
+    /// The moves made since the game was wrapped.
+    pub fn path(&self) -> &[A] {
+        &self.path
+    }
+
+    fn violation(&self, msg: String) -> ! {
+        let fingerprint = match self.fingerprint {
+            Some(fingerprint) => format!(" (fingerprint {:x})", fingerprint(&self.game)),
+            None => String::new(),
+        };
+        panic!("Invalid game after {:?}: {}{}", self.path, msg, fingerprint)
+    }
+}
+
+impl<G: Game<A>, A: GameAction> Game<A> for Validated<G, A> {
+    fn allowed_actions(&self) -> Vec<A> {
+        let actions = self.game.allowed_actions();
+        let mut seen = HashSet::new();
+        for action in &actions {
+            if !seen.insert(action) {
+                self.violation(format!("{:?} is allowed twice", action));
+            }
+        }
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        let terminal = self.game.is_terminal();
+        if terminal != self.game.allowed_actions().is_empty() {
+            self.violation(format!("is_terminal is {} but the allowed actions disagree", terminal));
+        }
+        terminal
+    }
+
+    fn make_move(&mut self, action: &A) {
+        if !self.game.allowed_actions().contains(action) {
+            self.violation(format!("{:?} is not allowed", action));
+        }
+        let before = self.fingerprint.map(|fingerprint| fingerprint(&self.game));
+        self.game.make_move(action);
+        self.path.push(*action);
+        if before.is_some() && before == self.fingerprint.map(|fingerprint| fingerprint(&self.game)) {
+            self.violation(format!("{:?} did not change the state", action));
+        }
+    }
+
+    fn reward(&self) -> f32 {
+        let reward = self.game.reward();
+        if !reward.is_finite() {
+            self.violation(format!("reward {} is not finite", reward));
+        }
+        reward
+    }
+
+    fn set_rng_seed(&mut self, seed: u32) {
+        self.game.set_rng_seed(seed)
+    }
+
+    fn running_reward(&self) -> bool {
+        self.game.running_reward()
+    }
+
+    fn reward_for(&self, player: usize) -> f32 {
+        let reward = self.game.reward_for(player);
+        if !reward.is_finite() {
+            self.violation(format!("reward {} of player {} is not finite", reward, player));
+        }
+        reward
+    }
+
+    fn evaluate(&self) -> f32 {
+        let value = self.game.evaluate();
+        if !value.is_finite() {
+            self.violation(format!("evaluation {} is not finite", value));
+        }
+        value
+    }
+
+    fn evaluate_for(&self, player: usize) -> f32 {
+        let value = self.game.evaluate_for(player);
+        if !value.is_finite() {
+            self.violation(format!("evaluation {} of player {} is not finite", value, player));
+        }
+        value
+    }
+
+    fn action_priors(&self) -> Vec<(A, f32)> {
+        let priors = self.game.action_priors();
+        let allowed = self.game.allowed_actions();
+        for &(action, prior) in &priors {
+            if !allowed.contains(&action) {
+                self.violation(format!("prior for {:?}, which is not allowed", action));
+            }
+            if !(prior >= 0. && prior.is_finite()) {
+                self.violation(format!("prior {} of {:?} is not a probability", prior, action));
+            }
+        }
+        priors
+    }
+
+    fn chance_outcomes(&self) -> Option<Vec<(A, f32)>> {
+        let outcomes = self.game.chance_outcomes();
+        if let Some(ref outcomes) = outcomes {
+            let allowed = self.game.allowed_actions();
+            for &(action, probability) in outcomes {
+                if !allowed.contains(&action) {
+                    self.violation(format!("chance outcome {:?}, which is not allowed", action));
+                }
+                if !(probability >= 0. && probability.is_finite()) {
+                    self.violation(format!("probability {} of {:?} is not a probability", probability, action));
+                }
+            }
+            let total = outcomes.iter().fold(0., |sum, &(_, probability)| sum + probability);
+            if (total - 1.).abs() > 1e-3 {
+                self.violation(format!("chance outcomes add up to {}, not 1", total));
+            }
+        }
+        outcomes
+    }
+
+    fn current_player(&self) -> usize {
+        self.game.current_player()
+    }
+
+    fn hash_state(&self) -> Option<u64> {
+        self.game.hash_state()
+    }
+}
+
+/// A fingerprint of a hashable state, for `Validated::with_fingerprint`.
+pub fn hash_state<T: Hash>(state: &T) -> u64 {
+    hash_value(state)
+}
+
+/// Run all checks.
+pub fn check_game<G, A, S, F>(game: &G, observe: F, actions: &[A], limits: &Limits) -> Result<(), String>
+        where G: Game<A>, A: GameAction, S: PartialEq + Debug, F: Fn(&G) -> S {
+    try!(check_termination(game, limits));
+    try!(check_rewards_finite(game, limits));
+    try!(check_determinism(game, &observe, limits));
+    try!(check_clone_independence(game, &observe, limits));
+    check_illegal_moves(game, &observe, actions, limits)
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use mcts::*;
+    #[cfg(feature = "serde")]
+    use protocol::SerializableGame;
+    use testkit::*;
+    use testkit::catch_silently;
+    use tictactoe::{TicTacToe, Action as TicTacToeAction};
+    #[cfg(feature = "serde")]
+    use twofortyeight::{TwoFortyEight, Action as TwoFortyEightAction};
+    #[cfg(feature = "serde")]
+    use hex::{Hex, Action as HexAction};
+    #[cfg(feature = "serde")]
+    use adv2048::Adversarial2048;
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_bundled_games() {
+        let limits = Limits { playouts: 5, ..Limits::default() };
+
+        let actions = (0..9).map(|i| TicTacToeAction { row: i / 3, col: i % 3 }).collect::<Vec<_>>();
+        check_game(&TicTacToe::new(), |game| game.to_json(), &actions, &limits).unwrap();
+
+        let actions = [TwoFortyEightAction::Up, TwoFortyEightAction::Down,
+                       TwoFortyEightAction::Left, TwoFortyEightAction::Right];
+        check_game(&TwoFortyEight::new(), |game| game.to_json(), &actions, &limits).unwrap();
+
+        let actions = (0..25).map(|i| HexAction { row: i / 5, col: i % 5 }).collect::<Vec<_>>();
+        check_game(&Hex::with_size(5), |game| game.to_json(), &actions, &limits).unwrap();
+    }
+
+    /// Counts up to `end`; every flag breaks one rule.
+    struct Counter {
+        count: Rc<Cell<u32>>,
+        end: u32,
+        shares_state: bool,
+        nan_reward: bool,
+        ignores_illegal: bool,
+    }
+
+    impl Clone for Counter {
+        fn clone(&self) -> Counter {
+            let count = if self.shares_state { self.count.clone() } else { Rc::new(Cell::new(self.count.get())) };
+            Counter { count: count, ..*self }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct Step(u32);
+    impl GameAction for Step {}
+
+    impl Counter {
+        fn new(end: u32) -> Counter {
+            Counter { count: Rc::new(Cell::new(0)), end: end, shares_state: false,
+                      nan_reward: false, ignores_illegal: true }
+        }
+    }
+
+    impl Game<Step> for Counter {
+        fn allowed_actions(&self) -> Vec<Step> {
+            if self.count.get() < self.end { vec![Step(1), Step(2)] } else { vec![] }
+        }
+
+        fn make_move(&mut self, action: &Step) {
+            if !self.ignores_illegal || self.allowed_actions().contains(action) {
+                self.count.set(self.count.get() + action.0);
+            }
+        }
+
+        fn reward(&self) -> f32 {
+            if self.nan_reward && self.count.get() > 3 { 0. / 0. } else { self.count.get() as f32 }
+        }
+
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    #[test]
+    fn test_violations() {
+        let limits = Limits { playouts: 3, max_moves: 100, seed: 1 };
+        let observe = |game: &Counter| game.count.get();
+
+        let game = Counter::new(10);
+        assert!(check_termination(&game, &limits).is_ok());
+        assert!(check_rewards_finite(&game, &limits).is_ok());
+        assert!(check_clone_independence(&game, observe, &limits).is_ok());
+
+        let shared = Counter { shares_state: true, ..Counter::new(10) };
+        let err = check_clone_independence(&shared, observe, &limits).unwrap_err();
+        assert!(err.contains("changed the original"));
+
+        let endless = Counter::new(u32::max_value());
+        assert!(check_termination(&endless, &limits).unwrap_err().contains("did not end"));
+
+        let nan = Counter { nan_reward: true, ..Counter::new(10) };
+        assert!(check_rewards_finite(&nan, &limits).is_err());
+
+        let sloppy = Counter { ignores_illegal: false, ..Counter::new(10) };
+        let err = check_illegal_moves(&sloppy, observe, &[Step(1), Step(3)], &limits).unwrap_err();
+        assert!(err.contains("Step(3) is not allowed"));
+        assert!(check_illegal_moves(&game, observe, &[Step(1), Step(3)], &limits).is_ok());
+    }
+
+    /// Lists its only action twice until it reaches `twice`.
+    #[derive(Clone)]
+    struct Stutter {
+        count: u32,
+        twice: u32,
+    }
+
+    impl Game<Step> for Stutter {
+        fn allowed_actions(&self) -> Vec<Step> {
+            match self.count {
+                count if count >= 10 => vec![],
+                count if count >= self.twice => vec![Step(1), Step(1)],
+                _ => vec![Step(1)]
+            }
+        }
+        fn make_move(&mut self, action: &Step) { self.count += action.0 }
+        fn reward(&self) -> f32 { self.count as f32 }
+        fn set_rng_seed(&mut self, _: u32) {}
+    }
+
+    /// Run `f` and return its panic message.
+    fn panic_message<F: FnOnce()>(f: F) -> String {
+        match catch_silently(f) {
+            Ok(()) => String::new(),
+            Err(payload) => payload.downcast_ref::<String>().cloned().unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_validated_search() {
+        let game = Validated::new(TicTacToe::new()).with_fingerprint(|game| hash_state(game));
+        let mut mcts = MCTS::new(&game, 2);
+        mcts.search(200, 1.);
+        assert!(mcts.best_action().is_some());
+
+        let game = Validated::new(TwoFortyEight::new())
+            .with_fingerprint(|game| hash_state(&game.to_json().to_string()));
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(50, 1.);
+        assert!(mcts.best_action().is_some());
+
+        let game = Validated::new(Adversarial2048 { random_spawns: true, ..Adversarial2048::with_seed(1) });
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(50, 1.);
+        assert!(mcts.best_action().is_some());
+    }
+
+    #[test]
+    fn test_validated_violations() {
+        let msg = panic_message(|| {
+            let mut mcts = MCTS::new(&Validated::new(Stutter { count: 0, twice: 3 }), 1);
+            mcts.search(10, 1.);
+        });
+        assert_eq!(msg, "Invalid game after [Step(1), Step(1), Step(1)]: Step(1) is allowed twice");
+
+        let msg = panic_message(|| {
+            let mut game = Validated::new(TicTacToe::new());
+            let action = TicTacToeAction { row: 1, col: 1 };
+            game.make_move(&action);
+            game.make_move(&action);
+        });
+        assert!(msg.contains("Action { row: 1, col: 1 } is not allowed"), "{}", msg);
+
+        let msg = panic_message(|| {
+            let game = Validated::new(Counter { nan_reward: true, ..Counter::new(10) });
+            let mut mcts = MCTS::new(&game, 1);
+            mcts.search(10, 1.);
+        });
+        assert!(msg.ends_with("reward NaN of player 0 is not finite"), "{}", msg);
+
+        let msg = panic_message(|| {
+            let frozen = Counter { ignores_illegal: false, ..Counter::new(10) };
+            let mut game = Validated::new(frozen).with_fingerprint(|game| game.count.get() as u64 / 2);
+            game.make_move(&Step(2));
+            game.make_move(&Step(1));
+        });
+        assert_eq!(msg, "Invalid game after [Step(2), Step(1)]: Step(1) did not change the state (fingerprint 1)");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_nondeterminism() {
+        #[derive(Clone)]
+        struct Dice(TwoFortyEight);
+        impl Game<TwoFortyEightAction> for Dice {
+            fn allowed_actions(&self) -> Vec<TwoFortyEightAction> { self.0.allowed_actions() }
+            fn make_move(&mut self, action: &TwoFortyEightAction) { self.0.make_move(action) }
+            fn reward(&self) -> f32 { self.0.reward() }
+            // Ignores the seed, so the tile spawns differ
+            fn set_rng_seed(&mut self, _: u32) { self.0.set_rng_seed(::rand::random()) }
+        }
+
+        let limits = Limits { playouts: 2, ..Limits::default() };
+        let err = check_determinism(&Dice(TwoFortyEight::new()), |game| game.0.to_json(), &limits);
+        assert!(err.unwrap_err().starts_with("identical games differ"));
+    }
+}
@@ -0,0 +1,408 @@
+//!
+//! Implementation of TicTacToe.
+//!
+//! Two players take turns placing their mark on a 3x3 board; the first one
+//! to get three marks in a row, column or diagonal wins. With perfect play
+//! from both sides the game ends in a draw.
+//!
+//! X moves first and is player 0; rewards are given from X's perspective.
+//!
+
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{GameAction, Game};
+use utils::hash_value;
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, parse_board_rows, split_notation};
+use cli::DisplayAction;
+
+pub const SIZE: usize = 3;
+
+/// All rows, columns and diagonals as board indices.
+const LINES: [[usize; 3]; 8] = [
+    [0, 1, 2], [3, 4, 5], [6, 7, 8],
+    [0, 3, 6], [1, 4, 7], [2, 5, 8],
+    [0, 4, 8], [2, 4, 6],
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Player {
+    X, O
+}
+
+impl Player {
+    /// The other player.
+    pub fn opponent(&self) -> Player {
+        match *self {
+            Player::X => Player::O,
+            Player::O => Player::X,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Place a mark on (row, col).
+pub struct Action {
+    pub row: u8,
+    pub col: u8,
+}
+impl GameAction for Action {}
+
+#[derive(Debug, Clone, Hash)]
+/// Implementation of the TicTacToe game mechanics.
+pub struct TicTacToe {
+    board: [Option<Player>; SIZE*SIZE],
+    to_move: Player,
+    winner: Option<Player>,
+    pub moves: usize,
+}
+
+impl TicTacToe {
+    /// Create a new game with an empty board; X moves first.
+    pub fn new() -> TicTacToe {
+        TicTacToe {
+            board: [None; SIZE*SIZE],
+            to_move: Player::X,
+            winner: None,
+            moves: 0,
+        }
+    }
+
+    /// The mark on the given cell, if any.
+    pub fn get_cell(&self, row: usize, col: usize) -> Option<Player> {
+        self.board[row * SIZE + col]
+    }
+
+    /// The player who makes the next move.
+    pub fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    /// The winner of the game, if any.
+    pub fn winner(&self) -> Option<Player> {
+        self.winner
+    }
+
+    /// Is the game over, either by a win or because the board is full?
+    pub fn is_finished(&self) -> bool {
+        self.winner.is_some() || self.moves == SIZE*SIZE
+    }
+}
+
+impl Game<Action> for TicTacToe {
+
+    /// Return a list with all allowed actions given the current game state.
+    fn allowed_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.is_finished() {
+            return actions;
+        }
+
+        for row in 0..SIZE {
+            for col in 0..SIZE {
+                if self.get_cell(row, col).is_none() {
+                    actions.push(Action { row: row as u8, col: col as u8 });
+                }
+            }
+        }
+        actions
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.is_finished()
+    }
+
+    /// Change the current game state according to the given action.
+    fn make_move(&mut self, action: &Action) {
+        let idx = (action.row as usize) * SIZE + action.col as usize;
+        assert!(!self.is_finished(), "Game is already over");
+        assert!(self.board[idx].is_none(), "Illegal move");
+
+        let player = self.to_move;
+        self.board[idx] = Some(player);
+        self.moves += 1;
+        self.to_move = player.opponent();
+
+        for line in &LINES {
+            if line.iter().all(|&i| self.board[i] == Some(player)) {
+                self.winner = Some(player);
+            }
+        }
+    }
+
+    /// Reward from the perspective of X.
+    ///
+    /// 1 for a win, -1 for a loss and 0 for draws and unfinished games.
+    fn reward(&self) -> f32 {
+        match self.winner {
+            Some(Player::X) =>  1.,
+            Some(Player::O) => -1.,
+            None => 0.
+        }
+    }
+
+    /// Derterminize the game
+    fn set_rng_seed(&mut self, _: u32) { }
+
+    /// X is player 0, O is player 1.
+    fn current_player(&self) -> usize {
+        match self.to_move {
+            Player::X => 0,
+            Player::O => 1,
+        }
+    }
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(self))
+    }
+
+    /// The rows of the board and optionally the player to move, like
+    /// `XX./OO./... X`; without it, X moves unless X has more marks.
+    #[cfg(feature = "serde")]
+    fn from_str(text: &str) -> Result<TicTacToe, String> {
+        let (rows, fields) = split_notation(text);
+        let marks = |mark: char| rows.iter().map(|row| row.matches(mark).count()).sum::<usize>();
+        let to_move = match fields.as_slice() {
+            [] if marks('X') > marks('O') => "O",
+            [] => "X",
+            [to_move] => to_move,
+            _ => return Err(format!("invalid state '{}'", text))
+        };
+        TicTacToe::from_json(&json!({ "board": rows, "to_move": to_move }))
+    }
+}
+
+/// The board as rows like `"X.O"`, and the player to move.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for TicTacToe {
+    fn to_json(&self) -> Value {
+        let rows = (0..SIZE).map(|row| {
+            (0..SIZE).map(|col| match self.get_cell(row, col) {
+                None => '.',
+                Some(Player::X) => 'X',
+                Some(Player::O) => 'O',
+            }).collect::<String>()
+        }).collect::<Vec<_>>();
+        json!({
+            "board": rows,
+            "to_move": format!("{:?}", self.to_move),
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<TicTacToe, String> {
+        let mut game = TicTacToe::new();
+        for (row, cells) in try!(parse_board_rows(value, SIZE)).iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                game.board[row * SIZE + col] = match *cell {
+                    '.' => None,
+                    'X' => Some(Player::X),
+                    'O' => Some(Player::O),
+                    _ => return Err(format!("invalid cell '{}'", cell))
+                };
+            }
+        }
+        game.to_move = match value["to_move"].as_str() {
+            Some("X") => Player::X,
+            Some("O") => Player::O,
+            _ => return Err("to_move must be \"X\" or \"O\"".to_string())
+        };
+        game.moves = game.board.iter().filter(|cell| cell.is_some()).count();
+        for &player in &[Player::X, Player::O] {
+            if LINES.iter().any(|line| line.iter().all(|&i| game.board[i] == Some(player))) {
+                game.winner = Some(player);
+            }
+        }
+        Ok(game)
+    }
+}
+
+impl fmt::Display for TicTacToe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "   1   2   3"));
+        for row in 0..SIZE {
+            if row > 0 {
+                try!(writeln!(f, "  ---+---+---"));
+            }
+            try!(write!(f, "{} ", row+1));
+            for col in 0..SIZE {
+                if col > 0 {
+                    try!(f.write_str("|"));
+                }
+                let symbol = match self.get_cell(row, col) {
+                    None            => "   ",
+                    Some(Player::X) => " X ",
+                    Some(Player::O) => " O ",
+                };
+                try!(f.write_str(symbol));
+            }
+            try!(f.write_str("\n"));
+        }
+        f.write_str("")
+    }
+}
+
+/// Moves are entered as "row col", 1-based.
+impl DisplayAction<Action> for TicTacToe {
+    fn parse_action(&self, text: &str) -> Option<Action> {
+        let coords: Vec<usize> = text.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| s.parse().ok())
+            .collect();
+        match coords.as_slice() {
+            [row, col] if *row >= 1 && *row <= SIZE && *col >= 1 && *col <= SIZE =>
+                Some(Action { row: (*row - 1) as u8, col: (*col - 1) as u8 }),
+            _ => None
+        }
+    }
+
+    fn show_action(&self, action: &Action) -> String {
+        format!("{} {}", action.row + 1, action.col + 1)
+    }
+
+    fn input_help(&self) -> String {
+        "row and column, e.g. '2 3'".to_string()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use test::Bencher;
+
+    use mcts::*;
+    use tictactoe::*;
+
+    fn play(game: &mut TicTacToe, moves: &[(u8, u8)]) {
+        for &(row, col) in moves {
+            game.make_move(&Action { row: row, col: col });
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let game = TicTacToe::new();
+
+        assert_eq!(game.allowed_actions().len(), 9);
+        assert_eq!(game.to_move(), Player::X);
+        assert_eq!(game.current_player(), 0);
+        assert_eq!(game.reward(), 0.);
+        println!("{}", game);
+    }
+
+    #[test]
+    fn test_win() {
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (1, 1), (0, 1), (2, 2), (0, 2)]);
+
+        assert_eq!(game.winner(), Some(Player::X));
+        assert_eq!(game.reward(), 1.);
+        assert_eq!(game.allowed_actions().len(), 0);
+
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (0, 2), (0, 1), (1, 1), (2, 2), (2, 0)]);
+
+        assert_eq!(game.winner(), Some(Player::O));
+        assert_eq!(game.reward(), -1.);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (1, 1), (2, 2), (0, 1), (2, 1),
+                          (2, 0), (0, 2), (1, 2), (1, 0)]);
+
+        assert!(game.is_finished());
+        assert_eq!(game.winner(), None);
+        assert_eq!(game.reward(), 0.);
+        println!("{}", game);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (1, 1), (0, 1)]);
+
+        let value = game.to_json();
+        assert_eq!(value["board"], json!(["XX.", ".O.", "..."]));
+        assert_eq!(value["to_move"], "O");
+
+        let restored = TicTacToe::from_json(&value).unwrap();
+        assert_eq!(restored.to_json(), value);
+        assert_eq!(restored.moves, 3);
+        assert_eq!(restored.current_player(), 1);
+
+        let won = json!({"board": ["XXX", "OO.", "..."], "to_move": "O"});
+        assert_eq!(TicTacToe::from_json(&won).unwrap().winner(), Some(Player::X));
+        assert!(TicTacToe::from_json(&json!({"board": ["XX", "", ""]})).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_from_str() {
+        let game = TicTacToe::from_str("XX./.O./... O").unwrap();
+        assert_eq!(game.to_json(), json!({"board": ["XX.", ".O.", "..."], "to_move": "O"}));
+        assert_eq!(game.moves, 3);
+
+        // Without the player to move, it follows from the marks
+        assert_eq!(TicTacToe::from_str("XX./.O./...").unwrap().to_json(), game.to_json());
+        assert_eq!(TicTacToe::from_str(".../.../...").unwrap().current_player(), 0);
+        assert_eq!(TicTacToe::from_str("XXX/OO./...").unwrap().winner(), Some(Player::X));
+
+        assert!(TicTacToe::from_str("XX./.O.").is_err());
+        assert!(TicTacToe::from_str("XX./.O./... O X").is_err());
+        assert!(TicTacToe::from_str("").is_err());
+    }
+
+    #[test]
+    fn test_playout() {
+        let game = TicTacToe::new();
+        let final_game = playout(&game);
+
+        assert!(final_game.is_finished());
+    }
+
+    #[test]
+    fn test_mcts_wins() {
+        // X to move can win immediately
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (1, 0), (0, 1), (1, 1)]);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(500, 1.);
+        assert_eq!(mcts.best_action(), Some(Action { row: 0, col: 2 }));
+    }
+
+    #[test]
+    fn test_mcts_blocks() {
+        // O to move has to block X's row
+        let mut game = TicTacToe::new();
+        play(&mut game, &[(0, 0), (1, 1), (0, 1)]);
+
+        let mut mcts = MCTS::new(&game, 1);
+        mcts.search(2000, 1.);
+        assert_eq!(mcts.best_action(), Some(Action { row: 0, col: 2 }));
+    }
+
+    #[test]
+    fn test_mcts_selfplay_draws() {
+        let mut game = TicTacToe::new();
+
+        while !game.is_finished() {
+            let mut mcts = MCTS::new(&game, 1);
+            mcts.search(3000, 1.);
+            game.make_move(&mcts.best_action().unwrap());
+        }
+        println!("{}", game);
+        assert_eq!(game.winner(), None);
+    }
+
+    #[bench]
+    fn bench_playout(b: &mut Bencher) {
+        let game = TicTacToe::new();
+        b.iter(|| playout(&game));
+    }
+}
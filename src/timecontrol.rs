@@ -0,0 +1,186 @@
+//!
+//! Chess-style time controls for match play.
+//!
+//! Every player starts with a total thinking time and gets an increment
+//! after each of their moves; whoever runs out of time loses. Instead of a
+//! fixed time per move, `timed_search` allocates a budget from the
+//! remaining clock: forced moves are played instantly, moves where the
+//! search quickly settles on one action take about half the nominal
+//! allocation and unclear positions up to twice as much.
+//!
+
+use std::fmt;
+use std::str::FromStr;
+
+use mcts::{Game, GameAction, MCTS};
+use utils::seconds;
+
+/// Number of moves the remaining time is expected to last.
+const MOVES_TO_GO: f32 = 30.;
+
+/// Share of root visits at which the search is considered settled.
+const SETTLED: f32 = 0.6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Total thinking time and increment per move (in s).
+///
+/// The textual form is `BASE+INCREMENT`, e.g. `60+0.5`, or just `BASE`.
+pub struct TimeControl {
+    pub base: f32,
+    pub increment: f32,
+}
+
+impl FromStr for TimeControl {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<TimeControl, String> {
+        let invalid = || format!("Invalid time control '{}', expected BASE[+INCREMENT]", text);
+        let mut parts = text.splitn(2, '+');
+        let base: f32 = try!(parts.next().unwrap().trim().parse().map_err(|_| invalid()));
+        let increment: f32 = match parts.next() {
+            Some(increment) => try!(increment.trim().parse().map_err(|_| invalid())),
+            None => 0.
+        };
+        if !(base > 0.) || !(increment >= 0.) {
+            return Err(invalid());
+        }
+        Ok(TimeControl { base: base, increment: increment })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}+{}", self.base, self.increment)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Remaining thinking time of every player.
+pub struct Clock {
+    pub control: TimeControl,
+    remaining: Vec<f32>,
+}
+
+impl Clock {
+    /// Start clocks for `n_players` players.
+    pub fn new(control: TimeControl, n_players: usize) -> Clock {
+        Clock { control: control, remaining: vec![control.base; n_players] }
+    }
+
+    /// Remaining time of the given player.
+    pub fn remaining(&self, player: usize) -> f32 {
+        self.remaining[player]
+    }
+
+    /// Charge a move that took `elapsed` seconds to the given player.
+    ///
+    /// Returns false if the player ran out of time; otherwise the
+    /// increment is added.
+    pub fn charge(&mut self, player: usize, elapsed: f32) -> bool {
+        self.remaining[player] -= elapsed;
+        if self.remaining[player] <= 0. {
+            self.remaining[player] = 0.;
+            return false;
+        }
+        self.remaining[player] += self.control.increment;
+        true
+    }
+}
+
+/// Nominal thinking time for the next move.
+///
+/// Spends an even share of the remaining time plus most of the increment,
+/// but never more than a fifth of what is left.
+pub fn allocate(remaining: f32, increment: f32) -> f32 {
+    (remaining / MOVES_TO_GO + 0.75 * increment).min(remaining / 5.).max(0.)
+}
+
+/// Share of the root visits going to the most visited action.
+fn visit_share<G: Game<A>, A: GameAction>(mcts: &MCTS<G, A>) -> f32 {
+    let values = mcts.action_values();
+    let total = values.iter().map(|&(_, n, _)| n).sum::<u64>();
+    let most = values.iter().map(|&(_, n, _)| n).max().unwrap_or(0);
+    if total > 0 { most as f32 / total as f32 } else { 0. }
+}
+
+/// Search the game within a budget taken from the remaining clock.
+///
+/// Forced moves get a single iteration. Otherwise half of the nominal
+/// allocation is spent first; the search continues for the other half
+/// unless one action already gets most of the visits, and for another
+/// full allocation if it is still undecided then.
+pub fn timed_search<G: Game<A>, A: GameAction>(game: &G, remaining: f32, increment: f32,
+                                               ensemble_size: usize, c: f32) -> MCTS<G, A> {
+    let mut mcts = MCTS::new(game, ensemble_size);
+    if game.allowed_actions().len() <= 1 {
+        mcts.search(1, c);
+        return mcts;
+    }
+
+    let t0 = seconds();
+    let target = allocate(remaining, increment);
+    let slices = [0.5 * target, target, (2. * target).min(remaining / 3.)];
+    for (i, &until) in slices.iter().enumerate() {
+        let spent = (seconds() - t0) as f32;
+        if until > spent {
+            mcts.search_time(until - spent, c);
+        }
+        if i < 2 && visit_share(&mcts) >= SETTLED {
+            break;
+        }
+    }
+    mcts
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use timecontrol::*;
+    use tictactoe::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("60+0.5".parse(), Ok(TimeControl { base: 60., increment: 0.5 }));
+        assert_eq!("10".parse(), Ok(TimeControl { base: 10., increment: 0. }));
+        assert_eq!(format!("{}", TimeControl { base: 60., increment: 0.5 }), "60+0.5");
+        assert!("".parse::<TimeControl>().is_err());
+        assert!("0+1".parse::<TimeControl>().is_err());
+        assert!("5+x".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::new(TimeControl { base: 1., increment: 0.5 }, 2);
+        assert!(clock.charge(0, 0.75));
+        assert_eq!(clock.remaining(0), 0.75);
+        assert_eq!(clock.remaining(1), 1.);
+        assert!(!clock.charge(1, 1.5));
+        assert_eq!(clock.remaining(1), 0.);
+    }
+
+    #[test]
+    fn test_allocate() {
+        assert_eq!(allocate(60., 0.), 2.);
+        assert_eq!(allocate(60., 1.), 2.75);
+        // Low on time: at most a fifth of what is left
+        assert_eq!(allocate(1., 10.), 0.2);
+        assert_eq!(allocate(0., 0.), 0.);
+    }
+
+    #[test]
+    fn test_forced_move() {
+        // Only one cell is left
+        let mut game = TicTacToe::new();
+        for &(row, col) in &[(0, 0), (1, 1), (2, 2), (0, 2), (2, 0), (1, 0),
+                             (1, 2), (2, 1)] {
+            game.make_move(&Action { row: row, col: col });
+        }
+        assert_eq!(game.allowed_actions().len(), 1);
+
+        let mcts = timed_search(&game, 100., 0., 1, 1.);
+        assert_eq!(mcts.best_action(), Some(Action { row: 0, col: 1 }));
+        assert!(mcts.tree_statistics().nodes() <= 2);
+    }
+}
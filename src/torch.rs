@@ -0,0 +1,285 @@
+//!
+//! Leaf evaluation and training with libtorch (with the `torch` feature).
+//!
+//! `TorchEvaluator` runs a TorchScript network, e.g. one exported from
+//! Python with `torch.jit.save`, on the CPU or a GPU. Like
+//! `onnx::OnnxEvaluator`, the network takes a batch of states, each encoded
+//! as `features` numbers, with the shape `[batch, features]`. It returns
+//! the value of every state for player 0, `[batch]` or `[batch, 1]`, or a
+//! tuple of the values and the logits of a policy over the action ids of
+//! `ActionCodec`, `[batch, num_distinct_actions]`.
+//!
+//! The parameters of the network live in a `VarStore`, so the network can
+//! be trained in the same process that searches with it:
+//!
+//! ```ignore
+//! let mut net = TorchEvaluator::load("2048.pt", Device::cuda_if_available(), 16, log2_tiles)?;
+//! let mut optimizer = nn::Adam::default().build(net.var_store(), 1e-3)?;
+//! let (value_loss, policy_loss) = net.train_batch(&mut optimizer, &games, &policies, &outcomes);
+//! net.save("2048.pt")?;
+//! ```
+//!
+//! The targets are those of AlphaZero: the outcome of the game for the
+//! values, see `value_loss`, and the visit distribution of the search at
+//! every state for the policy, see `policy_loss` and `policy_targets`.
+//!
+
+use std::convert::TryFrom;
+use std::f32;
+use std::slice;
+
+use tch::{self, nn, Device, IValue, Kind, Reduction, Tensor, TrainableCModule};
+
+use mcts::{Evaluator, GameAction};
+use openspiel::ActionCodec;
+
+/// An `Evaluator` running a TorchScript network on states encoded by
+/// `encode`.
+pub struct TorchEvaluator<F> {
+    vs: nn::VarStore,
+    module: TrainableCModule,
+    features: usize,
+    encode: F,
+}
+
+/// Squared error of the `values` of a batch against the `outcomes` of
+/// their games, averaged over the batch.
+pub fn value_loss(values: &Tensor, outcomes: &Tensor) -> Tensor {
+    values.mse_loss(outcomes, Reduction::Mean)
+}
+
+/// Cross-entropy of the policy `logits` of a batch against the visit
+/// distributions in `targets`, averaged over the batch.
+pub fn policy_loss(logits: &Tensor, targets: &Tensor) -> Tensor {
+    let log_probs = logits.log_softmax(-1, Kind::Float);
+    -(targets * log_probs).sum_dim_intlist(-1, false, Kind::Float).mean(Kind::Float)
+}
+
+/// The visit distributions of the searches at `games` as the rows of a
+/// tensor over the action ids of `ActionCodec`.
+pub fn policy_targets<G, A>(games: &[G], policies: &[Vec<(A, f32)>]) -> Tensor
+        where G: ActionCodec<A>, A: GameAction {
+    let width = games.first().map_or(0, |game| game.num_distinct_actions());
+    let mut targets = vec![0f32; games.len() * width];
+    for (row, (game, policy)) in games.iter().zip(policies).enumerate() {
+        let total: f32 = policy.iter().map(|&(_, visits)| visits).sum();
+        for &(action, visits) in policy {
+            targets[row * width + game.encode(&action) as usize] = visits / total.max(f32::MIN_POSITIVE);
+        }
+    }
+    Tensor::from_slice(&targets).view([games.len() as i64, width as i64])
+}
+
+impl<F> TorchEvaluator<F> {
+    /// Load a TorchScript network onto `device`; `encode` turns a state
+    /// into `features` numbers.
+    pub fn load(path: &str, device: Device, features: usize, encode: F) -> Result<TorchEvaluator<F>, String> {
+        let vs = nn::VarStore::new(device);
+        let mut module = try!(TrainableCModule::load(path, vs.root())
+            .map_err(|e| format!("Failed to load {}: {}", path, e)));
+        module.set_eval();
+        Ok(TorchEvaluator { vs: vs, module: module, features: features, encode: encode })
+    }
+
+    /// Save the network, e.g. to checkpoint it during training.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        self.module.save(path).map_err(|e| format!("Failed to save {}: {}", path, e))
+    }
+
+    /// The parameters of the network, to build an optimizer for
+    /// `train_batch`.
+    pub fn var_store(&self) -> &nn::VarStore {
+        &self.vs
+    }
+
+    /// Encode a batch of states as the input of the network.
+    pub fn input<G>(&self, games: &[G]) -> Tensor where F: Fn(&G) -> Vec<f32> {
+        let mut input = Vec::with_capacity(games.len() * self.features);
+        for game in games {
+            let features = (self.encode)(game);
+            assert_eq!(features.len(), self.features, "Encoded {} features instead of {}",
+                       features.len(), self.features);
+            input.extend(features);
+        }
+        Tensor::from_slice(&input).view([games.len() as i64, self.features as i64]).to_device(self.vs.device())
+    }
+
+    /// Run the network on an input; returns the values, with the shape
+    /// `[batch]`, and the policy logits if the network has a policy.
+    pub fn forward(&self, input: &Tensor) -> (Tensor, Option<Tensor>) {
+        let output = self.module.forward_is(&[IValue::Tensor(input.shallow_clone())])
+            .unwrap_or_else(|e| panic!("Network failed: {}", e));
+        match output {
+            IValue::Tensor(values) => (values.view([-1]), None),
+            IValue::Tuple(mut outputs) => {
+                let logits = match outputs.pop() {
+                    Some(IValue::Tensor(logits)) => logits,
+                    output => panic!("Expected policy logits, got {:?}", output)
+                };
+                match outputs.pop() {
+                    Some(IValue::Tensor(values)) => (values.view([-1]), Some(logits)),
+                    output => panic!("Expected values, got {:?}", output)
+                }
+            },
+            output => panic!("Expected values or (values, logits), got {:?}", output)
+        }
+    }
+
+    /// Run the network on a batch of states without tracking gradients;
+    /// returns their values and, if the network has a policy, its logits.
+    fn run<G>(&self, games: &[G]) -> (Vec<f32>, Option<Vec<Vec<f32>>>) where F: Fn(&G) -> Vec<f32> {
+        let input = self.input(games);
+        let (values, logits) = tch::no_grad(|| self.forward(&input));
+        let values = Vec::<f32>::try_from(&values.to_kind(Kind::Float).to_device(Device::Cpu))
+            .expect("Expected a vector of values");
+        assert_eq!(values.len(), games.len(), "Network returned {} values for a batch of {}",
+                   values.len(), games.len());
+        let logits = logits.map(|logits| {
+            Vec::<Vec<f32>>::try_from(&logits.to_kind(Kind::Float).to_device(Device::Cpu))
+                .expect("Expected a matrix of logits")
+        });
+        (values, logits)
+    }
+
+    /// Prior probabilities of the allowed actions of `game`: the softmax
+    /// of the policy logits over them, or uniform if the network has no
+    /// policy.
+    pub fn priors<G, A>(&self, game: &G) -> Vec<(A, f32)>
+            where G: ActionCodec<A>, A: GameAction, F: Fn(&G) -> Vec<f32> {
+        let actions = game.allowed_actions();
+        let logits = match self.run(slice::from_ref(game)).1 {
+            Some(mut logits) => logits.remove(0),
+            None => return actions.iter().map(|&action| (action, 1. / actions.len() as f32)).collect()
+        };
+        let logits = actions.iter().map(|action| logits[game.encode(action) as usize]).collect::<Vec<_>>();
+        let most = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let weights = logits.iter().map(|logit| (logit - most).exp()).collect::<Vec<_>>();
+        let total: f32 = weights.iter().sum();
+        actions.into_iter().zip(weights).map(|(action, weight)| (action, weight / total)).collect()
+    }
+
+    /// One optimizer step on a batch of `games`, with the visit
+    /// distributions of their searches and the outcomes of their games for
+    /// player 0; returns the value and policy losses before the step.
+    ///
+    /// Networks without a policy only learn the values.
+    pub fn train_batch<G, A>(&mut self, optimizer: &mut nn::Optimizer, games: &[G], policies: &[Vec<(A, f32)>],
+                             outcomes: &[f32]) -> (f32, f32)
+            where G: ActionCodec<A>, A: GameAction, F: Fn(&G) -> Vec<f32> {
+        let device = self.vs.device();
+        let input = self.input(games);
+        self.module.set_train();
+        let (values, logits) = self.forward(&input);
+        let value_loss = value_loss(&values, &Tensor::from_slice(outcomes).to_device(device));
+        let policy_loss = logits.map(|logits| policy_loss(&logits, &policy_targets(games, policies).to_device(device)));
+        let loss = match policy_loss {
+            Some(ref policy_loss) => &value_loss + policy_loss,
+            None => value_loss.shallow_clone()
+        };
+        optimizer.backward_step(&loss);
+        self.module.set_eval();
+        (value_loss.double_value(&[]) as f32, policy_loss.map_or(0., |loss| loss.double_value(&[]) as f32))
+    }
+}
+
+impl<G, F: Fn(&G) -> Vec<f32>> Evaluator<G> for TorchEvaluator<F> {
+    fn evaluate(&self, game: &G) -> f32 {
+        self.run(slice::from_ref(game)).0[0]
+    }
+
+    /// The whole batch goes through the network at once.
+    fn evaluate_batch(&self, games: &[G]) -> Vec<f32> {
+        if games.is_empty() {
+            return Vec::new();
+        }
+        self.run(games).0
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::env;
+    use std::process;
+
+    use tch::{nn, CModule, Device, Kind, Tensor};
+    use tch::nn::{Module, OptimizerConfig};
+
+    use mcts::*;
+    use openspiel::ActionCodec;
+    use torch::*;
+    use twofortyeight::{self, TwoFortyEight};
+
+    fn tiles(game: &TwoFortyEight) -> Vec<f32> {
+        (0..16).map(|i| game.get_tile(i / 4, i % 4) as f32).collect()
+    }
+
+    /// Save a linear network on the 16 tiles of a 2048 board, with a
+    /// value and 4 policy logits, and load it as an evaluator.
+    fn network(name: &str) -> TorchEvaluator<fn(&TwoFortyEight) -> Vec<f32>> {
+        let vs = nn::VarStore::new(Device::Cpu);
+        let value = nn::linear(vs.root() / "value", 16, 1, Default::default());
+        let policy = nn::linear(vs.root() / "policy", 16, 4, Default::default());
+        let mut closure = |inputs: &[Tensor]| vec![value.forward(&inputs[0]), policy.forward(&inputs[0])];
+        let module = CModule::create_by_tracing("Net", "forward", &[Tensor::zeros([1, 16], (Kind::Float, Device::Cpu))],
+                                                &mut closure).unwrap();
+        let path = env::temp_dir().join(format!("mcts-{}-{}.pt", name, process::id()));
+        let path = path.to_str().unwrap();
+        module.save(path).unwrap();
+        TorchEvaluator::load(path, Device::Cpu, 16, tiles as fn(&TwoFortyEight) -> Vec<f32>).unwrap()
+    }
+
+    #[test]
+    fn test_losses() {
+        let values = Tensor::from_slice(&[0.5f32, -1.]);
+        let outcomes = Tensor::from_slice(&[1f32, -1.]);
+        assert!((value_loss(&values, &outcomes).double_value(&[]) - 0.125).abs() < 1e-6);
+
+        // Uniform logits over 4 actions cost log(4) for any distribution
+        let logits = Tensor::zeros([2, 4], (Kind::Float, Device::Cpu));
+        let targets = Tensor::from_slice(&[1f32, 0., 0., 0., 0.25, 0.25, 0.25, 0.25]).view([2, 4]);
+        assert!((policy_loss(&logits, &targets).double_value(&[]) - 4f64.ln()).abs() < 1e-5);
+
+        let games = [TwoFortyEight::with_seed(1)];
+        let policy = vec![(twofortyeight::Action::Left, 3.), (twofortyeight::Action::Up, 1.)];
+        let targets = Vec::<f32>::try_from(&policy_targets(&games, &[policy]).view([-1])).unwrap();
+        assert_eq!(targets.iter().sum::<f32>(), 1.);
+        assert_eq!(targets[games[0].encode(&twofortyeight::Action::Left) as usize], 0.75);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let net = network("evaluate");
+        let games = (0..5).map(TwoFortyEight::with_seed).collect::<Vec<_>>();
+        let values = net.evaluate_batch(&games);
+        assert_eq!(values.len(), 5);
+        for (game, value) in games.iter().zip(values) {
+            assert!((net.evaluate(game) - value).abs() < 1e-5);
+        }
+        assert!(net.evaluate_batch(&[]).is_empty());
+
+        let priors = net.priors(&games[0]);
+        let total: f32 = priors.iter().map(|&(_, p)| p).sum();
+        assert_eq!(priors.len(), games[0].allowed_actions().len());
+        assert!((total - 1.).abs() < 1e-5);
+        assert!(TorchEvaluator::load("/nonexistent/net.pt", Device::Cpu, 16, tiles).is_err());
+    }
+
+    #[test]
+    fn test_train_batch() {
+        let mut net = network("train");
+        let mut optimizer = nn::Sgd::default().build(net.var_store(), 1e-3).unwrap();
+        let games = (0..8).map(TwoFortyEight::with_seed).collect::<Vec<_>>();
+        let policies = games.iter().map(|_| vec![(twofortyeight::Action::Left, 1.)]).collect::<Vec<_>>();
+        let outcomes = vec![1.; 8];
+
+        let (first, _) = net.train_batch(&mut optimizer, &games, &policies, &outcomes);
+        let mut last = first;
+        for _ in 0..20 {
+            last = net.train_batch(&mut optimizer, &games, &policies, &outcomes).0;
+        }
+        assert!(last < first);
+    }
+}
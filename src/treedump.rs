@@ -0,0 +1,249 @@
+//!
+//! Dumping search trees for offline inspection.
+//!
+//! When the engine makes a surprising choice, the tree it searched tells
+//! why. `TreeDump` writes the ensemble of trees after a search either as a
+//! Graphviz DOT graph (for paths ending in `.dot`) or as nested JSON
+//! objects. Deep or rarely visited subtrees can be left out to keep the
+//! dumps readable. `TreeNode::to_json` and `TreeNode::to_dot` dump a single
+//! tree completely. The JSON dumps need the `serde` feature.
+//!
+
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::Write;
+
+#[cfg(feature = "serde")]
+use serde_json::Value;
+
+use mcts::{Game, GameAction, MCTS, TreeNode};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Where and how much of the search trees to dump.
+///
+/// The placeholders `{game}` and `{move}` in the path are replaced by the
+/// game and move number, so that every search gets its own file; without
+/// them the file is overwritten and ends up with the last search.
+pub struct TreeDump {
+    pub path: String,
+    /// Only dump nodes up to this depth below the root.
+    pub max_depth: Option<usize>,
+    /// Only dump nodes visited at least this often.
+    pub min_visits: u64,
+}
+
+impl TreeDump {
+    /// Dump the complete trees to `path`.
+    pub fn new(path: &str) -> TreeDump {
+        TreeDump { path: path.to_string(), max_depth: None, min_visits: 0 }
+    }
+
+    /// The file for the given game and move.
+    pub fn path_for(&self, game: usize, move_number: usize) -> String {
+        self.path.replace("{game}", &game.to_string()).replace("{move}", &move_number.to_string())
+    }
+
+    /// Whether the file is a DOT graph rather than JSON.
+    pub fn is_dot(&self) -> bool {
+        self.path.ends_with(".dot")
+    }
+
+    /// Whether the children of a node at `depth` are dumped.
+    fn descend(&self, depth: usize) -> bool {
+        self.max_depth.map_or(true, |max_depth| depth < max_depth)
+    }
+
+    /// The trees as JSON: one nested object per root.
+    ///
+    /// Every node has its action, the player who made it, its visits and
+    /// mean reward, its dumped children and the number of children left out.
+    #[cfg(feature = "serde")]
+    pub fn to_json<G: Game<A>, A: GameAction>(&self, mcts: &MCTS<G, A>) -> Value {
+        let trees = mcts.roots().iter().map(|root| self.node_json(root, 0)).collect::<Vec<_>>();
+        json!({ "trees": trees })
+    }
+
+    /// The tree below `node` as nested JSON objects, see `to_json`.
+    #[cfg(feature = "serde")]
+    pub fn tree_json<A: GameAction>(&self, node: &TreeNode<A>) -> Value {
+        self.node_json(node, 0)
+    }
+
+    #[cfg(feature = "serde")]
+    fn node_json<A: GameAction>(&self, node: &TreeNode<A>, depth: usize) -> Value {
+        let mut children = Vec::new();
+        let mut pruned = 0;
+        for child in node.children() {
+            if self.descend(depth) && child.visits() >= self.min_visits {
+                children.push(self.node_json(&child, depth + 1));
+            } else {
+                pruned += 1;
+            }
+        }
+        json!({
+            "action": node.action().map(|action| format!("{:?}", action)),
+            "player": node.player(),
+            "visits": node.visits(),
+            "value": if node.visits() > 0 { Some(node.mean()) } else { None },
+            "children": children,
+            "pruned": pruned,
+        })
+    }
+
+    /// The trees as a DOT graph with one cluster per ensemble member.
+    pub fn to_dot<G: Game<A>, A: GameAction>(&self, mcts: &MCTS<G, A>) -> String {
+        let mut dot = String::from("digraph mcts {\n    node [shape=box];\n");
+        let mut next_id = 0;
+        for (i, root) in mcts.roots().iter().enumerate() {
+            writeln!(dot, "    subgraph cluster_{} {{\n        label=\"tree {}\";", i, i).unwrap();
+            self.node_dot(&mut dot, root, 0, &mut next_id);
+            dot.push_str("    }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The tree below `node` as a DOT graph of its own.
+    pub fn tree_dot<A: GameAction>(&self, node: &TreeNode<A>) -> String {
+        let mut dot = String::from("digraph mcts {\n    node [shape=box];\n");
+        self.node_dot(&mut dot, node, 0, &mut 0);
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Write a node and its dumped subtree; returns the node's id.
+    fn node_dot<A: GameAction>(&self, dot: &mut String, node: &TreeNode<A>, depth: usize,
+                               next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let action = match node.action() {
+            Some(action) => format!("{:?}", action).replace('"', "\\\""),
+            None => "root".to_string(),
+        };
+        writeln!(dot, "        n{} [label=\"{}\\nn={} v={:.3}\"];", id, action, node.visits(),
+                 if node.visits() > 0 { node.mean() } else { 0. }).unwrap();
+
+        if self.descend(depth) {
+            for child in node.children() {
+                if child.visits() >= self.min_visits {
+                    let child_id = self.node_dot(dot, &child, depth + 1, next_id);
+                    writeln!(dot, "        n{} -> n{};", id, child_id).unwrap();
+                }
+            }
+        }
+        id
+    }
+
+    /// Write the trees after the given move of the given game.
+    pub fn write<G: Game<A>, A: GameAction>(&self, mcts: &MCTS<G, A>, game: usize,
+                                            move_number: usize) -> Result<(), String> {
+        let path = self.path_for(game, move_number);
+        let contents = if self.is_dot() {
+            self.to_dot(mcts)
+        } else {
+            try!(self.json_contents(mcts))
+        };
+        File::create(&path)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| format!("Failed to dump the tree to {}: {}", path, e))
+    }
+
+    /// The contents of a JSON dump of the trees.
+    #[cfg(feature = "serde")]
+    fn json_contents<G: Game<A>, A: GameAction>(&self, mcts: &MCTS<G, A>) -> Result<String, String> {
+        Ok(format!("{}\n", self.to_json(mcts)))
+    }
+
+    /// JSON dumps need the `serde` feature.
+    #[cfg(not(feature = "serde"))]
+    fn json_contents<G: Game<A>, A: GameAction>(&self, _: &MCTS<G, A>) -> Result<String, String> {
+        Err(format!("Cannot dump the tree to {} without the serde feature; use a .dot file", self.path))
+    }
+}
+
+impl<'a, A: GameAction> TreeNode<'a, A> {
+    /// The complete tree below this node as nested JSON objects; see
+    /// `TreeDump::to_json` for the fields and for leaving out subtrees.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Value {
+        TreeDump::new("").tree_json(self)
+    }
+
+    /// The complete tree below this node as a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        TreeDump::new("").tree_dot(self)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use mcts::*;
+    use treedump::*;
+    use tictactoe::*;
+
+    fn searched() -> MCTS<TicTacToe, Action> {
+        let mut mcts = MCTS::new(&TicTacToe::new(), 2);
+        mcts.search(100, 1.);
+        mcts
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mcts = searched();
+        let dump = TreeDump::new("tree.json");
+        let value = dump.to_json(&mcts);
+        assert_eq!(value["trees"].as_array().unwrap().len(), 2);
+        assert_eq!(value["trees"][0]["visits"], json!(100));
+        assert_eq!(value["trees"][0]["children"].as_array().unwrap().len(), 9);
+
+        let shallow = TreeDump { max_depth: Some(1), ..dump.clone() };
+        let value = shallow.to_json(&mcts);
+        let child = &value["trees"][0]["children"][0];
+        assert!(child["children"].as_array().unwrap().is_empty());
+        assert!(child["pruned"].as_u64().unwrap() > 0);
+
+        let popular = TreeDump { min_visits: 1000, ..dump };
+        assert_eq!(popular.to_json(&mcts)["trees"][0]["pruned"], json!(9));
+    }
+
+    #[test]
+    fn test_dot() {
+        let mcts = searched();
+        let dump = TreeDump { max_depth: Some(1), ..TreeDump::new("tree.dot") };
+        assert!(dump.is_dot());
+
+        let dot = dump.to_dot(&mcts);
+        assert!(dot.starts_with("digraph mcts {"));
+        assert!(dot.contains("subgraph cluster_1"));
+        assert_eq!(dot.matches(" -> ").count(), 18);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_node_dumps() {
+        let mcts = searched();
+        let root = &mcts.roots()[0];
+        let nodes = root.iter().count();
+
+        let value = root.to_json();
+        assert_eq!(value["visits"], json!(100));
+        assert_eq!(value["pruned"], json!(0));
+        assert_eq!(value, TreeDump::new("tree.json").to_json(&mcts)["trees"][0]);
+
+        let dot = root.to_dot();
+        assert!(dot.starts_with("digraph mcts {"));
+        assert!(!dot.contains("subgraph"));
+        assert_eq!(dot.matches(" -> ").count(), nodes - 1);
+    }
+
+    #[test]
+    fn test_path_for() {
+        let dump = TreeDump::new("trees/g{game}-m{move}.json");
+        assert_eq!(dump.path_for(2, 17), "trees/g2-m17.json");
+        assert!(!dump.is_dot());
+    }
+}
@@ -0,0 +1,204 @@
+//!
+//! Live terminal viewer for 2048 runs.
+//!
+//! Instead of printing the board after every move, the viewer redraws a
+//! single screen several times per second while the MCTS is thinking:
+//! the board, the score, visit and value bars for every direction and
+//! the size and growth of the search tree.
+//!
+//! Only available with the `tui` feature.
+//!
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Gauge, Paragraph};
+
+use mcts::{Game, MCTS, MctsConfig};
+use twofortyeight::{TwoFortyEight, Action, HeuristicRollout};
+
+/// How often the screen is redrawn while searching (in s).
+const REFRESH: f32 = 0.05;
+
+/// Everything the viewer displays.
+struct Snapshot<'a> {
+    game: &'a TwoFortyEight,
+    actions: Vec<(Action, u64, f32)>,   // (action, visits, mean reward)
+    nodes: usize,
+    max_depth: usize,
+    nodes_per_s: f32,
+    last_action: Option<Action>,
+}
+
+/// Background color for a tile; larger tiles get warmer colors.
+fn tile_color(tile: u16) -> Color {
+    match tile {
+        0 => Color::Reset,
+        2 | 4 => Color::Gray,
+        8 | 16 => Color::Yellow,
+        32 | 64 => Color::LightRed,
+        128 | 256 => Color::Red,
+        512 | 1024 => Color::Magenta,
+        _ => Color::Green,
+    }
+}
+
+fn draw_board(frame: &mut Frame, area: Rect, game: &TwoFortyEight) {
+    let mut lines = Vec::new();
+    for row in 0..game.height() {
+        let spans = (0..game.width()).map(|col| {
+            let tile = game.get_tile(row, col);
+            let text = if tile == 0 { format!("{:^6}", ".") } else { format!("{:^6}", tile) };
+            Span::styled(text, Style::default().fg(Color::Black).bg(tile_color(tile)))
+        }).collect::<Vec<_>>();
+        lines.push(Line::from(spans));
+        lines.push(Line::from(""));
+    }
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" 2048 ")), area);
+}
+
+fn draw_info(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let last = match snapshot.last_action {
+        Some(action) => format!("{:?}", action),
+        None => "-".to_string(),
+    };
+    let lines = vec![
+        Line::from(format!("Score:       {}", snapshot.game.score)),
+        Line::from(format!("Moves:       {}", snapshot.game.moves)),
+        Line::from(format!("Max tile:    {}", snapshot.game.max_tile())),
+        Line::from(format!("Last move:   {}", last)),
+        Line::from(""),
+        Line::from(format!("Tree nodes:  {}", snapshot.nodes)),
+        Line::from(format!("Max depth:   {}", snapshot.max_depth)),
+        Line::from(format!("Nodes/s:     {:.0}", snapshot.nodes_per_s)),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(" Search ")), area);
+}
+
+fn draw_actions(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let block = Block::bordered().title(" Visits and values ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let total_visits = snapshot.actions.iter().map(|a| a.1).sum::<u64>();
+    let best_value = snapshot.actions.iter().fold(0., |best: f32, a| best.max(a.2));
+
+    let rows = Layout::vertical([Constraint::Length(1); 4]).split(inner);
+    for (row, &(action, visits, value)) in rows.iter().zip(&snapshot.actions) {
+        let [name, bar] = Layout::horizontal([Constraint::Length(7), Constraint::Min(0)]).areas(*row);
+        let ratio = if total_visits > 0 { visits as f64 / total_visits as f64 } else { 0. };
+        let gauge = Gauge::default()
+            .ratio(ratio.max(0.).min(1.))
+            .label(format!("visits={} value={:.1}", visits, value))
+            .gauge_style(Style::default().fg(if value == best_value { Color::Green } else { Color::Blue }));
+        frame.render_widget(Paragraph::new(format!("{:?}", action)), name);
+        frame.render_widget(gauge, bar);
+    }
+}
+
+fn draw(frame: &mut Frame, snapshot: &Snapshot) {
+    let [top, bottom, help] = Layout::vertical([
+        Constraint::Length(2 * snapshot.game.height() as u16 + 2),
+        Constraint::Length(6),
+        Constraint::Length(1),
+    ]).areas(frame.area());
+    let [board, info] = Layout::horizontal([
+        Constraint::Length(6 * snapshot.game.width() as u16 + 2),
+        Constraint::Min(0),
+    ]).areas(top);
+
+    draw_board(frame, board, snapshot.game);
+    draw_info(frame, info, snapshot);
+    draw_actions(frame, bottom, snapshot);
+    frame.render_widget(Paragraph::new("Press q to quit."), help);
+}
+
+/// Did the user ask us to quit?
+fn quit_requested() -> io::Result<bool> {
+    while try!(event::poll(Duration::from_millis(0))) {
+        if let Event::Key(key) = try!(event::read()) {
+            if key.kind == KeyEventKind::Press &&
+               (key.code == KeyCode::Char('q') || key.code == KeyCode::Esc) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn run(terminal: &mut DefaultTerminal, game: &mut TwoFortyEight, time_per_move: f32, ensemble_size: usize,
+       config: MctsConfig, rollout: Option<HeuristicRollout>, c: f32) -> io::Result<()> {
+    let mut mcts = MCTS::with_config(game, ensemble_size, config);
+    if let Some(rollout) = rollout {
+        mcts.set_rollout_policy(rollout);
+    }
+    let mut last_action = None;
+
+    while !game.is_terminal() {
+        let t0 = Instant::now();
+        let (mut nodes, mut max_depth) = (0, 0);
+        loop {
+            let report = mcts.search_time(REFRESH.min(time_per_move), c);
+            nodes += report.nodes;
+            max_depth = max_depth.max(report.max_depth);
+
+            let elapsed = t0.elapsed().as_secs_f32();
+            let snapshot = Snapshot {
+                game: game,
+                actions: report.actions.iter().map(|stats| (stats.action, stats.visits, stats.mean)).collect(),
+                nodes: nodes,
+                max_depth: max_depth,
+                nodes_per_s: nodes as f32 / elapsed,
+                last_action: last_action,
+            };
+            try!(terminal.draw(|frame| draw(frame, &snapshot)));
+
+            if try!(quit_requested()) {
+                return Ok(());
+            }
+            if elapsed >= time_per_move {
+                break;
+            }
+        }
+
+        match mcts.best_action() {
+            Some(action) => {
+                game.make_move(&action);
+                mcts.advance_game(game);
+                last_action = Some(action);
+            },
+            None => break
+        }
+    }
+
+    // Show the final position until the user quits
+    let snapshot = Snapshot {
+        game: game, actions: Vec::new(), nodes: 0, max_depth: 0,
+        nodes_per_s: 0., last_action: last_action,
+    };
+    try!(terminal.draw(|frame| draw(frame, &snapshot)));
+    while !try!(quit_requested()) {
+        ::std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+/// Let the MCTS play the given game of 2048 while showing a live view of
+/// the search.
+///
+/// The search follows `config` with the exploration constant `c`, and its
+/// playouts follow `rollout` if given, else they are random. Returns the
+/// final game state.
+pub fn play_2048(mut game: TwoFortyEight, time_per_move: f32, ensemble_size: usize, config: MctsConfig,
+                 rollout: Option<HeuristicRollout>, c: f32) -> io::Result<TwoFortyEight> {
+    let mut terminal = try!(ratatui::try_init());
+    let result = run(&mut terminal, &mut game, time_per_move, ensemble_size, config, rollout, c);
+    ratatui::restore();
+    result.map(|_| game)
+}
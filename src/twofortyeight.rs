@@ -1,22 +1,187 @@
 
 use std::fmt;
+use std::sync::OnceLock;
 use rand::{Rng, XorShiftRng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde_json::Value;
 
-use mcts::{GameAction, Game};
+use mcts::{GameAction, Game, RolloutPolicy};
+use utils::hash_value;
+#[cfg(feature = "serde")]
+use protocol::{SerializableGame, split_notation};
+use cli::DisplayAction;
 
+/// Size of the board unless another one is chosen with `with_size`.
 pub const WIDTH: usize = 4;
 pub const HEIGHT: usize = 4;
 
+/// Probability that a spawned tile is a 4 rather than a 2.
+pub const FOUR_PROBABILITY: f32 = 0.1;
+
+/// The tile that wins the game unless another target is set.
+pub const DEFAULT_TARGET: u16 = 2048;
+
+/// The largest tile; two of them do not merge.
+pub const MAX_TILE: u16 = 32768;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Whether the target tile was reached.
+///
+/// As in 2048, reaching the target does not end the game; a game stays won
+/// however it goes on.
+pub enum GameStatus {
+    Ongoing,
+    Won,
+    Lost,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// The tiles of a board, row by row.
+///
+/// The default board packs the exponents of its tiles into 4 bits each of
+/// a `u64`, four rows of 16 bits starting with the lowest. It slides rows
+/// with the lookup tables of `row_tables` and columns by transposing first.
+/// Other sizes keep a `u16` per cell and merge line by line.
+enum Board {
+    Packed(u64),
+    Cells(Vec<u16>),
+}
+
+/// Exponent of a tile of the packed board.
+fn tile_exponent(tile: u16) -> u64 {
+    if tile == 0 { 0 } else { tile.trailing_zeros() as u64 }
+}
+
+/// Tile of an exponent of the packed board.
+fn exponent_tile(exponent: u64) -> u16 {
+    if exponent == 0 { 0 } else { 1 << exponent }
+}
+
+/// Swap rows and columns of a packed board.
+fn transpose(x: u64) -> u64 {
+    let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+    let a2 = x & 0x0000_F0F0_0000_F0F0;
+    let a3 = x & 0x0F0F_0000_0F0F_0000;
+    let a = a1 | (a2 << 12) | (a3 >> 12);
+    let b1 = a & 0xFF00_FF00_00FF_00FF;
+    let b2 = a & 0x00FF_00FF_0000_0000;
+    let b3 = a & 0x0000_0000_FF00_FF00;
+    b1 | (b2 >> 24) | (b3 << 24)
+}
+
+/// Every packed row slid to the left and to the right, with the points
+/// scored by its merges.
+struct RowTables {
+    left: Vec<u16>,
+    right: Vec<u16>,
+    left_points: Vec<f32>,
+    right_points: Vec<f32>,
+}
+
+impl RowTables {
+    fn new() -> RowTables {
+        let mut tables = RowTables {
+            left: vec![0; 1 << 16],
+            right: vec![0; 1 << 16],
+            left_points: vec![0.; 1 << 16],
+            right_points: vec![0.; 1 << 16],
+        };
+        let pack = |tiles: &[u16]| tiles.iter().enumerate()
+            .fold(0, |row, (i, &tile)| row | (tile_exponent(tile) << (4*i)) as u16);
+        for row in 0..(1 << 16) {
+            let mut tiles = (0..4).map(|i| exponent_tile((row >> (4*i)) as u64 & 0xF)).collect::<Vec<_>>();
+            let (merged, points, _) = TwoFortyEight::merge_vec(&tiles);
+            tables.left[row] = pack(&merged);
+            tables.left_points[row] = points;
+
+            tiles.reverse();
+            let (mut merged, points, _) = TwoFortyEight::merge_vec(&tiles);
+            merged.reverse();
+            tables.right[row] = pack(&merged);
+            tables.right_points[row] = points;
+        }
+        tables
+    }
+}
+
+/// The row tables, computed on first use.
+fn row_tables() -> &'static RowTables {
+    static TABLES: OnceLock<RowTables> = OnceLock::new();
+    TABLES.get_or_init(RowTables::new)
+}
+
+impl Board {
+    /// An empty board; packed if it has the default size.
+    fn new(width: usize, height: usize) -> Board {
+        if width == WIDTH && height == HEIGHT {
+            Board::Packed(0)
+        } else {
+            Board::Cells(vec![0; width*height])
+        }
+    }
+
+    fn get(&self, idx: usize) -> u16 {
+        match *self {
+            Board::Packed(bits) => exponent_tile((bits >> (4*idx)) & 0xF),
+            Board::Cells(ref cells) => cells[idx],
+        }
+    }
+
+    fn set(&mut self, idx: usize, tile: u16) {
+        assert!(tile == 0 || (tile >= 2 && tile <= MAX_TILE && tile.is_power_of_two()),
+                "Invalid tile {}", tile);
+        match *self {
+            Board::Packed(ref mut bits) => {
+                *bits = (*bits & !(0xF << (4*idx))) | (tile_exponent(tile) << (4*idx));
+            },
+            Board::Cells(ref mut cells) => cells[idx] = tile,
+        }
+    }
+
+    /// All tiles, row by row.
+    fn tiles(&self) -> Vec<u16> {
+        match *self {
+            Board::Packed(_) => (0..WIDTH*HEIGHT).map(|idx| self.get(idx)).collect(),
+            Board::Cells(ref cells) => cells.clone(),
+        }
+    }
+}
+
 #[derive(Clone)]
 /// Implementation of the 2048 game mechanics.
 ///
 /// This game needs a random source to perform moves -- in order to fully derteminize it
-/// we need to store our own random number generator.
+/// we need to store our own random number generator. Its seed and the number
+/// of draws since seeding are kept as well, so that the generator can be
+/// saved and restored.
+///
+/// The board has `WIDTH` x `HEIGHT` cells unless another size is chosen at
+/// construction; rectangular boards are fine, but only the default board
+/// is packed into a bitboard for fast moves.
+///
+/// Some bookkeeping about how the position came about is kept for
+/// `check_invariants`: tiles placed with `set_tile` are not explained by
+/// spawns, and neither is their score by merges.
+///
+/// The reward is the score plus `win_bonus` once the `target` tile was
+/// reached, or minus `loss_penalty` when the game ended without it.
 pub struct TwoFortyEight {
     rng:   XorShiftRng,
-    board: [u16; WIDTH*HEIGHT],
+    rng_seed: [u32; 4],
+    rng_draws: u64,
+    width: usize,
+    height: usize,
+    board: Board,
     pub score: f32,
     pub moves: usize,
+    pub target: u16,
+    pub win_bonus: f32,
+    pub loss_penalty: f32,
+    spawns: usize,                      // tiles spawned so far
+    spawned: i64,                       // sum of the tiles spawned so far
+    placed: i64,                        // sum of the tiles placed with set_tile
+    score_offset: f32,                  // score not explained by merges of the tiles
+    setup: (usize, usize),              // moves and spawns when the position was set up
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
@@ -32,24 +197,88 @@ impl GameAction for Action {}
 impl TwoFortyEight {
     /// Create a new empty game
     pub fn new_empty() -> TwoFortyEight {
+        TwoFortyEight::empty(WIDTH, HEIGHT)
+    }
+
+    /// Create a new empty game on a board with the given number of columns
+    /// and rows.
+    pub fn empty(width: usize, height: usize) -> TwoFortyEight {
+        assert!(width >= 2 && height >= 2, "The board needs at least 2x2 cells");
         // XXX What about the seed?
         TwoFortyEight {
             rng: XorShiftRng::from_seed([1,2,3,4]),
+            rng_seed: [1,2,3,4],
+            rng_draws: 0,
             score: 0.0,
             moves: 0,
-            board: [0; WIDTH*HEIGHT]
+            target: DEFAULT_TARGET,
+            win_bonus: 0.,
+            loss_penalty: 0.,
+            width: width,
+            height: height,
+            board: Board::new(width, height),
+            spawns: 0,
+            spawned: 0,
+            placed: 0,
+            score_offset: 0.,
+            setup: (0, 0),
         }
     }
 
-    // Create a new game with two random two's in it.
+    // Create a new game with two random tiles in it.
     pub fn new() -> TwoFortyEight {
-        let mut game = TwoFortyEight::new_empty();
+        TwoFortyEight::with_size(WIDTH, HEIGHT, None)
+    }
+
+    /// Create a new game whose tile spawns are drawn from the given seed.
+    pub fn with_seed(seed: u32) -> TwoFortyEight {
+        TwoFortyEight::with_size(WIDTH, HEIGHT, Some(seed))
+    }
+
+    /// Create a new game with two random tiles on a board of the given
+    /// size, with the tile spawns drawn from the seed if one is given.
+    pub fn with_size(width: usize, height: usize, seed: Option<u32>) -> TwoFortyEight {
+        let mut game = TwoFortyEight::empty(width, height);
+        if let Some(seed) = seed {
+            game.set_rng_seed(seed);
+        }
         game.random_spawn();
         game.random_spawn();
+        game.setup = (0, 2);
         game
     }
 
-    /// Static method
+    /// Number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Seed of the tile spawn generator and the number of draws since.
+    pub fn rng_state(&self) -> ([u32; 4], u64) {
+        (self.rng_seed, self.rng_draws)
+    }
+
+    /// Restore the tile spawn generator from `rng_state`.
+    pub fn set_rng_state(&mut self, seed: [u32; 4], draws: u64) {
+        self.rng = XorShiftRng::from_seed(seed);
+        self.rng_seed = seed;
+        self.rng_draws = 0;
+        while self.rng_draws < draws {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) -> usize {
+        self.rng_draws += 1;
+        self.rng.gen::<usize>()
+    }
+
+    /// Static method; tiles of `MAX_TILE` do not merge.
     fn merge_vec(vec: &Vec<u16>) -> (Vec<u16>, f32, bool) {
         let mut points = 0.0;
 
@@ -61,7 +290,7 @@ impl TwoFortyEight {
         let mut merged = Vec::new();
         let mut next = 0;
         for t in filtered_vec {
-            if t == next {
+            if t == next && t < MAX_TILE {
                 merged.push(2*t);
                 next = 0;
                 points += 2.* (t as f32);
@@ -77,87 +306,225 @@ impl TwoFortyEight {
         }
 
         // Make sure we keep the original length and notice any changes
-        let changed = orig_len != merged.len();
         for _ in 0..(orig_len-merged.len()) {
             merged.push(0);
         }
+        let changed = merged != *vec;
         (merged, points, changed)
     }
 
     /// Shift and merge in the given direction
-    fn shift_and_merge(board: [u16; WIDTH*HEIGHT], action: &Action) -> ([u16; WIDTH*HEIGHT], Option<f32>) {
-        let (start, ostride, istride) = match *action {
-            Action::Up    => ( 0,  1,  4),
-            Action::Down  => (12,  1, -4),
-            Action::Left  => ( 0,  4,  1),
-            Action::Right => (15, -4, -1),
+    ///
+    /// Every column (for Up and Down) or row (for Left and Right) is merged
+    /// as a line running against the direction of the move.
+    fn shift_and_merge(&self, action: &Action) -> (Board, Option<f32>) {
+        let cells = match self.board {
+            Board::Packed(bits) => {
+                let (bits, points) = TwoFortyEight::shift_and_merge_packed(bits, action);
+                return (Board::Packed(bits), points);
+            },
+            Board::Cells(ref cells) => cells,
         };
 
-        let start = start as isize;
-        let ostride = ostride as isize;
-        let istride = istride as isize;
-        assert!(HEIGHT == WIDTH);
+        let (w, h) = (self.width as isize, self.height as isize);
+        let (start, ostride, istride, lines, len) = match *action {
+            Action::Up    => (0,         1,  w, w, h),
+            Action::Down  => ((h-1)*w,   1, -w, w, h),
+            Action::Left  => (0,         w,  1, h, w),
+            Action::Right => (w*h-1,    -w, -1, h, w),
+        };
 
-        let mut new_board = [0; WIDTH*HEIGHT];
+        let mut new_board = vec![0; cells.len()];
         let mut all_points = 0.0;    //  points we accumulate
         let mut any_changed = false;  // did any of the vectors change?
 
-        for outer in 0..(HEIGHT as isize) {
-            let mut vec = Vec::with_capacity(HEIGHT);
-            for inner in 0..(HEIGHT as isize) {
+        for outer in 0..lines {
+            let mut vec = Vec::with_capacity(len as usize);
+            for inner in 0..len {
                 let idx = start + outer*ostride + inner*istride;
-                vec.push(board[idx as usize]);
+                vec.push(cells[idx as usize]);
             }
 
             let (merged_vec, points, changed) = TwoFortyEight::merge_vec(&vec);
             all_points += points;
             any_changed |= changed;
 
-            for inner in 0..(HEIGHT as isize) {
+            for inner in 0..len {
                 let idx = start + outer*ostride + inner*istride;
                 new_board[idx as usize] = merged_vec[inner as usize];
             }
         }
         if any_changed {
-            (new_board, Some(all_points))
+            (Board::Cells(new_board), Some(all_points))
+        } else {
+            (Board::Cells(new_board), None)
+        }
+    }
+
+    /// Shift and merge the packed board row by row, or column by column
+    /// on the transposed board.
+    fn shift_and_merge_packed(bits: u64, action: &Action) -> (u64, Option<f32>) {
+        let tables = row_tables();
+        let (rows, points) = match *action {
+            Action::Up | Action::Left => (&tables.left, &tables.left_points),
+            Action::Down | Action::Right => (&tables.right, &tables.right_points),
+        };
+        let columns = *action == Action::Up || *action == Action::Down;
+
+        let old = if columns { transpose(bits) } else { bits };
+        let mut new = 0;
+        let mut all_points = 0.;
+        for row in 0..HEIGHT {
+            let line = (old >> (16*row)) as usize & 0xFFFF;
+            new |= (rows[line] as u64) << (16*row);
+            all_points += points[line];
+        }
+
+        if new == old {
+            (bits, None)
+        } else if columns {
+            (transpose(new), Some(all_points))
         } else {
-            (new_board, None)
+            (new, Some(all_points))
         }
     }
 
     ///
     pub fn get_tile(&self, row: usize, col: usize) -> u16 {
-        let idx = row * WIDTH + col;
-        self.board[idx]
+        self.board.get(row * self.width + col)
     }
 
+    /// Place a tile; this is not a spawn, see `spawn` for that.
     ///
+    /// The tile must be 0 for an empty cell or a power of two from 2 up to
+    /// `MAX_TILE`.
     pub fn set_tile(&mut self, row: usize, col: usize, num: u16) {
-        let idx = row * WIDTH + col;
-        self.board[idx] = num;
+        let idx = row * self.width + col;
+        let old = self.board.get(idx);
+        self.board.set(idx, num);
+        self.placed += num as i64 - old as i64;
+        self.score_offset += TwoFortyEight::merge_points(old) - TwoFortyEight::merge_points(num);
+    }
+
+    /// Spawn a 2 or a 4 on the given empty cell.
+    ///
+    /// A spawned 4 scores nothing, unlike one built from two 2's.
+    pub fn spawn(&mut self, row: usize, col: usize, tile: u16) {
+        let idx = row * self.width + col;
+        assert!(self.board.get(idx) == 0, "Cell is taken");
+        assert!(tile == 2 || tile == 4, "Only 2's and 4's spawn");
+        self.board.set(idx, tile);
+        self.spawns += 1;
+        self.spawned += tile as i64;
+        self.score_offset -= TwoFortyEight::merge_points(tile);
+    }
+
+    /// Points scored by the merges needed to build a tile from 2's.
+    ///
+    /// Building 2^k takes 2^(k-1-i) merges scoring 2^(i+1) each for i in
+    /// 1..k, i.e. (k-1)*2^k points.
+    fn merge_points(tile: u16) -> f32 {
+        if tile < 2 {
+            return 0.;
+        }
+        let k = 15 - tile.leading_zeros() as i32;
+        (k - 1) as f32 * tile as f32
+    }
+
+    /// Check the rules of 2048 against the current position.
+    ///
+    /// Every tile is a power of two, the tiles sum up to the tiles spawned
+    /// (plus the tiles placed with `set_tile`), the score equals the points
+    /// of the merges that built the tiles, and a tile was spawned for every
+    /// move since the position was set up. Assigning `score` or `moves`
+    /// directly breaks the latter two.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.check_invariants_pending(0)
+    }
+
+    /// Check the invariants with `pending` moves whose tile is not spawned
+    /// yet, as in adversarial 2048 while the spawner is to move.
+    pub fn check_invariants_pending(&self, pending: usize) -> Result<(), String> {
+        try!(self.check_tiles());
+        let (setup_moves, setup_spawns) = self.setup;
+        if self.spawns + pending != setup_spawns + self.moves - setup_moves.min(self.moves) {
+            return Err(format!("{} spawns for {} moves", self.spawns - setup_spawns, self.moves - setup_moves));
+        }
+        Ok(())
+    }
+
+    /// Check the tiles and the score, but not how many tiles were spawned.
+    pub fn check_tiles(&self) -> Result<(), String> {
+        let tiles = self.board.tiles();
+        if let Some(&tile) = tiles.iter().find(|&&tile| tile == 1 || !(tile == 0 || tile.is_power_of_two())) {
+            return Err(format!("tile {} is not a power of two", tile));
+        }
+
+        let sum = tiles.iter().map(|&tile| tile as i64).sum::<i64>();
+        if sum != self.spawned + self.placed {
+            return Err(format!("tiles sum up to {} after {} spawns", sum, self.spawns));
+        }
+
+        let merged = tiles.iter().map(|&tile| TwoFortyEight::merge_points(tile)).sum::<f32>();
+        if self.score != merged + self.score_offset {
+            return Err(format!("score {} but the tiles were built with {} points", self.score,
+                               merged + self.score_offset));
+        }
+        Ok(())
     }
 
     /// Check whether the currend board is full.
     pub fn board_full(&self) -> bool {
-        for row in 0..HEIGHT {
-            for col in 0..WIDTH {
-                if self.get_tile(row, col) == 0 {
-                    return false;
-                }
-            }
+        match self.board {
+            Board::Packed(bits) => {
+                // Some bit of every exponent is set
+                let set = bits | (bits >> 1) | (bits >> 2) | (bits >> 3);
+                set & 0x1111_1111_1111_1111 == 0x1111_1111_1111_1111
+            },
+            Board::Cells(ref cells) => cells.iter().all(|&tile| tile != 0),
         }
-        true
     }
 
-    /// Place a 2 into some random empty tile
+    /// The largest tile on the board.
+    pub fn max_tile(&self) -> u16 {
+        match self.board {
+            Board::Packed(bits) => exponent_tile((0..WIDTH*HEIGHT).map(|idx| (bits >> (4*idx)) & 0xF).max().unwrap()),
+            Board::Cells(ref cells) => *cells.iter().max().unwrap(),
+        }
+    }
+
+    /// Won once a tile reached the target, lost when no moves are left
+    /// before that.
+    pub fn game_status(&self) -> GameStatus {
+        if self.max_tile() >= self.target {
+            GameStatus::Won
+        } else if self.is_terminal() {
+            GameStatus::Lost
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    /// Shift and merge the tiles in the given direction without spawning
+    /// a new tile.
+    pub fn slide(&mut self, action: &Action) {
+        let (new_board, points) = self.shift_and_merge(action);
+        self.score += points.expect("Illegal move");
+        self.moves += 1;
+        self.board = new_board;
+    }
+
+    /// Place a 2 into some random empty tile, or a 4 with probability
+    /// `FOUR_PROBABILITY`
     pub fn random_spawn(&mut self) {
         assert!(!self.board_full());
 
         loop {
-            let row = self.rng.gen::<usize>() % HEIGHT;
-            let col = self.rng.gen::<usize>() % WIDTH;
+            let row = self.draw() % self.height;
+            let col = self.draw() % self.width;
             if self.get_tile(row, col) == 0 {
-                self.set_tile(row, col, 2);
+                let tile = if (self.draw() % 1000) as f32 / 1000. < FOUR_PROBABILITY { 4 } else { 2 };
+                self.spawn(row, col, tile);
                 break;
             }
         }
@@ -182,7 +549,7 @@ impl Game<Action> for TwoFortyEight {
         let actions = vec![Action::Up, Action::Down, Action::Left, Action::Right];
 
         actions.iter().map(|t| *t).filter(|&a| {
-                let (_, points) = TwoFortyEight::shift_and_merge(self.board, &a);
+                let (_, points) = self.shift_and_merge(&a);
                 match points {
                     Some(_) => true,
                     None => false
@@ -190,42 +557,183 @@ impl Game<Action> for TwoFortyEight {
             }).collect()
     }
 
+    /// Over when no two neighbouring tiles can merge and no tile can
+    /// slide into an empty cell; cheaper than trying all four slides.
+    fn is_terminal(&self) -> bool {
+        if let Board::Packed(bits) = self.board {
+            // No row of the board or its transpose slides either way
+            let tables = row_tables();
+            return [bits, transpose(bits)].iter().all(|&board| (0..HEIGHT).all(|row| {
+                let line = (board >> (16*row)) as usize & 0xFFFF;
+                tables.left[line] as usize == line && tables.right[line] as usize == line
+            }));
+        }
+        let movable = |a: u16, b: u16| (a != 0 || b != 0) && (a == 0 || b == 0 || a == b);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let tile = self.get_tile(row, col);
+                if col + 1 < self.width && movable(tile, self.get_tile(row, col + 1)) {
+                    return false;
+                }
+                if row + 1 < self.height && movable(tile, self.get_tile(row + 1, col)) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
     /// Change the current game state according to the given action.
     fn make_move(&mut self, action: &Action) {
-        let (new_board, points) = TwoFortyEight::shift_and_merge(self.board, action);
-        self.score += points.expect("Illegal move");
-        self.moves += 1;
-        self.board = new_board;
-        self.random_spawn()
+        self.slide(action);
+        self.random_spawn();
+        if cfg!(debug_assertions) {
+            if let Err(msg) = self.check_invariants() {
+                panic!("Broken invariant after {:?}: {}\n{}", action, msg, self);
+            }
+        }
+    }
+
+    /// Only slide, the afterstate is the board before the spawn.
+    fn apply_player_move(&mut self, action: &Action) {
+        self.slide(action);
+    }
+
+    fn resolve_chance(&mut self) {
+        self.random_spawn();
+    }
+
+    /// A 2 or a 4 on any empty cell; see `random_spawn`.
+    fn afterstate_outcomes(&self) -> Option<Vec<(TwoFortyEight, f32)>> {
+        let empty = (0..self.height).flat_map(|row| (0..self.width).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.get_tile(row, col) == 0)
+            .collect::<Vec<_>>();
+        let cell = 1. / empty.len() as f32;
+        let mut outcomes = Vec::with_capacity(2 * empty.len());
+        for &(row, col) in &empty {
+            for &(tile, probability) in &[(2, 1. - FOUR_PROBABILITY), (4, FOUR_PROBABILITY)] {
+                let mut outcome = self.clone();
+                outcome.spawn(row, col, tile);
+                outcomes.push((outcome, cell * probability));
+            }
+        }
+        Some(outcomes)
     }
 
     /// Reward for the player when reaching the current game state.
     fn reward(&self) -> f32 {
-        self.score
+        match self.game_status() {
+            GameStatus::Ongoing => self.score,
+            GameStatus::Won => self.score + self.win_bonus,
+            GameStatus::Lost => self.score - self.loss_penalty,
+        }
+    }
+
+    /// Every merge adds to the score.
+    fn running_reward(&self) -> bool {
+        true
     }
 
     /// Derterminize the game
     fn set_rng_seed(&mut self, seed: u32) {
-        self.rng = XorShiftRng::from_seed([seed+0, seed+1, seed+2, seed+3]);
+        self.set_rng_state([seed, seed.wrapping_add(1),
+                            seed.wrapping_add(2), seed.wrapping_add(3)], 0);
+    }
+
+    /// The board and the score, which the rewards of the moves to come
+    /// add to; the spawns to come differ between move orders.
+    fn hash_state(&self) -> Option<u64> {
+        Some(hash_value(&(&self.board, self.score.to_bits())))
+    }
+
+    /// The rows of tiles separated by `/`, with the tiles of a row
+    /// separated by commas, then optionally the score and the number of
+    /// moves, like `2,2,4,8/0,0,0,0/0,0,0,0/0,0,0,2 36 12`.
+    #[cfg(feature = "serde")]
+    fn from_str(text: &str) -> Result<TwoFortyEight, String> {
+        let (rows, fields) = split_notation(text);
+        let invalid = || format!("invalid state '{}'", text);
+        let board = try!(rows.iter().map(|row| {
+            row.split(',').map(|tile| tile.parse::<u64>()).collect::<Result<Vec<_>, _>>()
+        }).collect::<Result<Vec<_>, _>>().map_err(|_| invalid()));
+        let (score, moves) = match fields.as_slice() {
+            [] => ("0", "0"),
+            [score] => (*score, "0"),
+            [score, moves] => (*score, *moves),
+            _ => return Err(invalid())
+        };
+        let score = try!(score.parse::<f32>().map_err(|_| invalid()));
+        let moves = try!(moves.parse::<usize>().map_err(|_| invalid()));
+        TwoFortyEight::from_json(&json!({ "board": board, "score": score, "moves": moves }))
+    }
+}
+
+/// The board as an array of rows, the score and the number of moves.
+#[cfg(feature = "serde")]
+impl SerializableGame<Action> for TwoFortyEight {
+    fn to_json(&self) -> Value {
+        let rows = (0..self.height).map(|row| {
+            (0..self.width).map(|col| self.get_tile(row, col)).collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
+        json!({
+            "board": rows,
+            "score": self.score,
+            "moves": self.moves,
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<TwoFortyEight, String> {
+        let invalid = || "expected a board of at least 2 rows with the same number of tiles".to_string();
+        let rows = try!(value["board"].as_array().ok_or_else(&invalid));
+        let width = rows.first().and_then(|tiles| tiles.as_array()).map_or(0, |tiles| tiles.len());
+        if rows.len() < 2 || width < 2 {
+            return Err(invalid());
+        }
+
+        let mut game = TwoFortyEight::empty(width, rows.len());
+        for (row, tiles) in rows.iter().enumerate() {
+            let tiles = try!(tiles.as_array().ok_or_else(&invalid));
+            if tiles.len() != width {
+                return Err(invalid());
+            }
+            for (col, tile) in tiles.iter().enumerate() {
+                match tile.as_u64() {
+                    Some(tile) if tile == 0 || (tile >= 2 && tile <= MAX_TILE as u64 && tile.is_power_of_two()) =>
+                        game.set_tile(row, col, tile as u16),
+                    _ => return Err(format!("invalid tile {}", tile))
+                }
+            }
+        }
+        game.score = value["score"].as_f64().unwrap_or(0.) as f32;
+        game.score_offset += game.score;
+        game.moves = value["moves"].as_u64().unwrap_or(0) as usize;
+        game.setup = (game.moves, 0);
+        Ok(game)
     }
 }
 
+/// The board, score and number of moves; the generator is left out.
+impl fmt::Debug for TwoFortyEight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TwoFortyEight {{ board: {:?}, score: {}, moves: {} }}", self.board.tiles(), self.score, self.moves)
+    }
+}
 
 impl fmt::Display for TwoFortyEight {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         // XXX could be much nicer XXX
         try!(writeln!(f, "Moves={} Score={}:", self.moves, self.score));
-        for _ in 0..WIDTH {
+        for _ in 0..self.width {
             try!(write!(f, "|{: ^5}", "-----"));
         }
         try!(f.write_str("|"));
-        for row in 0..HEIGHT {
+        for row in 0..self.height {
             try!(f.write_str("\n"));
-            for _ in 0..WIDTH {
+            for _ in 0..self.width {
                 try!(write!(f, "|{: ^5}", ""));
             }
             try!(f.write_str("|\n"));
-            for col in 0..WIDTH {
+            for col in 0..self.width {
                 let tile =  self.get_tile(row, col);
                 if tile == 0 {
                     try!(write!(f, "|{: ^5}", ""));
@@ -234,11 +742,11 @@ impl fmt::Display for TwoFortyEight {
                 }
             }
             try!(f.write_str("|\n"));
-            for _ in 0..WIDTH {
+            for _ in 0..self.width {
                 try!(write!(f, "|{: ^5}", ""));
             }
             try!(f.write_str("|\n"));
-            for _ in 0..WIDTH {
+            for _ in 0..self.width {
                 try!(write!(f, "|{: ^5}", "-----"));
             }
             try!(f.write_str("|"));
@@ -247,6 +755,104 @@ impl fmt::Display for TwoFortyEight {
     }
 }
 
+impl DisplayAction<Action> for TwoFortyEight {
+    fn parse_action(&self, text: &str) -> Option<Action> {
+        match text {
+            "w" | "up"    => Some(Action::Up),
+            "s" | "down"  => Some(Action::Down),
+            "a" | "left"  => Some(Action::Left),
+            "d" | "right" => Some(Action::Right),
+            _ => None
+        }
+    }
+
+    fn show_action(&self, action: &Action) -> String {
+        format!("{:?}", action).to_lowercase()
+    }
+
+    fn input_help(&self) -> String {
+        "w/a/s/d or up/left/down/right".to_string()
+    }
+}
+
+/// Bonus of `HeuristicRollout::value` for the largest tile in a corner.
+pub const CORNER_BONUS: f32 = 4.;
+
+#[derive(Debug, Clone, Copy)]
+/// Rollout policy preferring moves that keep the largest tile in a corner
+/// and the rows and columns monotone.
+///
+/// Every allowed slide is tried without spawning and the board it leaves
+/// is valued with `value`; a move is then chosen with probability
+/// proportional to `exp(value / temperature)`. A high temperature plays
+/// nearly random playouts, a low one the greedy move.
+pub struct HeuristicRollout {
+    pub temperature: f32,
+}
+
+impl HeuristicRollout {
+    /// A policy with temperature 1.
+    pub fn new() -> HeuristicRollout {
+        HeuristicRollout { temperature: 1. }
+    }
+
+    /// The number of empty cells, plus `CORNER_BONUS` if the largest tile
+    /// is in a corner, minus how far the rows and columns are from being
+    /// monotone.
+    ///
+    /// A line that goes up and down by exponents of its tiles is penalized
+    /// by the smaller of its total ups and total downs.
+    pub fn value(game: &TwoFortyEight) -> f32 {
+        let (width, height) = (game.width(), game.height());
+        let exponent = |row, col| tile_exponent(game.get_tile(row, col)) as f32;
+        let empty = game.board.tiles().iter().filter(|&&tile| tile == 0).count() as f32;
+
+        let max_tile = game.max_tile();
+        let corner = [(0, 0), (0, width - 1), (height - 1, 0), (height - 1, width - 1)].iter()
+            .any(|&(row, col)| game.get_tile(row, col) == max_tile);
+
+        let unevenness = |line: Vec<f32>| {
+            let (mut ups, mut downs) = (0., 0.);
+            for pair in line.windows(2) {
+                if pair[1] > pair[0] {
+                    ups += pair[1] - pair[0];
+                } else {
+                    downs += pair[0] - pair[1];
+                }
+            }
+            f32::min(ups, downs)
+        };
+        let rows = (0..height).map(|row| unevenness((0..width).map(|col| exponent(row, col)).collect()));
+        let cols = (0..width).map(|col| unevenness((0..height).map(|row| exponent(row, col)).collect()));
+        let penalty = rows.chain(cols).sum::<f32>();
+
+        empty + if corner { CORNER_BONUS } else { 0. } - penalty
+    }
+
+    /// Weights of the given slides, the best of them 1.
+    pub fn slide_weights(&self, game: &TwoFortyEight, actions: &[Action]) -> Vec<f32> {
+        let values = actions.iter().map(|action| {
+            let mut slid = game.clone();
+            slid.slide(action);
+            HeuristicRollout::value(&slid)
+        }).collect::<Vec<_>>();
+        let best = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        values.iter().map(|&value| ((value - best) / self.temperature).exp()).collect()
+    }
+}
+
+impl Default for HeuristicRollout {
+    fn default() -> HeuristicRollout {
+        HeuristicRollout::new()
+    }
+}
+
+impl RolloutPolicy<TwoFortyEight, Action> for HeuristicRollout {
+    fn weights(&self, game: &TwoFortyEight, actions: &[Action]) -> Vec<f32> {
+        self.slide_weights(game, actions)
+    }
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -254,6 +860,7 @@ mod tests {
     use test::Bencher;
 
     use mcts::*;
+    use utils::choose_random;
     use twofortyeight::*;
 
     #[test]
@@ -263,6 +870,55 @@ mod tests {
         assert_eq!(game.reward(), 0.);
     }
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 1, 2);
+        game.set_tile(3, 3, 1024);
+        game.score = 12.;
+
+        let value = game.to_json();
+        assert_eq!(value["board"][3], json!([0, 0, 0, 1024]));
+
+        let restored = TwoFortyEight::from_json(&value).unwrap();
+        assert_eq!(restored.get_tile(0, 1), 2);
+        assert_eq!(restored.score, 12.);
+        assert_eq!(restored.to_json(), value);
+
+        assert!(TwoFortyEight::from_json(&json!({"board": [[0, 3, 0, 0]]})).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_from_str() {
+        let game = TwoFortyEight::from_str("0,2,0,0/0,0,0,0/0,0,0,0/0,0,0,1024 12 5").unwrap();
+        assert_eq!(game.get_tile(0, 1), 2);
+        assert_eq!(game.get_tile(3, 3), 1024);
+        assert_eq!(game.score, 12.);
+        assert_eq!(game.moves, 5);
+
+        let small = TwoFortyEight::from_str("2,4/0,0").unwrap();
+        assert_eq!((small.width(), small.height(), small.score), (2, 2, 0.));
+
+        assert!(TwoFortyEight::from_str("2,3/0,0").is_err());
+        assert!(TwoFortyEight::from_str("2,2/0").is_err());
+        assert!(TwoFortyEight::from_str("2,x/0,0").is_err());
+        assert!(TwoFortyEight::from_str("2,2/0,0 12 5 1").is_err());
+    }
+
+    #[test]
+    fn test_hash_state() {
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 0, 4);
+        let mut merged = game.clone();
+        merged.score = 4.;
+
+        // The same tiles with another score are another state
+        assert_eq!(game.hash_state(), game.clone().hash_state());
+        assert!(game.hash_state() != merged.hash_state());
+    }
+
     #[test]
     fn test_display() {
         let coords = vec![(0, 1, 2), (2, 2, 4), (3, 1, 2048)];
@@ -293,6 +949,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_with_seed() {
+        let game = TwoFortyEight::with_seed(42);
+        assert_eq!(game.max_tile(), 2);
+        assert_eq!(game.board, TwoFortyEight::with_seed(42).board);
+
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(1, 2, 64);
+        game.set_tile(3, 0, 8);
+        assert_eq!(game.max_tile(), 64);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_rng_state() {
+        let mut game = TwoFortyEight::with_seed(7);
+        game.make_move(&game.allowed_actions()[0]);
+        let (seed, draws) = game.rng_state();
+        assert!(draws >= 6);
+
+        let mut restored = TwoFortyEight::from_json(&game.to_json()).unwrap();
+        restored.set_rng_state(seed, draws);
+        for _ in 0..5 {
+            let action = game.allowed_actions()[0];
+            game.make_move(&action);
+            restored.make_move(&action);
+        }
+        assert_eq!(restored.board, game.board);
+    }
+
     #[test]
     fn test_random_spawn() {
         let mut game = TwoFortyEight::new_empty();
@@ -323,7 +1009,7 @@ mod tests {
             (vec![2, 2, 2, 2, 2]   , vec![4, 4, 2, 0, 0]),
             (vec![2, 0, 2, 0, 4]   , vec![4, 4, 0, 0, 0]),
             (vec![2, 2, 0, 4, 4]   , vec![4, 8, 0, 0, 0]),
-            (vec![2, 2, 4, 4, 4, 4], vec![4, 8, 8, 0, 0]),
+            (vec![2, 2, 4, 4, 4, 4], vec![4, 8, 8, 0, 0, 0]),
             (vec![4, 0, 0, 0, 0, 4], vec![8, 0, 0, 0, 0, 0]),
         ];
 
@@ -344,11 +1030,116 @@ mod tests {
         );*/
 
         for (input, should) in test_cases {
-            let  output = TwoFortyEight::merge_vec(&input);
-            println!("merge_vec({:?}) => {:?}  (should be {:?})", input, output, should);
+            let (output, points, changed) = TwoFortyEight::merge_vec(&input);
+            assert_eq!(output, should, "merge_vec({:?})", input);
+            assert_eq!(changed, output != input, "merge_vec({:?})", input);
+            let sum = |tiles: &[u16]| tiles.iter().map(|&t| t as f32).sum::<f32>();
+            assert_eq!(sum(&output), sum(&input));
+            assert!(points == 0. || points >= 4.);
         }
     }
 
+    #[test]
+    fn test_merge_points() {
+        let mut game = TwoFortyEight::new_empty();
+        game.spawn(0, 0, 2);
+        game.spawn(0, 1, 2);
+        game.spawn(1, 0, 2);
+        game.spawn(1, 1, 2);
+        game.slide(&Action::Left);
+        game.slide(&Action::Up);
+        assert_eq!(game.get_tile(0, 0), 8);
+        assert_eq!(game.score, 16.);
+        assert_eq!(TwoFortyEight::merge_points(8), 16.);
+        assert!(game.check_tiles().is_ok());
+
+        // Spawned 4's did not score
+        let mut fours = TwoFortyEight::new_empty();
+        fours.spawn(0, 0, 4);
+        fours.spawn(0, 1, 4);
+        fours.slide(&Action::Left);
+        assert_eq!(fours.score, 8.);
+        assert!(fours.check_tiles().is_ok());
+
+        // Slides without a spawn
+        assert!(game.check_invariants().is_err());
+        game.score += 2.;
+        assert!(game.check_tiles().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid tile 6")]
+    fn test_invalid_tile() {
+        TwoFortyEight::new_empty().set_tile(0, 0, 6);
+    }
+
+    #[test]
+    fn test_packed_board() {
+        // The bitboard moves exactly like a board of cells
+        for seed in 0..20 {
+            let mut game = TwoFortyEight::with_seed(seed);
+            loop {
+                let mut cells = game.clone();
+                cells.board = Board::Cells(game.board.tiles());
+                for action in &[Action::Up, Action::Down, Action::Left, Action::Right] {
+                    let (packed, points) = game.shift_and_merge(action);
+                    let (expected, expected_points) = cells.shift_and_merge(action);
+                    assert_eq!(packed.tiles(), expected.tiles(), "{:?} on\n{}", action, game);
+                    assert_eq!(points, expected_points);
+                }
+                assert_eq!(game.is_terminal(), cells.is_terminal());
+                assert_eq!(game.board_full(), cells.board_full());
+                assert_eq!(game.max_tile(), cells.max_tile());
+
+                match choose_random(&game.allowed_actions()) {
+                    Some(&action) => game.make_move(&action),
+                    None => break
+                }
+            }
+        }
+
+        // The largest tiles stay apart
+        let mut game = TwoFortyEight::new_empty();
+        game.set_tile(0, 0, MAX_TILE);
+        game.set_tile(0, 1, MAX_TILE);
+        assert_eq!(game.allowed_actions(), vec![Action::Down, Action::Right]);
+    }
+
+    #[test]
+    fn test_spawn_distribution() {
+        // One spawn in ten is a 4
+        let mut game = TwoFortyEight::new_empty();
+        game.set_rng_seed(5);
+        let mut fours = 0;
+        for _ in 0..2000 {
+            game.random_spawn();
+            fours += game.board.tiles().iter().filter(|&&tile| tile == 4).count();
+            game.board = Board::new(WIDTH, HEIGHT);
+        }
+        assert!(fours > 150 && fours < 250, "{}", fours);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_invariants() {
+        for seed in 0..20 {
+            let mut game = TwoFortyEight::with_seed(seed);
+            while let Some(&action) = choose_random(&game.allowed_actions()) {
+                game.make_move(&action);
+                assert!(game.check_invariants().is_ok(), "seed {} after {:?}:\n{}", seed, action, game);
+            }
+        }
+
+        // Placed tiles and a restored score are part of the setup
+        let mut game = TwoFortyEight::from_json(&json!({
+            "board": [[2, 2, 0, 0], [4, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 8]],
+            "score": 100, "moves": 30})).unwrap();
+        assert!(game.check_invariants().is_ok());
+        game.make_move(&Action::Left);
+        assert!(game.check_invariants().is_ok());
+        assert_eq!(game.score, 104.);
+    }
+
     #[test]
     fn test_shift_and_merge() {
         let mut game = TwoFortyEight::new_empty();
@@ -356,7 +1147,7 @@ mod tests {
 
         let actions = vec![Action::Down, Action::Right, Action::Up, Action::Left];
         for a in &actions {
-            let (board, points) = TwoFortyEight::shift_and_merge(game.board, a);
+            let (board, points) = game.shift_and_merge(a);
             assert!(points.unwrap() == 0.0);
             game.board = board;
             println!("{}", game);
@@ -364,6 +1155,40 @@ mod tests {
         assert!(game.get_tile(0, 0) == 4);
     }
 
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_rectangular() {
+        // Three columns and two rows
+        let mut game = TwoFortyEight::empty(3, 2);
+        game.set_tile(0, 0, 2);
+        game.set_tile(0, 1, 2);
+        game.set_tile(0, 2, 4);
+        assert_eq!((game.width(), game.height()), (3, 2));
+
+        let (board, points) = game.shift_and_merge(&Action::Left);
+        assert_eq!(board.tiles(), vec![4, 4, 0, 0, 0, 0]);
+        assert_eq!(points, Some(4.));
+        let (board, _) = game.shift_and_merge(&Action::Down);
+        assert_eq!(board.tiles(), vec![0, 0, 0, 2, 2, 4]);
+        assert_eq!(game.shift_and_merge(&Action::Up).1, None);
+
+        let value = game.to_json();
+        assert_eq!(value["board"], json!([[2, 2, 4], [0, 0, 0]]));
+        let restored = TwoFortyEight::from_json(&value).unwrap();
+        assert_eq!((restored.width(), restored.height()), (3, 2));
+        assert!(TwoFortyEight::from_json(&json!({"board": [[2, 0], [0]]})).is_err());
+        assert!(TwoFortyEight::from_json(&json!({"board": [[2, 0]]})).is_err());
+
+        for &(width, height) in &[(2, 2), (2, 5), (6, 3)] {
+            let mut game = TwoFortyEight::with_size(width, height, Some(7));
+            while let Some(&action) = choose_random(&game.allowed_actions()) {
+                game.make_move(&action);
+            }
+            assert!(game.is_terminal());
+            assert!(game.board_full());
+        }
+    }
+
     #[test]
     fn test_playout() {
         let game = TwoFortyEight::new();
@@ -371,6 +1196,106 @@ mod tests {
         println!("{}", final_game);
     }
 
+    #[test]
+    fn test_is_terminal() {
+        let mut game = TwoFortyEight::new();
+        while !game.is_terminal() {
+            let action = *choose_random(&game.allowed_actions()).expect("should have a move");
+            game.make_move(&action);
+        }
+        assert!(game.allowed_actions().is_empty());
+
+        let mut empty = TwoFortyEight::new();
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                empty.set_tile(row, col, 0);
+            }
+        }
+        assert!(empty.is_terminal());
+        assert!(empty.allowed_actions().is_empty());
+    }
+
+    #[test]
+    fn test_game_status() {
+        let mut game = TwoFortyEight::with_seed(1);
+        game.win_bonus = 1000.;
+        game.loss_penalty = 500.;
+        assert_eq!(game.game_status(), GameStatus::Ongoing);
+        assert_eq!(game.reward(), 0.);
+
+        // No two neighbours merge
+        for row in 0..HEIGHT {
+            for col in 0..WIDTH {
+                game.set_tile(row, col, if (row + col) % 2 == 0 { 2 } else { 4 });
+            }
+        }
+        assert_eq!(game.game_status(), GameStatus::Lost);
+        assert_eq!(game.reward(), -500.);
+
+        game.target = 4;
+        assert_eq!(game.game_status(), GameStatus::Won);
+        assert_eq!(game.reward(), 1000.);
+
+        // A game stays won while it goes on
+        game.set_tile(0, 1, 2);
+        assert!(!game.is_terminal());
+        assert_eq!(game.game_status(), GameStatus::Won);
+    }
+
+    #[test]
+    fn test_afterstates() {
+        let game = TwoFortyEight::with_seed(5);
+        let action = game.allowed_actions()[0];
+        let mut moved = game.clone();
+        moved.make_move(&action);
+        let mut after = game.clone();
+        after.apply_player_move(&action);
+        assert_eq!(after.board.tiles().iter().filter(|&&tile| tile > 0).count(), 2);
+
+        let outcomes = after.afterstate_outcomes().unwrap();
+        assert_eq!(outcomes.len(), 2 * 14);
+        assert!((outcomes.iter().map(|&(_, p)| p).sum::<f32>() - 1.).abs() < 1e-5);
+        assert!(outcomes.iter().all(|&(ref outcome, _)| outcome.check_invariants().is_ok()));
+        assert!(outcomes.iter().any(|&(ref outcome, _)| outcome.board == moved.board));
+
+        after.resolve_chance();
+        assert_eq!(after.board, moved.board);
+
+        let config = MctsConfig { afterstates: Some(Afterstates::Integrated), ..MctsConfig::default() };
+        let mut mcts = MCTS::with_config(&game, 1, config);
+        mcts.run(20);
+        assert!(mcts.best_action().is_some());
+    }
+
+    #[test]
+    fn test_heuristic_rollout() {
+        let mut game = TwoFortyEight::new_empty();
+        for (col, &tile) in [64, 16, 4, 2].iter().enumerate() {
+            game.set_tile(0, col, tile);
+        }
+        // 12 empty cells, the 64 in a corner, and no line goes up and down
+        assert_eq!(HeuristicRollout::value(&game), 12. + CORNER_BONUS);
+        game.set_tile(0, 0, 8);
+        game.set_tile(0, 1, 64);
+        // The first row goes up by 3 exponents and down by 5
+        assert_eq!(HeuristicRollout::value(&game), 12. - 3.);
+
+        // Sliding right takes the 64 out of its corner
+        game.set_tile(0, 0, 64);
+        game.set_tile(0, 1, 0);
+        let actions = vec![Action::Down, Action::Right];
+        let weights = HeuristicRollout::new().slide_weights(&game, &actions);
+        assert_eq!(weights[0], 1.);
+        assert!(weights[1] < 1. && weights[1] > 0.);
+        let greedy = HeuristicRollout { temperature: 0.01 }.slide_weights(&game, &actions);
+        assert_eq!(greedy, vec![1., 0.]);
+
+        let mut mcts = MCTS::new(&TwoFortyEight::with_seed(3), 1);
+        mcts.set_rollout_policy(HeuristicRollout::new());
+        mcts.search(20, 1.);
+        assert!(mcts.best_action().is_some());
+    }
+
     #[test]
     fn test_mcts() {
         let game = TwoFortyEight::new();
@@ -1,31 +1,433 @@
 
 extern crate rand;
 
-use rand::Rng;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::f32;
+use std::mem;
+use std::hash::{Hash, Hasher};
 
+use rand::{Rng, XorShiftRng, StdRng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde_json::Value;
+use rand::distributions::{Gamma, IndependentSample};
+use rand::distributions::normal::StandardNormal;
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+use time;
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+use wasm;
+
+thread_local!(
+    // Randomly seeded unless `seed_thread_rng` is called
+    static THREAD_RNG: RefCell<XorShiftRng> = RefCell::new(random_rng())
+);
+
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+fn random_rng() -> XorShiftRng {
+    rand::weak_rng()
+}
+
+// There is no OS entropy source in the browser
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+fn random_rng() -> XorShiftRng {
+    seeded_rng((wasm::random() * 4294967296.) as u32)
+}
+
+/// Wall clock time in seconds, for measuring time budgets.
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+pub fn seconds() -> f64 {
+    time::precise_time_s()
+}
+
+/// Wall clock time in seconds, for measuring time budgets.
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+pub fn seconds() -> f64 {
+    wasm::now() / 1000.
+}
+
+/// Reseed the random number generator behind `choose_random` for the
+/// current thread.
+///
+/// Random playouts and node expansion draw from this generator; seeding
+/// it makes searches with a fixed number of iterations reproducible.
+pub fn seed_thread_rng(seed: u32) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = seeded_rng(seed));
+}
+
+/// Run `f` with the random number generator of the current thread.
+///
+/// The generator is taken out of the thread while `f` runs, so `f` may use
+/// the convenience wrappers below, or call `with_thread_rng` again; those
+/// draw from a generator seeded from the state of the outer one, which
+/// keeps seeded runs reproducible.
+pub fn with_thread_rng<T, F: FnOnce(&mut XorShiftRng) -> T>(f: F) -> T {
+    let mut rng = THREAD_RNG.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let nested = seeded_rng(cell.clone().next_u32());
+        mem::replace(&mut *cell, nested)
+    });
+    let result = f(&mut rng);
+    THREAD_RNG.with(|cell| *cell.borrow_mut() = rng);
+    result
+}
 
 /// Various small utility functions
 
-#[allow(dead_code)]
-/// Return a random element from the vector.
-pub fn choose_random<T>(vec: &Vec<T>) -> &T {
-    let mut rng = rand::thread_rng();
+/// Draw a uniform random index smaller than `length`; `None` if `length`
+/// is zero.
+pub fn random_index_with<R: Rng>(rng: &mut R, length: usize) -> Option<usize> {
+    if length == 0 {
+        return None;
+    }
+    Some(rng.gen_range(0, length))
+}
+
+/// Draw `k` distinct uniform random indices smaller than `length`.
+///
+/// The indices come in random order; if `k` exceeds `length`, all indices
+/// are returned.
+pub fn sample_indices_with<R: Rng>(rng: &mut R, length: usize, k: usize) -> Vec<usize> {
+    // Partial Fisher-Yates shuffle
+    let mut indices = (0..length).collect::<Vec<_>>();
+    let k = k.min(length);
+    for i in 0..k {
+        let j = rng.gen_range(i, length);
+        indices.swap(i, j);
+    }
+    indices.truncate(k);
+    indices
+}
+
+/// Return a random element from the slice; `None` if it is empty.
+pub fn choose_random_with<'a, T, R: Rng>(rng: &mut R, slice: &'a [T]) -> Option<&'a T> {
+    random_index_with(rng, slice.len()).map(|idx| &slice[idx])
+}
+
+/// Return a random mutable element from the slice; `None` if it is empty.
+pub fn choose_random_mut_with<'a, T, R: Rng>(rng: &mut R, slice: &'a mut [T]) -> Option<&'a mut T> {
+    match random_index_with(rng, slice.len()) {
+        Some(idx) => Some(&mut slice[idx]),
+        None => None,
+    }
+}
+
+/// Return `k` distinct random elements from the slice, or all of them if
+/// there are fewer than `k`.
+pub fn sample_many_with<'a, T, R: Rng>(rng: &mut R, slice: &'a [T], k: usize) -> Vec<&'a T> {
+    sample_indices_with(rng, slice.len(), k).into_iter().map(|idx| &slice[idx]).collect()
+}
+
+/// Return a uniform random element of the iterator without collecting it;
+/// `None` if it is empty.
+pub fn reservoir_sample_with<I: Iterator, R: Rng>(rng: &mut R, iter: I) -> Option<I::Item> {
+    let mut chosen = None;
+    for (i, item) in iter.enumerate() {
+        // Replace the choice with probability 1/(i+1)
+        if rng.gen_range(0, i + 1) == 0 {
+            chosen = Some(item);
+        }
+    }
+    chosen
+}
+
+/// Return `k` distinct random elements of the iterator, or all of them if
+/// there are fewer than `k`.
+///
+/// Only the `k` chosen elements are kept in memory; their order is not
+/// random.
+pub fn reservoir_sample_many_with<I: Iterator, R: Rng>(rng: &mut R, iter: I, k: usize) -> Vec<I::Item> {
+    let mut reservoir = Vec::with_capacity(k);
+    for (i, item) in iter.enumerate() {
+        if i < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.gen_range(0, i + 1);
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    reservoir
+}
+
+/// Softmax probabilities of the scores at the given temperature.
+///
+/// Scores are shifted by their maximum before exponentiating, so huge and
+/// tiny scores neither overflow nor underflow to all zeros. Scores of -inf
+/// (or NaN) get probability zero. A temperature of zero puts all the mass
+/// on the best scores; if no score is finite the result is uniform.
+pub fn softmax(scores: &[f32], temperature: f32) -> Vec<f32> {
+    let max = scores.iter().cloned().filter(|s| !s.is_nan()).fold(f32::NEG_INFINITY, f32::max);
+    if max == f32::NEG_INFINITY || max == f32::INFINITY {
+        // Uniform over all or over the infinitely good scores
+        let count = scores.iter().filter(|&&s| max == f32::NEG_INFINITY || s == max).count();
+        return scores.iter()
+            .map(|&s| if max == f32::NEG_INFINITY || s == max { 1. / count as f32 } else { 0. })
+            .collect();
+    }
+
+    let weights = scores.iter().map(|&s| {
+        if s.is_nan() {
+            0.
+        } else if temperature > 0. {
+            ((s - max) / temperature).exp()
+        } else if s == max {
+            1.
+        } else {
+            0.
+        }
+    }).collect::<Vec<_>>();
+    let total = weights.iter().fold(0., |sum, w| sum + w);
+    weights.iter().map(|w| w / total).collect()
+}
+
+/// Sample an index with the softmax probabilities of the scores; `None`
+/// if there are no scores.
+pub fn sample_softmax_with<R: Rng>(rng: &mut R, scores: &[f32], temperature: f32) -> Option<usize> {
+    if scores.is_empty() {
+        return None;
+    }
+    let probabilities = softmax(scores, temperature);
+    let mut u = rng.next_f32();
+    for (idx, &p) in probabilities.iter().enumerate() {
+        if u < p {
+            return Some(idx);
+        }
+        u -= p;
+    }
+    // Rounding errors; fall back to the last index with positive mass
+    probabilities.iter().rposition(|&p| p > 0.)
+}
+
+// Convenience wrappers drawing from the thread generator
+
+/// `random_index_with` the thread generator.
+pub fn random_index(length: usize) -> Option<usize> {
+    with_thread_rng(|rng| random_index_with(rng, length))
+}
+
+/// `sample_indices_with` the thread generator.
+pub fn sample_indices(length: usize, k: usize) -> Vec<usize> {
+    with_thread_rng(|rng| sample_indices_with(rng, length, k))
+}
+
+/// `choose_random_with` the thread generator.
+pub fn choose_random<T>(slice: &[T]) -> Option<&T> {
+    with_thread_rng(|rng| choose_random_with(rng, slice))
+}
+
+/// `choose_random_mut_with` the thread generator.
+pub fn choose_random_mut<T>(slice: &mut [T]) -> Option<&mut T> {
+    with_thread_rng(move |rng| choose_random_mut_with(rng, slice))
+}
+
+/// `sample_many_with` the thread generator.
+pub fn sample_many<T>(slice: &[T], k: usize) -> Vec<&T> {
+    with_thread_rng(|rng| sample_many_with(rng, slice, k))
+}
+
+/// `reservoir_sample_with` the thread generator.
+pub fn reservoir_sample<I: Iterator>(iter: I) -> Option<I::Item> {
+    with_thread_rng(|rng| reservoir_sample_with(rng, iter))
+}
+
+/// `reservoir_sample_many_with` the thread generator.
+pub fn reservoir_sample_many<I: Iterator>(iter: I, k: usize) -> Vec<I::Item> {
+    with_thread_rng(|rng| reservoir_sample_many_with(rng, iter, k))
+}
+
+/// `sample_softmax_with` the thread generator.
+pub fn sample_softmax(scores: &[f32], temperature: f32) -> Option<usize> {
+    with_thread_rng(|rng| sample_softmax_with(rng, scores, temperature))
+}
+
+/// Draw from a Gamma distribution with the given shape and scale.
+///
+/// Panics unless both are positive.
+pub fn sample_gamma<R: Rng>(rng: &mut R, shape: f32, scale: f32) -> f32 {
+    Gamma::new(shape as f64, scale as f64).ind_sample(rng) as f32
+}
+
+/// Draw from a normal distribution with the given mean and standard
+/// deviation.
+pub fn sample_normal<R: Rng>(rng: &mut R, mean: f32, std: f32) -> f32 {
+    let StandardNormal(z) = rng.gen::<StandardNormal>();
+    mean + std * z as f32
+}
+
+/// Draw from a Dirichlet distribution with the given concentrations.
+///
+/// Normalizes independent Gamma draws. With very small concentrations all
+/// draws can underflow to zero; the result is uniform then. Panics unless
+/// all concentrations are positive.
+pub fn sample_dirichlet<R: Rng>(rng: &mut R, alphas: &[f32]) -> Vec<f32> {
+    let draws = alphas.iter()
+        .map(|&alpha| Gamma::new(alpha as f64, 1.0).ind_sample(rng))
+        .collect::<Vec<_>>();
+    let sum = draws.iter().fold(0., |sum, d| sum + d);
+    if sum > 0. {
+        draws.iter().map(|d| (d / sum) as f32).collect()
+    } else {
+        vec![1. / alphas.len() as f32; alphas.len()]
+    }
+}
+
+/// Draw from a symmetric Dirichlet distribution over `k` categories.
+pub fn sample_symmetric_dirichlet<R: Rng>(rng: &mut R, alpha: f32, k: usize) -> Vec<f32> {
+    sample_dirichlet(rng, &vec![alpha; k])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Count, mean, variance, minimum and maximum of a stream of values.
+///
+/// Uses Welford's online algorithm, which stays accurate where the naive
+/// sum of squares cancels catastrophically.
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    m2: f64,                            // sum of squared deviations from the mean
+    min: f32,
+    max: f32,
+}
+
+impl RunningStats {
+    pub fn new() -> RunningStats {
+        RunningStats { count: 0, mean: 0., m2: 0., min: f32::INFINITY, max: f32::NEG_INFINITY }
+    }
+
+    /// Add a value.
+    pub fn push(&mut self, value: f32) {
+        self.count += 1;
+        let delta = value as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value as f64 - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Add all values seen by `other`.
+    pub fn merge(&mut self, other: &RunningStats) {
+        if other.count == 0 {
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.count as f64 / count as f64;
+        self.m2 += other.m2 + delta * delta * (self.count as f64 * other.count as f64) / count as f64;
+        self.count = count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean; NaN if there are no values.
+    pub fn mean(&self) -> f32 {
+        if self.count == 0 { f32::NAN } else { self.mean as f32 }
+    }
+
+    /// The sample variance; zero for less than two values.
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 { 0. } else { (self.m2 / (self.count - 1) as f64) as f32 }
+    }
+
+    /// The sample standard deviation.
+    pub fn std_dev(&self) -> f32 {
+        self.variance().sqrt()
+    }
+
+    /// The standard error of the mean.
+    pub fn stderr(&self) -> f32 {
+        if self.count == 0 { 0. } else { (self.variance() / self.count as f32).sqrt() }
+    }
+
+    pub fn min(&self) -> Option<f32> {
+        if self.count == 0 { None } else { Some(self.min) }
+    }
+
+    pub fn max(&self) -> Option<f32> {
+        if self.count == 0 { None } else { Some(self.max) }
+    }
+
+    /// The complete state as JSON, so that `from_json` continues exactly
+    /// where these statistics left off; null without values.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Value {
+        if self.count == 0 {
+            return Value::Null;
+        }
+        json!([self.count, self.mean, self.m2, self.min, self.max])
+    }
+
+    /// Restore statistics saved with `to_json`.
+    #[cfg(feature = "serde")]
+    pub fn from_json(value: &Value) -> Result<RunningStats, String> {
+        if value.is_null() {
+            return Ok(RunningStats::new());
+        }
+        let invalid = || format!("expected statistics, got {}", value);
+        let fields = try!(value.as_array().ok_or_else(&invalid));
+        if fields.len() != 5 {
+            return Err(invalid());
+        }
+        let number = |i: usize| fields[i].as_f64().ok_or_else(&invalid);
+        Ok(RunningStats {
+            count: try!(fields[0].as_u64().ok_or_else(&invalid)),
+            mean: try!(number(1)),
+            m2: try!(number(2)),
+            min: try!(number(3)) as f32,
+            max: try!(number(4)) as f32,
+        })
+    }
+}
 
-    let length = vec.len();
-    let idx = rng.gen::<usize>() % length as usize;
+impl Default for RunningStats {
+    fn default() -> RunningStats {
+        RunningStats::new()
+    }
+}
 
-    &vec[idx]
+/// Create a XorShift generator for the given seed.
+///
+/// XorShift produces tiny numbers for the first few draws after being
+/// seeded with small integers; we discard those.
+pub fn seeded_rng(seed: u32) -> XorShiftRng {
+    let mut rng = XorShiftRng::from_seed([seed, seed.wrapping_add(1),
+                                          seed.wrapping_add(2), seed.wrapping_add(3)]);
+    for _ in 0..32 {
+        rng.next_u32();
+    }
+    rng
 }
 
-#[allow(dead_code)]
-/// Return a random mutable element from the vector.
-pub fn choose_random_mut<T>(vec: &mut Vec<T>) -> &mut T {
-    let mut rng = rand::thread_rng();
+/// Create the standard generator for the given seed.
+///
+/// Unlike XorShift it starts out well mixed for any seed; see
+/// `MCTS::set_seed`.
+pub fn seeded_std_rng(seed: u64) -> StdRng {
+    StdRng::from_seed(&[seed as usize, (seed >> 32) as usize][..])
+}
 
-    let length = vec.len();
-    let idx = rng.gen::<usize>() % length as usize;
+/// Hash a value, e.g. a game state for `Game::hash_state`.
+pub fn hash_value<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
 
-    &mut vec[idx]
+/// Derive a seed for the run with the given index from a master seed.
+///
+/// Neighbouring indices give unrelated seeds, so that e.g. every game of
+/// an experiment gets its own reproducible random stream.
+pub fn derive_seed(master: u32, index: u32) -> u32 {
+    // SplitMix64 finalizer
+    let mut z = ((master as u64) << 32 | index as u64).wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    (z ^ (z >> 31)) as u32
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -33,6 +435,7 @@ pub fn choose_random_mut<T>(vec: &mut Vec<T>) -> &mut T {
 #[cfg(test)]
 mod tests {
     use test::Bencher;
+    use rand::Rng;
 
     use utils::*;
 
@@ -40,7 +443,268 @@ mod tests {
     fn test_choose_random() {
         let vec = vec![23];
 
-        assert_eq!(*choose_random(&vec), 23);
+        assert_eq!(choose_random(&vec), Some(&23));
+        assert_eq!(choose_random::<u32>(&[]), None);
+
+        let mut vec = vec![1, 2];
+        *choose_random_mut(&mut vec).unwrap() = 0;
+        assert!(vec.contains(&0));
+        assert!(choose_random_mut::<u32>(&mut []).is_none());
+    }
+
+    #[test]
+    fn test_random_index_uniform() {
+        seed_thread_rng(7);
+        let mut counts = [0; 3];
+        for _ in 0..3000 {
+            counts[random_index(3).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&n| n > 900 && n < 1100), "{:?}", counts);
+        assert_eq!(random_index(0), None);
+    }
+
+    #[test]
+    fn test_sample_many() {
+        let vec = (0..10).collect::<Vec<_>>();
+        let mut sample = sample_many(&vec, 4).into_iter().cloned().collect::<Vec<_>>();
+        assert_eq!(sample.len(), 4);
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 4);
+
+        let mut all = sample_many(&vec, 20).into_iter().cloned().collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(all, vec);
+        assert!(sample_many::<u32>(&[], 3).is_empty());
+        assert_eq!(sample_indices(5, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_reservoir_sample() {
+        seed_thread_rng(3);
+        let mut counts = [0; 4];
+        for _ in 0..4000 {
+            counts[reservoir_sample(0..4).unwrap()] += 1;
+        }
+        assert!(counts.iter().all(|&n| n > 900 && n < 1100), "{:?}", counts);
+        assert_eq!(reservoir_sample(0..0), None);
+        assert_eq!(reservoir_sample((0..10).filter(|&i| i == 7)), Some(7));
+    }
+
+    #[test]
+    fn test_reservoir_sample_many() {
+        let mut sample = reservoir_sample_many(0..100, 5);
+        assert_eq!(sample.len(), 5);
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 5);
+        assert_eq!(reservoir_sample_many(0..3, 5), vec![0, 1, 2]);
+
+        // Every element ends up in the sample equally often
+        seed_thread_rng(5);
+        let mut counts = [0; 6];
+        for _ in 0..3000 {
+            for i in reservoir_sample_many(0..6, 2) {
+                counts[i] += 1;
+            }
+        }
+        assert!(counts.iter().all(|&n| n > 900 && n < 1100), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_softmax() {
+        let p = softmax(&[1., 2., 3.], 1.);
+        assert!((p.iter().fold(0., |sum, p| sum + p) - 1.).abs() < 1e-6);
+        assert!(p[0] < p[1] && p[1] < p[2]);
+        assert!((p[2] / p[1] - 1f32.exp()).abs() < 1e-4);
+
+        // Extreme score ranges
+        assert_eq!(softmax(&[1e30, 0.], 1.), vec![1., 0.]);
+        assert_eq!(softmax(&[-1e30, -1e30], 1.), vec![0.5, 0.5]);
+        assert_eq!(softmax(&[1000., 1000.], 1e-3), vec![0.5, 0.5]);
+        assert_eq!(softmax(&[f32::NEG_INFINITY, 0.], 1.), vec![0., 1.]);
+        assert_eq!(softmax(&[f32::INFINITY, 0.], 1.), vec![1., 0.]);
+        assert_eq!(softmax(&[f32::NEG_INFINITY; 2], 1.), vec![0.5, 0.5]);
+        assert_eq!(softmax(&[f32::NAN, 1.], 1.), vec![0., 1.]);
+
+        // Temperature
+        assert_eq!(softmax(&[1., 3., 3.], 0.), vec![0., 0.5, 0.5]);
+        let flat = softmax(&[1., 2.], 1000.);
+        assert!((flat[0] - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sample_softmax() {
+        assert_eq!(sample_softmax(&[], 1.), None);
+        assert_eq!(sample_softmax(&[0., 100.], 1.), Some(1));
+        assert_eq!(sample_softmax(&[5., 1.], 0.), Some(0));
+
+        seed_thread_rng(11);
+        let scores = [0., 2f32.ln()];
+        let ones = (0..3000).filter(|_| sample_softmax(&scores, 1.) == Some(1)).count();
+        assert!(ones > 1900 && ones < 2100, "{}", ones);
+    }
+
+    #[test]
+    fn test_running_stats() {
+        let mut stats = RunningStats::new();
+        assert!(stats.mean().is_nan());
+        assert_eq!((stats.variance(), stats.stderr(), stats.min()), (0., 0., None));
+
+        for &v in &[2., 4., 4., 4., 5., 5., 7., 9.] {
+            stats.push(v);
+        }
+        assert_eq!(stats.count(), 8);
+        assert_eq!(stats.mean(), 5.);
+        assert!((stats.variance() - 32. / 7.).abs() < 1e-5);
+        assert!((stats.stderr() - (32f32 / 7. / 8.).sqrt()).abs() < 1e-5);
+        assert_eq!((stats.min(), stats.max()), (Some(2.), Some(9.)));
+
+        // Large offsets do not cancel
+        let mut shifted = RunningStats::new();
+        for &v in &[1e6 + 1., 1e6 + 2., 1e6 + 3.] {
+            shifted.push(v);
+        }
+        assert!((shifted.variance() - 1.).abs() < 1e-3);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_running_stats_json() {
+        let mut stats = RunningStats::new();
+        assert_eq!(RunningStats::from_json(&stats.to_json()), Ok(stats));
+        for &x in &[0.1, -2.5, 7.25] {
+            stats.push(x);
+        }
+        assert_eq!(RunningStats::from_json(&stats.to_json()), Ok(stats));
+        assert!(RunningStats::from_json(&json!([1, 2.])).is_err());
+    }
+
+    #[test]
+    fn test_running_stats_merge() {
+        let values = [1., 3., 3., 8., -2., 0.5];
+        let mut all = RunningStats::new();
+        let mut a = RunningStats::new();
+        let mut b = RunningStats::new();
+        for (i, &v) in values.iter().enumerate() {
+            all.push(v);
+            if i < 2 { a.push(v) } else { b.push(v) }
+        }
+        a.merge(&b);
+        a.merge(&RunningStats::new());
+        assert_eq!(a.count(), all.count());
+        assert!((a.mean() - all.mean()).abs() < 1e-6);
+        assert!((a.variance() - all.variance()).abs() < 1e-5);
+        assert_eq!((a.min(), a.max()), (Some(-2.), Some(8.)));
+
+        let mut empty = RunningStats::new();
+        empty.merge(&all);
+        assert_eq!(empty, all);
+    }
+
+    #[test]
+    fn test_running_stats_large_counts() {
+        // Beyond 2^24 an f32 count would not notice a single increment
+        let mut stats = RunningStats::new();
+        stats.push(0.1);
+        for _ in 0..25 {
+            let copy = stats;
+            stats.merge(&copy);
+        }
+        assert_eq!(stats.count(), 1 << 25);
+        stats.push(0.1);
+        assert_eq!(stats.count(), (1 << 25) + 1);
+        assert!((stats.mean() - 0.1).abs() < 1e-7);
+    }
+
+    #[test]
+    fn test_sample_gamma() {
+        let mut rng = seeded_rng(1);
+        let mut stats = RunningStats::new();
+        for _ in 0..5000 {
+            let x = sample_gamma(&mut rng, 2., 3.);
+            assert!(x > 0.);
+            stats.push(x);
+        }
+        // Mean shape*scale and variance shape*scale^2
+        assert!((stats.mean() - 6.).abs() < 0.3, "{}", stats.mean());
+        assert!((stats.variance() - 18.).abs() < 2., "{}", stats.variance());
+    }
+
+    #[test]
+    fn test_sample_dirichlet() {
+        let mut rng = seeded_rng(2);
+        let mut first = RunningStats::new();
+        for _ in 0..2000 {
+            let p = sample_dirichlet(&mut rng, &[1., 2., 5.]);
+            assert_eq!(p.len(), 3);
+            assert!((p.iter().fold(0., |sum, p| sum + p) - 1.).abs() < 1e-5);
+            assert!(p.iter().all(|&p| p >= 0.));
+            first.push(p[0]);
+        }
+        assert!((first.mean() - 1. / 8.).abs() < 0.02, "{}", first.mean());
+
+        // Tiny concentrations must not produce NaNs
+        for _ in 0..100 {
+            let p = sample_symmetric_dirichlet(&mut rng, 1e-3, 4);
+            assert!(p.iter().all(|p| p.is_finite()));
+        }
+        assert!(sample_dirichlet(&mut rng, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let mut a = seeded_rng(0);
+        let mut b = seeded_rng(0);
+        for _ in 0..10 {
+            let u = a.next_f32();
+            assert_eq!(u, b.next_f32());
+        }
+
+        // Small seeds should not produce tiny numbers
+        let mean = (0..100).map(|_| a.next_f32()).fold(0., |sum, u| sum + u) / 100.;
+        assert!(mean > 0.3 && mean < 0.7);
+    }
+
+    #[test]
+    fn test_seed_thread_rng() {
+        let vec = (0..100).collect::<Vec<_>>();
+
+        seed_thread_rng(42);
+        let a = (0..20).map(|_| *choose_random(&vec).unwrap()).collect::<Vec<_>>();
+        seed_thread_rng(42);
+        let b = (0..20).map(|_| *choose_random(&vec).unwrap()).collect::<Vec<_>>();
+        assert_eq!(a, b);
+
+        // Large seeds must not overflow
+        seed_thread_rng(u32::max_value());
+        choose_random(&vec);
+    }
+
+    #[test]
+    fn test_nested_thread_rng() {
+        let vec = (0..100).collect::<Vec<_>>();
+        let nested = || with_thread_rng(|rng| {
+            let outer = rng.gen_range(0, 100);
+            let inner = with_thread_rng(|rng| rng.gen_range(0, 100));
+            (outer, inner, *choose_random(&vec).unwrap())
+        });
+
+        // The outer draws are those of an unnested call
+        seed_thread_rng(7);
+        let a = nested();
+        seed_thread_rng(7);
+        assert_eq!(with_thread_rng(|rng| rng.gen_range(0, 100)), a.0);
+        seed_thread_rng(7);
+        assert_eq!(nested(), a);
+    }
+
+    #[test]
+    fn test_derive_seed() {
+        assert_eq!(derive_seed(1, 2), derive_seed(1, 2));
+        assert!(derive_seed(1, 2) != derive_seed(1, 3));
+        assert!(derive_seed(1, 2) != derive_seed(2, 2));
+        assert!(derive_seed(0, 0) != 0);
     }
 
     #[bench]
@@ -0,0 +1,83 @@
+//!
+//! JavaScript bindings for the 2048 engine.
+//!
+//! Build with `wasm-pack build --target web -- --features wasm` to run the
+//! engine entirely client-side, e.g. in a web 2048 page:
+//!
+//! ```js
+//! import init, { suggest_move } from "./pkg/mcts.js";
+//! await init();
+//! suggest_move([0, 2, 0, 0,  0, 0, 4, 0,  0, 0, 0, 0,  2, 0, 0, 0], 200);  // "Left"
+//! ```
+//!
+//! Boards are given as 16 tiles, row by row, with 0 for empty cells.
+//!
+//! Only available with the `wasm` feature.
+//!
+
+use wasm_bindgen::prelude::*;
+
+use mcts::MCTS;
+use records::search_json;
+use twofortyeight::{TwoFortyEight, Action, WIDTH, HEIGHT, MAX_TILE};
+use utils::seed_thread_rng;
+
+#[wasm_bindgen]
+extern "C" {
+    /// Milliseconds since the epoch, from the JavaScript clock.
+    #[wasm_bindgen(js_namespace = Date)]
+    pub fn now() -> f64;
+
+    /// Uniform random number in [0, 1) from JavaScript.
+    #[wasm_bindgen(js_namespace = Math)]
+    pub fn random() -> f64;
+}
+
+/// Ensemble size used by the bindings.
+const ENSEMBLE_SIZE: usize = 4;
+
+/// Set up a game on the default board from 16 tiles, row by row.
+fn game_from_tiles(board: &[u32]) -> Result<TwoFortyEight, JsValue> {
+    if board.len() != WIDTH*HEIGHT {
+        return Err(JsValue::from_str("Expected 16 tiles"));
+    }
+    let mut game = TwoFortyEight::new_empty();
+    for (idx, &tile) in board.iter().enumerate() {
+        if tile != 0 && (tile < 2 || tile > MAX_TILE as u32 || !tile.is_power_of_two()) {
+            return Err(JsValue::from_str(&format!("Invalid tile {}", tile)));
+        }
+        game.set_tile(idx / WIDTH, idx % WIDTH, tile as u16);
+    }
+    Ok(game)
+}
+
+/// Search the board for `budget_ms` milliseconds.
+fn search(board: &[u32], budget_ms: f64) -> Result<MCTS<TwoFortyEight, Action>, JsValue> {
+    let game = try!(game_from_tiles(board));
+    let mut mcts = MCTS::new(&game, ENSEMBLE_SIZE);
+    mcts.search_time((budget_ms / 1000.) as f32, 1.);
+    Ok(mcts)
+}
+
+/// Suggest a move ("Up", "Down", "Left" or "Right") for the given board.
+///
+/// Returns undefined if no move is possible.
+#[wasm_bindgen]
+pub fn suggest_move(board: &[u32], budget_ms: f64) -> Result<Option<String>, JsValue> {
+    let mcts = try!(search(board, budget_ms));
+    Ok(mcts.best_action().map(|action| format!("{:?}", action)))
+}
+
+/// Search the given board and return the ranked action table, the
+/// principal variation and the tree size as a JSON string.
+#[wasm_bindgen]
+pub fn analyze(board: &[u32], budget_ms: f64) -> Result<String, JsValue> {
+    let mcts = try!(search(board, budget_ms));
+    Ok(search_json(&mcts).to_string())
+}
+
+/// Make the engine's random choices reproducible.
+#[wasm_bindgen]
+pub fn set_seed(seed: u32) {
+    seed_thread_rng(seed);
+}